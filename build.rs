@@ -0,0 +1,29 @@
+use std::{path::Path, process::Command};
+use chrono::{DateTime, Utc};
+
+/* Build metadata of mactime --version & of the JSON metadata: git commit & build date (SOURCE_DATE_EPOCH for reproducible builds) */
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|c| c.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let date = std::env::var("SOURCE_DATE_EPOCH").ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .and_then(|s| DateTime::from_timestamp(s, 0))
+        .unwrap_or_else(Utc::now);
+
+    println!("cargo:rustc-env=MACTIME_COMMIT={commit}");
+    println!("cargo:rustc-env=MACTIME_BUILD_DATE={}", date.format("%Y-%m-%d"));
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    // a new commit changes the index
+    for path in [".git/HEAD", ".git/index"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+}
@@ -0,0 +1,67 @@
+#!/usr/bin/env python3
+"""
+Builds src/zoneinfo.bin, the tzdata embedded in mactime for the systems without a time zone database (Windows).
+
+    scripts/zoneinfo.py tzdata2025b.tar.gz     # IANA release, compiled with zic
+    scripts/zoneinfo.py /usr/share/zoneinfo    # zoneinfo directory already compiled
+
+Format: raw deflate (level 9) of the TZif files sorted by name, [u16 LE name length][name][u32 LE TZif length][TZif]...
+The SHA-256 printed goes to the checksum test of src/timezone.rs, the version to the comment of EMBEDDED.
+"""
+import hashlib, os, re, struct, subprocess, sys, tarfile, tempfile, zlib
+
+OUTPUT = os.path.join(os.path.dirname(os.path.abspath(__file__)), "..", "src", "zoneinfo.bin")
+SKIPPED = {"posix", "right", "localtime", "posixrules", "Factory"}
+SOURCES = ["africa", "antarctica", "asia", "australasia", "europe", "northamerica", "southamerica", "etcetera", "backward"]
+
+
+def compile_release(archive, directory):
+    with tarfile.open(archive) as tar:
+        tar.extractall(directory, filter="data")
+    zoneinfo = os.path.join(directory, "zoneinfo")
+    subprocess.run(["zic", "-d", zoneinfo] + SOURCES, cwd=directory, check=True)
+    with open(os.path.join(directory, "version")) as f:
+        return zoneinfo, f.read().strip()
+
+
+def version_of(zoneinfo):
+    try:
+        with open(os.path.join(zoneinfo, "tzdata.zi")) as f:
+            return re.match(r"# version (\S+)", f.readline()).group(1)
+    except (OSError, AttributeError):
+        return "unknown"
+
+
+def zones(zoneinfo):
+    for root, directories, files in os.walk(zoneinfo):
+        directories[:] = [d for d in directories if d not in SKIPPED]
+        for file in files:
+            path = os.path.join(root, file)
+            name = os.path.relpath(path, zoneinfo).replace(os.sep, "/")
+            if file in SKIPPED or os.path.islink(path) and not os.path.exists(path):
+                continue
+            with open(path, "rb") as f:
+                data = f.read()
+            if data.startswith(b"TZif"):
+                yield name, data
+
+
+def main(source):
+    with tempfile.TemporaryDirectory() as directory:
+        if os.path.isdir(source):
+            zoneinfo, version = source, version_of(source)
+        else:
+            zoneinfo, version = compile_release(source, directory)
+        packed = b"".join(struct.pack("<H", len(name.encode())) + name.encode() + struct.pack("<I", len(data)) + data
+                          for name, data in sorted(zones(zoneinfo)))
+    compressor = zlib.compressobj(9, zlib.DEFLATED, -15)
+    blob = compressor.compress(packed) + compressor.flush()
+    with open(OUTPUT, "wb") as f:
+        f.write(blob)
+    print(f"tzdata {version}: {len(blob)} bytes, SHA-256 {hashlib.sha256(blob).hexdigest()}")
+
+
+if __name__ == "__main__":
+    if len(sys.argv) != 2:
+        sys.exit(__doc__)
+    main(sys.argv[1])
@@ -0,0 +1,48 @@
+use std::{path::Path, error::Error, collections::HashMap};
+
+/*
+Names of the numeric user & group IDs (-p/--passwd, -g/--group, as the Sleuthkit's mactime), written in the UID & GID columns.
+passwd: name:password:UID:GID:gecos:home:shell, group: name:password:GID:members => the name & the ID are the 1st & 3rd fields of both.
+Comments, NIS entries (+/-) and lines without a numeric ID are skipped; the first name of an ID wins, as with getpwuid.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct IdNames {
+    names: HashMap<String, String> // ID => name
+}
+
+impl IdNames {
+    pub fn parse(content: &str) -> Self {
+        let mut names = HashMap::new();
+        for line in content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with(['#', '+', '-'])) {
+            let fields : Vec<&str> = line.split(':').collect();
+            let (Some(name), Some(id)) = (fields.first(), fields.get(2)) else { continue };
+            if name.is_empty() || id.parse::<u32>().is_err() {
+                continue;
+            }
+            names.entry(id.to_string()).or_insert_with(|| name.to_string());
+        }
+        Self { names }
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let names = Self::parse(&String::from_utf8_lossy(&content));
+        if names.is_empty() {
+            return Err(format!("{}: no name:password:ID entry found", path.display()).into());
+        }
+        Ok(names)
+    }
+
+    // name of the ID, the ID itself when unknown
+    pub fn name<'a>(&'a self, id: &'a str) -> &'a str {
+        self.names.get(id).map_or(id, String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
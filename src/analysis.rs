@@ -0,0 +1,289 @@
+use std::{path::Path, error::Error, collections::{BTreeMap, HashMap, HashSet}, fmt};
+use chrono::{DateTime, Utc, Duration};
+
+use crate::bodyfile::{BodyFile, BodyFileEntry};
+
+const TOP_DIRECTORIES: usize = 5;
+
+pub struct AnalysisOptions {
+    pub burst_threshold: usize, // minimum number of entries sharing the same ctime
+    pub staging_count: usize, // minimum number of births in a directory ...
+    pub staging_window: Duration, // ... within this window
+    pub encryption_count: usize, // minimum number of files renamed to a common extension / high-entropy name ...
+    pub encryption_directories: usize, // ... in at least this number of directories ...
+    pub encryption_window: Duration // ... modified less than this window apart
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub count: usize,
+    pub details: String
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {} .. {}: {} entries ({})",
+            self.rule,
+            self.start.format("%Y-%m-%d %H:%M:%S"),
+            self.end.format("%Y-%m-%d %H:%M:%S"),
+            self.count,
+            self.details)
+    }
+}
+
+pub fn analyze(bodyfile: &BodyFile, options: &AnalysisOptions) -> Vec<Finding> {
+    let mut findings = vec![];
+    findings.extend(mass_deletion(bodyfile, options.burst_threshold));
+    findings.extend(staging(bodyfile, options.staging_count, options.staging_window));
+    findings.extend(encryption(bodyfile, options));
+    findings.sort_by(|a, b| a.start.cmp(&b.start).then(a.rule.cmp(b.rule)).then(a.details.cmp(&b.details)));
+    findings
+}
+
+/*
+Mass-deletion burst: wipers and cleanup scripts touch many entries at once, so an unusually
+high number of entries share an identical ctime. Consecutive seconds over the threshold are merged
+into a single window.
+*/
+fn mass_deletion(bodyfile: &BodyFile, threshold: usize) -> Vec<Finding> {
+    let mut by_ctime : BTreeMap<DateTime<Utc>, Vec<&BodyFileEntry>> = BTreeMap::new();
+    for entry in bodyfile.entries() {
+        if entry.ctime().timestamp() != 0 {
+            by_ctime.entry(entry.ctime()).or_default().push(entry);
+        }
+    }
+
+    let mut findings = vec![];
+    let mut window : Option<(DateTime<Utc>, DateTime<Utc>, Vec<&BodyFileEntry>)> = None;
+    for (ctime, entries) in by_ctime.into_iter().filter(|(_, e)| e.len() >= threshold) {
+        match window.as_mut() {
+            Some((_, end, window_entries)) if ctime - *end <= Duration::seconds(1) => {
+                *end = ctime;
+                window_entries.extend(entries);
+            }
+            _ => {
+                if let Some(w) = window.take() {
+                    findings.push(burst_finding(w));
+                }
+                window = Some((ctime, ctime, entries));
+            }
+        }
+    }
+    if let Some(w) = window {
+        findings.push(burst_finding(w));
+    }
+
+    findings
+}
+
+fn burst_finding((start, end, entries): (DateTime<Utc>, DateTime<Utc>, Vec<&BodyFileEntry>)) -> Finding {
+    let deleted = entries.iter().filter(|e| e.is_deleted()).count();
+    Finding {
+        rule: "mass-deletion",
+        start,
+        end,
+        count: entries.len(),
+        details: format!("{deleted} deleted, top directories: {}", top_directories(entries.iter().map(|e| e.parent())))
+    }
+}
+
+/*
+Mass-copy / staging: a directory receiving many file births within a short window is a potential
+data-staging location (archives before exfiltration, tool drops). Overlapping windows are merged.
+*/
+fn staging(bodyfile: &BodyFile, count: usize, window: Duration) -> Vec<Finding> {
+    let mut births : HashMap<&str, Vec<DateTime<Utc>>> = HashMap::new();
+    for entry in bodyfile.entries() {
+        if entry.crtime().timestamp() != 0 {
+            births.entry(entry.parent()).or_default().push(entry.crtime());
+        }
+    }
+
+    let mut findings = vec![];
+    for (directory, mut dates) in births.into_iter().filter(|(_, d)| d.len() >= count) {
+        dates.sort();
+
+        // sliding window: flag every birth belonging to a window holding at least `count` births
+        // (the births before `marked` are flagged already: each one is marked once)
+        let mut flagged = vec![false; dates.len()];
+        let (mut start, mut marked) = (0, 0);
+        for end in 0..dates.len() {
+            while dates[end] - dates[start] > window {
+                start += 1;
+            }
+            if end - start + 1 >= count {
+                flagged[start.max(marked)..=end].iter_mut().for_each(|f| *f = true);
+                marked = end + 1;
+            }
+        }
+
+        // merge consecutive flagged births into one finding
+        let mut i = 0;
+        while i < dates.len() {
+            if !flagged[i] {
+                i += 1;
+                continue;
+            }
+            let first = i;
+            while i < dates.len() && flagged[i] {
+                i += 1;
+            }
+            findings.push(Finding {
+                rule: "staging",
+                start: dates[first],
+                end: dates[i - 1],
+                count: i - first,
+                details: format!("births in {directory}")
+            });
+        }
+    }
+
+    findings
+}
+
+// outer extensions legitimately appended to another one (setup & update bursts: .dll.mui, .exe.config, .tar.gz, ...)
+const APPENDED_EXTENSIONS: [&str; 24] = ["mui", "config", "manifest", "mum", "cat", "gz", "bz2", "xz", "zst", "js", "css", "map",
+    "json", "xml", "txt", "log", "bak", "old", "tmp", "lnk", "dat", "db", "pdb", "sig"];
+
+/*
+Ransomware encryption burst: files modified & renamed to a common new extension (report.docx.lockbit) or to
+high-entropy names, in many directories within a short window. Modifications less than the window apart are merged,
+the start of a burst being the probable encryption start time, its directories the scope.
+*/
+fn encryption(bodyfile: &BodyFile, options: &AnalysisOptions) -> Vec<Finding> {
+    let mut renamed : HashMap<String, Vec<(DateTime<Utc>, &str)>> = HashMap::new();
+    for entry in bodyfile.entries().iter().filter(|e| !e.is_deleted() && e.mtime().timestamp() != 0) {
+        let name = entry.name().rsplit('/').next().unwrap_or_default();
+        if let Some(key) = encrypted_name(name) {
+            renamed.entry(key).or_default().push((entry.mtime(), entry.parent()));
+        }
+    }
+
+    let mut findings = vec![];
+    for (key, mut files) in renamed.into_iter().filter(|(_, f)| f.len() >= options.encryption_count) {
+        files.sort();
+        let mut start = 0;
+        for end in 1..=files.len() {
+            if end < files.len() && files[end].0 - files[end - 1].0 <= options.encryption_window {
+                continue;
+            }
+            let burst = &files[start..end];
+            let directories : HashSet<&str> = burst.iter().map(|(_, parent)| *parent).collect();
+            if burst.len() >= options.encryption_count && directories.len() >= options.encryption_directories {
+                findings.push(Finding {
+                    rule: "encryption",
+                    start: burst[0].0,
+                    end: burst[burst.len() - 1].0,
+                    count: burst.len(),
+                    details: format!("{key} in {} directories, top directories: {}", directories.len(), top_directories(burst.iter().map(|(_, parent)| *parent)))
+                });
+            }
+            start = end;
+        }
+    }
+
+    findings
+}
+
+// "extension .<ext>" for a name with an extension appended to another one, "high-entropy names" for random names
+fn encrypted_name(name: &str) -> Option<String> {
+    let mut extensions = name.rsplitn(3, '.');
+    if let (Some(extension), Some(_), Some(stem)) = (extensions.next(), extensions.next(), extensions.next()) {
+        let extension = extension.to_lowercase();
+        if !stem.is_empty() && (1..=16).contains(&extension.len()) && extension.chars().all(|c| c.is_ascii_alphanumeric())
+            && !APPENDED_EXTENSIONS.contains(&extension.as_str()) {
+            return Some(format!("extension .{extension}"));
+        }
+    }
+
+    // random names: at least 16 alphanumeric characters, spread over many different characters
+    let stem = name.split('.').next().unwrap_or_default();
+    (stem.len() >= 16 && stem.chars().all(|c| c.is_ascii_alphanumeric()) && entropy(stem) >= 3.5)
+        .then(|| "high-entropy names".to_string())
+}
+
+// Shannon entropy in bits per character
+fn entropy(s: &str) -> f64 {
+    let mut counts : HashMap<char, usize> = HashMap::new();
+    for c in s.chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+    let len = s.chars().count() as f64;
+    counts.values().map(|&n| n as f64 / len).map(|p| -p * p.log2()).sum()
+}
+
+// "<dir> (<count>), ..." for the most affected directories, given the parent directory of each entry/row
+pub fn top_directories<'a>(parents: impl Iterator<Item = &'a str>) -> String {
+    let mut counts : HashMap<&str, usize> = HashMap::new();
+    for parent in parents {
+        *counts.entry(parent).or_default() += 1;
+    }
+
+    let mut counts : Vec<(&str, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    counts.iter()
+        .take(TOP_DIRECTORIES)
+        .map(|(dir, count)| format!("{dir} ({count})"))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// label usable in a file name (rule names, IOC values)
+pub fn file_label(label: &str) -> String {
+    label.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .take(64)
+        .collect()
+}
+
+pub fn write_findings(findings: &[Finding], path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["Rule", "Start", "End", "Count", "Details"])?; // headers
+
+    for finding in findings {
+        writer.write_record([
+            finding.rule,
+            finding.start.format("%Y-%m-%d %H:%M:%S").to_string().as_str(),
+            finding.end.format("%Y-%m-%d %H:%M:%S").to_string().as_str(),
+            finding.count.to_string().as_str(),
+            finding.details.as_str()
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bodyfile::{BodyFileParser, ParseOptions};
+
+    // one file born in `directory` per second of `births`
+    fn bodyfile(directory: &str, births: &[i64]) -> BodyFile {
+        let lines : String = births.iter().enumerate()
+            .map(|(i, birth)| format!("0|{directory}/{i}.bin|{}-128-1|r|0|0|1|0|0|0|{birth}\n", i + 1))
+            .collect();
+        BodyFileParser::build_bytes(lines.into_bytes(), &ParseOptions::default()).expect("bodyfile parsed")
+    }
+
+    #[test]
+    fn staging_merges_overlapping_windows() {
+        // 3 births within 10s, 1 lone, then 3 more within 10s
+        let bodyfile = bodyfile("/tmp/drop", &[1000, 1005, 1010, 2000, 3000, 3001, 3002]);
+        let findings = staging(&bodyfile, 3, Duration::seconds(10));
+        let spans : Vec<(i64, i64, usize)> = findings.iter().map(|f| (f.start.timestamp(), f.end.timestamp(), f.count)).collect();
+        assert_eq!(spans, vec![(1000, 1010, 3), (3000, 3002, 3)]);
+    }
+
+    #[test]
+    fn staging_of_a_large_burst_is_one_finding() {
+        let births : Vec<i64> = (0..50_000).map(|i| 1_000_000 + i / 100).collect();
+        let findings = staging(&bodyfile("/tmp/burst", &births), 10, Duration::seconds(60));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].count, births.len());
+    }
+}
@@ -0,0 +1,95 @@
+use std::{path::Path, error::Error};
+use chrono::{DateTime, Utc, NaiveDate, NaiveDateTime};
+
+/*
+Annotation file: one `start,end,label` date range per line ("patch window", "AV full scan", "public holiday", ...),
+the labels of the ranges a row falls in are written to its Annotation column.
+Dates are YYYY-MM-DD (whole day) or YYYY-MM-DD HH:MM:SS, in UTC.
+*/
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub label: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub hits: usize
+}
+
+// a day alone covers the whole day: from its first second when starting a range, up to its last one when ending it
+fn parse_date(value: &str, end: bool) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Ok(datetime.and_utc());
+    }
+    let date = NaiveDate::parse_from_str(value, "%F").map_err(|_| format!("Invalid annotation date '{value}', expected YYYY-MM-DD [HH:MM:SS]"))?;
+    let time = if end { date.and_hms_nano_opt(23, 59, 59, 999_999_999) } else { date.and_hms_opt(0, 0, 0) };
+    Ok(time.expect("valid time").and_utc())
+}
+
+impl Annotation {
+    fn parse(record: &csv::StringRecord) -> Result<Self, Box<dyn Error>> {
+        let (Some(start), Some(end), Some(label)) = (record.get(0), record.get(1), record.get(2)) else {
+            return Err(format!("Invalid annotation '{}', expected start,end,label", record.iter().collect::<Vec<&str>>().join(",")).into());
+        };
+
+        Ok(Self {
+            label: label.trim().to_string(),
+            start: parse_date(start, false)?,
+            end: parse_date(end, true)?,
+            hits: 0
+        })
+    }
+
+    fn contains(&self, date: DateTime<Utc>) -> bool {
+        self.start <= date && date <= self.end
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Annotations {
+    annotations: Vec<Annotation>
+}
+
+impl Annotations {
+    pub fn from_path(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .comment(Some(b'#'))
+            .from_path(path)?;
+        let annotations = reader.records()
+            .filter(|r| !r.as_ref().is_ok_and(|r| r.get(0).is_some_and(|f| f.trim().eq_ignore_ascii_case("start")))) // header
+            .map(|r| Annotation::parse(&r?))
+            .collect::<Result<Vec<Annotation>, Box<dyn Error>>>()?;
+        Ok(Self { annotations })
+    }
+
+    // labels of the ranges a date falls in, ';' separated
+    pub fn label(&self, date: DateTime<Utc>) -> String {
+        self.annotations.iter()
+            .filter(|a| a.contains(date))
+            .map(|a| a.label.as_str())
+            .collect::<Vec<&str>>()
+            .join(";")
+    }
+
+    // hit counters of the rows falling in each range
+    pub fn count(&mut self, dates: impl Iterator<Item = DateTime<Utc>>) {
+        for date in dates {
+            for annotation in self.annotations.iter_mut().filter(|a| a.contains(date)) {
+                annotation.hits += 1;
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Annotation> {
+        self.annotations.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.annotations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.annotations.is_empty()
+    }
+}
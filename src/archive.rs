@@ -0,0 +1,236 @@
+use std::{path::{Path, PathBuf}, error::Error, fs::File, io::{self, Read, Seek, SeekFrom}};
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+
+/*
+Archive inputs: triage packages arrive as a single zip or tar(.gz) archive holding many bodyfiles. A member is read
+with <archive>::<member> (collection.zip::fls/host1.body), only its bytes being decompressed in memory: the archive is
+never unpacked to disk. Zip members are found through the central directory, tar members by reading the archive through.
+*/
+const SEPARATOR: &str = "::";
+// input of the standard input (no -b, or -b -)
+pub const STDIN: &str = "-";
+const ZIP_END: u32 = 0x06054b50; // end of central directory record
+const ZIP_ENTRY: u32 = 0x02014b50; // central directory header
+const ZIP_LOCAL: u32 = 0x04034b50; // local file header
+const TAR_BLOCK: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Zip,
+    Tar,
+    TarGz
+}
+
+fn kind(path: &Path) -> Option<Kind> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") {
+        Some(Kind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(Kind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Kind::TarGz)
+    } else {
+        None
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    kind(path).is_some()
+}
+
+// named pipes & character devices (/dev/stdin, process substitution): read once as their content comes, never re-opened
+#[cfg(unix)]
+pub fn is_stream(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    path == Path::new(STDIN) || std::fs::metadata(path).is_ok_and(|m| m.file_type().is_fifo() || m.file_type().is_char_device())
+}
+
+#[cfg(not(unix))]
+pub fn is_stream(path: &Path) -> bool {
+    path == Path::new(STDIN)
+}
+
+// (archive, member) of an <archive>::<member> input
+pub fn split(input: &str) -> Option<(&str, &str)> {
+    input.split_once(SEPARATOR).filter(|(archive, member)| is_archive(Path::new(archive)) && !member.is_empty())
+}
+
+// archive of a member input, the input itself otherwise (file hashed by the manifest, ...)
+pub fn evidence(input: &str) -> PathBuf {
+    PathBuf::from(split(input).map_or(input, |(archive, _)| archive))
+}
+
+// content of a file or of an archive member
+pub fn read(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    match split(&path.to_string_lossy()) {
+        Some((archive, member)) => read_member(Path::new(archive), member),
+        None => Ok(std::fs::read(path)?)
+    }
+}
+
+// <archive>::<member> inputs of the files of an archive, in archive order
+pub fn members(path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let names = match kind(path) {
+        Some(Kind::Zip) => zip_entries(&mut File::open(path)?)?.into_iter().map(|e| e.name).filter(|n| !n.ends_with('/')).collect(),
+        Some(kind) => {
+            let mut names = vec![];
+            tar_walk(tar_reader(path, kind)?, |name, _| {
+                names.push(name.to_string());
+                Ok(false)
+            })?;
+            names
+        }
+        None => return Err(format!("{} is not a zip/tar(.gz) archive", path.display()).into())
+    };
+    Ok(names.into_iter().map(|name| format!("{}{SEPARATOR}{name}", path.display())).collect())
+}
+
+pub fn read_member(path: &Path, member: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let not_found = || format!("No member {member} in {}", path.display());
+    match kind(path) {
+        Some(Kind::Zip) => {
+            let mut file = File::open(path)?;
+            let entry = zip_entries(&mut file)?.into_iter().find(|e| e.name == member).ok_or_else(not_found)?;
+            zip_read(&mut file, &entry)
+        }
+        Some(kind) => {
+            let mut content = None;
+            tar_walk(tar_reader(path, kind)?, |name, data| {
+                if name != member {
+                    return Ok(false);
+                }
+                let mut bytes = vec![];
+                data.read_to_end(&mut bytes)?;
+                content = Some(bytes);
+                Ok(true)
+            })?;
+            Ok(content.ok_or_else(not_found)?)
+        }
+        None => Err(format!("{} is not a zip/tar(.gz) archive", path.display()).into())
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+#[derive(Debug)]
+struct ZipEntry {
+    name: String,
+    method: u16, // 0: stored, 8: deflated
+    encrypted: bool,
+    compressed_size: u64,
+    offset: u64 // of the local file header
+}
+
+// central directory, found from the end of central directory record (followed by a comment of up to 64 KB)
+fn zip_entries(file: &mut File) -> Result<Vec<ZipEntry>, Box<dyn Error>> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let tail_len = len.min(22 + 0xFFFF);
+    let mut tail = vec![0; tail_len as usize];
+    file.seek(SeekFrom::Start(len - tail_len))?;
+    file.read_exact(&mut tail)?;
+    let end = (0..tail.len().saturating_sub(21)).rev()
+        .find(|&i| u32_at(&tail, i) == ZIP_END)
+        .ok_or("Invalid zip archive: no end of central directory")?;
+    let (count, size, start) = (u16_at(&tail, end + 10), u32_at(&tail, end + 12), u32_at(&tail, end + 16));
+    if count == 0xFFFF || size == 0xFFFF_FFFF || start == 0xFFFF_FFFF {
+        return Err("ZIP64 archives are not supported".into());
+    }
+
+    let mut directory = vec![0; size as usize];
+    file.seek(SeekFrom::Start(start as u64))?;
+    file.read_exact(&mut directory)?;
+    let mut entries = vec![];
+    let mut i = 0;
+    for _ in 0..count {
+        if i + 46 > directory.len() || u32_at(&directory, i) != ZIP_ENTRY {
+            return Err("Invalid zip archive: corrupted central directory".into());
+        }
+        let (name_len, extra_len, comment_len) = (u16_at(&directory, i + 28) as usize, u16_at(&directory, i + 30) as usize, u16_at(&directory, i + 32) as usize);
+        let name = directory.get(i + 46..i + 46 + name_len).ok_or("Invalid zip archive: truncated central directory")?;
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(name).to_string(),
+            method: u16_at(&directory, i + 10),
+            encrypted: u16_at(&directory, i + 8) & 1 != 0,
+            compressed_size: u32_at(&directory, i + 20) as u64,
+            offset: u32_at(&directory, i + 42) as u64
+        });
+        i += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+fn zip_read(file: &mut File, entry: &ZipEntry) -> Result<Vec<u8>, Box<dyn Error>> {
+    if entry.encrypted {
+        return Err(format!("Zip member {} is encrypted", entry.name).into());
+    }
+    let mut header = [0; 30];
+    file.seek(SeekFrom::Start(entry.offset))?;
+    file.read_exact(&mut header)?;
+    if u32_at(&header, 0) != ZIP_LOCAL {
+        return Err(format!("Invalid zip archive: no local header for {}", entry.name).into());
+    }
+    let data = entry.offset + 30 + u16_at(&header, 26) as u64 + u16_at(&header, 28) as u64;
+    file.seek(SeekFrom::Start(data))?;
+
+    let mut compressed = file.take(entry.compressed_size);
+    let mut content = vec![];
+    match entry.method {
+        0 => { compressed.read_to_end(&mut content)?; }
+        8 => { DeflateDecoder::new(compressed).read_to_end(&mut content)?; }
+        method => return Err(format!("Zip member {}: unsupported compression method {method}", entry.name).into())
+    }
+    Ok(content)
+}
+
+fn tar_reader(path: &Path, kind: Kind) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    let file = io::BufReader::new(File::open(path)?);
+    Ok(match kind {
+        Kind::TarGz => Box::new(MultiGzDecoder::new(file)),
+        _ => Box::new(file)
+    })
+}
+
+// NUL-terminated header field
+fn tar_field(bytes: &[u8]) -> &[u8] {
+    bytes.split(|b| *b == 0).next().unwrap_or_default()
+}
+
+// regular files of a tar stream passed to `visit` with their data until it returns true (ustar & GNU long names)
+fn tar_walk(mut reader: Box<dyn Read>, mut visit: impl FnMut(&str, &mut dyn Read) -> Result<bool, Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+    let mut long_name = None;
+    loop {
+        let mut header = [0; TAR_BLOCK];
+        if reader.read_exact(&mut header).is_err() || header.iter().all(|b| *b == 0) {
+            return Ok(()); // end of archive
+        }
+        let size = std::str::from_utf8(tar_field(&header[124..136]))?.trim();
+        let size = u64::from_str_radix(if size.is_empty() { "0" } else { size }, 8).map_err(|_| "Invalid tar archive: corrupted header")?;
+        let padding = (TAR_BLOCK as u64 - size % TAR_BLOCK as u64) % TAR_BLOCK as u64;
+        let name = long_name.take().unwrap_or_else(|| {
+            let (prefix, name) = (tar_field(&header[345..500]), tar_field(&header[..100]));
+            match (&header[257..262] == b"ustar", prefix.is_empty()) {
+                (true, false) => format!("{}/{}", String::from_utf8_lossy(prefix), String::from_utf8_lossy(name)),
+                _ => String::from_utf8_lossy(name).to_string()
+            }
+        });
+
+        let mut data = (&mut reader).take(size);
+        match header[156] {
+            b'L' => {
+                let mut name = vec![];
+                data.read_to_end(&mut name)?;
+                long_name = Some(String::from_utf8_lossy(tar_field(&name)).to_string());
+            }
+            b'0' | 0 if visit(&name, &mut data)? => return Ok(()),
+            _ => {}
+        }
+        io::copy(&mut data, &mut io::sink())?; // rest of the member
+        io::copy(&mut (&mut reader).take(padding), &mut io::sink())?;
+    }
+}
@@ -0,0 +1,48 @@
+use std::fmt;
+
+use crate::bodyfile::MACB;
+
+/*
+Artifact categorization: well-known paths left by an activity, matched case-insensitively on the file name (either path
+separator). A category only applies to the timestamps revealing the activity: the birth of a dropped service binary, the
+modification of the WMI repository, ...
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Artifact {
+    PsExec, // PsExec-like service binaries (PSEXESVC.exe, PAExec, RemCom)
+    ScheduledTask, // task files (System32\Tasks, .job)
+    Wmi, // WMI repository (event subscriptions, remote WMI)
+    Service // SYSTEM hive holding the services, binaries dropped in the Windows directory
+}
+
+impl fmt::Display for Artifact {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Artifact::PsExec => write!(f, "psexec"),
+            Artifact::ScheduledTask => write!(f, "scheduled-task"),
+            Artifact::Wmi => write!(f, "wmi"),
+            Artifact::Service => write!(f, "service")
+        }
+    }
+}
+
+// remote-execution artifact revealed by a row, if any
+pub(crate) fn categorize(filename: &str, macb: MACB) -> Option<Artifact> {
+    let path = filename.to_lowercase().replace('\\', "/");
+    let name = path.rsplit('/').next().unwrap_or(&path);
+    let born = macb.contains(MACB::BIRTH);
+    let modified = macb.contains(MACB::MODIFIED);
+
+    if born && ["psexesvc", "paexec", "remcomsvc"].iter().any(|n| name.starts_with(n)) {
+        Some(Artifact::PsExec)
+    } else if born && (path.contains("/windows/system32/tasks/") || (path.contains("/windows/tasks/") && name.ends_with(".job"))) {
+        Some(Artifact::ScheduledTask)
+    } else if modified && path.contains("/windows/system32/wbem/repository/") {
+        Some(Artifact::Wmi)
+    } else if (modified && path.ends_with("/windows/system32/config/system"))
+        || (born && name.ends_with(".exe") && path.trim_end_matches(name).ends_with("/windows/")) {
+        Some(Artifact::Service)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,339 @@
+use std::{error::Error, io::{Read, Write}, path::Path, borrow::Cow};
+use chrono::{DateTime, Utc};
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+
+use crate::bodyfile::{BodyFile, DateFilter, OutputFormat};
+use crate::input::{self, JsonEvent};
+use crate::interrupt;
+use crate::sink::{Event, OutputSink};
+
+/*
+Compact binary timeline (--format binary, supertimeline checkpoints): a header, then the records in compressed blocks,
+much faster to write & read back and smaller than CSV/JSON for the intermediate artifacts of a case (shards, per-host
+timelines merged later, cached parse results). Layout, integers little-endian:
+- "MACTIMEB" magic, format version (u32)
+- header: length (u32) & JSON document, {"kind": "events", "columns": [...], "metadata": {...}} for timelines
+- blocks: number of records (u32), compressed length (u32) & the records (bincode), deflate compressed
+- end: a block of 0 records, then 1 if the run was interrupted (partial output), 0 otherwise
+Timelines hold the events of the JSON output (sub-second datetimes included), `mactime convert` turning them into CSV/JSON.
+*/
+pub const MAGIC: &[u8; 8] = b"MACTIMEB";
+const VERSION: u32 = 4;
+const BLOCK_RECORDS: u32 = 65536;
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, Box<dyn Error>> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub struct Writer<W: Write> {
+    writer: W,
+    block: Vec<u8>, // records of the current block, serialized
+    records: u32
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(mut writer: W, header: &serde_json::Value) -> Result<Self, Box<dyn Error>> {
+        let header = serde_json::to_vec(header)?;
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(header.len() as u32).to_le_bytes())?;
+        writer.write_all(&header)?;
+        Ok(Self { writer, block: vec![], records: 0 })
+    }
+
+    pub fn push<T: Serialize>(&mut self, record: &T) -> Result<(), Box<dyn Error>> {
+        bincode::serialize_into(&mut self.block, record)?;
+        self.records += 1;
+        if self.records == BLOCK_RECORDS {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.records == 0 {
+            return Ok(());
+        }
+        let mut encoder = DeflateEncoder::new(vec![], Compression::fast());
+        encoder.write_all(&self.block)?;
+        let compressed = encoder.finish()?;
+        self.writer.write_all(&self.records.to_le_bytes())?;
+        self.writer.write_all(&(compressed.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&compressed)?;
+        self.block.clear();
+        self.records = 0;
+        Ok(())
+    }
+
+    pub fn finish(mut self, partial: bool) -> Result<W, Box<dyn Error>> {
+        self.flush_block()?;
+        self.writer.write_all(&0u32.to_le_bytes())?;
+        self.writer.write_all(&[partial as u8])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+pub struct Reader<R: Read> {
+    reader: R,
+    header: serde_json::Value,
+    block: std::io::Cursor<Vec<u8>>, // records of the current block, decompressed
+    records: u32, // left in the current block
+    partial: bool,
+    done: bool
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(mut reader: R) -> Result<Self, Box<dyn Error>> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic).map_err(|_| "Not a mactime binary timeline")?;
+        if &magic != MAGIC {
+            return Err("Not a mactime binary timeline".into());
+        }
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(format!("Binary timeline format {version} is not supported by this version of mactime (format {VERSION})").into());
+        }
+        let mut header = vec![0; read_u32(&mut reader)? as usize];
+        reader.read_exact(&mut header)?;
+        Ok(Self { reader, header: serde_json::from_slice(&header)?, block: std::io::Cursor::new(vec![]), records: 0, partial: false, done: false })
+    }
+
+    pub fn header(&self) -> &serde_json::Value {
+        &self.header
+    }
+
+    // written by an interrupted run, known once all the records are read
+    pub fn partial(&self) -> bool {
+        self.partial
+    }
+
+    pub fn read<T: DeserializeOwned>(&mut self) -> Result<Option<T>, Box<dyn Error>> {
+        while self.records == 0 {
+            if self.done {
+                return Ok(None);
+            }
+            let records = read_u32(&mut self.reader)?;
+            if records == 0 {
+                let mut partial = [0];
+                self.reader.read_exact(&mut partial)?;
+                self.partial = partial[0] != 0;
+                self.done = true;
+                continue;
+            }
+            let mut compressed = vec![0; read_u32(&mut self.reader)? as usize];
+            self.reader.read_exact(&mut compressed)?;
+            let mut block = vec![];
+            DeflateDecoder::new(compressed.as_slice()).read_to_end(&mut block)?;
+            self.block = std::io::Cursor::new(block);
+            self.records = records;
+        }
+        self.records -= 1;
+        Ok(Some(bincode::deserialize_from(&mut self.block)?))
+    }
+}
+
+// event of a binary timeline, the columns of the JSON output
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Record {
+    seconds: i64,
+    nanoseconds: u32,
+    macb: String,
+    meta: String,
+    size: u64,
+    filename: String,
+    md5: Option<String>,
+    mode: Option<String>,
+    uid: Option<String>,
+    gid: Option<String>,
+    unset: Option<String>,
+    tags: Option<(Vec<String>, u64)>,
+    detection: Option<Option<String>>,
+    source_type: Option<String>,
+    precision: Option<String>,
+    annotation: Option<String>,
+    session: Option<u64>,
+    provenance: Option<(Option<String>, Option<u64>)>,
+    raw: Option<Option<String>>
+}
+
+impl Record {
+    pub(crate) fn datetime(&self) -> DateTime<Utc> {
+        DateTime::<Utc>::from_timestamp(self.seconds, self.nanoseconds).unwrap_or_default()
+    }
+
+    pub(crate) fn event(&self) -> Event<'_> {
+        Event {
+            datetime: self.datetime(),
+            macb: self.macb.clone(),
+            meta: Cow::Borrowed(&self.meta),
+            size: self.size,
+            filename: Cow::Borrowed(&self.filename),
+            md5: self.md5.as_deref(),
+            mode: self.mode.as_deref(),
+            uid: self.uid.as_deref(),
+            gid: self.gid.as_deref(),
+            unset: self.unset.clone(),
+            tags: self.tags.as_ref().map(|(tags, score)| (tags.as_slice(), *score)),
+            detection: self.detection.clone(),
+            source_type: self.source_type.clone(),
+            precision: self.precision.clone(),
+            annotation: self.annotation.clone(),
+            session: self.session.map(|s| s as usize),
+            provenance: self.provenance.as_ref().map(|(file, line)| (file.as_deref(), *line)),
+            raw: self.raw.as_ref().map(|raw| raw.as_deref().map(Cow::Borrowed))
+        }
+    }
+
+    // the event as read back from a JSON output
+    pub(crate) fn into_json_event(self) -> JsonEvent {
+        let datetime = self.datetime();
+        let (tags, score) = self.tags.map_or((None, None), |(tags, score)| (Some(tags), Some(score)));
+        let (source_file, source_line) = self.provenance.unwrap_or_default();
+        JsonEvent {
+            datetime,
+            macb: self.macb,
+            meta: self.meta,
+            size: self.size,
+            filename: self.filename,
+            md5: self.md5,
+            mode: self.mode,
+            uid: self.uid,
+            gid: self.gid,
+            unset: self.unset,
+            tags,
+            score,
+            detection: self.detection,
+            source_type: self.source_type,
+            source_file,
+            source_line
+        }
+    }
+}
+
+impl From<&Event<'_>> for Record {
+    fn from(event: &Event) -> Self {
+        Record {
+            seconds: event.datetime.timestamp(),
+            nanoseconds: event.datetime.timestamp_subsec_nanos(),
+            macb: event.macb.clone(),
+            meta: event.meta.to_string(),
+            size: event.size,
+            filename: event.filename.to_string(),
+            md5: event.md5.map(String::from),
+            mode: event.mode.map(String::from),
+            uid: event.uid.map(String::from),
+            gid: event.gid.map(String::from),
+            unset: event.unset.clone(),
+            tags: event.tags.map(|(tags, score)| (tags.to_vec(), score)),
+            detection: event.detection.clone(),
+            source_type: event.source_type.clone(),
+            precision: event.precision.clone(),
+            annotation: event.annotation.clone(),
+            session: event.session.map(|s| s as u64),
+            provenance: event.provenance.map(|(file, line)| (file.map(String::from), line)),
+            raw: event.raw.as_ref().map(|raw| raw.as_ref().map(|r| r.to_string()))
+        }
+    }
+}
+
+// rows of a binary timeline, as the ones of a JSON output (--input-format events, mactime merge)
+pub(crate) fn parse_events(bodyfile: &mut BodyFile, bytes: &[u8], filter: &Option<DateFilter>) -> Result<(), Box<dyn Error>> {
+    let mut reader = Reader::new(bytes)?;
+    if reader.header()["kind"] != "events" {
+        return Err(format!("Not a binary timeline ({} file)", reader.header()["kind"]).into());
+    }
+    let mut index = 0;
+    while let Some(record) = reader.read::<Record>()? {
+        index += 1;
+        input::add_json_event(bodyfile, record.into_json_event(), None, filter, index);
+    }
+    Ok(())
+}
+
+// mactime convert: binary timeline streamed to the output options of `bodyfile`, columns & metadata kept, rows written
+pub fn convert(reader: impl Read, bodyfile: &BodyFile, output: Option<&Path>, format: OutputFormat) -> Result<usize, Box<dyn Error>> {
+    let mut reader = Reader::new(reader)?;
+    if reader.header()["kind"] != "events" {
+        return Err(format!("Not a binary timeline ({} file)", reader.header()["kind"]).into());
+    }
+    let columns : Vec<String> = serde_json::from_value(reader.header()["columns"].clone())?;
+    let metadata = reader.header()["metadata"].clone();
+
+    let mut written = 0;
+    bodyfile.write_with(output, format, |sink: &mut dyn OutputSink| {
+        sink.write_header(&columns.iter().map(String::as_str).collect::<Vec<&str>>(), &metadata)?;
+        while let Some(record) = reader.read::<Record>()? {
+            if interrupt::interrupted() {
+                break;
+            }
+            sink.write_event(&record.event())?;
+            written += 1;
+        }
+        sink.finish(interrupt::interrupted() || reader.partial())
+    })?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bodyfile::{BodyFileParser, ParseOptions};
+
+    fn written(records: u32, partial: bool) -> Vec<u8> {
+        let mut writer = Writer::new(vec![], &serde_json::json!({"kind": "test"})).expect("header");
+        for i in 0..records {
+            writer.push(&(i, format!("/{i}"))).expect("record");
+        }
+        writer.finish(partial).expect("end")
+    }
+
+    #[test]
+    fn records_across_blocks() {
+        let bytes = written(BLOCK_RECORDS + 10, true);
+        let mut reader = Reader::new(bytes.as_slice()).expect("header");
+        assert_eq!(reader.header()["kind"], "test");
+        let mut count = 0;
+        while let Some((i, name)) = reader.read::<(u32, String)>().expect("record") {
+            assert_eq!((i, name), (count, format!("/{count}")));
+            count += 1;
+        }
+        assert_eq!(count, BLOCK_RECORDS + 10);
+        assert!(reader.partial());
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert!(Reader::new(&b"MACTIME"[..]).is_err());
+        assert!(Reader::new(&b"NOTMACTB\x04\0\0\0"[..]).is_err());
+        let mut other_version = written(1, false);
+        other_version[8] = 99;
+        assert!(Reader::new(other_version.as_slice()).err().is_some_and(|e| e.to_string().contains("format 99")));
+        // truncated in a block: an error, not a panic
+        let bytes = written(100, false);
+        let mut reader = Reader::new(&bytes[..bytes.len() - 20]).expect("header");
+        assert!(reader.read::<(u32, String)>().is_err());
+    }
+
+    #[test]
+    fn timelines_round_trip() {
+        let content = "d41d8cd98f00b204e9800998ecf8427e|/a b|1-128-1|r/rrw-r--r--|1000|100|10|1595291898|1595291899|-1|1595291898\n";
+        let bodyfile = BodyFileParser::build_bytes(content.as_bytes().to_vec(), &ParseOptions::default()).expect("bodyfile parsed");
+        let path = std::env::temp_dir().join(format!("mactime-binary-{}.bin", std::process::id()));
+        bodyfile.generate(Some(&path), OutputFormat::Binary, &serde_json::json!({})).expect("binary written");
+        let bytes = std::fs::read(&path).expect("binary read");
+        std::fs::remove_file(&path).ok();
+        assert!(is_binary(&bytes));
+        let read = BodyFileParser::build_bytes(bytes, &ParseOptions::default()).expect("binary parsed");
+        let rows = |b: &BodyFile| b.meta_events().map(|(meta, name, datetime)| (meta.to_string(), name.to_string(), datetime)).collect::<Vec<_>>();
+        assert_eq!(rows(&read), rows(&bodyfile));
+        assert_eq!(read.datetime_len(), 2);
+    }
+}
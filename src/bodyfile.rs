@@ -1,29 +1,353 @@
-use std::{path::Path, error::Error, collections::HashMap, fmt};
-use chrono::{DateTime, Utc, NaiveDate};
+use std::{path::{Path, PathBuf}, error::Error, collections::{HashMap, HashSet, BTreeMap, BTreeSet, BinaryHeap}, fmt, ops::RangeInclusive, borrow::Cow};
+use chrono::{DateTime, Datelike, Utc, Duration};
 use csv::{StringRecord};
-use serde::Deserialize;
+use serde::{Serialize, Deserialize};
 use bitflags::bitflags;
+use ring::digest;
+
+use crate::accounts::IdNames;
+use crate::annotations::Annotations;
+use crate::archive;
+use crate::binary;
+use crate::deadletter::DeadLetterSink;
+use crate::redact::{Redaction, RedactColumn};
+use crate::retry::IoRetry;
+use crate::encrypt::Encryption;
+use crate::encoding::{self, Encoding};
+use crate::input::{self, InputFormat, InputAdapter, LineCounter};
+use crate::intern::{Interned, Interner};
+use crate::interrupt;
+use crate::ioc::IocList;
+use crate::limits::{FieldLimit, LimitedSink};
+use crate::locale::NumberFormat;
+use crate::ratelimit::RateLimitedSink;
+use crate::matcher::KeywordMatcher;
+use crate::metrics::{self, MeteredSink};
+use crate::sink::{OutputSink, Event, CsvSink, JsonSink, JsonlSink, BinarySink};
+use crate::rules::RuleSet;
+use crate::schema::SchemaLevel;
+use crate::script::{Row, Script};
+use crate::tagcmd::TagCommand;
+use crate::timezone::TimeZone;
+use crate::state::SeenSet;
+use crate::vt::Detection;
 
 pub struct BodyFileParser;
 
+// how an input is read
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub format: InputFormat,
+    pub filter: Option<DateFilter>,
+    pub sort: Option<SortKey>,
+    pub include_raw: bool,
+    pub lines: Option<RangeInclusive<usize>>, // 1-based input line numbers
+    pub shard: Option<(usize, usize)>, // (index, count), 1-based: every count-th line from the index-th one
+    pub delimiter: Option<u8>, // bodyfile column separator, '|' if not specified
+    pub has_header: bool, // bodyfile first line is a header
+    pub on_invalid_timestamp: InvalidTimestamp,
+    pub time_offset: Option<Duration>, // clock skew correction of the input
+    pub epoch_base: EpochBase, // bodyfile timestamps epoch ...
+    pub unit: TimeUnit, // ... & unit
+    pub intern: bool, // identical file names & meta addresses shared across records (--intern)
+    pub io_retry: IoRetry // transient read errors (network shares, ...)
+}
+
+impl ParseOptions {
+    pub(crate) fn selects(&self, line: usize) -> bool {
+        self.lines.as_ref().is_none_or(|lines| lines.contains(&line))
+            && self.shard.is_none_or(|(index, count)| (line - 1) % count == index - 1)
+    }
+}
+
+// only keep a slice of the input lines (bisecting a malformed region, sharding), the CSV header of KAPE inputs is kept
+// the other lines are blanked rather than removed, so that the line numbers (--provenance) are still the ones of the input
+fn select_lines(content: &str, options: &ParseOptions, header: bool) -> String {
+    content.lines()
+        .enumerate()
+        .map(|(i, line)| if (header && i == 0) || options.selects(i + 1) { line } else { "" })
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+// <output>.tmp, in the same directory for the rename to be atomic
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    PathBuf::from(temporary)
+}
+
+// bumped whenever the checkpoint layout (TimestampEntry) changes
+const CHECKPOINT_VERSION: u32 = 7;
+
+// integer timestamps out of the supported dates (i64 overflow, beyond the year 262143, ...), pre-1970 ones being valid
+fn invalid_timestamp(field: &str) -> bool {
+    let digits = field.strip_prefix('-').unwrap_or(field);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+        && field.parse::<i64>().ok().and_then(|t| DateTime::<Utc>::from_timestamp(t, 0)).is_none()
+}
+
+// placeholders written by some tools for an unset timestamp: all bits set (-1), the i64 bounds,
+// and the 32-bit maximums of time_t (0xFFFFFFFF, 0x7FFFFFFF)
+pub(crate) fn sentinel_timestamp(value: i64, unit: TimeUnit) -> bool {
+    matches!(value, -1 | i64::MIN | i64::MAX)
+        || (unit == TimeUnit::Seconds && (value == i64::from(u32::MAX) || value == i64::from(i32::MAX)))
+}
+
+// sentinel in the unit of the input, u64::MAX (-1 written unsigned) included
+fn sentinel_field(field: &str, unit: TimeUnit) -> bool {
+    let field = field.trim();
+    field.parse::<i64>().is_ok_and(|t| sentinel_timestamp(t, unit)) || field.parse::<u64>() == Ok(u64::MAX)
+}
+
+// timestamp in the epoch & unit of the input => Unix seconds, unset (0) & non-integer values kept as is
+fn unix_seconds(field: &str, base: EpochBase, unit: TimeUnit) -> Cow<'_, str> {
+    match field.trim().parse::<i64>() {
+        Ok(timestamp) if timestamp != 0 => match timestamp.div_euclid(unit.per_second()).checked_sub(base.offset()) {
+            Some(seconds) => Cow::Owned(seconds.to_string()),
+            None => Cow::Owned(i64::MIN.to_string()) // before the supported dates, handled by --on-invalid-timestamp
+        },
+        _ => Cow::Borrowed(field)
+    }
+}
+
+// timestamp columns (standard & extended) of a record converted to Unix seconds (--epoch-base, --unit)
+fn unix_timestamps(record: StringRecord, options: &ParseOptions, columns: usize) -> StringRecord {
+    if options.epoch_base == EpochBase::Unix && options.unit == TimeUnit::Seconds {
+        return record;
+    }
+    let mut converted : StringRecord = record.iter().enumerate()
+        .map(|(i, field)| match (i, field.split_once('=')) {
+            (0..=6, _) => Cow::Borrowed(field),
+            (i, _) if i < columns => unix_seconds(field, options.epoch_base, options.unit),
+            (_, Some((label, timestamp))) => Cow::Owned(format!("{label}={}", unix_seconds(timestamp, options.epoch_base, options.unit))),
+            (_, None) => Cow::Borrowed(field)
+        })
+        .collect();
+    converted.set_position(record.position().cloned());
+    converted
+}
+
+// extended bodyfile column: <label>=<unix timestamp> (APFS date_added, fsevents, ...)
+fn parse_extra_timestamp(field: &str) -> Result<(String, DateTime<Utc>), Box<dyn Error>> {
+    let error = || format!("invalid extra timestamp '{field}', expected label=timestamp");
+    let (label, timestamp) = field.split_once('=').ok_or_else(error)?;
+    let date = timestamp.trim().parse::<i64>().ok()
+        .and_then(|t| DateTime::<Utc>::from_timestamp(t, 0))
+        .ok_or_else(error)?;
+    Ok((label.trim().to_string(), date))
+}
+
 impl BodyFileParser {
-    pub fn build(path: &Path, filter: Option<DateFilter>, sorted: bool) -> Result<BodyFile, Box<dyn Error>> {
+    pub fn build(path: &Path, options: &ParseOptions) -> Result<BodyFile, Box<dyn Error>> {
+        let mut bodyfile = match path == Path::new(archive::STDIN) {
+            true => Self::build_reader(std::io::stdin().lock(), options)?, // fls -m ... | mactime
+            false => Self::build_bytes(options.io_retry.read(path)?, options)?
+        };
+        bodyfile.set_input(path);
+        Ok(bodyfile)
+    }
+
+    // input read to its end (standard input, sockets, decompressors, ...)
+    pub fn build_reader(mut reader: impl std::io::Read, options: &ParseOptions) -> Result<BodyFile, Box<dyn Error>> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        Self::build_bytes(bytes, options)
+    }
+
+    // input already in memory (embedded samples, ...)
+    pub fn build_bytes(bytes: Vec<u8>, options: &ParseOptions) -> Result<BodyFile, Box<dyn Error>> {
+        // the date filter applies to the corrected times
+        let filter = match options.time_offset {
+            Some(_) => &None,
+            None => &options.filter
+        };
+
+        let digest = Some(digest::digest(&digest::SHA256, &bytes).as_ref().to_vec());
+
+        // binary timelines (--format binary) are recognized whatever the input format
+        if binary::is_binary(&bytes) {
+            let mut bodyfile = BodyFile { raw: options.include_raw, sourced: true, digest, ..BodyFile::new() };
+            bodyfile.set_intern(options.intern);
+            binary::parse_events(&mut bodyfile, &bytes, filter)?;
+            Self::finish(&mut bodyfile, options);
+            return Ok(bodyfile);
+        }
+
+        let (mut bodyfile, content) = Self::read(bytes, options, options.format == InputFormat::Kape || options.has_header)?;
+        bodyfile.set_intern(options.intern);
+        bodyfile.digest = digest;
+
+        bodyfile.sourced = options.format != InputFormat::Bodyfile;
+        match options.format {
+            InputFormat::Bodyfile => Self::parse_bodyfile(&mut bodyfile, &content, options, filter),
+            InputFormat::Events => input::parse_events(&mut bodyfile, &content, filter),
+            InputFormat::Velociraptor => {
+                input::parse_velociraptor(&mut bodyfile, &content);
+                bodyfile.build_timeline(filter);
+            }
+            InputFormat::Kape => {
+                input::parse_kape(&mut bodyfile, &content);
+                bodyfile.build_timeline(filter);
+            }
+        }
+
+        Self::finish(&mut bodyfile, options);
+        Ok(bodyfile)
+    }
+
+    // input of a registered adapter (options.format is ignored)
+    pub fn build_with(path: &Path, adapter: &dyn InputAdapter, options: &ParseOptions) -> Result<BodyFile, Box<dyn Error>> {
+        let bytes = options.io_retry.read(path)?;
+        let digest = Some(digest::digest(&digest::SHA256, &bytes).as_ref().to_vec());
+        let (mut bodyfile, content) = Self::read(bytes, options, options.has_header)?;
+        bodyfile.set_intern(options.intern);
+        bodyfile.digest = digest;
+
+        let filter = match options.time_offset {
+            Some(_) => &None,
+            None => &options.filter
+        };
+        bodyfile.sourced = true;
+        for event in adapter.parse(&content, options.include_raw)? {
+            if DateFilter::in_range(filter, event.datetime) {
+                bodyfile.add_event(event.into(), false, false);
+            }
+        }
+
+        Self::finish(&mut bodyfile, options);
+        bodyfile.set_input(path);
+        Ok(bodyfile)
+    }
+
+    // lines of an input read by chunks (--stream), numbered from `first_line`, rows of the input of index `input`
+    pub(crate) fn build_chunk(content: &str, options: &ParseOptions, header: bool, first_line: u64, input: usize) -> BodyFile {
+        let mut bodyfile = BodyFile { raw: options.include_raw, ..BodyFile::new() };
+        let filter = match options.time_offset {
+            Some(_) => &None,
+            None => &options.filter
+        };
+        Self::parse_bodyfile(&mut bodyfile, content, &ParseOptions { has_header: header, ..options.clone() }, filter);
+        Self::finish(&mut bodyfile, options);
+        for entry in bodyfile.timeline.iter_mut() {
+            entry.line = entry.line.map(|l| l + first_line - 1);
+            entry.input = Some(input);
+        }
+        bodyfile
+    }
+
+    fn read(bytes: Vec<u8>, options: &ParseOptions, header: bool) -> Result<(BodyFile, String), Box<dyn Error>> {
         let mut bodyfile = BodyFile::new();
+        bodyfile.raw = options.include_raw;
+
+        // detect encoding & transcode to UTF-8
+        let decoded = encoding::decode(bytes);
+        bodyfile.encoding = decoded.encoding;
+        bodyfile.replaced_chars = decoded.replaced;
+
+        let content = match options.lines.is_some() || options.shard.is_some() {
+            true => select_lines(&decoded.content, options, header),
+            false => decoded.content
+        };
+        Ok((bodyfile, content))
+    }
+
+    // clock skew correction & sort
+    fn finish(bodyfile: &mut BodyFile, options: &ParseOptions) {
+        if let Some(offset) = options.time_offset {
+            bodyfile.shift(offset);
+            bodyfile.retain_dates(&options.filter);
+        }
+
+        if let Some(key) = options.sort {
+            bodyfile.sort_timeline(key);
+        }
+    }
 
-        // open file, read line, parse line, add entry, build timeline, sort
+    fn parse_bodyfile(bodyfile: &mut BodyFile, content: &str, options: &ParseOptions, filter: &Option<DateFilter>) {
+        // parse line, add entry, build timeline
         let mut reader = csv::ReaderBuilder::new()
-            .has_headers(true)             // we create them just after
-            .delimiter(b'|')
-            .from_path(path)?;
+            .has_headers(options.has_header) // skipped, we create them just after
+            .flexible(true)                // extended format: extra timestamp columns
+            .delimiter(options.delimiter.unwrap_or(b'|'))
+            .from_reader(content.as_bytes());
 
-        // MD5|name|inode|mode_as_string|UID|GID|size|atime|mtime|ctime|crtime
+        // MD5|name|inode|mode_as_string|UID|GID|size|atime|mtime|ctime|crtime[|label=timestamp...]
         // 0|c:/$MFT|0-128-6|r/rrwxrwxrwx|0|0|1835008|1595291898|1595291898|1595291898|1595291898
+        // 0|/Users/bob/a.dmg|1234|r/rrw-r--r--|501|20|42|1595291898|1595291898|1595291898|1595291898|date_added=1595291898
         let headers = StringRecord::from(vec!["md5", "name", "inode", "mode_as_string", "uid", "gid", "size", "atime", "mtime", "ctime", "crtime"]);
-        reader.set_headers(headers);
+        let mut lines = LineCounter::new(content);
 
-        for record in reader.deserialize() {
+        for record in reader.records() {
+            if interrupt::interrupted() {
+                break;
+            }
+            // fls comments, header lines repeated by concatenated bodyfiles
+            if let Ok(r) = &record {
+                let first = r.get(0).unwrap_or_default().trim();
+                if first.starts_with('#') || first.eq_ignore_ascii_case("md5") {
+                    bodyfile.skipped_lines += 1;
+                    continue;
+                }
+            }
+            // sentinels (0xFFFFFFFF, i64::MAX, ...): unset timestamps, no row of their own (Unset column), extended ones dropped
+            let mut unset = MACB::empty();
+            let record = record.map(|r| {
+                let count = r.iter().enumerate().skip(7)
+                    .filter(|(i, f)| sentinel_field(if *i < headers.len() { f } else { f.split_once('=').map_or(f, |(_, t)| t) }, options.unit))
+                    .count();
+                if count == 0 {
+                    return r;
+                }
+                bodyfile.sentinel_timestamps += count;
+                let mut cleared = StringRecord::new();
+                for (i, field) in r.iter().enumerate() {
+                    match (i, field.split_once('=')) {
+                        (0..=6, _) => cleared.push_field(field),
+                        (i, _) if i < headers.len() && sentinel_field(field, options.unit) => {
+                            unset |= [MACB::ACCESSED, MACB::MODIFIED, MACB::CHANGED, MACB::BIRTH][i - 7]; // atime|mtime|ctime|crtime
+                            cleared.push_field("0");
+                        },
+                        (_, Some((_, timestamp))) if i >= headers.len() && sentinel_field(timestamp, options.unit) => {},
+                        _ => cleared.push_field(field)
+                    }
+                }
+                cleared.set_position(r.position().cloned());
+                cleared
+            });
+            // invalid timestamps: record skipped, or timestamps set to 0 (and rows tagged)
+            let mut flagged = false;
+            let record = match record {
+                Ok(r) => {
+                    let r = unix_timestamps(r, options, headers.len());
+                    let invalid : Vec<usize> = (7..headers.len()).filter(|&i| r.get(i).is_some_and(invalid_timestamp)).collect();
+                    bodyfile.invalid_timestamps += invalid.len();
+                    match options.on_invalid_timestamp {
+                        _ if invalid.is_empty() => Ok(r),
+                        InvalidTimestamp::Skip => continue,
+                        policy => {
+                            flagged = policy == InvalidTimestamp::Flag;
+                            let mut zeroed : StringRecord = r.iter().enumerate().map(|(i, f)| if invalid.contains(&i) { "0" } else { f }).collect();
+                            zeroed.set_position(r.position().cloned());
+                            Ok(zeroed)
+                        }
+                    }
+                }
+                Err(e) => Err(e)
+            };
+            let record = record.map_err(Box::<dyn Error>::from).and_then(|r| {
+                let mut entry : BodyFileEntry = r.deserialize(Some(&headers))?;
+                entry.invalid = flagged;
+                entry.unset = unset;
+                entry.extra = r.iter().skip(headers.len()).map(parse_extra_timestamp).collect::<Result<_, _>>()?;
+                if bodyfile.raw {
+                    entry.raw = r.position().and_then(|p| content[p.byte() as usize..].lines().next()).map(String::from);
+                }
+                entry.line = r.position().map(|p| lines.line(p.byte() as usize));
+                Ok(entry)
+            });
             if let Err(e) = record {
-                println!("Error deserializing record => {e}");
+                bodyfile.invalid_line(format!("record => {e}"));
                 // println!("Error deserializing record:\n\t- Error: {e}\n\t- Raw record: {}", );
                 continue;
             }
@@ -32,18 +356,12 @@ impl BodyFileParser {
             bodyfile.add_entry(record);
         }
 
-        bodyfile.build_timeline(&filter);
-
-        if sorted {
-            bodyfile.sort_timeline();
-        }
-
-        Ok(bodyfile)
+        bodyfile.build_timeline(filter);
     }
 }
 
 bitflags! {
-    struct MACB : u8 {
+    pub(crate) struct MACB : u8 {
         const MODIFIED = 0x1;
         const ACCESSED = 0x2;
         const CHANGED  = 0x4;
@@ -51,6 +369,12 @@ bitflags! {
     }
 }
 
+impl Default for MACB {
+    fn default() -> Self {
+        MACB::empty()
+    }
+}
+
 impl fmt::Display for MACB {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut macb_str = String::new();
@@ -62,18 +386,231 @@ impl fmt::Display for MACB {
     }
 }
 
-#[derive(Debug)]
-struct TimestampEntry {
-    datetime: DateTime<Utc>,
-    macb: MACB,
-    meta: String,
-    size: u64,
-    filename: String
+// checkpoints store the flags as a single byte
+impl Serialize for MACB {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for MACB {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bits = u8::deserialize(deserializer)?;
+        MACB::from_bits(bits).ok_or_else(|| serde::de::Error::custom(format!("invalid MACB flags {bits}")))
+    }
+}
+
+impl std::str::FromStr for MACB {
+    type Err = String;
+
+    // "m.c." => MODIFIED | CHANGED
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let chars : Vec<char> = s.chars().collect();
+        if chars.len() != 4 {
+            return Err(format!("Invalid MACB string '{s}'"));
+        }
+        let mut macb = MACB::empty();
+        for (c, (letter, flag)) in chars.iter().zip([('m', MACB::MODIFIED), ('a', MACB::ACCESSED), ('c', MACB::CHANGED), ('b', MACB::BIRTH)]) {
+            match c {
+                '.' => {}
+                c if *c == letter => macb |= flag,
+                _ => return Err(format!("Invalid MACB string '{s}'"))
+            }
+        }
+        Ok(macb)
+    }
+}
+
+/*
+What a timestamp means: MACB flags for filesystem sources (printed as "m.c."), or a free label for
+other sources ("date_added", "Program Executed", "Key LastWrite", ...) so they coexist in the same timeline.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub(crate) enum TimestampDesc {
+    Macb(MACB),
+    Label(String)
+}
+
+impl fmt::Display for TimestampDesc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimestampDesc::Macb(macb) => write!(f, "{macb}"),
+            TimestampDesc::Label(label) => write!(f, "{label}")
+        }
+    }
+}
+
+impl From<&str> for TimestampDesc {
+    // MACB string when it is one, label otherwise
+    fn from(s: &str) -> Self {
+        s.parse().map(TimestampDesc::Macb).unwrap_or_else(|_| TimestampDesc::Label(s.to_string()))
+    }
+}
+
+// artifact an event comes from, set by each input adapter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SourceType {
+    #[default]
+    File, // filesystem metadata (bodyfile)
+    Mft,
+    UsnJrnl,
+    Evtx,
+    Reg,
+    Pf
+}
+
+impl fmt::Display for SourceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SourceType::File => "FILE",
+            SourceType::Mft => "MFT",
+            SourceType::UsnJrnl => "USNJRNL",
+            SourceType::Evtx => "EVTX",
+            SourceType::Reg => "REG",
+            SourceType::Pf => "PF"
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::str::FromStr for SourceType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "FILE" => Ok(SourceType::File),
+            "MFT" => Ok(SourceType::Mft),
+            "USNJRNL" => Ok(SourceType::UsnJrnl),
+            "EVTX" => Ok(SourceType::Evtx),
+            "REG" => Ok(SourceType::Reg),
+            "PF" => Ok(SourceType::Pf),
+            _ => Err(format!("Unknown source type '{s}' (FILE, MFT, USNJRNL, EVTX, REG, PF)"))
+        }
+    }
+}
+
+// precision of a timestamp, as recorded by its source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    Second, // Unix seconds (bodyfile)
+    SubSecond, // fractional seconds (Velociraptor, KAPE)
+    Filetime // Windows FILETIME, 100 nanoseconds
+}
+
+impl fmt::Display for Precision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Precision::Second => "second",
+            Precision::SubSecond => "sub-second",
+            Precision::Filetime => "100ns"
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct TimestampEntry {
+    pub(crate) datetime: DateTime<Utc>,
+    pub(crate) desc: TimestampDesc,
+    pub(crate) source: SourceType,
+    pub(crate) meta: Interned, // shared with the other rows of the record
+    pub(crate) size: u64,
+    pub(crate) filename: Interned,
+    pub(crate) md5: Interned,
+    pub(crate) mode: Interned, // r/rrwxrwxrwx
+    pub(crate) uid: Interned,
+    pub(crate) gid: Interned,
+    pub(crate) tags: Vec<String>,
+    pub(crate) score: u64, // sum of the weights of the matched rules/IOCs
+    pub(crate) detection: Option<Detection>,
+    pub(crate) raw: Option<String>, // source line (--include-raw)
+    pub(crate) input: Option<usize>, // index in BodyFile::inputs (--provenance)
+    pub(crate) host: Option<usize>, // index in BodyFile::hosts (--group-by host)
+    pub(crate) session: Option<usize>, // 1-based, chronological (--sessions)
+    pub(crate) line: Option<u64>, // line of the input the row comes from, None for binary artifacts
+    pub(crate) unset: MACB // timestamps of the record unset with a sentinel (Unset column)
+}
+
+impl TimestampEntry {
+    fn precision(&self) -> Precision {
+        match self.source {
+            SourceType::Mft | SourceType::UsnJrnl | SourceType::Evtx | SourceType::Reg | SourceType::Pf => Precision::Filetime,
+            SourceType::File if self.datetime.timestamp_subsec_nanos() != 0 => Precision::SubSecond,
+            SourceType::File => Precision::Second
+        }
+    }
+
+    // rows read back from a tagged timeline may already carry the tag
+    fn add_tag(&mut self, tag: String, weight: u32) {
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+            self.score += weight as u64;
+        }
+    }
+
+    fn field_cmp(&self, other: &Self, field: SortField) -> std::cmp::Ordering {
+        match field {
+            SortField::Datetime => self.datetime.cmp(&other.datetime),
+            SortField::Macb => self.desc.cmp(&other.desc),
+            SortField::Meta => meta_key(&self.meta).cmp(&meta_key(&other.meta)),
+            SortField::Size => self.size.cmp(&other.size),
+            SortField::FileName => self.filename.cmp(&other.filename),
+            SortField::Source => self.source.cmp(&other.source),
+            SortField::Score => self.score.cmp(&other.score),
+            SortField::Md5 => self.md5.cmp(&other.md5),
+            SortField::Uid => self.uid.cmp(&other.uid)
+        }
+    }
+
+    // datetime & filename, then all the other columns
+    fn total_cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.cmp(other)
+            .then_with(|| self.source.cmp(&other.source))
+            .then_with(|| self.desc.cmp(&other.desc))
+            .then_with(|| self.meta.cmp(&other.meta))
+            .then_with(|| self.size.cmp(&other.size))
+            .then_with(|| self.md5.cmp(&other.md5))
+            .then_with(|| self.mode.cmp(&other.mode))
+            .then_with(|| self.uid.cmp(&other.uid))
+            .then_with(|| self.gid.cmp(&other.gid))
+            .then_with(|| self.tags.cmp(&other.tags))
+            .then_with(|| self.score.cmp(&other.score))
+            .then_with(|| self.detection.map(|d| (d.malicious, d.total)).cmp(&other.detection.map(|d| (d.malicious, d.total))))
+    }
 }
 
 impl Ord for TimestampEntry {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // first compare datetime, then by filename
         self.datetime.cmp(&other.datetime)
+            .then_with(|| self.filename.cmp(&other.filename))
+    }
+}
+
+// next row of a sorted run (k-way merge): the heap pops the smallest row, of the first run on ties
+struct RunHead {
+    entry: TimestampEntry,
+    run: usize,
+    compare: fn(&TimestampEntry, &TimestampEntry) -> std::cmp::Ordering
+}
+
+impl Ord for RunHead {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.compare)(&other.entry, &self.entry).then_with(|| other.run.cmp(&self.run))
+    }
+}
+
+impl PartialOrd for RunHead {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Eq for RunHead {}
+
+impl PartialEq for RunHead {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
 }
 
@@ -81,23 +618,16 @@ impl Eq for TimestampEntry {}
 
 impl PartialEq for TimestampEntry {
     fn eq(&self, other: &Self) -> bool {
-        self.datetime == other.datetime && self.macb == other.macb && self.meta == other.meta && self.size == other.size && self.filename == other.filename
+        self.datetime == other.datetime && self.desc == other.desc && self.meta == other.meta && self.size == other.size && self.filename == other.filename
     }
 }
 
 impl PartialOrd for TimestampEntry {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        // first compare datetime
-        match self.datetime.partial_cmp(&other.datetime) {
-            Some(core::cmp::Ordering::Equal) => {}
-            ord => return ord,
-        }
-
-        // then by filename
-        self.filename.partial_cmp(&other.filename)
+        Some(self.cmp(other))
 
         /*
-        match self.macb.partial_cmp(&other.macb) {
+        match self.desc.partial_cmp(&other.desc) {
             Some(core::cmp::Ordering::Equal) => {}
             ord => return ord,
         }
@@ -114,167 +644,1768 @@ impl PartialOrd for TimestampEntry {
     }
 }
 
-pub struct DateFilter {
-    start: NaiveDate,
-    end: NaiveDate
+// a file matched by a tagging rule/IOC, with the time range of its matched rows
+#[derive(Debug)]
+pub struct Match {
+    pub tag: String,
+    pub filename: String,
+    pub meta: String,
+    pub md5: String,
+    pub size: u64,
+    pub first: DateTime<Utc>,
+    pub last: DateTime<Utc>,
+    pub count: usize
 }
 
-impl DateFilter {
-    pub fn new(d: [NaiveDate;2]) -> Self {
-        Self {
-            start: d[0],
-            end: d[1]
-        }
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    Json, // single document: {"metadata": {...}, "events": [...]}
+    Jsonl, // one event per line
+    Binary // compressed blocks of events (binary.rs)
 }
-// pub struct DateRange(NaiveDate, NaiveDate)
 
-#[derive(Debug)]
-pub struct BodyFile {
-    entries: Vec<BodyFileEntry>,
-    timeline: Vec<TimestampEntry>
+impl OutputFormat {
+    pub const ALL : [OutputFormat; 4] = [OutputFormat::Csv, OutputFormat::Json, OutputFormat::Jsonl, OutputFormat::Binary];
 }
 
-impl BodyFile {
-    fn new() -> Self {
-        Self {
-            entries: vec![],
-            timeline: vec![]
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "binary" => Ok(OutputFormat::Binary),
+            _ => Err(format!("Unknown output format '{s}'"))
         }
     }
+}
 
-    pub fn file_len(&self) -> usize {
-        self.entries.len()
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputFormat::Csv => write!(f, "CSV"),
+            OutputFormat::Json => write!(f, "JSON"),
+            OutputFormat::Jsonl => write!(f, "JSONL"),
+            OutputFormat::Binary => write!(f, "binary")
+        }
     }
+}
 
-    pub fn datetime_len(&self) -> usize {
-        self.timeline.len()
-    }
+// line terminator of the CSV/JSON outputs, some Windows ingestion tools refuse LF
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf
+}
 
-    fn add_entry(&mut self, entry: BodyFileEntry) {
-        self.entries.push(entry)
+impl LineEnding {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n"
+        }
     }
+}
 
-    fn sort_timeline(&mut self) {
-        self.timeline.sort()
+impl std::str::FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::Crlf),
+            _ => Err(format!("Unknown line ending '{s}'"))
+        }
     }
+}
 
-    fn build_timeline(&mut self, filter: &Option<DateFilter>) {
-        for entry in self.entries.iter() {
-            // for 1 entry, we can have 4 different CSV entries, one for each MACB timestamps
-            
-            // convert MACB into a HashMap : <timestamp> => <macb_string>
-            let mut macb : HashMap<DateTime<Utc>, MACB> = HashMap::new();
-            
-            let current_macb = macb.entry(entry.mtime).or_insert(MACB::MODIFIED);
-            *current_macb |= MACB::MODIFIED;
+// what happens to the bodyfile timestamps that don't fit Unix seconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvalidTimestamp {
+    Skip, // record skipped
+    #[default]
+    Zero, // timestamp set to 0, the other ones of the record kept
+    Flag // timestamp set to 0, rows tagged "invalid-timestamp"
+}
 
-            let current_macb = macb.entry(entry.atime).or_insert(MACB::ACCESSED);
-            *current_macb |= MACB::ACCESSED;
+impl fmt::Display for InvalidTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            InvalidTimestamp::Skip => "skipped",
+            InvalidTimestamp::Zero => "zeroed",
+            InvalidTimestamp::Flag => "flagged"
+        };
+        write!(f, "{name}")
+    }
+}
 
-            let current_macb = macb.entry(entry.ctime).or_insert(MACB::CHANGED);
-            *current_macb |= MACB::CHANGED;
+impl std::str::FromStr for InvalidTimestamp {
+    type Err = String;
 
-            let current_macb = macb.entry(entry.crtime).or_insert(MACB::BIRTH);
-            *current_macb |= MACB::BIRTH;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(InvalidTimestamp::Skip),
+            "zero" => Ok(InvalidTimestamp::Zero),
+            "flag" => Ok(InvalidTimestamp::Flag),
+            _ => Err(format!("Unknown invalid timestamp policy '{s}'"))
+        }
+    }
+}
 
-            // for each entry, generate a record & push it to the timeline
-            for (date, macb) in macb {
+// epoch of the bodyfile timestamps: Unix, or Windows FILETIME / WebKit (Chrome, Edge browser history)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EpochBase {
+    Windows, // 1601-01-01
+    #[default]
+    Unix // 1970-01-01
+}
 
-                let out_of_range = match filter.as_ref() {
-                    Some(date_filter) => {
-                        let naive = date.date().naive_utc();
-                        !(date_filter.start <= naive && naive <= date_filter.end) // filter out entries not in the date range
-                    }
-                    None => false // if date filter is unspecified => all dates are in range
-                };
+impl EpochBase {
+    // seconds from the epoch to the Unix one
+    fn offset(&self) -> i64 {
+        match self {
+            EpochBase::Windows => 11_644_473_600,
+            EpochBase::Unix => 0
+        }
+    }
+}
 
-                if out_of_range {
-                    continue;
-                }
+impl fmt::Display for EpochBase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpochBase::Windows => write!(f, "1601"),
+            EpochBase::Unix => write!(f, "1970")
+        }
+    }
+}
 
-                let timestamp_entry = TimestampEntry { // lots of copies here ...
-                    datetime: date,
-                    macb: macb,
-                    meta: entry.meta.clone(),
-                    size: entry.size,
-                    filename: entry.name.clone()
-                };
+impl std::str::FromStr for EpochBase {
+    type Err = String;
 
-                self.timeline.push(timestamp_entry);
-            }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1601" => Ok(EpochBase::Windows),
+            "1970" => Ok(EpochBase::Unix),
+            _ => Err(format!("Unknown epoch base '{s}' (1601, 1970)"))
         }
     }
+}
 
-    /*enum Destination<'a> {
-        File(&'a Path),
-        StdOut
-    }*/
+// unit of the bodyfile timestamps, sub-second parts being truncated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeUnit {
+    #[default]
+    Seconds,
+    Milliseconds,
+    Microseconds, // WebKit
+    Nanoseconds
+}
 
-    pub fn generate_csv(&self, output: Option<&Path>) -> Result<(), Box<dyn Error>> {
-        // generate CSV from entries
+impl TimeUnit {
+    fn per_second(&self) -> i64 {
+        match self {
+            TimeUnit::Seconds => 1,
+            TimeUnit::Milliseconds => 1_000,
+            TimeUnit::Microseconds => 1_000_000,
+            TimeUnit::Nanoseconds => 1_000_000_000
+        }
+    }
+}
 
-        // build the writer according to `output` => see https://github.com/BurntSushi/rust-csv/issues/196
-        let source_writer : Box<dyn std::io::Write> = match output {
-            Some(p) => {
-                println!("Writing CSV to {}", p.display());
-                Box::new(std::fs::File::create(p)?)
-            },
-            None => Box::new(std::io::stdout()) // write to stdout
+impl fmt::Display for TimeUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TimeUnit::Seconds => "s",
+            TimeUnit::Milliseconds => "ms",
+            TimeUnit::Microseconds => "us",
+            TimeUnit::Nanoseconds => "ns"
         };
+        write!(f, "{name}")
+    }
+}
 
-        let mut _count = 0;
-        let mut writer = csv::Writer::from_writer(source_writer);
-        writer.write_record(&["Datetime", "MACB", "Meta", "Size", "FileName"])?; // headers
-
-        for entry in self.timeline.iter() {
-            // TODO: serialize TimeStampEntry directly !
-            let date_str = format!("{}", entry.datetime.format("%Y-%m-%d %H:%M:%S"));
-            let macb_str = format!("{}", entry.macb);
-            let size_str = format!("{}", entry.size);
-            let result = writer.write_record(&[
-                date_str.as_str(),
-                macb_str.as_str(),
-                entry.meta.as_str(),
-                size_str.as_str(),
-                entry.filename.as_str()
-            ]);
-
-            if let Err(e) = result {
-                eprintln!("Error writing CSV result: {e}");
-                continue;
-            }
+impl std::str::FromStr for TimeUnit {
+    type Err = String;
 
-            _count += 1;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "s" => Ok(TimeUnit::Seconds),
+            "ms" => Ok(TimeUnit::Milliseconds),
+            "us" => Ok(TimeUnit::Microseconds),
+            "ns" => Ok(TimeUnit::Nanoseconds),
+            _ => Err(format!("Unknown time unit '{s}' (s, ms, us, ns)"))
         }
+    }
+}
 
-        //println!("Writing {_count} timestamp records to CSV");
-        writer.flush()?;
+// placeholder of the CSV fields missing from the source (md5, uid, detection, ...), loaders differ in what they expect
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingValue {
+    #[default]
+    Empty,
+    Dash,
+    Null
+}
 
-        Ok(())
+impl MissingValue {
+    pub(crate) fn or(&self, value: Option<String>) -> String {
+        value.unwrap_or_else(|| match self {
+            MissingValue::Empty => String::new(),
+            MissingValue::Dash => "-".to_string(),
+            MissingValue::Null => "NULL".to_string()
+        })
     }
 }
 
-/* bodyfile format : https://wiki.sleuthkit.org/index.php?title=Body_file */
-#[derive(Debug, Deserialize)]
-pub struct BodyFileEntry {
-    name: String, // c:/$MFT
-    #[serde(rename = "inode")]
-    meta: String, // 0-128-6
-    size: u64, // 1835008
-    #[serde(with = "unix_date_format")]
-    atime: DateTime<Utc>, // access
-    #[serde(with = "unix_date_format")]
-    mtime: DateTime<Utc>, // modified
-    #[serde(with = "unix_date_format")]
-    ctime: DateTime<Utc>, // metadata change
-    #[serde(with = "unix_date_format")]
-    crtime: DateTime<Utc>, // creation
+impl std::str::FromStr for MissingValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "empty" => Ok(MissingValue::Empty),
+            "-" => Ok(MissingValue::Dash),
+            "NULL" => Ok(MissingValue::Null),
+            _ => Err(format!("Unknown missing value '{s}'"))
+        }
+    }
 }
 
-mod unix_date_format {
-    use chrono::{DateTime, Utc, NaiveDateTime};
-    use serde::{self, Deserialize, Deserializer};
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Datetime,
+    Path, // full path, then datetime
+    Meta // meta address, then datetime
+}
+
+impl SortKey {
+    // order of the rows for this key
+    fn compare(&self) -> fn(&TimestampEntry, &TimestampEntry) -> std::cmp::Ordering {
+        match self {
+            SortKey::Datetime => TimestampEntry::cmp,
+            // all events of a file/directory are adjacent
+            SortKey::Path => |a, b| a.filename.cmp(&b.filename).then_with(|| a.cmp(b)),
+            // rows of hardlinks/index entries of the same record are adjacent
+            SortKey::Meta => |a, b| meta_key(&a.meta).cmp(&meta_key(&b.meta)).then_with(|| a.cmp(b))
+        }
+    }
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "datetime" => Ok(SortKey::Datetime),
+            "path" => Ok(SortKey::Path),
+            "meta" => Ok(SortKey::Meta),
+            _ => Err(format!("Unknown sort key '{s}'"))
+        }
+    }
+}
+
+// column of a --sort-by key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    Datetime,
+    Macb,
+    Meta,
+    Size,
+    FileName,
+    Source,
+    Score,
+    Md5,
+    Uid
+}
+
+impl fmt::Display for SortField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SortField::Datetime => "datetime",
+            SortField::Macb => "macb",
+            SortField::Meta => "meta",
+            SortField::Size => "size",
+            SortField::FileName => "filename",
+            SortField::Source => "source",
+            SortField::Score => "score",
+            SortField::Md5 => "md5",
+            SortField::Uid => "uid"
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::str::FromStr for SortField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "datetime" => Ok(SortField::Datetime),
+            "macb" => Ok(SortField::Macb),
+            "meta" => Ok(SortField::Meta),
+            "size" => Ok(SortField::Size),
+            "filename" | "path" => Ok(SortField::FileName),
+            "source" => Ok(SortField::Source),
+            "score" => Ok(SortField::Score),
+            "md5" => Ok(SortField::Md5),
+            "uid" => Ok(SortField::Uid),
+            _ => Err(format!("Unknown sort field '{s}' (datetime, macb, meta, size, filename, source, score, md5, uid)"))
+        }
+    }
+}
+
+// composite sort (--sort-by): fields compared in order, each one ascending or descending, e.g. "datetime,filename:desc,size"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortBy {
+    keys: Vec<(SortField, bool)> // (field, descending)
+}
+
+impl fmt::Display for SortBy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keys : Vec<String> = self.keys.iter()
+            .map(|(field, descending)| if *descending { format!("{field}:desc") } else { field.to_string() })
+            .collect();
+        write!(f, "{}", keys.join(","))
+    }
+}
+
+impl std::str::FromStr for SortBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s.split(',')
+            .map(|key| {
+                let (field, order) = key.trim().split_once(':').unwrap_or((key.trim(), "asc"));
+                let descending = match order {
+                    "asc" => false,
+                    "desc" => true,
+                    _ => return Err(format!("Invalid sort order '{order}' of '{field}', expected asc or desc"))
+                };
+                Ok((field.parse()?, descending))
+            })
+            .collect::<Result<Vec<(SortField, bool)>, String>>()?;
+        Ok(Self { keys })
+    }
+}
+
+// grouping of the rows of merged inputs, each group written in its own section or file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Host // one host per input
+}
+
+impl fmt::Display for GroupBy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GroupBy::Host => write!(f, "host")
+        }
+    }
+}
+
+impl std::str::FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "host" => Ok(GroupBy::Host),
+            _ => Err(format!("Unknown grouping '{s}'"))
+        }
+    }
+}
+
+// numeric record number first so that 555-128-1 comes before 1234-128-1
+fn meta_key(meta: &str) -> (u64, &str) {
+    let record = meta.split('-').next().and_then(|r| r.parse().ok()).unwrap_or(u64::MAX);
+    (record, meta)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DateFilter {
+    start: Option<DateTime<Utc>>, // none: open range
+    end: Option<DateTime<Utc>>
+}
+
+impl DateFilter {
+    pub fn new(d: [Option<DateTime<Utc>>;2]) -> Self {
+        Self {
+            start: d[0],
+            end: d[1]
+        }
+    }
+
+    pub(crate) fn in_range(filter: &Option<DateFilter>, date: DateTime<Utc>) -> bool {
+        match filter.as_ref() {
+            Some(date_filter) => {
+                // filter out entries not in the date range, both bounds included
+                date_filter.start.is_none_or(|start| start <= date) && date_filter.end.is_none_or(|end| date <= end)
+            }
+            None => true // if date filter is unspecified => all dates are in range
+        }
+    }
+}
+// pub struct DateRange(NaiveDate, NaiveDate)
+
+// clock skew correction: [<input>=]<+|->HH:MM:SS, applied to all inputs if no input is given
+#[derive(Debug, Clone)]
+pub struct TimeOffset {
+    input: Option<PathBuf>,
+    offset: Duration
+}
+
+impl TimeOffset {
+    pub fn offset(&self) -> Duration {
+        self.offset
+    }
+
+    // offset of an input (or of the files of an input directory), the ones given for this input first
+    pub fn find<'a>(offsets: &'a [TimeOffset], path: &Path) -> Option<&'a TimeOffset> {
+        offsets.iter().find(|o| o.input.as_ref().is_some_and(|input| path.starts_with(input)))
+            .or_else(|| offsets.iter().find(|o| o.input.is_none()))
+    }
+}
+
+impl fmt::Display for TimeOffset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.offset.num_seconds();
+        let sign = if seconds < 0 { '-' } else { '+' };
+        let seconds = seconds.abs();
+        write!(f, "{sign}{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    }
+}
+
+impl std::str::FromStr for TimeOffset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Invalid time offset '{s}', expected [<input>=]<+|->HH:MM:SS (e.g. +02:13:45)");
+        let (input, offset) = match s.rsplit_once('=') {
+            Some((input, offset)) => (Some(PathBuf::from(input)), offset),
+            None => (None, s)
+        };
+        let (sign, offset) = match offset.strip_prefix('-') {
+            Some(offset) => (-1, offset),
+            None => (1, offset.strip_prefix('+').unwrap_or(offset))
+        };
+        let fields = offset.split(':').map(|f| f.parse::<i64>().map_err(|_| error())).collect::<Result<Vec<i64>, String>>()?;
+        let [hours, minutes, seconds] = fields[..] else { return Err(error()) };
+        if minutes >= 60 || seconds >= 60 {
+            return Err(error());
+        }
+        // out of the range of a Duration: an error, not a panic
+        let offset = hours.checked_mul(3600).and_then(|h| h.checked_add(minutes * 60 + seconds)).and_then(|s| Duration::try_seconds(sign * s));
+        Ok(Self { input, offset: offset.ok_or_else(error)? })
+    }
+}
+
+// durations like 30s, 10m, 2h or 1d
+pub fn parse_duration(arg: &str) -> Result<Duration, String> {
+    let error = || format!("Invalid duration '{arg}', expected <number>[s|m|h|d] (e.g. 10m)");
+    let (value, unit) = arg.split_at(arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len()));
+    let value : i64 = value.parse().map_err(|_| error())?;
+    // out of the range of a Duration: an error, not a panic
+    match unit {
+        "" | "s" => Duration::try_seconds(value),
+        "m" => Duration::try_minutes(value),
+        "h" => Duration::try_hours(value),
+        "d" => Duration::try_days(value),
+        _ => None
+    }.ok_or_else(error)
+}
+
+// activity of a user: entries under its profile directory, or owned by its UID
+pub struct UserFilter {
+    name: String,
+    uid: Option<String>
+}
+
+impl UserFilter {
+    // <name> or <name>:<uid>
+    pub fn new(arg: &str) -> Self {
+        let (name, uid) = match arg.split_once(':') {
+            Some((name, uid)) => (name, Some(uid.to_string())),
+            None => (arg, None)
+        };
+        Self {
+            name: name.to_lowercase(),
+            uid
+        }
+    }
+
+    fn matches(&self, filename: &str, uid: &str) -> bool {
+        if self.uid.as_deref() == Some(uid) {
+            return true;
+        }
+
+        // c:/Users/<name>/, /Users/<name>/ (macOS), /home/<name>/, c:/Documents and Settings/<name>/
+        let filename = filename.to_lowercase();
+        let path = match filename.split_once(':') {
+            Some((drive, path)) if drive.len() == 1 => path,
+            _ => filename.as_str()
+        };
+        ["/users/", "/home/", "/documents and settings/"].iter().any(|home| {
+            path.strip_prefix(home)
+                .and_then(|p| p.strip_prefix(self.name.as_str()))
+                .is_some_and(|p| p.is_empty() || p.starts_with('/'))
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BodyFile {
+    entries: Vec<BodyFileEntry>,
+    timeline: Vec<TimestampEntry>,
+    encoding: Encoding,
+    digest: Option<Vec<u8>>, // SHA-256 of the input read, identical inputs found without reading them again
+    replaced_chars: usize,
+    skipped_lines: usize, // comment & header lines
+    invalid_lines: usize, // parse errors
+    invalid_timestamps: usize, // handled by --on-invalid-timestamp
+    sentinel_timestamps: usize, // placeholders of unset timestamps (0xFFFFFFFF, ...), set to 0
+    tagged: bool, // output the Tags column
+    enriched: bool, // output the Detection column
+    sourced: bool, // output the SourceType column (non-bodyfile inputs)
+    raw: bool, // output the Raw column (--include-raw)
+    precision: bool, // output the Precision column
+    annotations: Option<Annotations>, // output the Annotation column (--annotate)
+    session_id: bool, // output the SessionId column (--session-id)
+    provenance: bool, // output the SourceFile & SourceLine columns (--provenance)
+    schema: SchemaLevel, // v2: output the MD5, UID & SourceType columns (--schema)
+    users: Option<IdNames>, // names written in the UID column (--passwd)
+    flag_unset: bool, // output the Unset column (--on-invalid-timestamp flag)
+    groups: Option<IdNames>, // names written in the GID column (--group)
+    inputs: Vec<String>, // files the rows come from
+    group_by: Option<GroupBy>, // output a section per group after the combined rows (--group-by)
+    hosts: Vec<String>, // hosts the rows come from, one per merged input
+    redactions: Vec<Redaction>, // output-time rewrites (--redact)
+    encryption: Option<Encryption>, // output streamed through age (--encrypt)
+    atomic: bool, // output written to a temporary file, then renamed (--no-atomic)
+    tcp_out: Option<String>, // output streamed to host:port instead of stdout (--tcp-out)
+    rate_limit: Option<u64>, // maximum rows per second written to --tcp-out (--rate-limit)
+    dead_letter: Option<PathBuf>, // events rejected by the output, with the error (--dead-letter)
+    field_limits: Vec<FieldLimit>, // maximum sizes of the output columns (--max-field-length)
+    timezone: Option<TimeZone>, // output datetimes in local time (-z), UTC if None
+    extra_timezones: Vec<TimeZone>, // more datetime columns, in the local times of these zones (--extra-tz)
+    number_format: NumberFormat, // Size & sub-seconds of the CSV output
+    line_ending: LineEnding,
+    missing_value: MissingValue,
+    interner: Option<Interner> // strings shared across records & inputs (--intern)
+}
+
+impl Default for BodyFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BodyFile {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![],
+            timeline: vec![],
+            encoding: Encoding::Utf8,
+            digest: None,
+            replaced_chars: 0,
+            skipped_lines: 0,
+            invalid_lines: 0,
+            invalid_timestamps: 0,
+            sentinel_timestamps: 0,
+            tagged: false,
+            enriched: false,
+            sourced: false,
+            raw: false,
+            precision: false,
+            annotations: None,
+            session_id: false,
+            users: None,
+            flag_unset: false,
+            groups: None,
+            provenance: false,
+            schema: SchemaLevel::V1,
+            inputs: vec![],
+            group_by: None,
+            hosts: vec![],
+            redactions: vec![],
+            encryption: None,
+            atomic: true,
+            tcp_out: None,
+            rate_limit: None,
+            dead_letter: None,
+            field_limits: vec![],
+            timezone: None,
+            extra_timezones: vec![],
+            number_format: NumberFormat::default(),
+            line_ending: LineEnding::default(),
+            missing_value: MissingValue::default(),
+            interner: None
+        }
+    }
+
+    pub fn set_number_format(&mut self, number_format: NumberFormat) {
+        self.number_format = number_format;
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    pub fn set_missing_value(&mut self, missing_value: MissingValue) {
+        self.missing_value = missing_value;
+    }
+
+    pub fn set_precision(&mut self, precision: bool) {
+        self.precision = precision;
+    }
+
+    pub fn set_provenance(&mut self, provenance: bool) {
+        self.provenance = provenance;
+    }
+
+    pub fn set_schema(&mut self, schema: SchemaLevel) {
+        self.schema = schema;
+    }
+
+    // file the rows read so far come from, the ones restored with their own (mactime JSON with SourceFile) are kept
+    pub fn set_input(&mut self, path: &Path) {
+        let index = self.inputs.len();
+        let mut used = false;
+        for entry in self.timeline.iter_mut().filter(|e| e.input.is_none()) {
+            entry.input = Some(index);
+            used = true;
+        }
+        if used {
+            self.inputs.push(path.display().to_string());
+        }
+    }
+
+    // files the rows of the chunks come from (--stream), in the order of their indexes
+    pub fn set_inputs(&mut self, inputs: &[&str]) {
+        self.inputs = inputs.iter().map(|i| i.to_string()).collect();
+    }
+
+    // Tags & Score columns in the output, decided before the rows are read (--stream)
+    pub fn set_tagged(&mut self, tagged: bool) {
+        self.tagged = tagged;
+    }
+
+    pub fn set_session_id(&mut self, session_id: bool) {
+        self.session_id = session_id;
+    }
+
+    pub fn set_group_by(&mut self, group_by: GroupBy) {
+        self.group_by = Some(group_by);
+    }
+
+    // host of the rows read so far (merged inputs)
+    pub fn set_host(&mut self, host: &str) {
+        let index = self.hosts.len();
+        let mut used = false;
+        for entry in self.timeline.iter_mut().filter(|e| e.host.is_none()) {
+            entry.host = Some(index);
+            used = true;
+        }
+        if used {
+            self.hosts.push(host.to_string());
+        }
+    }
+
+    pub fn hosts(&self) -> &[String] {
+        &self.hosts
+    }
+
+    // index of an input file, added if not known yet
+    pub(crate) fn input_index(&mut self, file: &str) -> usize {
+        match self.inputs.iter().position(|i| i == file) {
+            Some(index) => index,
+            None => {
+                self.inputs.push(file.to_string());
+                self.inputs.len() - 1
+            }
+        }
+    }
+
+    // index of a host, added if not known yet
+    fn host_index(&mut self, host: &str) -> usize {
+        match self.hosts.iter().position(|h| h == host) {
+            Some(index) => index,
+            None => {
+                self.hosts.push(host.to_string());
+                self.hosts.len() - 1
+            }
+        }
+    }
+
+    pub fn set_redactions(&mut self, redactions: Vec<Redaction>) {
+        self.redactions = redactions;
+    }
+
+    pub fn set_atomic(&mut self, atomic: bool) {
+        self.atomic = atomic;
+    }
+
+    pub fn set_tcp_out(&mut self, address: &str) {
+        self.tcp_out = Some(address.to_string());
+    }
+
+    pub fn set_rate_limit(&mut self, rate: u64) {
+        self.rate_limit = Some(rate);
+    }
+
+    pub fn set_dead_letter(&mut self, path: &Path) {
+        self.dead_letter = Some(path.to_path_buf());
+    }
+
+    pub fn set_field_limits(&mut self, limits: Vec<FieldLimit>) {
+        self.field_limits = limits;
+    }
+
+    // datetimes written in the local time of the zone, UTC ones named as such
+    pub fn set_timezone(&mut self, timezone: TimeZone) {
+        self.timezone = (!timezone.is_utc()).then_some(timezone);
+    }
+
+    pub fn set_extra_timezones(&mut self, timezones: Vec<TimeZone>) {
+        self.extra_timezones = timezones;
+    }
+
+    pub fn set_flag_unset(&mut self, flag_unset: bool) {
+        self.flag_unset = flag_unset;
+    }
+
+    // user & group names of the UID & GID columns, the IDs without a name kept as is
+    pub fn set_id_names(&mut self, users: Option<IdNames>, groups: Option<IdNames>) {
+        self.users = users;
+        self.groups = groups;
+    }
+
+    pub fn set_encryption(&mut self, encryption: Encryption) {
+        self.encryption = Some(encryption);
+    }
+
+    pub fn set_annotations(&mut self, annotations: Annotations) {
+        self.annotations = Some(annotations);
+    }
+
+    pub fn annotations(&self) -> Option<&Annotations> {
+        self.annotations.as_ref()
+    }
+
+    // keep the source line of each row (--include-raw)
+    pub(crate) fn include_raw(&self) -> bool {
+        self.raw
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn digest(&self) -> Option<&[u8]> {
+        self.digest.as_deref()
+    }
+
+    pub fn replaced_chars(&self) -> usize {
+        self.replaced_chars
+    }
+
+    pub fn skipped_lines(&self) -> usize {
+        self.skipped_lines
+    }
+
+    pub fn invalid_lines(&self) -> usize {
+        self.invalid_lines
+    }
+
+    // line of an input that couldn't be read: counted & reported on stderr, stdout being the output
+    pub(crate) fn invalid_line(&mut self, error: impl fmt::Display) {
+        self.invalid_lines += 1;
+        eprintln!("Error deserializing {error}");
+    }
+
+    pub fn invalid_timestamps(&self) -> usize {
+        self.invalid_timestamps
+    }
+
+    pub fn sentinel_timestamps(&self) -> usize {
+        self.sentinel_timestamps
+    }
+
+    pub fn file_len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn datetime_len(&self) -> usize {
+        self.timeline.len()
+    }
+
+    pub fn entries(&self) -> &[BodyFileEntry] {
+        &self.entries
+    }
+
+    // (filename, datetime) of each timeline row
+    pub fn events(&self) -> impl Iterator<Item = (&str, DateTime<Utc>)> {
+        self.timeline.iter().map(|e| (e.filename.as_str(), e.datetime))
+    }
+
+    // (meta address, filename, datetime) of each timeline row
+    pub fn meta_events(&self) -> impl Iterator<Item = (&str, &str, DateTime<Utc>)> {
+        self.timeline.iter().map(|e| (e.meta.as_str(), e.filename.as_str(), e.datetime))
+    }
+
+    // (datetime, MACB flags) of each timeline row, labeled rows have none
+    pub(crate) fn macb_events(&self) -> impl Iterator<Item = (DateTime<Utc>, MACB)> + '_ {
+        self.timeline.iter().map(|e| match &e.desc {
+            TimestampDesc::Macb(macb) => (e.datetime, *macb),
+            TimestampDesc::Label(_) => (e.datetime, MACB::empty())
+        })
+    }
+
+    // (host, datetime, MACB flags, filename) of each timeline row
+    pub(crate) fn host_rows(&self) -> impl Iterator<Item = (Option<&str>, DateTime<Utc>, MACB, &str)> {
+        self.timeline.iter().map(|e| (
+            e.host.map(|h| self.hosts[h].as_str()),
+            e.datetime,
+            match &e.desc {
+                TimestampDesc::Macb(macb) => *macb,
+                TimestampDesc::Label(_) => MACB::empty()
+            },
+            e.filename.as_str()
+        ))
+    }
+
+    // only keep the rows coming from one of `sources`
+    pub fn retain_sources(&mut self, sources: &[SourceType]) {
+        self.sourced = true;
+        self.entries.retain(|e| sources.contains(&e.source));
+        self.timeline.retain(|e| sources.contains(&e.source));
+    }
+
+    // only keep the entries & rows whose name contains one of the keywords (include), or none of them (exclude)
+    pub fn retain_keywords(&mut self, keywords: &KeywordMatcher, include: bool) {
+        self.entries.retain(|e| keywords.is_match(&e.name) == include);
+        self.timeline.retain(|e| keywords.is_match(&e.filename) == include);
+    }
+
+    // only keep the entries & rows of a user
+    pub fn retain_user(&mut self, user: &UserFilter) {
+        self.entries.retain(|e| user.matches(&e.name, &e.uid));
+        self.timeline.retain(|e| user.matches(&e.filename, &e.uid));
+    }
+
+    pub(crate) fn add_entry(&mut self, entry: BodyFileEntry) {
+        self.entries.push(entry)
+    }
+
+    // event read from a previously exported timeline or an artifact, tags & detection are restored
+    pub(crate) fn add_event(&mut self, event: TimestampEntry, tagged: bool, enriched: bool) {
+        self.tagged |= tagged;
+        self.enriched |= enriched;
+        let event = self.share(event);
+        self.timeline.push(event);
+    }
+
+    // strings of a row shared with the identical ones already stored (--intern)
+    fn share(&mut self, mut entry: TimestampEntry) -> TimestampEntry {
+        if let Some(interner) = self.interner.as_mut() {
+            entry.filename = interner.share(entry.filename);
+            entry.meta = interner.share(entry.meta);
+            entry.md5 = interner.share(entry.md5);
+            entry.mode = interner.share(entry.mode);
+            entry.uid = interner.share(entry.uid);
+            entry.gid = interner.share(entry.gid);
+        }
+        entry
+    }
+
+    pub fn set_intern(&mut self, intern: bool) {
+        self.interner = match intern {
+            true => self.interner.take().or_else(|| Some(Interner::default())),
+            false => None
+        };
+    }
+
+    // number of distinct strings stored (--intern)
+    pub fn interned_len(&self) -> usize {
+        self.interner.as_ref().map_or(0, Interner::len)
+    }
+
+    // append the entries & rows of another input (supertimeline)
+    pub fn merge(&mut self, other: BodyFile) {
+        self.sourced = true;
+        self.append(other);
+    }
+
+    // rows of another input of the same kind (repeated -b)
+    pub fn append(&mut self, other: BodyFile) {
+        self.tagged |= other.tagged;
+        self.enriched |= other.enriched;
+        self.sourced |= other.sourced;
+        self.raw |= other.raw;
+        self.replaced_chars += other.replaced_chars;
+        self.skipped_lines += other.skipped_lines;
+        self.invalid_lines += other.invalid_lines;
+        self.invalid_timestamps += other.invalid_timestamps;
+        self.sentinel_timestamps += other.sentinel_timestamps;
+        self.entries.extend(other.entries);
+        self.interner = self.interner.take().or(other.interner);
+        let inputs : Vec<usize> = other.inputs.iter().map(|i| self.input_index(i)).collect();
+        let hosts : Vec<usize> = other.hosts.iter().map(|h| self.host_index(h)).collect();
+        for mut e in other.timeline {
+            e.input = e.input.map(|i| inputs[i]);
+            e.host = e.host.map(|h| hosts[h]);
+            let e = self.share(e);
+            self.timeline.push(e);
+        }
+    }
+
+    // share of the rows of the smallest timeline also found in the other one (same host collected twice)
+    pub fn overlap(&self, other: &BodyFile) -> f64 {
+        let (small, large) = if self.timeline.len() <= other.timeline.len() { (self, other) } else { (other, self) };
+        if small.timeline.is_empty() {
+            return 0.0;
+        }
+        let key = |e: &TimestampEntry| (e.datetime, e.filename.clone(), e.meta.clone(), e.size);
+        let rows : HashSet<_> = large.timeline.iter().map(key).collect();
+        small.timeline.iter().filter(|e| rows.contains(&key(e))).count() as f64 / small.timeline.len() as f64
+    }
+
+    // sort by datetime & remove the rows read several times (same artifact given twice, overlapping bodyfiles), returns the number of removed rows
+    pub fn dedup(&mut self) -> usize {
+        let count = self.timeline.len();
+        self.timeline.sort_by(TimestampEntry::total_cmp);
+        self.timeline.dedup_by(|a, b| a == b && a.source == b.source);
+        count - self.timeline.len()
+    }
+
+    // number of rows of each source
+    pub fn source_counts(&self) -> BTreeMap<SourceType, usize> {
+        let mut counts = BTreeMap::new();
+        for entry in self.timeline.iter() {
+            *counts.entry(entry.source).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    // clock skew correction (--time-offset) of the file records & the rows, the dates shifted out of the supported range
+    // being invalid timestamps set to 0
+    pub fn shift(&mut self, offset: Duration) {
+        let mut invalid = 0;
+        let mut shift = |date: &mut DateTime<Utc>| {
+            *date = date.checked_add_signed(offset).unwrap_or_else(|| {
+                invalid += 1;
+                DateTime::default()
+            });
+        };
+        for entry in self.entries.iter_mut() {
+            for date in [&mut entry.atime, &mut entry.mtime, &mut entry.ctime, &mut entry.crtime].into_iter().chain(entry.extra.iter_mut().map(|(_, date)| date)) {
+                shift(date);
+            }
+        }
+        // rows of the file records counted with them, the ones of event inputs (JSON, binary) have no record
+        let records = !self.entries.is_empty();
+        for entry in self.timeline.iter_mut() {
+            match records {
+                true => entry.datetime = entry.datetime.checked_add_signed(offset).unwrap_or_default(),
+                false => shift(&mut entry.datetime)
+            }
+        }
+        self.invalid_timestamps += invalid;
+    }
+
+    // only keep the rows missing from the output of a previous run over the same evidence (incremental delivery), returns the number of rows removed
+    pub fn retain_new(&mut self, previous: &BodyFile) -> usize {
+        // JSON outputs keep whole seconds
+        let key = |e: &TimestampEntry| (e.datetime.timestamp(), e.desc.to_string(), e.meta.clone(), e.size, e.filename.clone());
+        let known : HashSet<_> = previous.timeline.iter().map(key).collect();
+        let count = self.timeline.len();
+        self.timeline.retain(|e| !known.contains(&key(e)));
+        count - self.timeline.len()
+    }
+
+    // only keep the rows not emitted by a previous run (--state), returns the number of rows removed
+    pub fn retain_unseen(&mut self, state: &SeenSet) -> usize {
+        let count = self.timeline.len();
+        self.timeline.retain(|e| !state.contains(e));
+        count - self.timeline.len()
+    }
+
+    // rows about to be emitted, recorded in the state of the next runs
+    pub fn record_seen(&self, state: &mut SeenSet) {
+        for entry in self.timeline.iter() {
+            state.insert(entry);
+        }
+    }
+
+    // only keep the rows in the date range (filter applied after a checkpoint or a clock correction)
+    pub fn retain_dates(&mut self, filter: &Option<DateFilter>) {
+        self.timeline.retain(|e| DateFilter::in_range(filter, e.datetime));
+    }
+
+    // intermediate artifact of the supertimeline stages: the rows, bincode encoded
+    // fails early if the file system can't hold it, written to a temporary file first so an interrupted run leaves no truncated checkpoint
+    // binary timeline of TimestampEntry blocks (binary.rs), the space check being on the uncompressed rows
+    pub fn write_checkpoint(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let size = bincode::serialized_size(&self.timeline)?;
+        let directory = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let available = fs2::available_space(directory)?;
+        if size > available {
+            return Err(format!("Not enough space in {} for checkpoint {} ({size} bytes needed, {available} available)", directory.display(), path.display()).into());
+        }
+
+        let header = serde_json::json!({
+            "kind": "checkpoint",
+            "checkpoint_version": CHECKPOINT_VERSION,
+            "tagged": self.tagged,
+            "enriched": self.enriched,
+            "raw": self.raw,
+            "inputs": self.inputs,
+            "hosts": self.hosts
+        });
+        let temporary = path.with_extension("tmp");
+        let mut writer = binary::Writer::new(std::io::BufWriter::new(std::fs::File::create(&temporary)?), &header)?;
+        for entry in self.timeline.iter() {
+            writer.push(entry)?;
+        }
+        writer.finish(false)?;
+        std::fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn read_checkpoint(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let invalid = |e: Box<dyn Error>| format!("Invalid checkpoint {}: {e}", path.display());
+        let mut reader = binary::Reader::new(std::io::BufReader::new(std::fs::File::open(path)?)).map_err(invalid)?;
+        let header = reader.header();
+        if header["kind"] != "checkpoint" || header["checkpoint_version"] != CHECKPOINT_VERSION {
+            return Err(format!("Checkpoint {} was written by another version of mactime", path.display()).into());
+        }
+        let strings = |key: &str| header[key].as_array().map(|a| a.iter().filter_map(|s| s.as_str()).map(String::from).collect()).unwrap_or_default();
+        let mut bodyfile = Self {
+            inputs: strings("inputs"),
+            hosts: strings("hosts"),
+            tagged: header["tagged"] == true,
+            enriched: header["enriched"] == true,
+            raw: header["raw"] == true,
+            sourced: true,
+            ..Self::new()
+        };
+        while let Some(entry) = reader.read::<TimestampEntry>().map_err(invalid)? {
+            bodyfile.timeline.push(entry);
+        }
+        Ok(bodyfile)
+    }
+
+    // rows in the same order whatever the input order (--deterministic): every column breaks ties, then `key` (stable sort)
+    pub fn sort_deterministic(&mut self, key: Option<SortKey>) {
+        self.timeline.sort_by(TimestampEntry::total_cmp);
+        if let Some(key) = key {
+            self.sort_timeline(key);
+        }
+    }
+
+    pub fn sort_timeline(&mut self, key: SortKey) {
+        self.timeline.sort_by(key.compare());
+    }
+
+    // total order of the rows, as the supertimeline dedup's
+    pub fn sort_total(&mut self) {
+        self.timeline.sort_by(TimestampEntry::total_cmp);
+    }
+
+    // inputs sorted on their own merged in a single sorted timeline (k-way merge), equal rows keeping the input order
+    pub fn merge_sorted(runs: Vec<BodyFile>, key: SortKey) -> BodyFile {
+        Self::merge_runs(runs, key.compare())
+    }
+
+    // runs of sort_total
+    pub fn merge_total(runs: Vec<BodyFile>) -> BodyFile {
+        Self::merge_runs(runs, TimestampEntry::total_cmp)
+    }
+
+    fn merge_runs(runs: Vec<BodyFile>, compare: fn(&TimestampEntry, &TimestampEntry) -> std::cmp::Ordering) -> BodyFile {
+        let mut merged = BodyFile::new();
+        let mut timelines = vec![];
+        for mut run in runs {
+            let inputs : Vec<usize> = run.inputs.iter().map(|i| merged.input_index(i)).collect();
+            let hosts : Vec<usize> = run.hosts.iter().map(|h| merged.host_index(h)).collect();
+            timelines.push(std::mem::take(&mut run.timeline).into_iter().map(move |mut e| {
+                e.input = e.input.map(|i| inputs[i]);
+                e.host = e.host.map(|h| hosts[h]);
+                e
+            }));
+            merged.append(run); // entries & counters, the timeline being taken
+        }
+
+        merged.timeline.reserve(timelines.iter().map(|t| t.len()).sum());
+        let mut heap = BinaryHeap::new();
+        for (run, timeline) in timelines.iter_mut().enumerate() {
+            heap.extend(timeline.next().map(|entry| RunHead { entry, run, compare }));
+        }
+        while let Some(RunHead { entry, run, .. }) = heap.pop() {
+            heap.extend(timelines[run].next().map(|entry| RunHead { entry, run, compare }));
+            let entry = merged.share(entry);
+            merged.timeline.push(entry);
+        }
+        merged
+    }
+
+    // rows less than `gap` apart belong to the same session, returns the number of sessions
+    pub fn assign_sessions(&mut self, gap: Duration) -> usize {
+        let mut order : Vec<usize> = (0..self.timeline.len()).collect();
+        order.sort_by_key(|&i| self.timeline[i].datetime);
+
+        let mut sessions = 0;
+        let mut previous : Option<DateTime<Utc>> = None;
+        for i in order {
+            let datetime = self.timeline[i].datetime;
+            if previous.is_none_or(|p| datetime - p >= gap) {
+                sessions += 1;
+            }
+            self.timeline[i].session = Some(sessions);
+            previous = Some(datetime);
+        }
+        sessions
+    }
+
+    // (session, filename, datetime) of the rows assigned to a session
+    pub(crate) fn session_rows(&self) -> impl Iterator<Item = (usize, &str, DateTime<Utc>)> {
+        self.timeline.iter().filter_map(|e| e.session.map(|s| (s, e.filename.as_str(), e.datetime)))
+    }
+
+    // stable: rows equal on all the keys keep their order (--deterministic total order, input order)
+    pub fn sort_by(&mut self, sort: &SortBy) {
+        self.timeline.sort_by(|a, b| {
+            sort.keys.iter().fold(std::cmp::Ordering::Equal, |ordering, (field, descending)| {
+                ordering.then_with(|| match descending {
+                    true => a.field_cmp(b, *field).reverse(),
+                    false => a.field_cmp(b, *field)
+                })
+            })
+        });
+    }
+
+    pub(crate) fn build_timeline(&mut self, filter: &Option<DateFilter>) {
+        for entry in self.entries.iter() {
+            // for 1 entry, we can have 4 different CSV entries, one for each MACB timestamps
+            
+            // convert MACB into a HashMap : <timestamp> => <macb_string>
+            // the timestamps unset with a sentinel have no row
+            let mut macb : HashMap<DateTime<Utc>, MACB> = HashMap::new();
+            for (date, flag) in [(entry.mtime, MACB::MODIFIED), (entry.atime, MACB::ACCESSED), (entry.ctime, MACB::CHANGED), (entry.crtime, MACB::BIRTH)] {
+                if !entry.unset.contains(flag) {
+                    *macb.entry(date).or_insert(flag) |= flag;
+                }
+            }
+            // none set: a single "...." row, so that the file still shows up, its Unset column telling why
+            if entry.unset.is_all() && entry.extra.is_empty() {
+                macb.insert(DateTime::default(), MACB::empty());
+            }
+
+            // for each entry, generate a record & push it to the timeline
+            // extra timestamps are labeled events on their own, they don't take part in MACB
+            // stored once, shared by the rows of the record
+            let [filename, meta, md5, mode, uid, gid] = [&entry.name, &entry.meta, &entry.md5, &entry.mode, &entry.uid, &entry.gid].map(|s| match self.interner.as_mut() {
+                Some(interner) => interner.intern(s),
+                None => Interned::from(s.as_str())
+            });
+
+            let macb = macb.into_iter().map(|(date, macb)| (date, TimestampDesc::Macb(macb)));
+            let extra = entry.extra.iter().map(|(label, date)| (*date, TimestampDesc::Label(label.clone())));
+
+            for (date, desc) in macb.chain(extra) {
+
+                if !DateFilter::in_range(filter, date) {
+                    continue;
+                }
+
+                let mut timestamp_entry = TimestampEntry { // lots of copies here ...
+                    datetime: date,
+                    desc,
+                    source: entry.source,
+                    meta: meta.clone(),
+                    size: entry.size,
+                    filename: filename.clone(),
+                    md5: md5.clone(),
+                    mode: mode.clone(),
+                    uid: uid.clone(),
+                    gid: gid.clone(),
+                    tags: vec![],
+                    score: 0,
+                    detection: None,
+                    raw: entry.raw.clone(),
+                    input: None,
+                    host: None,
+                    session: None,
+                    line: entry.line,
+                    unset: entry.unset
+                };
+                if entry.invalid && date.timestamp() == 0 {
+                    timestamp_entry.add_tag("invalid-timestamp".to_string(), 0);
+                    self.tagged = true;
+                }
+
+                self.timeline.push(timestamp_entry);
+            }
+        }
+    }
+
+    // tag timeline rows matching an IOC, hit counters are updated in `iocs`
+    pub fn tag_iocs(&mut self, iocs: &mut IocList) {
+        self.tagged = true;
+        // checked once per file (meta & name): the hit counters count files, not their up to 4 MACB rows
+        let mut matched : HashMap<(Interned, Interned), Vec<usize>> = HashMap::new();
+        for entry in self.timeline.iter_mut() {
+            let indexes = matched.entry((entry.meta.clone(), entry.filename.clone()))
+                .or_insert_with(|| iocs.check(&entry.filename, &entry.md5));
+            for &index in indexes.iter() {
+                entry.add_tag(iocs.get(index).tag(), iocs.get(index).weight);
+            }
+        }
+    }
+
+    // tag timeline rows matching a rule, hit counters are updated in `rules`
+    pub fn tag_rules(&mut self, rules: &mut RuleSet) {
+        self.tagged = true;
+        // checked once per file, as the IOCs
+        let mut matched : HashMap<(Interned, Interned), Vec<usize>> = HashMap::new();
+        for entry in self.timeline.iter_mut() {
+            let indexes = matched.entry((entry.meta.clone(), entry.filename.clone()))
+                .or_insert_with(|| rules.check(&entry.filename));
+            for &index in indexes.iter() {
+                entry.add_tag(rules.get(index).tag.clone(), rules.get(index).weight);
+            }
+        }
+    }
+
+    // tags of an external classifier (--tag-cmd), returns the number of rows tagged
+    pub fn tag_command(&mut self, classifier: &mut TagCommand) -> Result<usize, Box<dyn Error>> {
+        self.tagged = true;
+        let mut tagged = 0;
+        for rows in self.timeline.chunks_mut(classifier.batch()) {
+            let requests : Vec<serde_json::Value> = rows.iter().map(|e| serde_json::json!({
+                "datetime": e.datetime.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true),
+                "macb": e.desc.to_string(),
+                "meta": &*e.meta,
+                "size": e.size,
+                "filename": &*e.filename,
+                "md5": &*e.md5,
+                "uid": &*e.uid,
+                "source_type": e.source.to_string(),
+                "tags": e.tags
+            })).collect();
+            for (entry, tags) in rows.iter_mut().zip(classifier.classify(&requests)?) {
+                if !tags.is_empty() {
+                    tagged += 1;
+                }
+                for (tag, weight) in tags {
+                    entry.add_tag(tag, weight);
+                }
+            }
+        }
+        Ok(tagged)
+    }
+
+    // rows rewritten, tagged or dropped by a row script (--script), returns the number of rows dropped
+    pub fn run_script(&mut self, script: &Script) -> Result<usize, Box<dyn Error>> {
+        self.tagged |= script.tags();
+        let count = self.timeline.len();
+        let mut kept = Vec::with_capacity(count);
+        for mut entry in std::mem::take(&mut self.timeline) {
+            let mut row = Row {
+                datetime: entry.datetime,
+                macb: entry.desc.to_string(),
+                meta: entry.meta.to_string(),
+                size: entry.size,
+                filename: entry.filename.to_string(),
+                md5: entry.md5.to_string(),
+                uid: entry.uid.to_string(),
+                source: entry.source.to_string(),
+                score: entry.score,
+                tags: std::mem::take(&mut entry.tags),
+                added: vec![],
+                dropped: false
+            };
+            script.run(&mut row)?;
+            if row.dropped {
+                continue;
+            }
+            // unchanged strings still shared with the other rows of the record
+            for (value, changed) in [(&mut entry.meta, row.meta), (&mut entry.filename, row.filename), (&mut entry.md5, row.md5), (&mut entry.uid, row.uid)] {
+                if **value != *changed {
+                    *value = Interned::from(changed);
+                }
+            }
+            entry.size = row.size;
+            entry.tags = row.tags;
+            for (tag, weight) in row.added {
+                entry.add_tag(tag, weight);
+            }
+            kept.push(entry);
+        }
+        self.timeline = kept;
+        Ok(count - self.timeline.len())
+    }
+
+    // only keep rows scoring at least `min_score`
+    pub fn retain_min_score(&mut self, min_score: u64) {
+        self.timeline.retain(|e| e.score >= min_score);
+    }
+
+    // rows dated outside a plausible range (overflowed values like year 2106), dropped or tagged "invalid-year", returns their number
+    pub fn check_years(&mut self, years: &RangeInclusive<i32>, flag: bool) -> usize {
+        let count = self.timeline.len();
+        if !flag {
+            self.timeline.retain(|e| years.contains(&e.datetime.year()));
+            return count - self.timeline.len();
+        }
+
+        let mut flagged = 0;
+        for entry in self.timeline.iter_mut().filter(|e| !years.contains(&e.datetime.year())) {
+            entry.add_tag("invalid-year".to_string(), 0);
+            flagged += 1;
+        }
+        self.tagged |= flagged > 0;
+        flagged
+    }
+
+    // rows in the hours of the DST switches of the output zone, tagged "dst-ambiguous" or "dst-skipped", returns their number
+    pub fn flag_dst(&mut self, timezone: &TimeZone) -> usize {
+        let mut flagged = 0;
+        for entry in self.timeline.iter_mut() {
+            if let Some(transition) = timezone.transition(entry.datetime) {
+                entry.add_tag(transition.to_string(), 0);
+                flagged += 1;
+            }
+        }
+        self.tagged |= flagged > 0;
+        flagged
+    }
+
+    // only keep the first `max` rows (--max-output-rows), followed by a warning row telling how many were dropped
+    pub fn truncate(&mut self, max: usize) -> usize {
+        let dropped = self.timeline.len().saturating_sub(max);
+        if dropped == 0 {
+            return 0;
+        }
+        self.timeline.truncate(max);
+        let mut warning = TimestampEntry {
+            datetime: self.timeline.last().map_or(DateTime::<Utc>::UNIX_EPOCH, |e| e.datetime),
+            desc: TimestampDesc::Label("warning".to_string()),
+            source: SourceType::default(),
+            meta: Interned::default(),
+            size: 0,
+            filename: format!("[mactime] output truncated to {max} rows, {dropped} rows dropped (--max-output-rows)").into(),
+            md5: Interned::default(),
+            mode: Interned::default(),
+            uid: Interned::default(),
+            gid: Interned::default(),
+            tags: vec![],
+            score: 0,
+            detection: None,
+            raw: None,
+            input: None,
+            host: None,
+            session: None,
+            line: None,
+            unset: Default::default()
+        };
+        warning.add_tag("truncated".to_string(), 0);
+        self.timeline.push(warning);
+        self.tagged = true;
+        dropped
+    }
+
+    // tag rows of the parent directory of tagged files occurring within `window` of the tagged row
+    pub fn propagate_tags(&mut self, window: Duration) {
+        let mut directories : HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, entry) in self.timeline.iter().enumerate() {
+            directories.entry(entry.filename.trim_end_matches("/.")).or_default().push(i);
+        }
+
+        let mut context = BTreeSet::new();
+        for entry in self.timeline.iter().filter(|e| !e.tags.is_empty()) {
+            let parent = entry.filename.rsplit_once('/').map_or("", |(parent, _)| parent);
+            for &i in directories.get(parent).into_iter().flatten() {
+                if (self.timeline[i].datetime - entry.datetime).abs() <= window {
+                    context.insert(i);
+                }
+            }
+        }
+
+        for i in context {
+            let tags = &mut self.timeline[i].tags;
+            if !tags.iter().any(|t| t == "context") {
+                tags.push("context".to_string());
+            }
+        }
+    }
+
+    // unique MD5s of the timeline rows (bodyfiles use "0" when no hash was computed)
+    pub fn md5s(&self) -> BTreeSet<String> {
+        self.timeline.iter()
+            .filter(|e| e.md5.len() == 32)
+            .map(|e| e.md5.to_lowercase())
+            .collect()
+    }
+
+    pub fn set_detections(&mut self, detections: &BTreeMap<String, Option<Detection>>) {
+        self.enriched = true;
+        for entry in self.timeline.iter_mut() {
+            entry.detection = detections.get(&entry.md5.to_lowercase()).copied().flatten();
+        }
+    }
+
+    // tagged rows aggregated per (tag, file)
+    pub fn matches(&self) -> Vec<Match> {
+        let mut matches : Vec<Match> = vec![];
+        let mut index : HashMap<(&str, &str, &str), usize> = HashMap::new();
+        for entry in self.timeline.iter() {
+            for tag in entry.tags.iter() {
+                let key = (tag.as_str(), entry.filename.as_str(), entry.meta.as_str());
+                match index.get(&key) {
+                    Some(&i) => {
+                        let current = &mut matches[i];
+                        current.first = current.first.min(entry.datetime);
+                        current.last = current.last.max(entry.datetime);
+                        current.count += 1;
+                    }
+                    None => {
+                        index.insert(key, matches.len());
+                        matches.push(Match {
+                            tag: tag.clone(),
+                            filename: entry.filename.to_string(),
+                            meta: entry.meta.to_string(),
+                            md5: entry.md5.to_string(),
+                            size: entry.size,
+                            first: entry.datetime,
+                            last: entry.datetime,
+                            count: 1
+                        });
+                    }
+                }
+            }
+        }
+        matches
+    }
+
+    /*enum Destination<'a> {
+        File(&'a Path),
+        StdOut
+    }*/
+
+    pub fn generate(&self, output: Option<&Path>, format: OutputFormat, metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        self.write_output(output, format, metadata, None)
+    }
+
+    // one file per host in `directory` (--group-dir), named after the host, returns their paths
+    pub fn generate_groups(&self, directory: &Path, format: OutputFormat, metadata: &serde_json::Value) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let extension = match format {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Binary => "bin"
+        };
+        let mut paths = vec![];
+        for (index, host) in self.hosts.iter().enumerate() {
+            let path = directory.join(format!("{host}.{extension}"));
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?; // hosts labeled with a relative path (directory inputs)
+            }
+            let mut metadata = metadata.clone();
+            metadata["host"] = serde_json::json!(host);
+            self.write_output(Some(&path), format, &metadata, Some(index))?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    // rows of `chunks` written as they are parsed (--stream), with the columns & output options of this empty timeline
+    pub fn generate_stream(&self, output: Option<&Path>, format: OutputFormat, metadata: &serde_json::Value, chunks: impl Iterator<Item = Result<BodyFile, Box<dyn Error>>>) -> Result<(), Box<dyn Error>> {
+        self.write_with(output, format, |sink| {
+            sink.write_header(&self.columns(), metadata)?;
+            for chunk in chunks {
+                let chunk = chunk?;
+                self.write_events(sink, chunk.timeline.iter(), false)?;
+                sink.flush()?; // the rows of a chunk readable at once (--follow)
+            }
+            sink.finish(interrupt::interrupted())
+        })
+    }
+
+    // all the rows, or the ones of a host
+    fn write_output(&self, output: Option<&Path>, format: OutputFormat, metadata: &serde_json::Value, host: Option<usize>) -> Result<(), Box<dyn Error>> {
+        self.write_with(output, format, |sink| match host {
+            Some(host) => self.write_rows(sink, self.timeline.iter().filter(|e| e.host == Some(host)), metadata),
+            None => self.write_to(sink, metadata)
+        })
+    }
+
+    // sink of the output options (atomic, encrypted, line ending, ...) the rows are written to by `write`
+    pub(crate) fn write_with(&self, output: Option<&Path>, format: OutputFormat, write: impl FnOnce(&mut dyn OutputSink) -> Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+        // generate CSV/JSON from entries
+
+        // written to <output>.tmp, then renamed once complete: a crash, a full disk or Ctrl-C never leaves a truncated output behind
+        // named pipes are written in place, their reader waiting on them
+        let atomic = self.atomic && !output.is_some_and(archive::is_stream);
+        let target = output.map(|p| match atomic {
+            true => temporary_path(p),
+            false => p.to_path_buf()
+        });
+
+        // build the writer according to `output` => see https://github.com/BurntSushi/rust-csv/issues/196
+        let mut child = self.encryption.as_ref().map(|e| e.spawn(target.as_deref())).transpose()?;
+        let source_writer : Box<dyn std::io::Write> = match (child.as_mut(), target.as_deref()) {
+            (Some(child), _) => {
+                if let Some(p) = output {
+                    eprintln!("Writing encrypted {format} to {}", p.display());
+                }
+                Box::new(std::io::BufWriter::new(child.stdin.take().expect("piped stdin"))) // write to age
+            },
+            (None, Some(p)) => {
+                eprintln!("Writing {format} to {}", output.unwrap_or(p).display());
+                Box::new(std::io::BufWriter::new(std::fs::File::create(p)?))
+            },
+            (None, None) => match &self.tcp_out {
+                Some(address) => {
+                    eprintln!("Streaming {format} to {address}");
+                    let stream = std::net::TcpStream::connect(address).map_err(|e| format!("Connection to {address} failed: {e}"))?;
+                    Box::new(std::io::BufWriter::new(stream)) // raw rows, closed once written
+                }
+                None => Box::new(std::io::stdout()) // write to stdout
+            }
+        };
+
+        let mut sink : Box<dyn OutputSink> = match format {
+            OutputFormat::Csv => Box::new(self.csv_sink(source_writer)),
+            OutputFormat::Json => Box::new(JsonSink::new(source_writer, self.line_ending).with_timezone(self.timezone.clone()).with_extra_timezones(self.extra_timezones.clone())),
+            OutputFormat::Jsonl => Box::new(JsonlSink::new(source_writer, self.line_ending).with_timezone(self.timezone.clone()).with_extra_timezones(self.extra_timezones.clone())),
+            OutputFormat::Binary => Box::new(BinarySink::new(source_writer))
+        };
+        if !self.field_limits.is_empty() {
+            sink = Box::new(LimitedSink::new(sink, &self.field_limits)); // --max-field-length
+        }
+        if let Some(path) = &self.dead_letter {
+            sink = Box::new(DeadLetterSink::new(sink, path)); // rejected events set aside, the others still written
+        }
+        if metrics::enabled() {
+            sink = Box::new(MeteredSink::new(sink)); // --metrics
+        }
+        if let Some(rate) = self.rate_limit {
+            sink = Box::new(RateLimitedSink::new(sink, rate)); // throttling outside of the measured sink latency
+        }
+        let mut result = write(sink.as_mut());
+        drop(sink); // end of the plaintext for age
+        if let Some(child) = child {
+            result = Encryption::wait(child).and(result); // a failing age is the cause of a broken pipe
+        }
+
+        if let (Some(output), Some(target)) = (output, target.filter(|_| atomic)) {
+            match result {
+                // interrupted (Ctrl-C): a partial output is never given the name of a complete one
+                Ok(()) if interrupt::interrupted() => eprintln!("Partial output left in {}", target.display()),
+                Ok(()) => std::fs::rename(&target, output)?,
+                Err(_) => {
+                    let _ = std::fs::remove_file(&target);
+                }
+            }
+        }
+        result
+    }
+
+    // CSV of the rows between `start` and `end` (inclusive), sorted by datetime
+    pub fn generate_context_csv(&self, output: &Path, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<usize, Box<dyn Error>> {
+        let mut rows : Vec<&TimestampEntry> = self.timeline.iter()
+            .filter(|e| start <= e.datetime && e.datetime <= end)
+            .collect();
+        rows.sort();
+
+        let count = rows.len();
+        self.write_rows(&mut self.csv_sink(std::fs::File::create(output)?), rows.into_iter(), &serde_json::Value::Null)?;
+        Ok(count)
+    }
+
+    // first rows in CSV (mactime repl)
+    pub fn preview(&self, writer: impl std::io::Write, rows: usize) -> Result<(), Box<dyn Error>> {
+        self.write_rows(&mut self.csv_sink(writer), self.timeline.iter().take(rows), &serde_json::Value::Null)
+    }
+
+    fn csv_sink<W: std::io::Write>(&self, writer: W) -> CsvSink<W> {
+        CsvSink::new(writer, self.line_ending, self.number_format.clone(), self.missing_value).with_timezone(self.timezone.clone()).with_extra_timezones(self.extra_timezones.clone())
+    }
+
+    // stream the timeline to a sink, built-in or implemented by a library user
+    pub fn write_to(&self, sink: &mut dyn OutputSink, metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        match self.group_by {
+            None => self.write_rows(sink, self.timeline.iter(), metadata),
+            Some(GroupBy::Host) => {
+                // combined rows, then the rows of each host in the same order
+                let columns = self.columns();
+                sink.write_header(&columns, metadata)?;
+                let interrupted = interrupt::interrupted();
+                self.write_events(sink, self.timeline.iter(), interrupted)?;
+                for (index, host) in self.hosts.iter().enumerate() {
+                    sink.write_section(host, &columns)?;
+                    self.write_events(sink, self.timeline.iter().filter(|e| e.host == Some(index)), interrupted)?;
+                }
+                sink.finish(interrupt::interrupted())
+            }
+        }
+    }
+
+    fn write_rows<'a>(&'a self, sink: &mut dyn OutputSink, rows: impl Iterator<Item = &'a TimestampEntry>, metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        sink.write_header(&self.columns(), metadata)?;
+        self.write_events(sink, rows, interrupt::interrupted())?; // while parsing: the rows read so far are all written
+        sink.finish(interrupt::interrupted())
+    }
+
+    fn write_events<'a>(&'a self, sink: &mut dyn OutputSink, rows: impl Iterator<Item = &'a TimestampEntry>, interrupted: bool) -> Result<(), Box<dyn Error>> {
+        for entry in rows {
+            if !interrupted && interrupt::interrupted() {
+                break;
+            }
+            sink.write_event(&self.event(entry))?;
+        }
+        Ok(())
+    }
+
+    // MACB of the sentinel timestamps of each row's record (--schema v3, --on-invalid-timestamp flag)
+    fn unset_column(&self) -> bool {
+        self.schema >= SchemaLevel::V3 || self.flag_unset
+    }
+
+    // columns of the output, optional ones depending on the run
+    fn columns(&self) -> Vec<&'static str> {
+        let mut columns = vec!["Datetime", "MACB", "Meta", "Size", "FileName"];
+        match self.schema {
+            SchemaLevel::V1 => {}
+            SchemaLevel::V2 => columns.extend(["MD5", "UID"]),
+            SchemaLevel::V3 => columns.extend(["MD5", "Mode", "UID", "GID"])
+        }
+        if self.unset_column() {
+            columns.push("Unset");
+        }
+        if self.tagged {
+            columns.extend(["Tags", "Score"]);
+        }
+        if self.enriched {
+            columns.push("Detection");
+        }
+        if self.sourced || self.schema >= SchemaLevel::V2 {
+            columns.push("SourceType");
+        }
+        if self.precision {
+            columns.push("Precision");
+        }
+        if self.annotations.is_some() {
+            columns.push("Annotation");
+        }
+        if self.session_id {
+            columns.push("SessionId");
+        }
+        if self.provenance {
+            columns.extend(["SourceFile", "SourceLine"]);
+        }
+        if self.raw {
+            columns.push("Raw");
+        }
+        columns
+    }
+
+    fn event<'a>(&'a self, entry: &'a TimestampEntry) -> Event<'a> {
+        Event {
+            datetime: entry.datetime,
+            macb: entry.desc.to_string(),
+            meta: Redaction::apply(&self.redactions, RedactColumn::Meta, &entry.meta),
+            size: entry.size,
+            filename: Redaction::apply(&self.redactions, RedactColumn::FileName, &entry.filename),
+            md5: (self.schema >= SchemaLevel::V2).then_some(entry.md5.as_str()),
+            mode: (self.schema >= SchemaLevel::V3).then_some(entry.mode.as_str()),
+            uid: (self.schema >= SchemaLevel::V2).then(|| self.users.as_ref().map_or(entry.uid.as_str(), |u| u.name(&entry.uid))),
+            gid: (self.schema >= SchemaLevel::V3).then(|| self.groups.as_ref().map_or(entry.gid.as_str(), |g| g.name(&entry.gid))),
+            unset: self.unset_column().then(|| if entry.unset.is_empty() { String::new() } else { entry.unset.to_string() }),
+            tags: self.tagged.then_some((entry.tags.as_slice(), entry.score)),
+            detection: self.enriched.then(|| entry.detection.map(|d| d.to_string())),
+            source_type: (self.sourced || self.schema >= SchemaLevel::V2).then(|| entry.source.to_string()),
+            precision: self.precision.then(|| entry.precision().to_string()),
+            annotation: self.annotations.as_ref().map(|a| a.label(entry.datetime)),
+            session: self.session_id.then(|| entry.session.unwrap_or(0)),
+            provenance: self.provenance.then(|| (entry.input.map(|i| self.inputs[i].as_str()), entry.line)),
+            raw: self.raw.then(|| entry.raw.as_deref().map(|r| Redaction::apply(&self.redactions, RedactColumn::Raw, r)))
+        }
+    }
+}
+
+/* bodyfile format : https://wiki.sleuthkit.org/index.php?title=Body_file */
+#[derive(Debug, Clone, Deserialize)]
+pub struct BodyFileEntry {
+    pub(crate) md5: String, // 0 if not computed
+    pub(crate) name: String, // c:/$MFT
+    #[serde(rename = "inode")]
+    pub(crate) meta: String, // 0-128-6
+    #[serde(rename = "mode_as_string")]
+    pub(crate) mode: String, // r/rrwxrwxrwx
+    pub(crate) uid: String, // 0
+    pub(crate) gid: String, // 0
+    pub(crate) size: u64, // 1835008
+    #[serde(with = "unix_date_format")]
+    pub(crate) atime: DateTime<Utc>, // access
+    #[serde(with = "unix_date_format")]
+    pub(crate) mtime: DateTime<Utc>, // modified
+    #[serde(with = "unix_date_format")]
+    pub(crate) ctime: DateTime<Utc>, // metadata change
+    #[serde(with = "unix_date_format")]
+    pub(crate) crtime: DateTime<Utc>, // creation
+    #[serde(skip)]
+    pub(crate) extra: Vec<(String, DateTime<Utc>)>, // extended format: labeled timestamps (date_added, ...)
+    #[serde(skip)]
+    pub(crate) source: SourceType,
+    #[serde(skip)]
+    pub(crate) raw: Option<String>, // source line, kept with --include-raw
+    #[serde(skip)]
+    pub(crate) line: Option<u64>, // line of the input, None for binary artifacts
+    #[serde(skip)]
+    pub(crate) invalid: bool, // invalid timestamps set to 0 & flagged (--on-invalid-timestamp flag)
+    #[serde(skip)]
+    pub(crate) unset: MACB // timestamps set to a sentinel (0xFFFFFFFF, ...) by the source, without a row
+}
+
+impl BodyFileEntry {
+    // fls appends " (deleted)" or " (deleted-realloc)" to the name of unallocated entries
+    pub fn is_deleted(&self) -> bool {
+        self.name.ends_with("(deleted)") || self.name.ends_with("(deleted-realloc)")
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn mtime(&self) -> DateTime<Utc> {
+        self.mtime
+    }
+
+    pub fn ctime(&self) -> DateTime<Utc> {
+        self.ctime
+    }
+
+    pub fn crtime(&self) -> DateTime<Utc> {
+        self.crtime
+    }
+
+    // parent directory, "" for entries at the root
+    pub fn parent(&self) -> &str {
+        self.name.rsplit_once('/').map_or("", |(parent, _)| parent)
+    }
+
+    // all MACB timestamps, unset ones (epoch 0) excluded
+    pub fn timestamps(&self) -> impl Iterator<Item = DateTime<Utc>> {
+        [self.mtime, self.atime, self.ctime, self.crtime].into_iter()
+            .filter(|d| d.timestamp() != 0)
+    }
+}
+
+mod unix_date_format {
+    use chrono::{DateTime, Utc};
+    use serde::{self, Deserialize, Deserializer};
 
     // ` Utc.datetime_from_str(&s, FORMAT).map_err(serde::de::Error::custom)` does not work on negative numbers => so we parse the value to i64 and then use `from_timestamp`
     // const FORMAT: &'static str = "%s";
@@ -286,8 +2417,8 @@ mod unix_date_format {
         let s = String::deserialize(deserializer)?;
         // Utc.datetime_from_str(&s, FORMAT).map_err(serde::de::Error::custom)
         let timestamp: i64 = s.parse().map_err(serde::de::Error::custom)?;
-        let datetime = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(timestamp, 0), Utc);
-        Ok(datetime)
+        DateTime::<Utc>::from_timestamp(timestamp, 0)
+            .ok_or_else(|| serde::de::Error::custom(format!("timestamp out of range: {timestamp}")))
 
         /*
         let result = Utc.datetime_from_str(&s, FORMAT);
@@ -299,3 +2430,174 @@ mod unix_date_format {
         */
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str, options: &ParseOptions) -> BodyFile {
+        BodyFileParser::build_bytes(content.as_bytes().to_vec(), options).expect("bodyfile parsed")
+    }
+
+    // (datetime, MACB) of the rows, in the order of the timeline
+    fn rows(bodyfile: &BodyFile) -> Vec<(i64, String)> {
+        bodyfile.timeline.iter().map(|e| (e.datetime.timestamp(), e.desc.to_string())).collect()
+    }
+
+    #[test]
+    fn invalid_timestamp_keeps_the_record_by_default() {
+        let bodyfile = parse("0|/a|1-128-1|r/rrw-r--r--|0|0|10|99999999999999|1595291898|1595291898|1595291898\n", &ParseOptions::default());
+        assert_eq!(bodyfile.invalid_timestamps(), 1);
+        let rows = rows(&bodyfile);
+        assert!(rows.contains(&(1595291898, "m.cb".to_string())));
+        assert!(rows.contains(&(0, ".a..".to_string())));
+    }
+
+    #[test]
+    fn sentinel_timestamps_have_no_row() {
+        let bodyfile = parse("0|/x.tmp|1-128-1|r/rrw-r--r--|0|0|10|1595291898|4294967295|-1|9223372036854775807\n", &ParseOptions::default());
+        assert_eq!(bodyfile.sentinel_timestamps(), 3);
+        assert_eq!(rows(&bodyfile), vec![(1595291898, ".a..".to_string())]);
+        assert_eq!(bodyfile.timeline[0].unset, MACB::MODIFIED | MACB::CHANGED | MACB::BIRTH);
+    }
+
+    #[test]
+    fn all_sentinel_timestamps_keep_a_single_row() {
+        let bodyfile = parse("0|/all|2-128-1|r|0|0|1|-1|18446744073709551615|-9223372036854775808|2147483647|date_added=4294967295\n", &ParseOptions::default());
+        assert_eq!(rows(&bodyfile), vec![(0, "....".to_string())]);
+        assert!(bodyfile.timeline[0].unset.is_all());
+    }
+
+    #[test]
+    fn sentinels_are_in_the_unit_of_the_input() {
+        // 0xFFFFFFFF milliseconds is a real date, -1 is a sentinel whatever the unit
+        let options = ParseOptions { unit: TimeUnit::Milliseconds, ..Default::default() };
+        let bodyfile = parse("0|/a|1-128-1|r|0|0|1|4294967295|-1|4294967295|4294967295\n", &options);
+        assert_eq!(rows(&bodyfile), vec![(4294967, ".acb".to_string())]);
+    }
+
+    #[test]
+    fn windows_epoch_overflow_is_an_invalid_timestamp() {
+        let options = ParseOptions { epoch_base: EpochBase::Windows, on_invalid_timestamp: InvalidTimestamp::Flag, ..Default::default() };
+        let bodyfile = parse("0|/e|5-128-1|r|0|0|1|-9223372036854775807|13240000000|0|0\n", &options);
+        assert_eq!(bodyfile.invalid_timestamps(), 1);
+    }
+
+    #[test]
+    fn invalid_timestamp_skip_drops_the_record() {
+        let options = ParseOptions { on_invalid_timestamp: InvalidTimestamp::Skip, ..Default::default() };
+        let bodyfile = parse("0|/a|1-128-1|r/rrw-r--r--|0|0|10|99999999999999|1595291898|1595291898|1595291898\n", &options);
+        assert!(bodyfile.timeline.is_empty());
+    }
+
+    #[test]
+    fn pre_1970_timestamps_are_valid() {
+        let bodyfile = parse("0|/old|1-128-1|r|0|0|1|-100|-86400|-86400|-86400\n", &ParseOptions::default());
+        assert_eq!(bodyfile.invalid_timestamps(), 0);
+        let mut rows = rows(&bodyfile);
+        rows.sort();
+        assert_eq!(rows, vec![(-86400, "m.cb".to_string()), (-100, ".a..".to_string())]);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::seconds(90)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::minutes(10)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::days(2)));
+        assert!(parse_duration("10w").is_err());
+        assert!(parse_duration("-1h").is_err());
+        // out of range: an error, not a panic
+        assert!(parse_duration("99999999999999d").is_err());
+        assert!(parse_duration("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn hits_count_files_not_rows() {
+        let content = "d41d8cd98f00b204e9800998ecf8427e|/tmp/evil.exe|1-128-1|r|0|0|1|1|2|3|4\n0|/tmp/other|2-128-1|r|0|0|1|1|1|1|1\n";
+        let mut bodyfile = parse(content, &ParseOptions::default());
+        let mut rules = RuleSet::parse("exe|10|\\.exe$", false).expect("rules");
+        let mut iocs = IocList::parse("evil.exe\nd41d8cd98f00b204e9800998ecf8427e|5").expect("IOCs");
+        bodyfile.tag_rules(&mut rules);
+        bodyfile.tag_iocs(&mut iocs);
+        assert_eq!(rules.iter().map(|r| r.hits).collect::<Vec<usize>>(), vec![1]);
+        assert_eq!(iocs.iter().map(|i| i.hits).collect::<Vec<usize>>(), vec![1, 1]);
+        // the 4 rows of the file are still tagged
+        assert_eq!(bodyfile.timeline.iter().filter(|e| e.score == 16).count(), 4);
+    }
+
+    #[test]
+    fn adapter_errors_are_invalid_lines() {
+        let options = ParseOptions { format: InputFormat::Velociraptor, ..Default::default() };
+        let bodyfile = parse("{\"FullPath\": \"/a\", \"Mtime\": \"2020-07-21T00:38:18Z\"}\n[1]\n{\"Size\": 1}\n", &options);
+        assert_eq!(bodyfile.invalid_lines(), 2);
+        assert_eq!(bodyfile.file_len(), 1);
+        let options = ParseOptions { format: InputFormat::Events, ..Default::default() };
+        assert_eq!(parse("{\"datetime\": 1}\n", &options).invalid_lines(), 1);
+    }
+
+    #[test]
+    fn rows_of_the_same_second_are_sorted_by_filename() {
+        let mut bodyfile = parse("0|/z|1-128-1|r|0|0|1|5|5|5|5\n0|/a|2-128-1|r|0|0|1|5|5|5|5\n0|/m|3-128-1|r|0|0|1|1|1|1|1\n", &ParseOptions::default());
+        bodyfile.sort_timeline(SortKey::Datetime);
+        let names : Vec<&str> = bodyfile.events().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["/m", "/a", "/z"]);
+    }
+
+    #[test]
+    fn sort_keys_break_ties_on_the_datetime() {
+        let content = "0|/b|10-128-1|r|0|0|1|7|7|7|7\n0|/a|9-128-1|r|0|0|1|3|3|3|3\n0|/a|9-128-2|r|0|0|1|1|1|1|1\n0|/b|2-128-1|r|0|0|1|5|5|5|5\n0|/c|9-128-1|r|0|0|1|2|2|2|2\n";
+        let mut bodyfile = parse(content, &ParseOptions::default());
+        bodyfile.sort_timeline(SortKey::Path);
+        let names : Vec<(&str, i64)> = bodyfile.events().map(|(name, datetime)| (name, datetime.timestamp())).collect();
+        assert_eq!(names, vec![("/a", 1), ("/a", 3), ("/b", 5), ("/b", 7), ("/c", 2)]);
+
+        // record numbers compared as numbers, hardlinks by datetime
+        bodyfile.sort_timeline(SortKey::Meta);
+        let metas : Vec<(&str, &str)> = bodyfile.meta_events().map(|(meta, name, _)| (meta, name)).collect();
+        assert_eq!(metas, vec![("2-128-1", "/b"), ("9-128-1", "/c"), ("9-128-1", "/a"), ("9-128-2", "/a"), ("10-128-1", "/b")]);
+    }
+
+    #[test]
+    fn sort_by_compares_the_keys_in_order() {
+        let content = "0|/a|1-128-1|r|0|0|20|5|5|5|5\n0|/b|2-128-1|r|0|0|10|5|5|5|5\n0|/c|3-128-1|r|0|0|20|1|1|1|1\n0|/d|4-128-1|r|0|0|10|5|5|5|5\n";
+        let mut bodyfile = parse(content, &ParseOptions::default());
+        let sort : SortBy = "size:desc,datetime,filename:desc".parse().expect("sort keys");
+        assert_eq!(sort.to_string(), "size:desc,datetime,filename:desc");
+        bodyfile.sort_by(&sort);
+        let names : Vec<&str> = bodyfile.events().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["/c", "/a", "/d", "/b"]);
+
+        // rows equal on all the keys keep their order
+        bodyfile.sort_timeline(SortKey::Datetime);
+        bodyfile.sort_by(&"path".parse().expect("sort keys"));
+        bodyfile.sort_by(&"size".parse().expect("sort keys"));
+        let names : Vec<&str> = bodyfile.events().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["/b", "/d", "/a", "/c"]);
+
+        assert!("size:up".parse::<SortBy>().is_err());
+        assert!("inode".parse::<SortBy>().is_err());
+    }
+
+    #[test]
+    fn identical_inputs_have_the_same_digest() {
+        let content = "0|/a|1-128-1|r|0|0|1|1|1|1|1\n";
+        let (a, b) = (parse(content, &ParseOptions::default()), parse(content, &ParseOptions::default()));
+        assert!(a.digest().is_some() && a.digest() == b.digest());
+        assert_ne!(a.digest(), parse("0|/b|1-128-1|r|0|0|1|1|1|1|1\n", &ParseOptions::default()).digest());
+    }
+
+    #[test]
+    fn time_offsets_out_of_range() {
+        assert_eq!("-01:30:00".parse::<TimeOffset>().map(|o| o.offset()), Ok(Duration::minutes(-90)));
+        assert!("+99999999999999:00:00".parse::<TimeOffset>().is_err());
+        let offset = "+2562047788:00:00".parse::<TimeOffset>().expect("in the range of a Duration").offset();
+        let mut bodyfile = parse("0|/a|1-128-1|r|0|0|1|1595291898|1595291898|1595291898|1595291898\n", &ParseOptions::default());
+        bodyfile.shift(offset);
+        assert_eq!(bodyfile.invalid_timestamps(), 4);
+        // last second supported by chrono, shifted by an hour
+        let mut bodyfile = parse("0|/max|1-128-1|r|0|0|1|8210266876799|0|0|0\n", &ParseOptions::default());
+        bodyfile.shift(Duration::hours(1));
+        assert_eq!(bodyfile.invalid_timestamps(), 1);
+    }
+}
+
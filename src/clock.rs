@@ -0,0 +1,405 @@
+use std::{error::Error, fs, path::Path};
+
+use crate::mft::{u16_at, u32_at};
+
+/*
+Clock settings of the suspect machine (--system-tz-from): the time zone configured on the host, read from its SYSTEM
+registry hive (ControlSet\Control\TimeZoneInformation of the current control set) or from a small JSON file of
+clock settings ({"timezone": "Europe/Paris"}, e.g. written from timedatectl or a collection tool), then mapped to
+its IANA name when it's a Windows one (Romance Standard Time => Europe/Paris, CLDR windowsZones mapping).
+*/
+const HBIN_START: usize = 0x1000;
+const MAX_DEPTH: usize = 8; // nested ri lists
+
+// Windows time zone key names => IANA names (CLDR windowsZones, territory 001)
+const WINDOWS_ZONES: &[(&str, &str)] = &[
+    ("Dateline Standard Time", "Etc/GMT+12"),
+    ("UTC-11", "Etc/GMT+11"),
+    ("Aleutian Standard Time", "America/Adak"),
+    ("Hawaiian Standard Time", "Pacific/Honolulu"),
+    ("Marquesas Standard Time", "Pacific/Marquesas"),
+    ("Alaskan Standard Time", "America/Anchorage"),
+    ("UTC-09", "Etc/GMT+9"),
+    ("Pacific Standard Time (Mexico)", "America/Tijuana"),
+    ("UTC-08", "Etc/GMT+8"),
+    ("Pacific Standard Time", "America/Los_Angeles"),
+    ("US Mountain Standard Time", "America/Phoenix"),
+    ("Mountain Standard Time (Mexico)", "America/Mazatlan"),
+    ("Mountain Standard Time", "America/Denver"),
+    ("Yukon Standard Time", "America/Whitehorse"),
+    ("Central America Standard Time", "America/Guatemala"),
+    ("Central Standard Time", "America/Chicago"),
+    ("Easter Island Standard Time", "Pacific/Easter"),
+    ("Central Standard Time (Mexico)", "America/Mexico_City"),
+    ("Canada Central Standard Time", "America/Regina"),
+    ("SA Pacific Standard Time", "America/Bogota"),
+    ("Eastern Standard Time (Mexico)", "America/Cancun"),
+    ("Eastern Standard Time", "America/New_York"),
+    ("Haiti Standard Time", "America/Port-au-Prince"),
+    ("Cuba Standard Time", "America/Havana"),
+    ("US Eastern Standard Time", "America/Indiana/Indianapolis"),
+    ("Turks And Caicos Standard Time", "America/Grand_Turk"),
+    ("Paraguay Standard Time", "America/Asuncion"),
+    ("Atlantic Standard Time", "America/Halifax"),
+    ("Venezuela Standard Time", "America/Caracas"),
+    ("Central Brazilian Standard Time", "America/Cuiaba"),
+    ("SA Western Standard Time", "America/La_Paz"),
+    ("Pacific SA Standard Time", "America/Santiago"),
+    ("Newfoundland Standard Time", "America/St_Johns"),
+    ("Tocantins Standard Time", "America/Araguaina"),
+    ("E. South America Standard Time", "America/Sao_Paulo"),
+    ("SA Eastern Standard Time", "America/Cayenne"),
+    ("Argentina Standard Time", "America/Argentina/Buenos_Aires"),
+    ("Greenland Standard Time", "America/Nuuk"),
+    ("Montevideo Standard Time", "America/Montevideo"),
+    ("Magallanes Standard Time", "America/Punta_Arenas"),
+    ("Saint Pierre Standard Time", "America/Miquelon"),
+    ("Bahia Standard Time", "America/Bahia"),
+    ("UTC-02", "Etc/GMT+2"),
+    ("Mid-Atlantic Standard Time", "Etc/GMT+2"),
+    ("Azores Standard Time", "Atlantic/Azores"),
+    ("Cape Verde Standard Time", "Atlantic/Cape_Verde"),
+    ("UTC", "Etc/UTC"),
+    ("GMT Standard Time", "Europe/London"),
+    ("Greenwich Standard Time", "Atlantic/Reykjavik"),
+    ("Sao Tome Standard Time", "Africa/Sao_Tome"),
+    ("Morocco Standard Time", "Africa/Casablanca"),
+    ("W. Europe Standard Time", "Europe/Berlin"),
+    ("Central Europe Standard Time", "Europe/Budapest"),
+    ("Romance Standard Time", "Europe/Paris"),
+    ("Central European Standard Time", "Europe/Warsaw"),
+    ("W. Central Africa Standard Time", "Africa/Lagos"),
+    ("Jordan Standard Time", "Asia/Amman"),
+    ("GTB Standard Time", "Europe/Bucharest"),
+    ("Middle East Standard Time", "Asia/Beirut"),
+    ("Egypt Standard Time", "Africa/Cairo"),
+    ("E. Europe Standard Time", "Europe/Chisinau"),
+    ("Syria Standard Time", "Asia/Damascus"),
+    ("West Bank Standard Time", "Asia/Hebron"),
+    ("South Africa Standard Time", "Africa/Johannesburg"),
+    ("FLE Standard Time", "Europe/Kyiv"),
+    ("Israel Standard Time", "Asia/Jerusalem"),
+    ("South Sudan Standard Time", "Africa/Juba"),
+    ("Kaliningrad Standard Time", "Europe/Kaliningrad"),
+    ("Sudan Standard Time", "Africa/Khartoum"),
+    ("Libya Standard Time", "Africa/Tripoli"),
+    ("Namibia Standard Time", "Africa/Windhoek"),
+    ("Arabic Standard Time", "Asia/Baghdad"),
+    ("Turkey Standard Time", "Europe/Istanbul"),
+    ("Arab Standard Time", "Asia/Riyadh"),
+    ("Belarus Standard Time", "Europe/Minsk"),
+    ("Russian Standard Time", "Europe/Moscow"),
+    ("E. Africa Standard Time", "Africa/Nairobi"),
+    ("Volgograd Standard Time", "Europe/Volgograd"),
+    ("Iran Standard Time", "Asia/Tehran"),
+    ("Arabian Standard Time", "Asia/Dubai"),
+    ("Astrakhan Standard Time", "Europe/Astrakhan"),
+    ("Azerbaijan Standard Time", "Asia/Baku"),
+    ("Russia Time Zone 3", "Europe/Samara"),
+    ("Mauritius Standard Time", "Indian/Mauritius"),
+    ("Saratov Standard Time", "Europe/Saratov"),
+    ("Georgian Standard Time", "Asia/Tbilisi"),
+    ("Caucasus Standard Time", "Asia/Yerevan"),
+    ("Afghanistan Standard Time", "Asia/Kabul"),
+    ("West Asia Standard Time", "Asia/Tashkent"),
+    ("Ekaterinburg Standard Time", "Asia/Yekaterinburg"),
+    ("Pakistan Standard Time", "Asia/Karachi"),
+    ("Qyzylorda Standard Time", "Asia/Qyzylorda"),
+    ("India Standard Time", "Asia/Kolkata"),
+    ("Sri Lanka Standard Time", "Asia/Colombo"),
+    ("Nepal Standard Time", "Asia/Kathmandu"),
+    ("Central Asia Standard Time", "Asia/Almaty"),
+    ("Bangladesh Standard Time", "Asia/Dhaka"),
+    ("Omsk Standard Time", "Asia/Omsk"),
+    ("Myanmar Standard Time", "Asia/Yangon"),
+    ("SE Asia Standard Time", "Asia/Bangkok"),
+    ("Altai Standard Time", "Asia/Barnaul"),
+    ("W. Mongolia Standard Time", "Asia/Hovd"),
+    ("North Asia Standard Time", "Asia/Krasnoyarsk"),
+    ("N. Central Asia Standard Time", "Asia/Novosibirsk"),
+    ("Tomsk Standard Time", "Asia/Tomsk"),
+    ("China Standard Time", "Asia/Shanghai"),
+    ("North Asia East Standard Time", "Asia/Irkutsk"),
+    ("Singapore Standard Time", "Asia/Singapore"),
+    ("W. Australia Standard Time", "Australia/Perth"),
+    ("Taipei Standard Time", "Asia/Taipei"),
+    ("Ulaanbaatar Standard Time", "Asia/Ulaanbaatar"),
+    ("Aus Central W. Standard Time", "Australia/Eucla"),
+    ("Transbaikal Standard Time", "Asia/Chita"),
+    ("Tokyo Standard Time", "Asia/Tokyo"),
+    ("North Korea Standard Time", "Asia/Pyongyang"),
+    ("Korea Standard Time", "Asia/Seoul"),
+    ("Yakutsk Standard Time", "Asia/Yakutsk"),
+    ("Cen. Australia Standard Time", "Australia/Adelaide"),
+    ("AUS Central Standard Time", "Australia/Darwin"),
+    ("E. Australia Standard Time", "Australia/Brisbane"),
+    ("AUS Eastern Standard Time", "Australia/Sydney"),
+    ("West Pacific Standard Time", "Pacific/Port_Moresby"),
+    ("Tasmania Standard Time", "Australia/Hobart"),
+    ("Vladivostok Standard Time", "Asia/Vladivostok"),
+    ("Lord Howe Standard Time", "Australia/Lord_Howe"),
+    ("Bougainville Standard Time", "Pacific/Bougainville"),
+    ("Russia Time Zone 10", "Asia/Srednekolymsk"),
+    ("Magadan Standard Time", "Asia/Magadan"),
+    ("Norfolk Standard Time", "Pacific/Norfolk"),
+    ("Sakhalin Standard Time", "Asia/Sakhalin"),
+    ("Central Pacific Standard Time", "Pacific/Guadalcanal"),
+    ("Russia Time Zone 11", "Asia/Kamchatka"),
+    ("New Zealand Standard Time", "Pacific/Auckland"),
+    ("UTC+12", "Etc/GMT-12"),
+    ("Fiji Standard Time", "Pacific/Fiji"),
+    ("Kamchatka Standard Time", "Asia/Kamchatka"),
+    ("Chatham Islands Standard Time", "Pacific/Chatham"),
+    ("UTC+13", "Etc/GMT-13"),
+    ("Tonga Standard Time", "Pacific/Tongatapu"),
+    ("Samoa Standard Time", "Pacific/Apia"),
+    ("Line Islands Standard Time", "Pacific/Kiritimati")
+];
+
+// IANA name of a Windows time zone
+pub fn iana_name(windows: &str) -> Option<&'static str> {
+    WINDOWS_ZONES.iter().find(|(w, _)| w.eq_ignore_ascii_case(windows)).map(|(_, iana)| *iana)
+}
+
+// configured time zone of a host: (as found, IANA name)
+pub fn system_timezone(path: &Path) -> Result<(String, String), Box<dyn Error>> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let found = match bytes.starts_with(b"regf") {
+        true => hive_timezone(&bytes).map_err(|e| format!("{}: {e}", path.display()))?,
+        false => {
+            let settings : serde_json::Value = serde_json::from_slice(&bytes)
+                .map_err(|e| format!("{}: neither a registry hive nor a JSON file of clock settings ({e})", path.display()))?;
+            settings["timezone"].as_str().ok_or_else(|| format!("{}: no \"timezone\" in the clock settings", path.display()))?.to_string()
+        }
+    };
+    let iana = iana_name(&found).map_or_else(|| found.clone(), str::to_string);
+    Ok((found, iana))
+}
+
+// TimeZoneInformation of the current control set of a SYSTEM hive
+fn hive_timezone(hive: &[u8]) -> Result<String, Box<dyn Error>> {
+    let root = u32_at(hive, 0x24).ok_or("Invalid registry hive: truncated base block")?;
+    let root = Key::at(hive, root).ok_or("Invalid registry hive: no root key")?;
+    let select = root.subkey("Select").ok_or("no Select key, not a SYSTEM hive")?;
+    let current = select.value("Current").and_then(|v| u32_at(&v.1, 0)).ok_or("no Select\\Current value")?;
+    let information = root.subkey(&format!("ControlSet{current:03}"))
+        .and_then(|set| set.subkey("Control"))
+        .and_then(|control| control.subkey("TimeZoneInformation"))
+        .ok_or_else(|| format!("no ControlSet{current:03}\\Control\\TimeZoneInformation key"))?;
+
+    // Vista+: TimeZoneKeyName, XP: StandardName (a @tzres.dll resource on later versions)
+    let name = ["TimeZoneKeyName", "StandardName"].iter()
+        .filter_map(|name| information.value(name).map(|(_, data)| utf16_string(&data)))
+        .find(|name| !name.is_empty() && !name.starts_with('@'));
+    if let Some(name) = name {
+        return Ok(name);
+    }
+    // only the bias left: fixed offset, Etc/GMT+5 being UTC-05:00 like a bias of 300 minutes
+    match information.value("Bias").and_then(|v| u32_at(&v.1, 0)).map(|b| b as i32) {
+        Some(bias) if bias % 60 == 0 => Ok(match bias {
+            0 => "Etc/UTC".to_string(),
+            _ => format!("Etc/GMT{:+}", bias / 60)
+        }),
+        _ => Err("no time zone name in TimeZoneInformation".into())
+    }
+}
+
+fn utf16_string(data: &[u8]) -> String {
+    let units : Vec<u16> = data.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).take_while(|u| *u != 0).collect();
+    String::from_utf16_lossy(&units)
+}
+
+// nk cell of a hive
+struct Key<'a> {
+    hive: &'a [u8],
+    cell: &'a [u8]
+}
+
+impl<'a> Key<'a> {
+    fn at(hive: &'a [u8], offset: u32) -> Option<Self> {
+        let cell = cell(hive, offset)?;
+        cell.starts_with(b"nk").then_some(Self { hive, cell })
+    }
+
+    fn name(&self) -> Option<String> {
+        let length = u16_at(self.cell, 0x48)? as usize;
+        let name = self.cell.get(0x4C..0x4C + length)?;
+        Some(match u16_at(self.cell, 2)? & 0x20 {
+            0 => utf16_string(name),
+            _ => name.iter().map(|b| *b as char).collect() // compressed (Latin-1) name
+        })
+    }
+
+    fn subkey(&self, name: &str) -> Option<Key<'a>> {
+        let count = u32_at(self.cell, 0x14)?;
+        if count == 0 {
+            return None;
+        }
+        let mut offsets = vec![];
+        subkey_offsets(self.hive, u32_at(self.cell, 0x1C)?, &mut offsets, 0);
+        offsets.into_iter()
+            .filter_map(|offset| Key::at(self.hive, offset))
+            .find(|key| key.name().is_some_and(|n| n.eq_ignore_ascii_case(name)))
+    }
+
+    // (type, data) of a value
+    fn value(&self, name: &str) -> Option<(u32, Vec<u8>)> {
+        let count = u32_at(self.cell, 0x24)? as usize;
+        let list = cell(self.hive, u32_at(self.cell, 0x28)?)?;
+        (0..count).filter_map(|i| cell(self.hive, u32_at(list, i * 4)?))
+            .filter(|vk| vk.starts_with(b"vk"))
+            .find(|vk| {
+                let length = u16_at(vk, 2).unwrap_or_default() as usize;
+                let Some(value_name) = vk.get(20..20 + length) else { return false };
+                let value_name = match u16_at(vk, 16).unwrap_or_default() & 1 {
+                    0 => utf16_string(value_name),
+                    _ => value_name.iter().map(|b| *b as char).collect()
+                };
+                value_name.eq_ignore_ascii_case(name)
+            })
+            .and_then(|vk| {
+                let size = u32_at(vk, 4)?;
+                let data = match size & 0x8000_0000 {
+                    0 => cell(self.hive, u32_at(vk, 8)?)?.get(..size as usize)?.to_vec(),
+                    _ => vk.get(8..8 + (size & 0x7FFF_FFFF).min(4) as usize)?.to_vec() // resident data
+                };
+                Some((u32_at(vk, 12)?, data))
+            })
+    }
+}
+
+// data of the allocated cell at an offset from the first hive bin
+fn cell(hive: &[u8], offset: u32) -> Option<&[u8]> {
+    let start = HBIN_START + offset as usize;
+    let size = u32_at(hive, start)? as i32;
+    // negative size: allocated (unsigned_abs, i32::MIN having no positive counterpart)
+    (size < 0).then(|| hive.get(start + 4..start.checked_add(size.unsigned_abs() as usize)?)).flatten()
+}
+
+// nk offsets of a subkey list: lf/lh (offset, hash), li (offset), ri (lists)
+fn subkey_offsets(hive: &[u8], list: u32, offsets: &mut Vec<u32>, depth: usize) {
+    let Some(list) = cell(hive, list) else { return };
+    let count = u16_at(list, 2).unwrap_or_default() as usize;
+    match list.get(..2) {
+        Some(b"lf" | b"lh") => offsets.extend((0..count).filter_map(|i| u32_at(list, 4 + i * 8))),
+        Some(b"li") => offsets.extend((0..count).filter_map(|i| u32_at(list, 4 + i * 4))),
+        Some(b"ri") if depth < MAX_DEPTH => {
+            for i in 0..count {
+                if let Some(sublist) = u32_at(list, 4 + i * 4) {
+                    subkey_offsets(hive, sublist, offsets, depth + 1);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // hive written cell after cell, the offsets being the ones from the first hive bin
+    struct Hive(Vec<u8>);
+
+    impl Hive {
+        fn new() -> Self {
+            let mut bytes = vec![0; HBIN_START];
+            bytes[..4].copy_from_slice(b"regf");
+            Self(bytes)
+        }
+
+        fn cell(&mut self, data: &[u8]) -> u32 {
+            let offset = (self.0.len() - HBIN_START) as u32;
+            self.0.extend(&(-(data.len() as i32 + 4)).to_le_bytes());
+            self.0.extend(data);
+            offset
+        }
+
+        fn offsets(&mut self, offsets: &[u32]) -> u32 {
+            self.cell(&offsets.iter().flat_map(|o| o.to_le_bytes()).collect::<Vec<u8>>())
+        }
+
+        fn key(&mut self, name: &str, subkeys: &[u32], values: &[u32]) -> u32 {
+            let mut list = b"li".to_vec();
+            list.extend((subkeys.len() as u16).to_le_bytes());
+            list.extend(subkeys.iter().flat_map(|o| o.to_le_bytes()));
+            let list = self.cell(&list);
+            let values_list = self.offsets(values);
+            let mut nk = vec![0; 0x4C];
+            nk[..2].copy_from_slice(b"nk");
+            nk[2..4].copy_from_slice(&0x20u16.to_le_bytes()); // compressed name
+            nk[0x14..0x18].copy_from_slice(&(subkeys.len() as u32).to_le_bytes());
+            nk[0x1C..0x20].copy_from_slice(&list.to_le_bytes());
+            nk[0x24..0x28].copy_from_slice(&(values.len() as u32).to_le_bytes());
+            nk[0x28..0x2C].copy_from_slice(&values_list.to_le_bytes());
+            nk[0x48..0x4A].copy_from_slice(&(name.len() as u16).to_le_bytes());
+            nk.extend(name.as_bytes());
+            self.cell(&nk)
+        }
+
+        fn value(&mut self, name: &str, kind: u32, data: &[u8]) -> u32 {
+            let mut vk = vec![0; 20];
+            vk[..2].copy_from_slice(b"vk");
+            vk[2..4].copy_from_slice(&(name.len() as u16).to_le_bytes());
+            match data.len() {
+                0..=4 => {
+                    vk[4..8].copy_from_slice(&(data.len() as u32 | 0x8000_0000).to_le_bytes());
+                    vk[8..8 + data.len()].copy_from_slice(data);
+                }
+                _ => {
+                    let cell = self.cell(data);
+                    vk[4..8].copy_from_slice(&(data.len() as u32).to_le_bytes());
+                    vk[8..12].copy_from_slice(&cell.to_le_bytes());
+                }
+            }
+            vk[12..16].copy_from_slice(&kind.to_le_bytes());
+            vk[16..18].copy_from_slice(&1u16.to_le_bytes()); // compressed name
+            vk.extend(name.as_bytes());
+            self.cell(&vk)
+        }
+
+        // SYSTEM hive with ControlSet001 current, its TimeZoneInformation holding `values`
+        fn system(values: &[(&str, u32, Vec<u8>)]) -> Vec<u8> {
+            let mut hive = Self::new();
+            let values : Vec<u32> = values.iter().map(|(name, kind, data)| hive.value(name, *kind, data)).collect();
+            let information = hive.key("TimeZoneInformation", &[], &values);
+            let control = hive.key("Control", &[information], &[]);
+            let set = hive.key("ControlSet001", &[control], &[]);
+            let current = hive.value("Current", 4, &1u32.to_le_bytes());
+            let select = hive.key("Select", &[], &[current]);
+            let root = hive.key("ROOT", &[select, set], &[]);
+            hive.0[0x24..0x28].copy_from_slice(&root.to_le_bytes());
+            hive.0
+        }
+    }
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn time_zone_key_name() {
+        let hive = Hive::system(&[("TimeZoneKeyName", 1, utf16("Romance Standard Time")), ("Bias", 4, (-60i32).to_le_bytes().to_vec())]);
+        assert_eq!(hive_timezone(&hive).expect("time zone"), "Romance Standard Time");
+        assert_eq!(iana_name("romance standard time"), Some("Europe/Paris"));
+    }
+
+    #[test]
+    fn bias_only() {
+        let hive = Hive::system(&[("StandardName", 1, utf16("@tzres.dll,-112")), ("Bias", 4, 300i32.to_le_bytes().to_vec())]);
+        assert_eq!(hive_timezone(&hive).expect("time zone"), "Etc/GMT+5");
+    }
+
+    #[test]
+    fn corrupted_cells() {
+        let mut hive = Hive::new();
+        let offset = hive.cell(b"nk");
+        // i32::MIN size, and a size past the end of the hive
+        hive.0[HBIN_START..HBIN_START + 4].copy_from_slice(&i32::MIN.to_le_bytes());
+        assert_eq!(cell(&hive.0, offset), None);
+        hive.0[HBIN_START..HBIN_START + 4].copy_from_slice(&(-1000i32).to_le_bytes());
+        assert_eq!(cell(&hive.0, offset), None);
+        assert_eq!(cell(&hive.0, u32::MAX), None);
+        assert!(hive_timezone(&hive.0).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use std::{error::Error, fs::File, io::{BufWriter, Write}, path::{Path, PathBuf}};
+
+use crate::sink::{self, Event, OutputSink};
+
+/*
+Dead-letter file (--dead-letter): the events a sink rejects (connection of --tcp-out broken, events refused by a
+custom OutputSink of a library user, ...) are appended to an NDJSON file with the error, one
+{"error": "...", "event": {...}} object per line, and the run goes on instead of dropping the following events, so
+that nothing is lost from the evidentiary record. The file is only created once an event is rejected.
+*/
+pub struct DeadLetterSink {
+    sink: Box<dyn OutputSink>,
+    path: PathBuf,
+    file: Option<BufWriter<File>>,
+    rejected: usize
+}
+
+impl DeadLetterSink {
+    pub fn new(sink: Box<dyn OutputSink>, path: &Path) -> Self {
+        Self { sink, path: path.to_path_buf(), file: None, rejected: 0 }
+    }
+
+    fn reject(&mut self, event: &Event, error: &dyn Error) -> Result<(), Box<dyn Error>> {
+        if self.file.is_none() {
+            let file = File::options().create(true).append(true).open(&self.path)
+                .map_err(|e| format!("Dead-letter file {}: {e}", self.path.display()))?;
+            self.file = Some(BufWriter::new(file));
+        }
+        let file = self.file.as_mut().expect("dead-letter file opened");
+        serde_json::to_writer(&mut *file, &serde_json::json!({ "error": error.to_string(), "event": sink::json_value(event) }))?;
+        writeln!(file)?;
+        self.rejected += 1;
+        Ok(())
+    }
+}
+
+impl OutputSink for DeadLetterSink {
+    fn write_header(&mut self, columns: &[&str], metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        self.sink.write_header(columns, metadata)
+    }
+
+    fn write_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        match self.sink.write_event(event) {
+            Ok(()) => Ok(()),
+            Err(error) => self.reject(event, error.as_ref()) // an unwritable dead-letter file stops the run
+        }
+    }
+
+    fn write_section(&mut self, name: &str, columns: &[&str]) -> Result<(), Box<dyn Error>> {
+        self.sink.write_section(name, columns)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sink.flush()
+    }
+
+    // the sink's own error (e.g. the end of the output not sent) is still the run's
+    fn finish(&mut self, partial: bool) -> Result<(), Box<dyn Error>> {
+        let result = self.sink.finish(partial);
+        if let Some(file) = self.file.as_mut() {
+            file.flush()?;
+            eprintln!("Number of datetime records rejected by the output, written to {}: {}", self.path.display(), self.rejected);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+    use crate::bodyfile::{BodyFileParser, ParseOptions};
+
+    // file names written, the ones of `rejected` refused
+    struct Rejecting {
+        written: Rc<RefCell<Vec<String>>>,
+        rejected: &'static str
+    }
+
+    impl OutputSink for Rejecting {
+        fn write_header(&mut self, _columns: &[&str], _metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn write_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+            if event.filename == self.rejected {
+                return Err("connection reset".into());
+            }
+            self.written.borrow_mut().push(event.filename.to_string());
+            Ok(())
+        }
+
+        fn finish(&mut self, _partial: bool) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn rejected_events_set_aside() {
+        let content = "0|/a|1-128-1|r|0|0|1|1|1|1|1\n0|/bad|2-128-1|r|0|0|1|2|2|2|2\n0|/c|3-128-1|r|0|0|1|3|3|3|3\n";
+        let bodyfile = BodyFileParser::build_bytes(content.as_bytes().to_vec(), &ParseOptions::default()).expect("bodyfile parsed");
+        let path = std::env::temp_dir().join(format!("mactime-deadletter-{}.ndjson", std::process::id()));
+        let written = Rc::new(RefCell::new(vec![]));
+        let mut sink = DeadLetterSink::new(Box::new(Rejecting { written: written.clone(), rejected: "/bad" }), &path);
+        bodyfile.write_to(&mut sink, &serde_json::json!({})).expect("written");
+        let letters = std::fs::read_to_string(&path).expect("dead-letter file");
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(*written.borrow(), vec!["/a", "/c"]);
+        let letters : Vec<serde_json::Value> = letters.lines().map(|l| serde_json::from_str(l).expect("NDJSON")).collect();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0]["error"], "connection reset");
+        assert_eq!(letters[0]["event"]["filename"], "/bad");
+        assert_eq!(letters[0]["event"]["meta"], "2-128-1");
+    }
+}
@@ -0,0 +1,152 @@
+use std::{fmt, char::REPLACEMENT_CHARACTER};
+
+/* Some collectors write UTF-16LE or Latin-1 bodyfiles: detect them and transcode to UTF-8 before parsing */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Latin1
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf8Bom => "UTF-8 (BOM)",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Latin1 => "Latin-1"
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug)]
+pub struct Decoded {
+    pub content: String,
+    pub encoding: Encoding,
+    pub replaced: usize // number of characters lossily replaced by U+FFFD
+}
+
+pub fn decode(bytes: Vec<u8>) -> Decoded {
+    // BOMs first
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return decode_utf8(bytes[3..].to_vec(), Encoding::Utf8Bom);
+    }
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return decode_utf16(&bytes[2..], Encoding::Utf16Le);
+    }
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        return decode_utf16(&bytes[2..], Encoding::Utf16Be);
+    }
+
+    // BOM-less UTF-16: bodyfiles are mostly ASCII, so every other byte is NUL
+    if let Some(encoding) = guess_utf16(&bytes) {
+        return decode_utf16(&bytes, encoding);
+    }
+
+    decode_utf8(bytes, Encoding::Utf8)
+}
+
+fn decode_utf8(bytes: Vec<u8>, encoding: Encoding) -> Decoded {
+    let bytes = match String::from_utf8(bytes) {
+        Ok(content) => return Decoded { content, encoding, replaced: 0 },
+        Err(e) => e.into_bytes()
+    };
+
+    // invalid UTF-8, decoded line by line: the valid lines are kept, the invalid ones are read as Latin-1 (every byte maps
+    // to the code point of the same value), unless other lines hold UTF-8 characters: the few corrupted bytes of a UTF-8
+    // file are then replaced by U+FFFD, in their own line only
+    let lines : Vec<&[u8]> = bytes.split_inclusive(|b| *b == b'\n').collect();
+    let utf8 = lines.iter().any(|line| !line.is_ascii() && std::str::from_utf8(line).is_ok());
+    let mut content = String::with_capacity(bytes.len());
+    let mut replaced = 0;
+    for line in lines {
+        match std::str::from_utf8(line) {
+            Ok(line) => content.push_str(line),
+            Err(_) if utf8 => {
+                let line = String::from_utf8_lossy(line);
+                replaced += line.matches(REPLACEMENT_CHARACTER).count();
+                content.push_str(&line);
+            }
+            Err(_) => content.extend(line.iter().map(|&b| b as char))
+        }
+    }
+    Decoded { content, encoding: if utf8 { encoding } else { Encoding::Latin1 }, replaced }
+}
+
+fn decode_utf16(bytes: &[u8], encoding: Encoding) -> Decoded {
+    let units = bytes.chunks_exact(2).map(|pair| match encoding {
+        Encoding::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+        _ => u16::from_le_bytes([pair[0], pair[1]])
+    });
+
+    let mut replaced = if bytes.len() % 2 == 1 { 1 } else { 0 }; // dangling byte
+    let mut content: String = char::decode_utf16(units)
+        .map(|c| c.unwrap_or_else(|_| { replaced += 1; REPLACEMENT_CHARACTER }))
+        .collect();
+    if bytes.len() % 2 == 1 {
+        content.push(REPLACEMENT_CHARACTER);
+    }
+
+    Decoded { content, encoding, replaced }
+}
+
+fn guess_utf16(bytes: &[u8]) -> Option<Encoding> {
+    let sample = &bytes[..bytes.len().min(512) & !1];
+    if sample.is_empty() {
+        return None;
+    }
+
+    let pairs = sample.len() / 2;
+    let even_nul = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd_nul = sample.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+
+    // ASCII text in UTF-16 => (almost) all high bytes are NUL, low bytes are not
+    if odd_nul * 10 >= pairs * 9 && even_nul == 0 {
+        Some(Encoding::Utf16Le)
+    } else if even_nul * 10 >= pairs * 9 && odd_nul == 0 {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latin1_lines() {
+        let decoded = decode(b"0|/caf\xe9|1-128-1|r|0|0|1|1|1|1|1\n0|/plain|2-128-1|r|0|0|1|1|1|1|1\n".to_vec());
+        assert_eq!(decoded.encoding, Encoding::Latin1);
+        assert!(decoded.content.starts_with("0|/café|"));
+        assert_eq!(decoded.replaced, 0);
+    }
+
+    #[test]
+    fn a_corrupted_line_of_utf8_is_decoded_alone() {
+        let decoded = decode("0|/été|1\n".bytes().chain(*b"0|/bad\xff|2\n").chain("0|/zoë|3\n".bytes()).collect());
+        assert_eq!(decoded.encoding, Encoding::Utf8);
+        assert_eq!(decoded.content, "0|/été|1\n0|/bad\u{FFFD}|2\n0|/zoë|3\n");
+        assert_eq!(decoded.replaced, 1);
+    }
+
+    #[test]
+    fn utf16_with_and_without_bom() {
+        let utf16 : Vec<u8> = "0|/a|1\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let decoded = decode([&[0xFF, 0xFE][..], &utf16].concat());
+        assert_eq!((decoded.encoding, decoded.content.as_str()), (Encoding::Utf16Le, "0|/a|1\n"));
+        assert_eq!(decode(utf16).encoding, Encoding::Utf16Le);
+        let utf16be : Vec<u8> = "0|/a|1\n".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(decode(utf16be).content, "0|/a|1\n");
+    }
+
+    #[test]
+    fn utf8_bom() {
+        let decoded = decode(b"\xEF\xBB\xBF0|/a\n".to_vec());
+        assert_eq!((decoded.encoding, decoded.content.as_str()), (Encoding::Utf8Bom, "0|/a\n"));
+    }
+}
@@ -0,0 +1,69 @@
+use std::{fmt, error::Error, path::{Path, PathBuf}, process::{Child, Command, Stdio}};
+
+/*
+Output encryption (--encrypt): the output is streamed through the age tool (https://age-encryption.org) while it is written,
+rather than encrypted in a separate step once the whole timeline is on disk.
+- age:<recipients file>: public keys (age1..., ssh-ed25519 ...), one per line
+- passphrase: age asks for a passphrase on the terminal
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Encryption {
+    Age(PathBuf),
+    Passphrase
+}
+
+impl fmt::Display for Encryption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Encryption::Age(recipients) => write!(f, "age:{}", recipients.display()),
+            Encryption::Passphrase => write!(f, "passphrase")
+        }
+    }
+}
+
+impl std::str::FromStr for Encryption {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("age", recipients)) if !recipients.is_empty() => Ok(Encryption::Age(PathBuf::from(recipients))),
+            None if s == "passphrase" => Ok(Encryption::Passphrase),
+            _ => Err(format!("Invalid encryption '{s}', expected age:<recipients file> or passphrase"))
+        }
+    }
+}
+
+impl Encryption {
+    // age process writing the encrypted output to `output` (stdout if not specified), the plaintext goes to its stdin
+    pub fn spawn(&self, output: Option<&Path>) -> Result<Child, Box<dyn Error>> {
+        let mut command = Command::new("age");
+        command.arg("--encrypt");
+        match self {
+            Encryption::Age(recipients) => {
+                command.arg("--recipients-file").arg(recipients);
+                // Ctrl-C is for mactime only: age must still encrypt the rows written so far (partial output)
+                #[cfg(unix)]
+                std::os::unix::process::CommandExt::process_group(&mut command, 0);
+            }
+            Encryption::Passphrase => {
+                command.arg("--passphrase");
+            }
+        }
+        if let Some(output) = output {
+            command.arg("--output").arg(output);
+        }
+
+        command.stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Cannot run age for --encrypt {self} (https://age-encryption.org): {e}").into())
+    }
+
+    // once the plaintext is written & its pipe closed
+    pub fn wait(mut child: Child) -> Result<(), Box<dyn Error>> {
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(format!("age failed to encrypt the output ({status})").into());
+        }
+        Ok(())
+    }
+}
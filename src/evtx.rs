@@ -0,0 +1,125 @@
+use std::{path::Path, error::Error};
+
+use crate::bodyfile::{BodyFile, DateFilter, TimestampDesc, TimestampEntry, SourceType};
+use crate::mft::{self, u16_at, u32_at, u64_at, utf16_at};
+
+/* Raw EVTX parser: record timestamps & ids, EventID/Channel/Computer read from the substitution values of the System template */
+
+const FILE_HEADER_SIZE: usize = 4096;
+const CHUNK_SIZE: usize = 65536;
+const CHUNK_HEADER_SIZE: usize = 512;
+const RECORD_HEADER_SIZE: usize = 24;
+
+// substitution indexes of the System element in the event log service templates
+const EVENT_ID: usize = 3;
+const CHANNEL: usize = 16;
+const COMPUTER: usize = 17;
+
+const TYPE_STRING: u8 = 0x01;
+const TYPE_UINT16: u8 = 0x06;
+
+#[derive(Debug, Default)]
+struct System {
+    event_id: Option<u16>,
+    channel: Option<String>,
+    computer: Option<String>
+}
+
+// BinXml of a record: fragment header, template instance (definition possibly inline) then substitution values
+fn parse_system(chunk: &[u8], offset: usize) -> Option<System> {
+    let mut position = offset;
+    if *chunk.get(position)? == 0x0F {
+        position += 4; // fragment header
+    }
+    if *chunk.get(position)? & 0x1F != 0x0C {
+        return None; // not a template instance
+    }
+    let definition = u32_at(chunk, position + 6)? as usize;
+    position += 10;
+    if definition == position {
+        // inline template definition: next offset, GUID, data size & data
+        let size = u32_at(chunk, position + 20)? as usize;
+        position += 24 + size;
+    }
+
+    let count = u32_at(chunk, position)? as usize;
+    position += 4;
+    let descriptors : Vec<(usize, u8)> = (0..count)
+        .map(|i| Some((u16_at(chunk, position + i * 4)? as usize, *chunk.get(position + i * 4 + 2)?)))
+        .collect::<Option<_>>()?;
+    position += count * 4;
+
+    let mut system = System::default();
+    for (i, (size, kind)) in descriptors.into_iter().enumerate() {
+        match (i, kind) {
+            (EVENT_ID, TYPE_UINT16) => system.event_id = u16_at(chunk, position),
+            (CHANNEL, TYPE_STRING) => system.channel = utf16_at(chunk, position, size / 2).map(|s| s.trim_end_matches('\0').to_string()),
+            (COMPUTER, TYPE_STRING) => system.computer = utf16_at(chunk, position, size / 2).map(|s| s.trim_end_matches('\0').to_string()),
+            _ => {}
+        }
+        position += size;
+    }
+    Some(system)
+}
+
+pub fn parse_evtx(bodyfile: &mut BodyFile, path: &Path, filter: &Option<DateFilter>) -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(path)?;
+    if !data.starts_with(b"ElfFile\0") {
+        return Err(format!("{} is not an EVTX file", path.display()).into());
+    }
+    let file = path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+
+    for chunk in data.get(FILE_HEADER_SIZE..).unwrap_or_default().chunks_exact(CHUNK_SIZE) {
+        if !chunk.starts_with(b"ElfChnk\0") {
+            continue; // unused chunk
+        }
+
+        let mut offset = CHUNK_HEADER_SIZE;
+        while chunk.get(offset..offset + 4) == Some(&[0x2A, 0x2A, 0x00, 0x00]) {
+            let (Some(size), Some(id), Some(timestamp)) = (u32_at(chunk, offset + 4), u64_at(chunk, offset + 8), u64_at(chunk, offset + 16)) else { break };
+            if size < RECORD_HEADER_SIZE as u32 {
+                break;
+            }
+
+            let datetime = mft::filetime(timestamp);
+            if !mft::sentinel_filetime(timestamp) && DateFilter::in_range(filter, datetime) {
+                let system = parse_system(chunk, offset + RECORD_HEADER_SIZE).unwrap_or_default();
+                let desc = match system.event_id {
+                    Some(event_id) => format!("EventID {event_id}"),
+                    None => "Event Logged".to_string()
+                };
+                let channel = system.channel.unwrap_or_else(|| file.clone());
+                let filename = match system.computer {
+                    Some(computer) => format!("{channel}/{computer}#{id}"),
+                    None => format!("{channel}#{id}")
+                };
+
+                bodyfile.add_event(TimestampEntry {
+                    datetime,
+                    desc: TimestampDesc::Label(desc),
+                    source: SourceType::Evtx,
+                    meta: id.to_string().into(),
+                    size: size as u64,
+                    filename: filename.into(),
+                    md5: "0".into(),
+                    mode: "".into(),
+                    uid: "".into(),
+                    gid: "".into(),
+                    tags: vec![],
+                    score: 0,
+                    detection: None,
+                    raw: None,
+                    input: None,
+                    host: None,
+                    session: None,
+                    line: None,
+                    unset: Default::default()
+                }, false, false);
+            }
+
+            offset += size as usize;
+        }
+    }
+
+    Ok(())
+}
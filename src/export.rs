@@ -0,0 +1,191 @@
+use std::{path::Path, error::Error, collections::HashSet};
+use chrono::{DateTime, Utc, SecondsFormat};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::bodyfile::Match;
+use crate::ioc::{IocList, IocKind};
+
+/* Export of IOC/rule matches to threat-intel formats */
+
+// namespace defined by STIX 2.1 for deterministic SCO identifiers
+const STIX_NAMESPACE: Uuid = Uuid::from_u128(0x00abedb4_aa42_466c_9c01_fed23315a9b7);
+
+fn stix_id(object_type: &str, contributing: &Value) -> String {
+    format!("{object_type}--{}", Uuid::new_v5(&STIX_NAMESPACE, contributing.to_string().as_bytes()))
+}
+
+fn stix_timestamp(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// quote a string literal inside a STIX pattern
+fn stix_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn basename(filename: &str) -> &str {
+    filename.rsplit('/').next().unwrap_or(filename)
+}
+
+fn has_md5(md5: &str) -> bool {
+    md5.len() == 32 // bodyfiles use "0" when no hash was computed
+}
+
+/*
+STIX 2.1 bundle: one indicator per IOC with hits, one file (+ observed-data) per matched file
+and a sighting linking both, with first/last seen taken from the matched rows.
+*/
+pub fn stix(matches: &[Match], iocs: &IocList, path: &Path) -> Result<(), Box<dyn Error>> {
+    let now = stix_timestamp(Utc::now());
+    let identity_id = stix_id("identity", &json!({"name": "mactime"}));
+    let mut objects = vec![json!({
+        "type": "identity",
+        "spec_version": "2.1",
+        "id": identity_id,
+        "created": now,
+        "modified": now,
+        "name": "mactime",
+        "identity_class": "system"
+    })];
+    let mut files = HashSet::new(); // a file matched by several IOCs is emitted once
+
+    for ioc in iocs.iter().filter(|ioc| ioc.hits > 0) {
+        let pattern = match ioc.kind {
+            IocKind::Md5 => format!("[file:hashes.MD5 = {}]", stix_literal(&ioc.value.to_lowercase())),
+            IocKind::Filename => format!("[file:name = {}]", stix_literal(&ioc.value)),
+            IocKind::Path => format!("[file:name = {}]", stix_literal(basename(&ioc.value.replace('\\', "/"))))
+        };
+        let indicator_id = stix_id("indicator", &json!({"pattern": pattern}));
+        objects.push(json!({
+            "type": "indicator",
+            "spec_version": "2.1",
+            "id": indicator_id,
+            "created_by_ref": identity_id,
+            "created": now,
+            "modified": now,
+            "name": ioc.value,
+            "pattern": pattern,
+            "pattern_type": "stix",
+            "valid_from": now
+        }));
+
+        for m in matches.iter().filter(|m| m.tag == ioc.tag()) {
+            let mut file = json!({
+                "type": "file",
+                "spec_version": "2.1",
+                "name": basename(&m.filename),
+                "size": m.size,
+                "x_mactime_path": m.filename,
+                "x_mactime_meta": m.meta
+            });
+            let file_id = if has_md5(&m.md5) {
+                file["hashes"] = json!({"MD5": m.md5.to_lowercase()});
+                stix_id("file", &json!({"hashes": {"MD5": m.md5.to_lowercase()}, "name": basename(&m.filename)}))
+            } else {
+                stix_id("file", &json!({"name": basename(&m.filename), "x_mactime_path": m.filename, "x_mactime_meta": m.meta}))
+            };
+            file["id"] = json!(file_id);
+            if files.insert(file_id.clone()) {
+                objects.push(file);
+            }
+
+            let observed_id = stix_id("observed-data", &json!({"file": file_id, "first": m.first.timestamp(), "last": m.last.timestamp()}));
+            let observed = json!({
+                "type": "observed-data",
+                "spec_version": "2.1",
+                "id": observed_id,
+                "created_by_ref": identity_id,
+                "created": now,
+                "modified": now,
+                "first_observed": stix_timestamp(m.first),
+                "last_observed": stix_timestamp(m.last),
+                "number_observed": m.count,
+                "object_refs": [file_id]
+            });
+            let sighting = json!({
+                "type": "sighting",
+                "spec_version": "2.1",
+                "id": stix_id("sighting", &json!({"indicator": indicator_id, "observed": observed_id})),
+                "created_by_ref": identity_id,
+                "created": now,
+                "modified": now,
+                "first_seen": stix_timestamp(m.first),
+                "last_seen": stix_timestamp(m.last),
+                "count": m.count,
+                "sighting_of_ref": indicator_id,
+                "observed_data_refs": [observed_id]
+            });
+            objects.extend([observed, sighting]);
+        }
+    }
+
+    let bundle = json!({
+        "type": "bundle",
+        "id": format!("bundle--{}", Uuid::new_v5(&STIX_NAMESPACE, Value::Array(objects.clone()).to_string().as_bytes())),
+        "objects": objects
+    });
+    serde_json::to_writer_pretty(std::fs::File::create(path)?, &bundle)?;
+    Ok(())
+}
+
+/*
+MISP event: filename (and filename|md5 when hashed) attributes per matched file, with first/last seen
+taken from the matched rows. The JSON is ready for upload or can be pushed with `misp_push`.
+*/
+pub fn misp_event(matches: &[Match], info: &str) -> Value {
+    let mut attributes = vec![];
+    let mut seen = HashSet::new();
+    for m in matches.iter().filter(|m| m.tag != "context") { // derived tags are not findings
+        let (attribute_type, value) = if has_md5(&m.md5) {
+            ("filename|md5", format!("{}|{}", m.filename, m.md5.to_lowercase()))
+        } else {
+            ("filename", m.filename.clone())
+        };
+        if !seen.insert((attribute_type, value.clone(), m.tag.as_str())) {
+            continue;
+        }
+
+        attributes.push(json!({
+            "type": attribute_type,
+            "category": "Artifacts dropped",
+            "to_ids": false,
+            "value": value,
+            "first_seen": stix_timestamp(m.first),
+            "last_seen": stix_timestamp(m.last),
+            "comment": format!("{} ({} rows, meta {}, size {})", m.tag, m.count, m.meta, m.size)
+        }));
+    }
+
+    json!({
+        "Event": {
+            "info": info,
+            "date": Utc::now().format("%Y-%m-%d").to_string(),
+            "threat_level_id": "4", // undefined
+            "analysis": "0", // initial
+            "distribution": "0", // your organisation only
+            "Attribute": attributes
+        }
+    })
+}
+
+pub fn misp(matches: &[Match], info: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer_pretty(std::fs::File::create(path)?, &misp_event(matches, info))?;
+    Ok(())
+}
+
+// PyMISP-compatible REST call: POST <url>/events/add with the API key in the Authorization header
+#[cfg(feature = "misp")]
+pub fn misp_push(matches: &[Match], info: &str, url: &str, key: &str) -> Result<String, Box<dyn Error>> {
+    let response : Value = ureq::post(&format!("{}/events/add", url.trim_end_matches('/')))
+        .set("Authorization", key)
+        .set("Accept", "application/json")
+        .send_json(misp_event(matches, info))?
+        .into_json()?;
+    Ok(response["Event"]["id"].as_str().unwrap_or("?").to_string())
+}
+
+#[cfg(not(feature = "misp"))]
+pub fn misp_push(_matches: &[Match], _info: &str, _url: &str, _key: &str) -> Result<String, Box<dyn Error>> {
+    Err("MISP push requires mactime to be built with the `misp` feature".into())
+}
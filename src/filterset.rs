@@ -0,0 +1,190 @@
+use std::{path::Path, error::Error, ops::RangeInclusive};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::bodyfile::{BodyFile, DateFilter, SourceType, UserFilter};
+use crate::matcher::KeywordMatcher;
+use crate::rules::RuleSet;
+
+/*
+Filter files (--save-filter, --load-filter): the active filters of a run (dates, source types, keywords, user, plausible
+years, rules & minimum score) in a JSON file, so that a reviewed filter set is attached to the case file and re-applied
+identically months later. Keywords read from file descriptors and the rules are stored themselves, not their source,
+which may be gone by then. Options given on the command line take precedence over the ones of a loaded file.
+*/
+pub const VERSION: u32 = 1;
+
+// --filter date range
+pub const FORMAT : &str = "Date filter format: <start>..<end>, each one YYYY-MM-DD or YYYY-MM-DDTHH:MM[:SS] (UTC), one of them can be left out for an open range (e.g. 2023-01-01T22:00..2023-01-02T00:30, 2023-01-01..)";
+
+// [start, end], both included: a day alone covers the whole day, a time without seconds the whole minute
+pub fn parse_filter_args(args: &str) -> Result<[Option<DateTime<Utc>>;2], String> {
+    fn parse_date(date: &str, end: bool) -> Result<Option<DateTime<Utc>>, String> {
+        let date = date.trim().trim_end_matches('Z');
+        if date.is_empty() {
+            return Ok(None); // open range
+        }
+        let date = date.replacen(' ', "T", 1);
+        let (naive, last) = if let Ok(datetime) = NaiveDateTime::parse_from_str(&date, "%Y-%m-%dT%H:%M:%S") {
+            (datetime, Duration::seconds(1))
+        } else if let Ok(datetime) = NaiveDateTime::parse_from_str(&date, "%Y-%m-%dT%H:%M") {
+            (datetime, Duration::minutes(1))
+        } else {
+            let day = NaiveDate::parse_from_str(&date, "%F") // Year-month-day format (ISO 8601). Same as %Y-%m-%d
+                .map_err(|_| format!("Invalid date '{date}': dates must be in the YYYY-MM-DD or YYYY-MM-DDTHH:MM[:SS] format"))?;
+            (day.and_hms_opt(0, 0, 0).expect("midnight"), Duration::days(1))
+        };
+        Ok(Some(match end {
+            true => naive + last - Duration::nanoseconds(1),
+            false => naive
+        }.and_utc()))
+    }
+
+    let Some((start, end)) = args.split_once("..") else {
+        return Err(FORMAT.into())
+    };
+    let (start, end) = (parse_date(start, false)?, parse_date(end, true)?);
+    match (start, end) {
+        (None, None) => Err(FORMAT.into()),
+        (Some(s), Some(e)) if s > e => Err(format!("Invalid date range '{args}': its start is after its end")),
+        _ => Ok([start, end])
+    }
+}
+
+// plausible years like 1990..2035
+pub fn parse_year_range(arg: &str) -> Result<RangeInclusive<i32>, String> {
+    let error = || format!("Invalid year range '{arg}', expected <first>..<last> (e.g. 1990..2035)");
+    let (first, last) = arg.split_once("..").ok_or_else(error)?;
+    let (first, last) : (i32, i32) = (first.parse().map_err(|_| error())?, last.parse().map_err(|_| error())?);
+    if first > last {
+        return Err(error());
+    }
+    Ok(first..=last)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedRules {
+    pub path: String, // file the rules were read from, for the reports
+    pub content: String
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterSet {
+    pub version: u32,
+    pub filter: Option<String>, // date range, --filter format
+    pub source_type: Option<Vec<String>>,
+    pub include: Vec<String>, // keywords, the ones of --keywords-fd included
+    pub exclude: Vec<String>,
+    pub ignore_case: bool,
+    pub user: Option<String>,
+    pub valid_years: Option<String>,
+    pub flag_invalid_years: bool,
+    pub rules: Option<SavedRules>,
+    pub min_score: Option<u64>
+}
+
+impl FilterSet {
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let filters : Self = serde_json::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| format!("Invalid filter file {}: {e}", path.display()))?;
+        if filters.version != VERSION {
+            return Err(format!("Filter file {} was written by another version of mactime (version {}, expected {VERSION})", path.display(), filters.version).into());
+        }
+        Ok(filters)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, serde_json::to_string_pretty(&Self { version: VERSION, ..self.clone() })? + "\n")?;
+        Ok(())
+    }
+
+    // filters of a loaded file, each one replaced by the one of `command_line` if set
+    pub fn overridden_by(self, command_line: FilterSet) -> FilterSet {
+        FilterSet {
+            version: VERSION,
+            filter: command_line.filter.or(self.filter),
+            source_type: command_line.source_type.or(self.source_type),
+            include: match command_line.include.is_empty() {
+                true => self.include,
+                false => command_line.include
+            },
+            exclude: match command_line.exclude.is_empty() {
+                true => self.exclude,
+                false => command_line.exclude
+            },
+            ignore_case: command_line.ignore_case || self.ignore_case,
+            user: command_line.user.or(self.user),
+            valid_years: command_line.valid_years.or(self.valid_years),
+            flag_invalid_years: command_line.flag_invalid_years || self.flag_invalid_years,
+            rules: command_line.rules.or(self.rules),
+            min_score: command_line.min_score.or(self.min_score)
+        }
+    }
+
+    // filters applied to a timeline in the order of a run (mactime repl), rules tagging the rows for --min-score
+    pub fn apply(&self, bodyfile: &mut BodyFile) -> Result<(), Box<dyn Error>> {
+        if let Some(filter) = &self.filter {
+            bodyfile.retain_dates(&Some(DateFilter::new(parse_filter_args(filter)?)));
+        }
+        if let Some(years) = &self.valid_years {
+            bodyfile.check_years(&parse_year_range(years)?, self.flag_invalid_years);
+        }
+        if let Some(source_types) = &self.source_type {
+            bodyfile.retain_sources(&source_types.iter().map(|s| s.parse()).collect::<Result<Vec<SourceType>, String>>()?);
+        }
+        for (keywords, include) in [(&self.include, true), (&self.exclude, false)] {
+            if !keywords.is_empty() {
+                bodyfile.retain_keywords(&KeywordMatcher::new(keywords.iter().map(String::as_str), self.ignore_case)?, include);
+            }
+        }
+        if let Some(user) = &self.user {
+            bodyfile.retain_user(&UserFilter::new(user));
+        }
+        if let Some(rules) = &self.rules {
+            bodyfile.tag_rules(&mut RuleSet::parse(&rules.content, self.ignore_case)?);
+        }
+        if let Some(min_score) = self.min_score {
+            bodyfile.retain_min_score(min_score);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bodyfile::{BodyFileParser, ParseOptions};
+
+    #[test]
+    fn date_ranges() {
+        let [start, end] = parse_filter_args("2023-01-01..2023-01-02").expect("range");
+        assert_eq!(start.map(|d| d.to_rfc3339()).as_deref(), Some("2023-01-01T00:00:00+00:00"));
+        // a day alone covers the whole day
+        assert_eq!(end.map(|d| d.to_rfc3339()).as_deref(), Some("2023-01-02T23:59:59.999999999+00:00"));
+        let [start, end] = parse_filter_args("2023-01-01T22:00..").expect("open range");
+        assert!(start.is_some() && end.is_none());
+        assert!(parse_filter_args("..").is_err());
+        assert!(parse_filter_args("2023-01-02..2023-01-01").is_err());
+        assert!(parse_filter_args("2023-13-01..").is_err());
+        assert!(parse_filter_args("2023-01-01").is_err());
+    }
+
+    #[test]
+    fn year_ranges() {
+        assert_eq!(parse_year_range("1990..2035"), Ok(1990..=2035));
+        assert!(parse_year_range("2035..1990").is_err());
+        assert!(parse_year_range("1990").is_err());
+    }
+
+    #[test]
+    fn valid_years_are_opt_in() {
+        // 1985-06-01 & 2020-07-21
+        let content = b"0|/old|1-128-1|r|0|0|1|486432000|486432000|486432000|486432000\n0|/new|2-128-1|r|0|0|1|1595291898|1595291898|1595291898|1595291898\n";
+        let mut bodyfile = BodyFileParser::build_bytes(content.to_vec(), &ParseOptions::default()).expect("bodyfile parsed");
+        FilterSet::default().apply(&mut bodyfile).expect("no filter");
+        assert_eq!(bodyfile.datetime_len(), 2);
+        FilterSet { valid_years: Some("1990..2035".to_string()), ..Default::default() }.apply(&mut bodyfile).expect("valid years");
+        assert_eq!(bodyfile.datetime_len(), 1);
+    }
+}
@@ -0,0 +1,83 @@
+use std::{path::{Path, PathBuf}, error::Error};
+
+/*
+Glob patterns (-b "collections/host?/fls_*.body"), expanded by mactime itself since the Windows shells don't: `*` matches
+any characters of a file name, `?` one character, `**` any number of directories. Names are compared case-insensitively
+on Windows. Matches are sorted, so that inputs are read in a reproducible order.
+*/
+pub fn is_pattern(value: &str) -> bool {
+    value.contains(['*', '?'])
+}
+
+pub fn expand(pattern: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let components : Vec<&str> = pattern.split(['/', '\\']).collect();
+    // directory before the first wildcard
+    let fixed = components.iter().take_while(|c| !is_pattern(c)).count();
+    let base = match fixed {
+        0 => PathBuf::from("."),
+        _ if components[0].is_empty() => PathBuf::from("/").join(components[1..fixed].join("/")), // absolute path
+        _ => PathBuf::from(components[..fixed].join("/"))
+    };
+
+    let mut matches = vec![];
+    walk(&base, &components[fixed..], fixed == 0, &mut matches)?;
+    matches.sort();
+    matches.dedup();
+    Ok(matches)
+}
+
+// paths under `directory` matching the remaining components, "./" left out of relative patterns
+fn walk(directory: &Path, components: &[&str], relative: bool, matches: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let Some((component, rest)) = components.split_first() else {
+        matches.push(directory.to_path_buf());
+        return Ok(());
+    };
+    if component.is_empty() {
+        return walk(directory, rest, relative, matches); // repeated or trailing separator
+    }
+    if !directory.is_dir() {
+        return Ok(());
+    }
+
+    let join = |name: &std::ffi::OsStr| if relative { PathBuf::from(name) } else { directory.join(name) };
+    let mut entries : Vec<std::fs::DirEntry> = std::fs::read_dir(directory)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    if *component == "**" {
+        walk(directory, rest, relative, matches)?; // no directory
+        for entry in entries.iter().filter(|e| e.path().is_dir()) {
+            walk(&join(&entry.file_name()), components, false, matches)?;
+        }
+        return Ok(());
+    }
+    for entry in entries {
+        let name = entry.file_name();
+        if matches_name(component, &name.to_string_lossy()) {
+            walk(&join(&name), rest, false, matches)?;
+        }
+    }
+    Ok(())
+}
+
+fn matches_name(pattern: &str, name: &str) -> bool {
+    let fold = |s: &str| if cfg!(windows) { s.to_lowercase() } else { s.to_string() };
+    let (pattern, name) : (Vec<char>, Vec<char>) = (fold(pattern).chars().collect(), fold(name).chars().collect());
+    // hidden files are only matched by patterns starting with a dot, as in shells
+    if name.first() == Some(&'.') && pattern.first() != Some(&'.') {
+        return false;
+    }
+
+    // matched[j]: name[..j] matches the pattern read so far
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+    for p in pattern {
+        let previous = std::mem::replace(&mut matched, vec![false; name.len() + 1]);
+        for j in 0..=name.len() {
+            matched[j] = match p {
+                '*' => previous[j] || (j > 0 && matched[j - 1]),
+                '?' => j > 0 && previous[j - 1],
+                c => j > 0 && previous[j - 1] && name[j - 1] == c
+            };
+        }
+    }
+    matched[name.len()]
+}
@@ -0,0 +1,82 @@
+use std::{path::Path, error::Error, collections::{BTreeMap, BTreeSet}};
+use chrono::{DateTime, Utc};
+
+use crate::bodyfile::BodyFile;
+
+/*
+Rows of the (filtered) timeline grouped by file: several names for the same MFT record/inode expose hardlinks and NTFS index
+entries. The $FILE_NAME pseudo-entries of a record are its own timestamps, not other names, and are left out.
+*/
+pub fn write_hardlinks(bodyfile: &BodyFile, path: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut records : BTreeMap<&str, (BTreeSet<&str>, Vec<DateTime<Utc>>)> = BTreeMap::new();
+    for (meta, name, datetime) in bodyfile.meta_events().filter(|(_, name, _)| !name.ends_with(" ($FILE_NAME)")) {
+        let record = records.entry(file_address(meta)).or_default();
+        record.0.insert(name);
+        if datetime.timestamp() != 0 {
+            record.1.push(datetime);
+        }
+    }
+
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["Meta", "Names", "First", "Last", "FileNames"])?; // headers
+
+    let mut count = 0;
+    for (meta, (names, dates)) in records.iter().filter(|(_, (names, _))| names.len() > 1) {
+        let format = |date: Option<&DateTime<Utc>>| date.map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_default();
+        writer.write_record([
+            meta.to_string(),
+            names.len().to_string(),
+            format(dates.iter().min()),
+            format(dates.iter().max()),
+            names.iter().copied().collect::<Vec<&str>>().join(";")
+        ])?;
+        count += 1;
+    }
+
+    writer.flush()?;
+    Ok(count)
+}
+
+// meta address of the file: the one of TSK without its attribute type & id (1234-128-1, 1234-48-2 => 1234), the MFT
+// record & sequence of the $MFT & USN readers (1234-5) or the inode kept whole
+fn file_address(meta: &str) -> &str {
+    match meta.splitn(3, '-').collect::<Vec<&str>>()[..] {
+        [record, _, _] => record,
+        _ => meta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bodyfile::{BodyFileParser, ParseOptions};
+
+    fn report(content: &str) -> String {
+        let bodyfile = BodyFileParser::build_bytes(content.as_bytes().to_vec(), &ParseOptions::default()).expect("bodyfile parsed");
+        let path = std::env::temp_dir().join(format!("mactime-hardlinks-{}.csv", std::process::id()));
+        write_hardlinks(&bodyfile, &path).expect("report written");
+        let report = std::fs::read_to_string(&path).expect("report read");
+        std::fs::remove_file(&path).ok();
+        report
+    }
+
+    #[test]
+    fn file_name_attributes_are_not_hardlinks() {
+        let report = report("0|/Windows/a.exe|42-128-1|r|0|0|1|10|10|10|10\n0|/Windows/a.exe ($FILE_NAME)|42-48-2|r|0|0|1|5|5|5|5\n");
+        assert_eq!(report.lines().count(), 1);
+    }
+
+    #[test]
+    fn names_of_a_record_are_grouped() {
+        let report = report("0|/a/x.dll|42-128-1|r|0|0|1|10|10|10|10\n0|/b/x.dll|42-128-4|r|0|0|1|20|20|20|20\n0|/c/y|43-128-1|r|0|0|1|1|1|1|1\n");
+        assert_eq!(report.lines().nth(1), Some("42,2,1970-01-01 00:00:10,1970-01-01 00:00:20,/a/x.dll;/b/x.dll"));
+        assert_eq!(report.lines().count(), 2);
+    }
+
+    #[test]
+    fn file_addresses() {
+        assert_eq!(file_address("1234-48-2"), "1234");
+        assert_eq!(file_address("1234-5"), "1234-5");
+        assert_eq!(file_address("1234"), "1234");
+    }
+}
@@ -0,0 +1,80 @@
+use std::{path::Path, error::Error, collections::BTreeMap, fmt};
+use chrono::{Datelike, NaiveDate};
+
+use crate::bodyfile::BodyFile;
+
+/*
+Calendar heatmap (--heatmap): number of rows per day with its ISO year, week & weekday (1 = Monday), so that reporting
+templates lay out months of activity as a calendar grid. Days without rows between the first & last ones are written with
+a count of 0, the grid having no holes.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapFormat {
+    Csv,
+    Json // array of {"date", "year", "week", "weekday", "count"}
+}
+
+impl fmt::Display for HeatmapFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeatmapFormat::Csv => write!(f, "csv"),
+            HeatmapFormat::Json => write!(f, "json")
+        }
+    }
+}
+
+impl std::str::FromStr for HeatmapFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(HeatmapFormat::Csv),
+            "json" => Ok(HeatmapFormat::Json),
+            _ => Err(format!("Unknown heatmap format '{s}'"))
+        }
+    }
+}
+
+// rows per day, from the first to the last day of the timeline
+fn days(bodyfile: &BodyFile) -> BTreeMap<NaiveDate, usize> {
+    let mut days : BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for (_, date) in bodyfile.events() {
+        *days.entry(date.date_naive()).or_insert(0) += 1;
+    }
+
+    if let (Some(&first), Some(&last)) = (days.keys().next(), days.keys().next_back()) {
+        for day in first.iter_days().take_while(|d| *d <= last) {
+            days.entry(day).or_insert(0);
+        }
+    }
+    days
+}
+
+// returns the number of days written
+pub fn write_heatmap(bodyfile: &BodyFile, format: HeatmapFormat, path: &Path) -> Result<usize, Box<dyn Error>> {
+    let days = days(bodyfile);
+    match format {
+        HeatmapFormat::Csv => {
+            let mut writer = csv::Writer::from_path(path)?;
+            writer.write_record(["Date", "Year", "Week", "Weekday", "Count"])?;
+            for (day, count) in days.iter() {
+                let week = day.iso_week();
+                writer.write_record([day.format("%Y-%m-%d").to_string(), week.year().to_string(), week.week().to_string(), day.weekday().number_from_monday().to_string(), count.to_string()])?;
+            }
+            writer.flush()?;
+        }
+        HeatmapFormat::Json => {
+            let rows : Vec<serde_json::Value> = days.iter()
+                .map(|(day, count)| serde_json::json!({
+                    "date": day.format("%Y-%m-%d").to_string(),
+                    "year": day.iso_week().year(),
+                    "week": day.iso_week().week(),
+                    "weekday": day.weekday().number_from_monday(),
+                    "count": count
+                }))
+                .collect();
+            std::fs::write(path, serde_json::to_string(&rows)?)?;
+        }
+    }
+    Ok(days.len())
+}
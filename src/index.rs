@@ -0,0 +1,100 @@
+use std::{path::Path, error::Error, collections::{BTreeMap, BTreeSet}, fmt};
+
+use crate::bodyfile::{BodyFile, MACB};
+
+/* Index of the timeline (mactime -i): number of rows per day or hour, broken down by M/A/C/B since a period dominated by access times (AV scan) looks very different from one dominated by births (tool drop) */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexPeriod {
+    Day,
+    Hour
+}
+
+impl IndexPeriod {
+    fn format(&self) -> &'static str {
+        match self {
+            IndexPeriod::Day => "%Y-%m-%d",
+            IndexPeriod::Hour => "%Y-%m-%d %H:00"
+        }
+    }
+}
+
+impl fmt::Display for IndexPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            IndexPeriod::Day => "day",
+            IndexPeriod::Hour => "hour"
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::str::FromStr for IndexPeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(IndexPeriod::Day),
+            "hour" => Ok(IndexPeriod::Hour),
+            _ => Err(format!("Unknown index period '{s}'"))
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    total: usize,
+    macb: [usize; 4], // rows with the M, A, C & B flags, a row can have several
+    labels: BTreeSet<String> // annotations of the rows (--annotate)
+}
+
+impl Counts {
+    const FLAGS : [(MACB, &'static str); 4] = [(MACB::MODIFIED, "M"), (MACB::ACCESSED, "A"), (MACB::CHANGED, "C"), (MACB::BIRTH, "B")];
+
+    fn add(&mut self, macb: MACB) {
+        self.total += 1;
+        for (count, (flag, _)) in self.macb.iter_mut().zip(Self::FLAGS) {
+            if macb.contains(flag) {
+                *count += 1;
+            }
+        }
+    }
+
+    // flag of most rows (first one on ties), none for periods of labeled rows only
+    fn dominant(&self) -> &'static str {
+        self.macb.iter().zip(Self::FLAGS).rev()
+            .filter(|(count, _)| **count > 0)
+            .max_by_key(|(count, _)| **count)
+            .map_or("", |(_, (_, name))| name)
+    }
+}
+
+// returns the number of periods written
+pub fn write_index(bodyfile: &BodyFile, period: IndexPeriod, path: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut periods : BTreeMap<String, Counts> = BTreeMap::new();
+    for (date, macb) in bodyfile.macb_events() {
+        let counts = periods.entry(date.format(period.format()).to_string()).or_default();
+        counts.add(macb);
+        if let Some(label) = bodyfile.annotations().map(|a| a.label(date)).filter(|l| !l.is_empty()) {
+            counts.labels.extend(label.split(';').map(String::from));
+        }
+    }
+
+    let mut writer = csv::Writer::from_path(path)?;
+    let mut headers = vec!["Period", "Total", "M", "A", "C", "B", "Dominant"];
+    if bodyfile.annotations().is_some() {
+        headers.push("Annotation");
+    }
+    writer.write_record(&headers)?;
+    for (name, counts) in periods.iter() {
+        let mut record = vec![name.clone(), counts.total.to_string()];
+        record.extend(counts.macb.iter().map(|c| c.to_string()));
+        record.push(counts.dominant().to_string());
+        if bodyfile.annotations().is_some() {
+            record.push(counts.labels.iter().cloned().collect::<Vec<String>>().join(";"));
+        }
+        writer.write_record(&record)?;
+    }
+
+    writer.flush()?;
+    Ok(periods.len())
+}
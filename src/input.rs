@@ -0,0 +1,400 @@
+use std::{fmt, error::Error, collections::HashMap};
+use chrono::{DateTime, Utc, NaiveDateTime};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::bodyfile::{BodyFile, BodyFileEntry, DateFilter, TimestampEntry, SourceType, TimeUnit, MACB, sentinel_timestamp};
+use crate::interrupt;
+
+/* Input formats besides bodyfiles */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    #[default]
+    Bodyfile,
+    Events, // events exported by mactime: one JSON object per line, or the --format json document
+    Velociraptor, // timeline/MFT artifacts exported as JSON (one object per line, or an array)
+    Kape // KAPE module CSV output (MFTECmd $MFT)
+}
+
+impl InputFormat {
+    pub const ALL : [InputFormat; 4] = [InputFormat::Bodyfile, InputFormat::Events, InputFormat::Velociraptor, InputFormat::Kape];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            InputFormat::Bodyfile => "TSK bodyfile (fls, ils, ...), extended columns & other delimiters supported",
+            InputFormat::Events => "timeline previously exported by mactime in JSON",
+            InputFormat::Velociraptor => "Velociraptor timeline/MFT artifacts in JSON",
+            InputFormat::Kape => "KAPE MFTECmd $MFT CSV"
+        }
+    }
+}
+
+impl std::str::FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bodyfile" => Ok(InputFormat::Bodyfile),
+            "events" => Ok(InputFormat::Events),
+            "velociraptor" => Ok(InputFormat::Velociraptor),
+            "kape" => Ok(InputFormat::Kape),
+            _ => Err(format!("Unknown input format '{s}'"))
+        }
+    }
+}
+
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputFormat::Bodyfile => write!(f, "bodyfile"),
+            InputFormat::Events => write!(f, "events"),
+            InputFormat::Velociraptor => write!(f, "velociraptor"),
+            InputFormat::Kape => write!(f, "kape")
+        }
+    }
+}
+
+/*
+Input adapters: parsers of the artifact formats mactime doesn't know, added by library users without forking the crate.
+An adapter turns an input, decoded to UTF-8, into normalized events, which then go through the same filters, sort & outputs as the built-in formats.
+*/
+#[derive(Debug, Clone)]
+pub struct InputEvent {
+    pub datetime: DateTime<Utc>,
+    pub desc: String, // MACB flags ("m.c.") or label of the timestamp ("Program Executed", ...)
+    pub source: SourceType,
+    pub meta: String,
+    pub size: u64,
+    pub filename: String,
+    pub md5: Option<String>,
+    pub uid: Option<String>,
+    pub raw: Option<String> // source line/record (--include-raw)
+}
+
+impl From<InputEvent> for TimestampEntry {
+    fn from(event: InputEvent) -> Self {
+        TimestampEntry {
+            datetime: event.datetime,
+            desc: event.desc.as_str().into(),
+            source: event.source,
+            meta: event.meta.into(),
+            size: event.size,
+            filename: event.filename.into(),
+            md5: event.md5.unwrap_or_else(|| "0".to_string()).into(),
+            mode: "".into(),
+            uid: event.uid.unwrap_or_default().into(),
+            gid: "".into(),
+            tags: vec![],
+            score: 0,
+            detection: None,
+            raw: event.raw,
+            input: None,
+            host: None,
+            session: None,
+            line: None,
+            unset: Default::default()
+        }
+    }
+}
+
+pub trait InputAdapter {
+    // --input-format value
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    fn parse(&self, content: &str, include_raw: bool) -> Result<Vec<InputEvent>, Box<dyn Error>>;
+}
+
+// adapters registered next to the built-in formats
+#[derive(Default)]
+pub struct InputAdapters {
+    adapters: Vec<Box<dyn InputAdapter>>
+}
+
+impl InputAdapters {
+    pub fn register(&mut self, adapter: Box<dyn InputAdapter>) -> Result<(), String> {
+        if adapter.name().parse::<InputFormat>().is_ok() || self.get(adapter.name()).is_some() {
+            return Err(format!("Input format '{}' already exists", adapter.name()));
+        }
+        self.adapters.push(adapter);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn InputAdapter> {
+        self.adapters.iter().find(|a| a.name() == name).map(|a| a.as_ref())
+    }
+
+    // (name, description) of the built-in formats, then of the registered adapters
+    pub fn list(&self) -> Vec<(String, String)> {
+        InputFormat::ALL.iter().map(|f| (f.to_string(), f.description().to_string()))
+            .chain(self.adapters.iter().map(|a| (a.name().to_string(), a.description().to_string())))
+            .collect()
+    }
+}
+
+// event as written by `JsonSink`
+#[derive(Debug, Deserialize)]
+pub(crate) struct JsonEvent {
+    pub(crate) datetime: DateTime<Utc>,
+    pub(crate) macb: String,
+    pub(crate) meta: String,
+    pub(crate) size: u64,
+    pub(crate) filename: String,
+    #[serde(default)]
+    pub(crate) md5: Option<String>,
+    #[serde(default)]
+    pub(crate) mode: Option<String>,
+    #[serde(default)]
+    pub(crate) uid: Option<String>,
+    #[serde(default)]
+    pub(crate) gid: Option<String>,
+    #[serde(default)]
+    pub(crate) unset: Option<String>, // MACB of the sentinel timestamps of the record
+    #[serde(default)]
+    pub(crate) tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub(crate) score: Option<u64>,
+    #[serde(default, deserialize_with = "present")]
+    pub(crate) detection: Option<Option<String>>, // Some(None): column present but empty
+    #[serde(default)]
+    pub(crate) source_type: Option<String>,
+    #[serde(default)]
+    pub(crate) source_file: Option<String>, // --provenance
+    #[serde(default)]
+    pub(crate) source_line: Option<u64>
+}
+
+// distinguish a null field from a missing one
+fn present<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Option<String>>, D::Error> {
+    Option::<String>::deserialize(deserializer).map(Some)
+}
+
+// 1-based line of the byte offsets of a content, read in increasing order
+pub(crate) struct LineCounter<'a> {
+    content: &'a str,
+    offset: usize,
+    line: u64
+}
+
+impl<'a> LineCounter<'a> {
+    pub(crate) fn new(content: &'a str) -> Self {
+        Self { content, offset: 0, line: 1 }
+    }
+
+    // line of the first value/record after `offset` (JSON values are separated by whitespace)
+    pub(crate) fn line(&mut self, offset: usize) -> u64 {
+        let start = self.content[offset..].find(|c: char| !c.is_whitespace()).map_or(self.content.len(), |i| offset + i);
+        self.line += self.content[self.offset..start].matches('\n').count() as u64;
+        self.offset = start;
+        self.line
+    }
+}
+
+pub fn parse_events(bodyfile: &mut BodyFile, content: &str, filter: &Option<DateFilter>) {
+    let mut stream = serde_json::Deserializer::from_str(content).into_iter::<Value>();
+    let mut lines = LineCounter::new(content);
+    loop {
+        let line = lines.line(stream.byte_offset());
+        let Some(value) = stream.next().filter(|_| !interrupt::interrupted()) else { break };
+        match value {
+            // --format json document => its events, one per line after the metadata as written by mactime
+            Ok(Value::Object(mut document)) if document.contains_key("events") => {
+                if let Some(Value::Array(events)) = document.remove("events") {
+                    for (i, event) in events.into_iter().enumerate() {
+                        add_event(bodyfile, event, filter, line + 1 + i as u64);
+                    }
+                }
+            }
+            Ok(event) => add_event(bodyfile, event, filter, line),
+            Err(e) => {
+                bodyfile.invalid_line(format!("event => {e}"));
+                break; // the stream can't be resynchronized
+            }
+        }
+    }
+}
+
+// rows of a timeline exported with --provenance keep their own source file & line
+fn add_event(bodyfile: &mut BodyFile, value: Value, filter: &Option<DateFilter>, line: u64) {
+    let raw = bodyfile.include_raw().then(|| value.to_string());
+    match serde_json::from_value(value) {
+        Ok(event) => add_json_event(bodyfile, event, raw, filter, line),
+        Err(e) => bodyfile.invalid_line(format!("event => {e}"))
+    }
+}
+
+// event of a mactime output (JSON, binary), `line` being its line or record number
+pub(crate) fn add_json_event(bodyfile: &mut BodyFile, event: JsonEvent, raw: Option<String>, filter: &Option<DateFilter>, line: u64) {
+    if !DateFilter::in_range(filter, event.datetime) {
+        return;
+    }
+
+    let tagged = event.tags.is_some();
+    let enriched = event.detection.is_some();
+    let entry = TimestampEntry {
+        datetime: event.datetime,
+        desc: event.macb.as_str().into(), // MACB string, or label of a non-MACB timestamp
+        source: event.source_type.and_then(|s| s.parse().ok()).unwrap_or_default(),
+        meta: event.meta.into(),
+        size: event.size,
+        filename: event.filename.into(),
+        md5: event.md5.unwrap_or_else(|| "0".to_string()).into(),
+        mode: event.mode.unwrap_or_default().into(),
+        uid: event.uid.unwrap_or_default().into(),
+        gid: event.gid.unwrap_or_default().into(),
+        tags: event.tags.unwrap_or_default(),
+        score: event.score.unwrap_or(0),
+        detection: event.detection.flatten().and_then(|d| d.parse().ok()),
+        raw,
+        input: event.source_file.as_deref().map(|f| bodyfile.input_index(f)),
+        host: None,
+        session: None,
+        line: if event.source_file.is_some() { event.source_line } else { Some(line) },
+        unset: event.unset.and_then(|u| u.parse().ok()).unwrap_or_default()
+    };
+    bodyfile.add_event(entry, tagged, enriched);
+}
+
+/*
+Field names of the file records of endpoint triage tools, first match wins:
+- Velociraptor: Generic.Forensic.Timeline (FullPath, Mtime, ...) and Windows.NTFS.MFT (OSPath, Created0x10, ...)
+- KAPE: MFTECmd CSV (ParentPath + FileName, Created0x10, ...)
+*/
+const PATH: &[&str] = &["OSPath", "FullPath", "Path"];
+const PARENT: &[&str] = &["ParentPath"];
+const NAME: &[&str] = &["FileName", "Name"];
+const SIZE: &[&str] = &["Size", "FileSize"];
+const META: &[&str] = &["Inode", "EntryNumber"];
+const SEQUENCE: &[&str] = &["SequenceNumber"];
+const IN_USE: &[&str] = &["InUse"];
+const MTIME: &[&str] = &["Mtime", "Modified", "LastModified0x10"];
+const ATIME: &[&str] = &["Atime", "Accessed", "LastAccess0x10"];
+const CTIME: &[&str] = &["Ctime", "Changed", "LastRecordChange0x10"];
+const BTIME: &[&str] = &["Btime", "Created", "Created0x10"];
+const ADDED: &[&str] = &["DateAdded", "date_added", "Added"]; // APFS
+
+// Unix seconds placeholder of an unset timestamp (0xFFFFFFFF, ...)
+fn sentinel(value: &str) -> bool {
+    value.trim().parse::<i64>().is_ok_and(|s| sentinel_timestamp(s, TimeUnit::Seconds))
+}
+
+// RFC 3339, "YYYY-MM-DD HH:MM:SS[.f]" (KAPE) or Unix seconds; unset timestamps and sentinels (0xFFFFFFFF, ...) are epoch 0 as in bodyfiles
+fn parse_timestamp(value: Option<&str>) -> DateTime<Utc> {
+    let value = value.map(str::trim).unwrap_or_default();
+    DateTime::parse_from_rfc3339(value).map(|d| d.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f").map(|d| d.and_utc()))
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f").map(|d| d.and_utc()))
+        .ok()
+        .or_else(|| value.parse::<i64>().ok().filter(|s| !sentinel_timestamp(*s, TimeUnit::Seconds)).and_then(|s| DateTime::from_timestamp(s, 0)))
+        .unwrap_or_default()
+}
+
+// normalized record => bodyfile entry, None if no path can be found
+fn to_entry(record: &HashMap<String, String>, source: SourceType) -> Option<BodyFileEntry> {
+    let get = |names: &[&str]| names.iter().find_map(|n| record.get(*n)).map(String::as_str).filter(|v| !v.is_empty());
+
+    let mut name = match (get(PATH), get(PARENT), get(NAME)) {
+        (Some(path), _, _) => path.to_string(),
+        (None, Some(parent), Some(name)) => format!("{}/{name}", parent.trim_end_matches(['/', '\\'])),
+        (None, None, Some(name)) => name.to_string(),
+        _ => return None
+    };
+    name = name.replace('\\', "/");
+    if get(IN_USE).is_some_and(|v| v.eq_ignore_ascii_case("false")) {
+        name.push_str(" (deleted)"); // same marker as fls
+    }
+
+    let meta = match (get(META), get(SEQUENCE)) {
+        (Some(entry), Some(sequence)) => format!("{entry}-{sequence}"),
+        (Some(entry), None) => entry.to_string(),
+        _ => "0".to_string()
+    };
+
+    Some(BodyFileEntry {
+        md5: "0".to_string(),
+        name,
+        meta,
+        mode: String::new(),
+        uid: String::new(),
+        gid: String::new(),
+        size: get(SIZE).and_then(|s| s.parse().ok()).unwrap_or(0),
+        atime: parse_timestamp(get(ATIME)),
+        mtime: parse_timestamp(get(MTIME)),
+        ctime: parse_timestamp(get(CTIME)),
+        crtime: parse_timestamp(get(BTIME)),
+        extra: get(ADDED).filter(|d| !sentinel(d)).map(|d| ("date_added".to_string(), parse_timestamp(Some(d)))).into_iter().collect(),
+        source,
+        raw: None,
+        line: None,
+        invalid: false,
+        unset: [(ATIME, MACB::ACCESSED), (MTIME, MACB::MODIFIED), (CTIME, MACB::CHANGED), (BTIME, MACB::BIRTH)].into_iter()
+            .filter(|(names, _)| get(names).is_some_and(sentinel))
+            .fold(MACB::empty(), |unset, (_, flag)| unset | flag)
+    })
+}
+
+fn add_record(bodyfile: &mut BodyFile, record: HashMap<String, String>, source: SourceType, raw: Option<String>, line: Option<u64>) {
+    match to_entry(&record, source) {
+        Some(entry) => bodyfile.add_entry(BodyFileEntry { raw, line, ..entry }),
+        None => bodyfile.invalid_line(format!("record => no path field in {record:?}"))
+    }
+}
+
+pub fn parse_velociraptor(bodyfile: &mut BodyFile, content: &str) {
+    let mut stream = serde_json::Deserializer::from_str(content).into_iter::<Value>();
+    let mut lines = LineCounter::new(content);
+    loop {
+        let line = lines.line(stream.byte_offset());
+        let Some(value) = stream.next().filter(|_| !interrupt::interrupted()) else { break };
+        // the line of the rows of an array is unknown
+        let (rows, line) = match value {
+            Ok(Value::Array(rows)) => (rows, None),
+            Ok(row) => (vec![row], Some(line)),
+            Err(e) => {
+                bodyfile.invalid_line(format!("record => {e}"));
+                break; // the stream can't be resynchronized
+            }
+        };
+
+        for row in rows {
+            let raw = bodyfile.include_raw().then(|| row.to_string());
+            let Value::Object(fields) = row else {
+                bodyfile.invalid_line(format!("record => not an object: {row}"));
+                continue;
+            };
+            let record : HashMap<String, String> = fields.into_iter()
+                .map(|(k, v)| (k, match v { Value::String(s) => s, v => v.to_string() }))
+                .collect();
+            // Windows.NTFS.MFT rows carry the MFT entry number, Generic.Forensic.Timeline rows don't
+            let source = if META.iter().any(|m| record.contains_key(*m)) { SourceType::Mft } else { SourceType::File };
+            add_record(bodyfile, record, source, raw, line);
+        }
+    }
+}
+
+pub fn parse_kape(bodyfile: &mut BodyFile, content: &str) {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.as_bytes());
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            eprintln!("Error reading CSV headers => {e}");
+            return;
+        }
+    };
+
+    let mut lines = LineCounter::new(content);
+    for record in reader.records().take_while(|_| !interrupt::interrupted()) {
+        match record {
+            Ok(record) => {
+                let raw = record.position().filter(|_| bodyfile.include_raw()).and_then(|p| content[p.byte() as usize..].lines().next()).map(String::from);
+                let line = record.position().map(|p| lines.line(p.byte() as usize));
+                add_record(bodyfile, headers.iter().map(String::from).zip(record.iter().map(String::from)).collect(), SourceType::Mft, raw, line);
+            }
+            Err(e) => bodyfile.invalid_line(format!("record => {e}"))
+        }
+    }
+}
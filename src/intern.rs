@@ -0,0 +1,105 @@
+use std::{sync::Arc, collections::HashSet, fmt, ops::Deref, borrow::Borrow};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+/*
+Shared strings of the in-memory timeline: the file name, meta address, MD5 & UID of a bodyfile record are stored once,
+then shared by each of its datetime records (up to 4 MACB rows, plus the labeled timestamps) instead of being copied to
+each of them. With --intern, identical strings are also shared across records and inputs through an interning table:
+MD5s & UIDs repeated by most records ("0"), the same path read from several inputs (the same host collected twice) are
+then stored once, at the cost of the table while parsing.
+*/
+#[derive(Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Interned(Arc<str>);
+
+impl Interned {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Interned {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Interned {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Interned {
+    fn from(s: &str) -> Self {
+        Interned(Arc::from(s))
+    }
+}
+
+impl From<String> for Interned {
+    fn from(s: String) -> Self {
+        Interned(Arc::from(s))
+    }
+}
+
+impl fmt::Display for Interned {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Interned {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+// serialized as a plain string (checkpoints)
+impl Serialize for Interned {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Interned {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Interned::from)
+    }
+}
+
+// table of the strings already stored (--intern)
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    strings: HashSet<Interned>
+}
+
+impl Interner {
+    pub fn intern(&mut self, s: &str) -> Interned {
+        if let Some(interned) = self.strings.get(s) {
+            return interned.clone();
+        }
+        let interned = Interned::from(s);
+        self.strings.insert(interned.clone());
+        interned
+    }
+
+    // string of another table (merged inputs), shared with this one's copy if any
+    pub fn share(&mut self, s: Interned) -> Interned {
+        match self.strings.get(s.as_str()) {
+            Some(interned) => interned.clone(),
+            None => {
+                self.strings.insert(s.clone());
+                s
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
@@ -0,0 +1,20 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/* Ctrl-C: parsing stops, the rows read so far are written & flushed and the output is flagged as partial. A second Ctrl-C exits at once */
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+pub const EXIT_CODE: i32 = 130; // 128 + SIGINT
+
+pub fn install() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(EXIT_CODE);
+        }
+        eprintln!("Interrupted, finalizing partial output (Ctrl-C again to abort)");
+    })
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,156 @@
+use std::{path::Path, error::Error, fmt, collections::HashMap};
+
+use crate::matcher::KeywordMatcher;
+
+/* IOC list: one indicator per line, type auto-detected (MD5, full path or bare filename), with an optional `|weight` (default 1) */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IocKind {
+    Md5,
+    Path,
+    Filename
+}
+
+impl fmt::Display for IocKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            IocKind::Md5 => "md5",
+            IocKind::Path => "path",
+            IocKind::Filename => "filename"
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug)]
+pub struct Ioc {
+    pub kind: IocKind,
+    pub value: String, // as written in the IOC file
+    normalized: String, // lowercase, '/' separators
+    pub weight: u32,
+    pub hits: usize
+}
+
+impl Ioc {
+    fn parse(line: &str) -> Result<Self, Box<dyn Error>> {
+        match line.rsplit_once('|') {
+            Some((value, weight)) => {
+                let weight = weight.trim().parse().map_err(|e| format!("Invalid weight in IOC '{line}': {e}"))?;
+                Ok(Self { weight, ..Self::new(value.trim()) })
+            }
+            None => Ok(Self::new(line))
+        }
+    }
+
+    fn new(value: &str) -> Self {
+        let kind = if value.len() == 32 && value.chars().all(|c| c.is_ascii_hexdigit()) {
+            IocKind::Md5
+        } else if value.contains('/') || value.contains('\\') {
+            IocKind::Path
+        } else {
+            IocKind::Filename
+        };
+
+        Self {
+            kind,
+            value: value.to_string(),
+            normalized: normalize(value),
+            weight: 1,
+            hits: 0
+        }
+    }
+
+    // `filename` already normalized
+    fn matches(&self, filename: &str, md5: &str) -> bool {
+        match self.kind {
+            IocKind::Md5 => md5.eq_ignore_ascii_case(&self.normalized),
+            IocKind::Path => {
+                // IOC paths may lack the drive/mount prefix: match on a path component boundary
+                filename == self.normalized
+                    || (filename.ends_with(&self.normalized) && (self.normalized.starts_with('/') || filename[..filename.len() - self.normalized.len()].ends_with('/')))
+            }
+            IocKind::Filename => filename.rsplit('/').next() == Some(self.normalized.as_str())
+        }
+    }
+
+    pub fn tag(&self) -> String {
+        format!("ioc:{}", self.value)
+    }
+}
+
+// lowercase, forward slashes, fls "(deleted)" markers stripped
+pub fn normalize(name: &str) -> String {
+    let name = name.trim_end_matches(" (deleted)").trim_end_matches(" (deleted-realloc)");
+    name.replace('\\', "/").to_lowercase()
+}
+
+// a row is only checked against the few IOCs found in its file name (single automaton of the paths & names) or sharing its MD5, so sweeps with tens of thousands of IOCs stay linear in the number of rows
+#[derive(Debug)]
+pub struct IocList {
+    iocs: Vec<Ioc>,
+    names: KeywordMatcher,
+    name_iocs: Vec<usize>, // IOC of each keyword of `names`
+    md5s: HashMap<String, Vec<usize>>
+}
+
+impl IocList {
+    pub fn from_path(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, Box<dyn Error>> {
+        let iocs = content.lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#')) // skip blank lines & comments
+            .map(Ioc::parse)
+            .collect::<Result<Vec<Ioc>, Box<dyn Error>>>()?;
+
+        let mut md5s : HashMap<String, Vec<usize>> = HashMap::new();
+        let mut name_iocs = vec![];
+        for (i, ioc) in iocs.iter().enumerate() {
+            match ioc.kind {
+                IocKind::Md5 => md5s.entry(ioc.normalized.clone()).or_default().push(i),
+                IocKind::Path | IocKind::Filename => name_iocs.push(i)
+            }
+        }
+        let names = KeywordMatcher::new(name_iocs.iter().map(|&i| iocs[i].normalized.as_str()), false)?; // already lowercase
+        Ok(Self { iocs, names, name_iocs, md5s })
+    }
+
+    // indices of the IOCs matching a file entry, hit counters are updated: called once per file, not per row
+    pub fn check(&mut self, filename: &str, md5: &str) -> Vec<usize> {
+        let filename = normalize(filename);
+        let md5 = md5.to_lowercase();
+        let mut candidates : Vec<usize> = self.names.matches(&filename).into_iter()
+            .map(|k| self.name_iocs[k])
+            .chain(self.md5s.get(&md5).into_iter().flatten().copied())
+            .collect();
+        candidates.sort_unstable(); // IOC file order
+        candidates.dedup();
+
+        let mut matched = vec![];
+        for i in candidates {
+            let ioc = &mut self.iocs[i];
+            if ioc.matches(&filename, &md5) {
+                ioc.hits += 1;
+                matched.push(i);
+            }
+        }
+        matched
+    }
+
+    pub fn get(&self, index: usize) -> &Ioc {
+        &self.iocs[index]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Ioc> {
+        self.iocs.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.iocs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iocs.is_empty()
+    }
+}
@@ -0,0 +1,86 @@
+use std::{path::Path, error::Error, collections::{BTreeMap, BTreeSet}};
+use chrono::{DateTime, Utc, Duration};
+
+use crate::artifacts::{self, Artifact};
+use crate::bodyfile::{BodyFile, MACB};
+
+const MAX_FILES: usize = 5;
+
+type ArtifactRow<'a> = (DateTime<Utc>, Artifact, bool, &'a str); // datetime, artifact, birth, filename
+
+/*
+Lateral movement (--lateral-movement): remote-execution artifacts of a host (PsExec service binaries, scheduled task
+files, WMI repository and services changes) less than a window apart are correlated into a single activity. Only the
+activities holding the birth of an artifact are reported, WMI repository & SYSTEM hive modifications alone being routine.
+*/
+#[derive(Debug)]
+pub struct LateralMovement {
+    pub host: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub count: usize,
+    pub artifacts: BTreeMap<Artifact, usize>,
+    pub files: BTreeSet<String>
+}
+
+impl LateralMovement {
+    fn new(host: &str, date: DateTime<Utc>) -> Self {
+        Self { host: host.to_string(), start: date, end: date, count: 0, artifacts: BTreeMap::new(), files: BTreeSet::new() }
+    }
+
+    fn add(&mut self, date: DateTime<Utc>, artifact: Artifact, filename: &str) {
+        self.end = date;
+        self.count += 1;
+        *self.artifacts.entry(artifact).or_insert(0) += 1;
+        self.files.insert(filename.to_string());
+    }
+}
+
+// likely lateral-movement times of each host, in chronological order
+pub fn correlate(bodyfile: &BodyFile, window: Duration) -> Vec<LateralMovement> {
+    let mut rows : BTreeMap<&str, Vec<ArtifactRow>> = BTreeMap::new();
+    for (host, date, macb, filename) in bodyfile.host_rows() {
+        if let Some(artifact) = artifacts::categorize(filename, macb) {
+            rows.entry(host.unwrap_or("")).or_default().push((date, artifact, macb.contains(MACB::BIRTH), filename));
+        }
+    }
+
+    let mut movements = vec![];
+    for (host, mut rows) in rows.into_iter() {
+        rows.sort_by_key(|(date, _, _, _)| *date);
+        let mut current : Option<(LateralMovement, bool)> = None; // activity, holding a birth
+        for (date, artifact, born, filename) in rows {
+            if current.as_ref().is_none_or(|(m, _)| date - m.end > window) {
+                movements.extend(current.take().filter(|(_, born)| *born).map(|(m, _)| m));
+                current = Some((LateralMovement::new(host, date), false));
+            }
+            let (movement, birth) = current.as_mut().expect("current activity");
+            movement.add(date, artifact, filename);
+            *birth |= born;
+        }
+        movements.extend(current.filter(|(_, born)| *born).map(|(m, _)| m));
+    }
+    movements.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.host.cmp(&b.host)));
+    movements
+}
+
+pub fn write_movements(movements: &[LateralMovement], path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["Host", "Start", "End", "Count", "Artifacts", "Files"])?;
+
+    for movement in movements {
+        let artifacts : Vec<String> = movement.artifacts.iter().map(|(artifact, count)| format!("{artifact} ({count})")).collect();
+        let files : Vec<&str> = movement.files.iter().map(String::as_str).take(MAX_FILES).collect();
+        writer.write_record([
+            movement.host.clone(),
+            movement.start.format("%Y-%m-%d %H:%M:%S").to_string(),
+            movement.end.format("%Y-%m-%d %H:%M:%S").to_string(),
+            movement.count.to_string(),
+            artifacts.join(", "),
+            files.join(", ")
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
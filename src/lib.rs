@@ -0,0 +1,55 @@
+/* mactime as a library: parse inputs into a BodyFile, then write its timeline to a built-in or custom OutputSink */
+pub mod accounts;
+pub mod analysis;
+pub mod archive;
+pub mod annotations;
+pub mod artifacts;
+pub mod binary;
+pub mod bodyfile;
+pub mod clock;
+pub mod deadletter;
+pub mod encoding;
+pub mod encrypt;
+pub mod evtx;
+pub mod glob;
+pub mod export;
+pub mod filterset;
+pub mod hardlinks;
+pub mod heatmap;
+pub mod index;
+pub mod input;
+pub mod intern;
+pub mod interrupt;
+pub mod ioc;
+pub mod lateral;
+pub mod limits;
+pub mod locale;
+pub mod manifest;
+pub mod matcher;
+pub mod metrics;
+pub mod mft;
+pub mod parallel;
+pub mod prefetch;
+pub mod preflight;
+pub mod ratelimit;
+pub mod redact;
+pub mod repl;
+pub mod retry;
+pub mod rules;
+pub mod schema;
+pub mod script;
+pub mod selftest;
+pub mod sequence;
+pub mod session;
+pub mod sink;
+pub mod sorted;
+pub mod state;
+pub mod stats;
+pub mod stream;
+pub mod supertimeline;
+pub mod tagcmd;
+pub mod timezone;
+pub mod tree;
+pub mod usn;
+pub mod version;
+pub mod vt;
@@ -0,0 +1,223 @@
+use std::{borrow::Cow, error::Error, fmt};
+use ring::digest;
+
+use crate::preflight;
+use crate::sink::{Event, OutputSink};
+
+/*
+Field size limits (--max-field-length): SIEMs refuse oversized values (32 KB Elasticsearch keywords, ...), and a
+single pathological file name would otherwise break a bulk ingestion late in a run. Each limit applies to a column
+of the output rows with a policy: truncate the value, truncate it with a hash of the whole value as suffix (values
+still told apart & searchable), or skip the row. Limits are in bytes, values cut on a character boundary.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitedField {
+    FileName,
+    Meta,
+    Raw
+}
+
+impl fmt::Display for LimitedField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LimitedField::FileName => "filename",
+            LimitedField::Meta => "meta",
+            LimitedField::Raw => "raw"
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::str::FromStr for LimitedField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "filename" => Ok(LimitedField::FileName),
+            "meta" => Ok(LimitedField::Meta),
+            "raw" => Ok(LimitedField::Raw),
+            _ => Err(format!("Unknown limited field '{s}' (filename, meta, raw)"))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LimitPolicy {
+    #[default]
+    Truncate,
+    HashSuffix,
+    Skip
+}
+
+impl fmt::Display for LimitPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LimitPolicy::Truncate => "truncate",
+            LimitPolicy::HashSuffix => "hash-suffix",
+            LimitPolicy::Skip => "skip"
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::str::FromStr for LimitPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "truncate" => Ok(LimitPolicy::Truncate),
+            "hash-suffix" => Ok(LimitPolicy::HashSuffix),
+            "skip" => Ok(LimitPolicy::Skip),
+            _ => Err(format!("Unknown field limit policy '{s}' (truncate, hash-suffix, skip)"))
+        }
+    }
+}
+
+// ~ & 16 hex digits of the SHA-256 of the whole value
+const HASH_SUFFIX_LEN: usize = 17;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FieldLimit {
+    pub field: LimitedField,
+    pub max: usize,
+    pub policy: LimitPolicy
+}
+
+impl FieldLimit {
+    // None: the row is skipped
+    fn apply<'a>(&self, value: Cow<'a, str>) -> Option<Cow<'a, str>> {
+        if value.len() <= self.max {
+            return Some(value);
+        }
+        match self.policy {
+            LimitPolicy::Truncate => Some(Cow::Owned(value[..boundary(&value, self.max)].to_string())),
+            LimitPolicy::HashSuffix => {
+                let hash : String = digest::digest(&digest::SHA256, value.as_bytes()).as_ref()[..8].iter().map(|b| format!("{b:02x}")).collect();
+                Some(Cow::Owned(format!("{}~{hash}", &value[..boundary(&value, self.max - HASH_SUFFIX_LEN)])))
+            }
+            LimitPolicy::Skip => None
+        }
+    }
+}
+
+// longest prefix of at most `max` bytes ending on a character boundary
+fn boundary(value: &str, max: usize) -> usize {
+    (0..=max).rev().find(|&i| value.is_char_boundary(i)).unwrap_or(0)
+}
+
+impl fmt::Display for FieldLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.field, self.max, self.policy)
+    }
+}
+
+impl std::str::FromStr for FieldLimit {
+    type Err = String;
+
+    // <field>:<size>[:<policy>]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Invalid field limit '{s}', expected <field>:<size>[:truncate|hash-suffix|skip] (e.g. filename:32K:hash-suffix)");
+        let mut parts = s.split(':');
+        let (Some(field), Some(max)) = (parts.next(), parts.next()) else { return Err(error()) };
+        let policy = parts.next().map(str::parse).transpose()?.unwrap_or_default();
+        if parts.next().is_some() {
+            return Err(error());
+        }
+        let max = preflight::parse_size(max)? as usize;
+        if max == 0 || (policy == LimitPolicy::HashSuffix && max <= HASH_SUFFIX_LEN) {
+            return Err(format!("Field limit '{s}' too small{}", if policy == LimitPolicy::HashSuffix { " for its hash suffix" } else { "" }));
+        }
+        Ok(Self { field: field.parse()?, max, policy })
+    }
+}
+
+// rows of the wrapped sink with their fields limited, the skipped ones counted
+pub struct LimitedSink {
+    sink: Box<dyn OutputSink>,
+    limits: Vec<FieldLimit>,
+    skipped: usize
+}
+
+impl LimitedSink {
+    pub fn new(sink: Box<dyn OutputSink>, limits: &[FieldLimit]) -> Self {
+        Self { sink, limits: limits.to_vec(), skipped: 0 }
+    }
+}
+
+impl OutputSink for LimitedSink {
+    fn write_header(&mut self, columns: &[&str], metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        self.sink.write_header(columns, metadata)
+    }
+
+    fn write_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let mut event = event.clone();
+        for limit in self.limits.iter() {
+            let value = match limit.field {
+                LimitedField::FileName => Some(&mut event.filename),
+                LimitedField::Meta => Some(&mut event.meta),
+                LimitedField::Raw => event.raw.as_mut().and_then(Option::as_mut)
+            };
+            let Some(value) = value else { continue };
+            match limit.apply(std::mem::take(value)) {
+                Some(limited) => *value = limited,
+                None => {
+                    self.skipped += 1;
+                    return Ok(());
+                }
+            }
+        }
+        self.sink.write_event(&event)
+    }
+
+    fn write_section(&mut self, name: &str, columns: &[&str]) -> Result<(), Box<dyn Error>> {
+        self.sink.write_section(name, columns)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sink.flush()
+    }
+
+    fn finish(&mut self, partial: bool) -> Result<(), Box<dyn Error>> {
+        if self.skipped > 0 {
+            eprintln!("Number of datetime records skipped over --max-field-length: {}", self.skipped);
+        }
+        self.sink.finish(partial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limited(limit: &str, value: &str) -> Option<String> {
+        limit.parse::<FieldLimit>().expect("field limit").apply(Cow::Borrowed(value)).map(Cow::into_owned)
+    }
+
+    #[test]
+    fn truncate() {
+        assert_eq!(limited("filename:8", "/tmp/a"), Some("/tmp/a".to_string()));
+        assert_eq!(limited("filename:8", "/tmp/abcdef"), Some("/tmp/abc".to_string()));
+        // é is 2 bytes: never cut in its middle
+        assert_eq!(limited("filename:6", "/tmp/é"), Some("/tmp/".to_string()));
+        assert_eq!(limited("filename:7", "/tmp/é"), Some("/tmp/é".to_string()));
+    }
+
+    #[test]
+    fn hash_suffix() {
+        let value = "ab€€€€€€€€€€€€€€€€";
+        let limited = limited("filename:21:hash-suffix", value).expect("kept");
+        // 4 bytes left for the value, the third one being inside the € of 3 bytes
+        let (prefix, hash) = limited.split_once('~').expect("hash suffix");
+        assert_eq!(prefix, "ab");
+        assert_eq!(hash.len(), 16);
+        assert!(limited.len() <= 21);
+        assert_ne!(Some(limited), self::limited("filename:21:hash-suffix", &format!("{value}€")));
+        assert!("filename:17:hash-suffix".parse::<FieldLimit>().is_err());
+    }
+
+    #[test]
+    fn skip() {
+        assert_eq!(limited("meta:4:skip", "1-128-1"), None);
+        assert_eq!(limited("meta:8:skip", "1-128-1"), Some("1-128-1".to_string()));
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Timelike};
+
+/* Number formatting of the human-oriented outputs (CSV): thousands separator of sizes & decimal point of sub-seconds, JSON stays raw */
+#[derive(Debug, Clone)]
+pub struct NumberFormat {
+    thousands: Option<String>, // none: 1835008
+    decimal: String
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self { thousands: None, decimal: ".".to_string() }
+    }
+}
+
+impl NumberFormat {
+    pub fn new(thousands: Option<&str>, decimal: &str) -> Self {
+        Self {
+            thousands: thousands.map(str::to_string),
+            decimal: decimal.to_string()
+        }
+    }
+
+    // 1835008 => "1,835,008" / "1 835 008"
+    pub fn size(&self, size: u64) -> String {
+        let digits = size.to_string();
+        let Some(separator) = self.thousands.as_ref() else { return digits };
+
+        let mut grouped = String::new();
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push_str(separator);
+            }
+            grouped.push(c);
+        }
+        grouped
+    }
+
+    // "2020-07-21 00:38:18", sub-seconds only when the source has them: "2020-07-21 00:38:18.1234567"
+    pub fn datetime<Tz: chrono::TimeZone>(&self, datetime: DateTime<Tz>) -> String where Tz::Offset: std::fmt::Display {
+        let seconds = datetime.format("%Y-%m-%d %H:%M:%S").to_string();
+        match datetime.nanosecond() % 1_000_000_000 { // leap second: 23:59:60, nanoseconds past 10^9
+            0 => seconds,
+            nanos => format!("{seconds}{}{}", self.decimal, format!("{nanos:09}").trim_end_matches('0'))
+        }
+    }
+}
+
+// separators must not be mistaken for digits
+pub fn validate_separator(separator: &str) -> Result<(), String> {
+    if separator.is_empty() || separator.chars().any(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid separator '{separator}'"));
+    }
+    Ok(())
+}
@@ -1,32 +1,1023 @@
-use std::{error::Error, path::Path};
-use chrono::{NaiveDate};
-use clap::{Command, Arg};
+use std::{error::Error, path::{Path, PathBuf}, collections::BTreeMap, ops::RangeInclusive, fs::File, io::{BufRead, BufReader, IsTerminal, Write}};
+use chrono::{DateTime, Utc};
+use clap::{Command, Arg, ArgGroup, ArgMatches};
 
-mod bodyfile;
-use bodyfile::{BodyFileParser, DateFilter};
+use mactime::{accounts, analysis, annotations, archive, binary, bodyfile, clock, encoding, encrypt, export, filterset, glob, hardlinks, heatmap, index, input, interrupt, ioc, lateral, limits, locale, manifest, matcher, metrics, parallel, preflight, redact, repl, retry, rules, schema, script, selftest, sequence, session, sorted, state, stats, stream, supertimeline, tagcmd, timezone, tree, version, vt};
+use accounts::IdNames;
+use analysis::AnalysisOptions;
+use annotations::Annotations;
+use bodyfile::{BodyFile, BodyFileParser, ParseOptions, LineEnding, MissingValue, DateFilter, TimeOffset, UserFilter, SortKey, SortBy, GroupBy, OutputFormat, InvalidTimestamp, parse_duration, SourceType};
+use encoding::Encoding;
+use encrypt::Encryption;
+use filterset::{FilterSet, SavedRules, FORMAT, parse_filter_args, parse_year_range};
+use heatmap::HeatmapFormat;
+use index::IndexPeriod;
+use input::{InputFormat, InputAdapters};
+use ioc::IocList;
+use limits::FieldLimit;
+use locale::NumberFormat;
+use matcher::KeywordMatcher;
+use redact::Redaction;
+use repl::Repl;
+use retry::{IoRetry, OnUnreadable, Unreadable};
+use rules::RuleSet;
+use schema::{SchemaFormat, SchemaLevel};
+use script::Script;
+use state::SeenSet;
+use stats::Stats;
+use supertimeline::{Sources, Stage};
+use tagcmd::TagCommand;
+use timezone::TimeZone;
+use vt::VirusTotal;
 
-const FORMAT : &str = "Date filter format: YYYY-MM-DD..YYYY-MM-DD (time not handled yet)";
+// share of common rows from which two inputs are reported as overlapping
+const OVERLAP_WARNING : f64 = 0.5;
 
-fn parse_filter_args(args: &str) -> Result<[NaiveDate;2], String> {
-    fn validate_date(date: &str) -> Result<NaiveDate, String> {
-        NaiveDate::parse_from_str(date, "%F")
-            // .map(|_| ()) // ignore NaiveDate
-            .map_err(|_| String::from("Dates must be in the YYYY-MM-DD format")) // Year-month-day format (ISO 8601). Same as %Y-%m-%d
+fn validate_filter_args(args: &str) -> Result<(), String> {
+    parse_filter_args(args).map(|_| ()) // clap doesn't want a value!
+}
+
+// 1-based line ranges: 1000000..2000000, ..500 or 1000..
+fn parse_line_range(arg: &str) -> Result<RangeInclusive<usize>, String> {
+    let error = || format!("Invalid line range '{arg}', expected <start>..<end> (e.g. 1000000..2000000)");
+    let (start, end) = arg.split_once("..").ok_or_else(error)?;
+    let start = if start.is_empty() { 1 } else { start.parse().map_err(|_| error())? };
+    let end = if end.is_empty() { usize::MAX } else { end.parse().map_err(|_| error())? };
+    if start == 0 || start > end {
+        return Err(error());
     }
+    Ok(start..=end)
+}
+
+fn validate_line_range(arg: &str) -> Result<(), String> {
+    parse_line_range(arg).map(|_| ())
+}
 
-    let dates : Vec<&str> = args.split("..").collect();
-    if dates.len() != 2 {
-        return Err(FORMAT.into())
+// shards like 3/8: the 3rd of 8 shards
+fn parse_shard(arg: &str) -> Result<(usize, usize), String> {
+    let error = || format!("Invalid shard '{arg}', expected <index>/<count> (e.g. 3/8)");
+    let (index, count) = arg.split_once('/').ok_or_else(error)?;
+    let (index, count) : (usize, usize) = (index.parse().map_err(|_| error())?, count.parse().map_err(|_| error())?);
+    if index == 0 || index > count {
+        return Err(error());
     }
+    Ok((index, count))
+}
 
-    let start = validate_date(dates[0])?; // start
-    let end = validate_date(dates[1])?; // end
+fn validate_shard(arg: &str) -> Result<(), String> {
+    parse_shard(arg).map(|_| ())
+}
 
-    Ok([start, end])
+fn validate_year_range(arg: &str) -> Result<(), String> {
+    parse_year_range(arg).map(|_| ())
 }
 
-fn validate_filter_args(args: &str) -> Result<(), String> {
-    parse_filter_args(args).map(|_| ()) // clap doesn't want a value!
+// single ASCII character, \t or "tab" for tab-delimited files
+fn parse_delimiter(arg: &str) -> Result<u8, String> {
+    match arg {
+        "\\t" | "tab" => Ok(b'\t'),
+        _ if arg.len() == 1 && arg.is_ascii() => Ok(arg.as_bytes()[0]),
+        _ => Err(format!("Invalid delimiter '{arg}', expected a single character (e.g. ',' or tab)"))
+    }
+}
+
+fn validate_delimiter(arg: &str) -> Result<(), String> {
+    parse_delimiter(arg).map(|_| ())
+}
+
+fn validate_duration(arg: &str) -> Result<(), String> {
+    parse_duration(arg).map(|_| ())
+}
+
+fn validate_time_offset(arg: &str) -> Result<(), String> {
+    arg.parse::<TimeOffset>().map(|_| ())
+}
+
+fn validate_encryption(arg: &str) -> Result<(), String> {
+    arg.parse::<Encryption>().map(|_| ())
+}
+
+fn validate_redaction(arg: &str) -> Result<(), String> {
+    arg.parse::<Redaction>().map(|_| ())
+}
+
+// --tcp-out host:port, resolved when the output is written
+fn validate_tcp_address(arg: &str) -> Result<(), String> {
+    match arg.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(()),
+        _ => Err(format!("Invalid address '{arg}', expected <host>:<port>"))
+    }
+}
+
+fn validate_rate(arg: &str) -> Result<(), String> {
+    match arg.parse::<u64>() {
+        Ok(rate) if rate > 0 => Ok(()),
+        _ => Err(format!("Invalid rate '{arg}', expected a number of rows per second (at least 1)"))
+    }
+}
+
+fn validate_field_limit(arg: &str) -> Result<(), String> {
+    arg.parse::<FieldLimit>().map(|_| ())
+}
+
+fn validate_batch(arg: &str) -> Result<(), String> {
+    match arg.parse::<usize>() {
+        Ok(batch) if batch > 0 => Ok(()),
+        _ => Err(format!("Invalid batch size '{arg}', expected at least 1 row"))
+    }
+}
+
+fn validate_timezone(arg: &str) -> Result<(), String> {
+    TimeZone::from_name(arg).map(|_| ()).map_err(|e| e.to_string())
+}
+
+fn validate_sort_by(arg: &str) -> Result<(), String> {
+    arg.parse::<SortBy>().map(|_| ())
+}
+
+fn validate_threads(arg: &str) -> Result<(), String> {
+    match arg.parse::<usize>() {
+        Ok(threads) if threads > 0 => Ok(()),
+        _ => Err(format!("Invalid number of threads '{arg}', expected at least 1"))
+    }
+}
+
+// --time-offset values, [<input>=]<+|->HH:MM:SS
+fn time_offsets(matches: &ArgMatches) -> Vec<TimeOffset> {
+    matches.values_of("time-offset")
+        .map(|o| o.map(|o| o.parse().expect("validated offset")).collect())
+        .unwrap_or_default()
+}
+
+// lists passed through a file descriptor (--ioc-fd 3 3<iocs.txt) stay out of the shell history & process listings
+fn fd_path(fd: &str) -> PathBuf {
+    PathBuf::from(format!("/dev/fd/{fd}"))
+}
+
+fn validate_fd(arg: &str) -> Result<(), String> {
+    if !cfg!(unix) {
+        return Err("File descriptors are only supported on Unix systems".to_string());
+    }
+    arg.parse::<u32>().map(|_| ()).map_err(|_| format!("Invalid file descriptor '{arg}'"))
+}
+
+// arguments of the output shared by the runs & subcommands, read by output_options()
+fn output_args<'a>() -> [Arg<'a>; 10] {
+    [
+        Arg::new("output")
+            .short('o')
+            .long("output")
+            .takes_value(true)
+            .help("Output to file (stdout if not specified)"),
+        Arg::new("create-dirs")
+            .long("create-dirs")
+            .takes_value(false)
+            .help("Create the missing parent directories of the output file"),
+        Arg::new("no-atomic")
+            .long("no-atomic")
+            .takes_value(false)
+            .help("Write the output file in place, instead of a temporary file renamed once complete"),
+        Arg::new("force")
+            .long("force")
+            .takes_value(false)
+            .help("Overwrite the output file if it exists"),
+        Arg::new("output-suffix")
+            .long("output-suffix")
+            .takes_value(false)
+            .conflicts_with("force")
+            .help("Write to <output>_1, <output>_2, ... if the output file exists"),
+        Arg::new("tcp-out")
+            .long("tcp-out")
+            .takes_value(true)
+            .conflicts_with("output")
+            .validator(validate_tcp_address)
+            .help("Stream the output to host:port over TCP instead of stdout"),
+        Arg::new("rate-limit")
+            .long("rate-limit")
+            .takes_value(true)
+            .requires("tcp-out")
+            .validator(validate_rate)
+            .help("Maximum number of rows per second streamed to --tcp-out, smoothed over one second"),
+        Arg::new("dead-letter")
+            .long("dead-letter")
+            .takes_value(true)
+            .help("Append the events rejected by the output (e.g. --tcp-out connection lost) to this NDJSON file with the error, and go on"),
+        Arg::new("max-field-length")
+            .long("max-field-length")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .validator(validate_field_limit)
+            .help("Limit the size of a column of the output rows: <field>:<size>[:truncate|hash-suffix|skip] (e.g. filename:32K:hash-suffix), field being filename, meta or raw, can be repeated"),
+        Arg::new("line-ending")
+            .long("line-ending")
+            .takes_value(true)
+            .default_value("lf")
+            .possible_values(["lf", "crlf"])
+            .help("Line terminator of the CSV/JSON output")
+    ]
+}
+
+// -o path, checked before the run: missing parent directories are created with --create-dirs,
+// an existing output is only overwritten with --force, or kept with --output-suffix (timeline_1.csv, timeline_2.csv, ...)
+fn output_path(matches: &ArgMatches) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let Some(output) = matches.value_of("output") else { return Ok(None) };
+    let mut output = extended_length(Path::new(output))?;
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if matches.is_present("create-dirs") {
+            std::fs::create_dir_all(parent)?;
+        } else if !parent.is_dir() {
+            return Err(format!("Output directory {} does not exist (--create-dirs to create it)", parent.display()).into());
+        }
+    }
+
+    // a named pipe exists before its reader waits on it, it is written to as is
+    if output.exists() && !archive::is_stream(&output) && !matches.is_present("force") {
+        if !matches.is_present("output-suffix") {
+            return Err(format!("Output file {} already exists (--force to overwrite it, --output-suffix to number the new one)", output.display()).into());
+        }
+        let numbered = (1..).map(|i| numbered_path(&output, i)).find(|p| !p.exists()).expect("free output name");
+        eprintln!("Output file {} already exists, writing to {}", output.display(), numbered.display());
+        output = numbered;
+    }
+    Ok(Some(output))
+}
+
+// timeline.csv => timeline_<number>.csv
+fn numbered_path(path: &Path, number: usize) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push(format!("_{number}"));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+// extended-length form of a Windows path (\\?\C:\..., \\?\UNC\server\share\...): deep case folders exceed MAX_PATH (260 characters)
+#[cfg(windows)]
+fn extended_length(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    use std::{ffi::OsString, path::{Component, Prefix}};
+
+    let absolute = std::path::absolute(path)?; // separators & . / .. normalized, they aren't in extended-length paths
+    let mut components = absolute.components();
+    let Some(Component::Prefix(prefix)) = components.next() else { return Ok(absolute) };
+    let mut extended = match prefix.kind() {
+        Prefix::Disk(_) => {
+            let mut extended = OsString::from(r"\\?\");
+            extended.push(prefix.as_os_str());
+            extended
+        }
+        Prefix::UNC(server, share) => {
+            let mut extended = OsString::from(r"\\?\UNC\");
+            extended.push(server);
+            extended.push(r"\");
+            extended.push(share);
+            extended
+        }
+        _ => return Ok(absolute) // already extended-length (\\?\) or a device (\\.\)
+    };
+    extended.push(r"\");
+
+    let mut extended = PathBuf::from(extended);
+    for component in components.filter(|c| !matches!(c, Component::RootDir)) {
+        extended.push(component);
+    }
+    Ok(extended)
+}
+
+#[cfg(not(windows))]
+fn extended_length(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(path.to_path_buf())
+}
+
+// host of the rows of an input (--group-by host): its file name without extension
+fn host_name(input: &str) -> String {
+    if input == archive::STDIN {
+        return "stdin".to_string();
+    }
+    Path::new(input).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| input.to_string())
+}
+
+// --group-by: a section per group after the combined rows, or one file per group in --group-dir, returns their paths
+fn generate_groups(bodyfile: &mut BodyFile, matches: &ArgMatches, format: OutputFormat, metadata: &serde_json::Value) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let Some(group_by) = matches.value_of("group-by").map(|g| g.parse::<GroupBy>()).transpose()? else { return Ok(vec![]) };
+    match matches.value_of("group-dir") {
+        Some(directory) => {
+            std::fs::create_dir_all(directory)?;
+            bodyfile.generate_groups(Path::new(directory), format, metadata)
+        }
+        None => {
+            bodyfile.set_group_by(group_by);
+            Ok(vec![])
+        }
+    }
+}
+
+// bodyfiles of a directory tree (*.body, *.txt), sorted for a reproducible order
+fn directory_bodyfiles(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let mut entries : Vec<PathBuf> = std::fs::read_dir(directory)?.map(|e| e.map(|e| e.path())).collect::<Result<_, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            directory_bodyfiles(&path, files)?;
+        } else if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("body") || e.eq_ignore_ascii_case("txt")) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// -b values => (input, label) of each input, the label being its host (--group-by host):
+// - glob patterns are expanded to the matching paths
+// - the files of an archive given without a member are read as separate inputs
+// - the bodyfiles of a directory are read as separate inputs, labeled with their path relative to the directory
+fn expand_inputs<'a>(values: impl Iterator<Item = &'a str>) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let mut paths = vec![];
+    for value in values {
+        match glob::is_pattern(value) && !Path::new(value).exists() {
+            true => {
+                let matches = glob::expand(value)?;
+                if matches.is_empty() {
+                    return Err(format!("No input matching {value}").into());
+                }
+                paths.extend(matches.iter().map(|p| p.to_string_lossy().to_string()));
+            }
+            false => paths.push(value.to_string())
+        }
+    }
+
+    let mut inputs = vec![];
+    for value in paths.iter() {
+        let path = Path::new(value);
+        if path.is_dir() {
+            let mut files = vec![];
+            directory_bodyfiles(path, &mut files)?;
+            if files.is_empty() {
+                return Err(format!("No bodyfile (*.body, *.txt) found in {value}").into());
+            }
+            for file in files {
+                let label = file.strip_prefix(path).unwrap_or(&file).to_string_lossy().replace('\\', "/");
+                inputs.push((file.to_string_lossy().to_string(), label));
+            }
+        } else if archive::is_archive(path) && path.is_file() {
+            inputs.extend(archive::members(path)?.into_iter().map(|member| (member.clone(), host_name(&member))));
+        } else {
+            inputs.push((value.to_string(), host_name(value)));
+        }
+    }
+    Ok(inputs)
+}
+
+// several -b inputs: the ones read twice (same host collected twice) are reported, or dropped & deduplicated with --dedup-inputs
+fn merge_inputs(inputs: &[&str], bodyfiles: Vec<BodyFile>, dedup: bool, sort: Option<SortKey>) -> Result<BodyFile, Box<dyn Error>> {
+    let mut dropped = vec![false; bodyfiles.len()];
+    for i in 0..bodyfiles.len() {
+        for j in i + 1..bodyfiles.len() {
+            if dropped[i] || dropped[j] {
+                continue;
+            }
+            // digests of the inputs, hashed while they were parsed
+            let identical = bodyfiles[i].digest().is_some() && bodyfiles[i].digest() == bodyfiles[j].digest();
+            if identical {
+                eprintln!("Warning: {} and {} are identical{}", inputs[i], inputs[j], if dedup { ", the latter is skipped" } else { "" });
+                dropped[j] = dedup;
+                continue;
+            }
+            let overlap = bodyfiles[i].overlap(&bodyfiles[j]);
+            if overlap >= OVERLAP_WARNING {
+                eprintln!("Warning: {} and {} overlap ({:.0}% of the rows of the smallest one), same host collected twice?", inputs[i], inputs[j], overlap * 100.0);
+            }
+        }
+    }
+
+    let kept : Vec<BodyFile> = bodyfiles.into_iter().zip(dropped).filter(|(_, dropped)| !dropped).map(|(bodyfile, _)| bodyfile).collect();
+    let mut merged = match sort {
+        Some(key) => BodyFile::merge_sorted(kept, key), // inputs sorted on their own
+        None => kept.into_iter().fold(BodyFile::new(), |mut merged, bodyfile| {
+            merged.append(bodyfile);
+            merged
+        })
+    };
+    if dedup {
+        eprintln!("Number of duplicate datetime records removed: {}", merged.dedup());
+        if let Some(key) = sort {
+            merged.sort_timeline(key);
+        }
+    }
+    Ok(merged)
+}
+
+// write the output, flagged as partial after a Ctrl-C (exit code 130)
+fn generate(bodyfile: &BodyFile, output: Option<&Path>, format: OutputFormat, mut metadata: serde_json::Value) -> Result<(), Box<dyn Error>> {
+    if interrupt::interrupted() {
+        metadata["partial"] = serde_json::json!(true);
+    }
+    bodyfile.generate(output, format, &metadata)?;
+    if interrupt::interrupted() {
+        eprintln!("Run interrupted: the output is partial");
+        std::process::exit(interrupt::EXIT_CODE);
+    }
+    Ok(())
+}
+
+// output options of the runs & subcommands, the arguments of output_args() (line ending, network output, ...)
+fn output_options(bodyfile: &mut BodyFile, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    bodyfile.set_line_ending(matches.value_of_t::<LineEnding>("line-ending")?);
+    bodyfile.set_atomic(!matches.is_present("no-atomic"));
+    if let Some(address) = matches.value_of("tcp-out") {
+        bodyfile.set_tcp_out(address);
+    }
+    if let Some(rate) = matches.value_of("rate-limit") {
+        bodyfile.set_rate_limit(rate.parse()?);
+    }
+    if let Some(path) = matches.value_of("dead-letter") {
+        bodyfile.set_dead_letter(Path::new(path));
+    }
+    if let Some(limits) = matches.values_of("max-field-length") {
+        bodyfile.set_field_limits(limits.map(|l| l.parse()).collect::<Result<Vec<FieldLimit>, String>>()?);
+    }
+    Ok(())
+}
+
+// output options of a run (columns, encryption, time zones, ...)
+fn run_output_options(bodyfile: &mut BodyFile, matches: &ArgMatches, timezone: Option<&TimeZone>) -> Result<(), Box<dyn Error>> {
+    output_options(bodyfile, matches)?;
+    bodyfile.set_missing_value(matches.value_of_t::<MissingValue>("missing-value")?);
+    bodyfile.set_precision(matches.is_present("precision"));
+    bodyfile.set_provenance(matches.is_present("provenance"));
+    bodyfile.set_schema(matches.value_of_t("schema")?);
+    bodyfile.set_flag_unset(matches.value_of_t::<InvalidTimestamp>("on-invalid-timestamp")? == InvalidTimestamp::Flag);
+    if let Some(encryption) = matches.value_of("encrypt") {
+        bodyfile.set_encryption(encryption.parse()?);
+    }
+    if let Some(redactions) = matches.values_of("redact") {
+        bodyfile.set_redactions(redactions.map(|r| r.parse()).collect::<Result<Vec<Redaction>, String>>()?);
+    }
+    bodyfile.set_number_format(NumberFormat::new(matches.value_of("thousands-separator"), matches.value_of("decimal-separator").expect("default value")));
+    if let Some(timezone) = timezone {
+        bodyfile.set_timezone(timezone.clone());
+    }
+    let users = matches.value_of("passwd").map(|path| id_names(Path::new(path), "user")).transpose()?;
+    let groups = matches.value_of("group").map(|path| id_names(Path::new(path), "group")).transpose()?;
+    bodyfile.set_id_names(users, groups);
+    if let Some(names) = matches.values_of("extra-tz") {
+        bodyfile.set_extra_timezones(names.map(TimeZone::from_name).collect::<Result<Vec<TimeZone>, _>>()?);
+    }
+    Ok(())
+}
+
+// names of the UIDs (--passwd) or GIDs (--group)
+fn id_names(path: &Path, kind: &str) -> Result<IdNames, Box<dyn Error>> {
+    let names = IdNames::from_path(path)?;
+    eprintln!("Number of {kind} names read from {}: {}", path.display(), names.len());
+    Ok(names)
+}
+
+// zone of the output: -z, or the one configured on the suspect machine (--system-tz-from)
+fn output_timezone(matches: &ArgMatches) -> Result<Option<TimeZone>, Box<dyn Error>> {
+    if let Some(timezone) = matches.value_of("timezone") {
+        return Ok(Some(TimeZone::from_name(timezone)?));
+    }
+    let Some(path) = matches.value_of("system-tz-from") else { return Ok(None) };
+    let (found, name) = clock::system_timezone(Path::new(path))?;
+    let timezone = TimeZone::from_name(&name).map_err(|_| format!("{path}: no IANA time zone known for '{found}', give the zone with -z"))?;
+    match found == name {
+        true => eprintln!("Time zone of {path}: {timezone}"),
+        false => eprintln!("Time zone of {path}: {found} ({timezone})")
+    }
+    Ok(Some(timezone))
+}
+
+// --stream: the rows of the inputs parsed, filtered, tagged & written by chunks, never held together in memory
+fn stream_run(matches: &ArgMatches, inputs: &[&str], output: Option<&Path>, options: &ParseOptions, filters: &FilterSet, offsets: &[TimeOffset], timezone: Option<&TimeZone>) -> Result<(), Box<dyn Error>> {
+    if options.format != InputFormat::Bodyfile {
+        return Err("--stream reads bodyfile inputs (--input-format bodyfile)".into());
+    }
+    let years = filters.valid_years.as_deref().map(parse_year_range).transpose()?;
+    let sources = filters.source_type.as_ref()
+        .map(|s| s.iter().map(|s| s.parse()).collect::<Result<Vec<SourceType>, String>>())
+        .transpose()?;
+    let mut keywords = vec![];
+    for (list, include) in [(&filters.include, true), (&filters.exclude, false)] {
+        if !list.is_empty() {
+            keywords.push((KeywordMatcher::new(list.iter().map(String::as_str), filters.ignore_case)?, include));
+        }
+    }
+    let user = filters.user.as_deref().map(UserFilter::new);
+    let script = matches.value_of("script").map(|path| Script::from_path(Path::new(path))).transpose()?;
+    let mut rules = filters.rules.as_ref().map(|saved| RuleSet::parse(&saved.content, filters.ignore_case)).transpose()?;
+    let ioc_path = matches.value_of("ioc").map(PathBuf::from).or_else(|| matches.value_of("ioc-fd").map(fd_path));
+    let mut iocs = ioc_path.as_deref().map(IocList::from_path).transpose()?;
+    let mut classifier = match matches.value_of("tag-cmd") {
+        Some(command) => {
+            let timeout = parse_duration(matches.value_of("tag-timeout").expect("default value"))?.to_std()?;
+            Some(TagCommand::start(command, matches.value_of("tag-batch").expect("default value").parse()?, timeout)?)
+        }
+        None => None
+    };
+
+    // the columns are written before the rows are read
+    let mut bodyfile = BodyFile::new();
+    bodyfile.set_inputs(inputs);
+    let dst = timezone.filter(|_| matches.is_present("flag-dst"));
+    bodyfile.set_tagged(rules.is_some() || iocs.is_some() || classifier.is_some() || script.as_ref().is_some_and(Script::tags) || filters.flag_invalid_years || dst.is_some()
+        || options.on_invalid_timestamp == InvalidTimestamp::Flag);
+    run_output_options(&mut bodyfile, matches, timezone)?;
+    if matches.is_present("follow") {
+        bodyfile.set_atomic(false); // only ended by Ctrl-C: written in place, as the rows come
+    }
+    let format : OutputFormat = matches.value_of_t("format")?;
+    let input = inputs.join(", ");
+    let metadata = serde_json::json!({
+        "tool": "mactime",
+        "version": env!("CARGO_PKG_VERSION"),
+        "schema_version": schema::VERSION,
+        "build": version::build_info(),
+        "input": input,
+        "input_format": options.format.to_string(),
+        "encoding": Encoding::Utf8.to_string(),
+        "lines": matches.value_of("lines"),
+        "shard": matches.value_of("shard"),
+        "time_offset": matches.values_of("time-offset").map(|o| o.collect::<Vec<&str>>()),
+        "filter": filters.filter,
+        "valid_years": filters.valid_years,
+        "on_invalid_timestamp": matches.value_of("on-invalid-timestamp"),
+        "epoch_base": matches.value_of("epoch-base"),
+        "unit": matches.value_of("unit"),
+        "user": filters.user,
+        "source_type": filters.source_type,
+        "include": matches.values_of("include").map(|k| k.collect::<Vec<&str>>()),
+        "exclude": matches.values_of("exclude").map(|k| k.collect::<Vec<&str>>()),
+        "ignore_case": filters.ignore_case,
+        "load_filter": matches.value_of("load-filter"),
+        "encrypt": matches.value_of("encrypt"),
+        "redact": matches.values_of("redact").map(|r| r.collect::<Vec<&str>>()),
+        "min_score": filters.min_score.map(|s| s.to_string()),
+        "tag_cmd": matches.value_of("tag-cmd"),
+        "script": matches.value_of("script"),
+        "schema": matches.value_of("schema"),
+        "stream": true,
+        "follow": matches.is_present("follow"),
+        "timezone": timezone.map_or("UTC", TimeZone::name),
+        "extra_tz": matches.values_of("extra-tz").map(|t| t.collect::<Vec<&str>>()),
+        "passwd": matches.value_of("passwd"),
+        "group": matches.value_of("group"),
+        "generated": Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string()
+    });
+
+    let (mut skipped_lines, mut invalid_lines, mut invalid_timestamps, mut sentinel_timestamps) = (0, 0, 0, 0);
+    let (mut file_records, mut datetime_records, mut out_of_years, mut scripted, mut tagged, mut written) = (0, 0, 0, 0, 0, 0);
+    let mut dst_flagged = 0;
+    let chunks = stream::chunks(inputs, options, offsets, matches.is_present("follow")).map(|chunk| {
+        let mut chunk = chunk?;
+        skipped_lines += chunk.skipped_lines();
+        invalid_lines += chunk.invalid_lines();
+        invalid_timestamps += chunk.invalid_timestamps();
+        sentinel_timestamps += chunk.sentinel_timestamps();
+        file_records += chunk.file_len();
+        datetime_records += chunk.datetime_len();
+        if let Some(years) = &years {
+            out_of_years += chunk.check_years(years, filters.flag_invalid_years);
+        }
+        if let Some(timezone) = dst {
+            dst_flagged += chunk.flag_dst(timezone);
+        }
+        if let Some(sources) = &sources {
+            chunk.retain_sources(sources);
+        }
+        for (matcher, include) in keywords.iter() {
+            chunk.retain_keywords(matcher, *include);
+        }
+        if let Some(user) = &user {
+            chunk.retain_user(user);
+        }
+        if let Some(script) = &script {
+            scripted += chunk.run_script(script)?;
+        }
+        if let Some(rules) = rules.as_mut() {
+            chunk.tag_rules(rules);
+        }
+        if let Some(classifier) = classifier.as_mut() {
+            tagged += chunk.tag_command(classifier)?;
+        }
+        if let Some(iocs) = iocs.as_mut() {
+            chunk.tag_iocs(iocs);
+        }
+        if let Some(min_score) = filters.min_score {
+            chunk.retain_min_score(min_score);
+        }
+        written += chunk.datetime_len();
+        Ok(chunk)
+    });
+    bodyfile.generate_stream(output, format, &metadata, chunks)?;
+
+    if skipped_lines > 0 {
+        eprintln!("Number of comment/header lines skipped in {input}: {skipped_lines}");
+    }
+    if invalid_lines > 0 {
+        eprintln!("Number of invalid lines in {input}: {invalid_lines}");
+    }
+    if invalid_timestamps > 0 {
+        eprintln!("Number of invalid timestamps {} in {input}: {invalid_timestamps}", options.on_invalid_timestamp);
+    }
+    if sentinel_timestamps > 0 {
+        eprintln!("Number of sentinel timestamps unset in {input}: {sentinel_timestamps}");
+    }
+    eprintln!("Number of file records read from {input}: {file_records}");
+    eprintln!("Number of datetime records read from {input}: {datetime_records}");
+    if let Some(years) = &filters.valid_years {
+        eprintln!("Number of datetime records outside {years} {}: {out_of_years}", if filters.flag_invalid_years { "flagged" } else { "dropped" });
+    }
+    if let Some(timezone) = dst {
+        eprintln!("Number of datetime records in the DST switches of {timezone} flagged: {dst_flagged}");
+    }
+    if let Some(script_path) = matches.value_of("script") {
+        eprintln!("Number of datetime records dropped by {script_path}: {scripted}");
+    }
+    if let Some(rules) = &rules {
+        eprintln!("Rule hits ({} rules read from {}):", rules.len(), filters.rules.as_ref().expect("rules read").path);
+        for rule in rules.iter() {
+            eprintln!("\t{} (weight {}): {}", rule.tag, rule.weight, rule.hits);
+        }
+    }
+    if let (Some(command), Some(classifier)) = (matches.value_of("tag-cmd"), classifier) {
+        classifier.finish()?;
+        eprintln!("Number of datetime records tagged by {command}: {tagged}");
+    }
+    if let (Some(ioc_path), Some(iocs)) = (&ioc_path, &iocs) {
+        eprintln!("IOC hits ({} IOCs read from {}):", iocs.len(), ioc_path.display());
+        for ioc in iocs.iter() {
+            eprintln!("\t{} ({}, weight {}): {}", ioc.value, ioc.kind, ioc.weight, ioc.hits);
+        }
+    }
+    eprintln!("Number of datetime records written: {written}");
+    if interrupt::interrupted() {
+        eprintln!("Run interrupted: the output is partial");
+        std::process::exit(interrupt::EXIT_CODE);
+    }
+    Ok(())
+}
+
+// --preflight: estimates of the inputs & of the run, refused over --max-memory
+fn run_preflight(inputs: &[&str], options: &ParseOptions, threads: usize, sorted: bool, max_memory: Option<u64>) -> Result<(), Box<dyn Error>> {
+    let mut estimates = vec![];
+    for input in inputs {
+        match preflight::estimate(input, options) {
+            Ok(estimate) => {
+                eprintln!("Preflight of {input}: {}, ~{} lines, ~{} datetime records", preflight::format_size(estimate.bytes), estimate.lines, estimate.datetime_records);
+                estimates.push(estimate);
+            }
+            Err(e) => eprintln!("Preflight of {input} failed: {e}")
+        }
+    }
+    let peak = preflight::peak_memory(&estimates, threads, sorted);
+    let rows : u64 = estimates.iter().map(|e| e.datetime_records).sum();
+    eprintln!("Estimated peak memory: {} (~{rows} datetime records)", preflight::format_size(peak));
+
+    let Some(max_memory) = max_memory else { return Ok(()) };
+    let advice = "split the input with --shard and merge the sorted shards with `mactime merge --assume-sorted`, or lower --threads";
+    if peak > max_memory {
+        return Err(format!("Estimated peak memory {} over --max-memory {}: {advice}", preflight::format_size(peak), preflight::format_size(max_memory)).into());
+    }
+    if peak > max_memory / 5 * 4 {
+        eprintln!("Warning: estimated peak memory {} close to --max-memory {}, {advice}", preflight::format_size(peak), preflight::format_size(max_memory));
+    }
+    Ok(())
+}
+
+// filters of the command line, keyword files descriptors & rules files read
+fn command_line_filters(matches: &ArgMatches) -> Result<FilterSet, Box<dyn Error>> {
+    let keywords = |option: &str, fd: &str| -> Result<Vec<String>, Box<dyn Error>> {
+        let mut keywords : Vec<String> = matches.values_of(option).map(|k| k.map(String::from).collect()).unwrap_or_default();
+        if let Some(fd) = matches.value_of(fd) {
+            keywords.extend(std::fs::read_to_string(fd_path(fd))?.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(String::from));
+        }
+        Ok(keywords)
+    };
+    Ok(FilterSet {
+        version: filterset::VERSION,
+        filter: matches.value_of("filter").map(String::from),
+        source_type: matches.values_of("source-type").map(|s| s.map(String::from).collect()),
+        include: keywords("include", "keywords-fd")?,
+        exclude: keywords("exclude", "exclude-fd")?,
+        ignore_case: matches.is_present("ignore-case"),
+        user: matches.value_of("user").map(String::from),
+        valid_years: matches.value_of("valid-years").map(String::from),
+        flag_invalid_years: matches.is_present("flag-invalid-years"),
+        rules: matches.value_of("rules")
+            .map(|path| std::fs::read_to_string(path).map(|content| SavedRules { path: path.to_string(), content }))
+            .transpose()?,
+        min_score: matches.value_of("min-score").map(str::parse).transpose()?
+    })
+}
+
+// values of a loaded filter file, checked as clap checks the command line ones (valid years flagged, whatever the origin)
+fn validate_filters(filters: &FilterSet) -> Result<(), String> {
+    if let Some(filter) = &filters.filter {
+        parse_filter_args(filter)?;
+    }
+    for source in filters.source_type.iter().flatten() {
+        source.parse::<SourceType>()?;
+    }
+    if let Some(years) = &filters.valid_years {
+        parse_year_range(years)?;
+    }
+    if filters.flag_invalid_years && filters.valid_years.is_none() {
+        return Err("--flag-invalid-years requires --valid-years".to_string());
+    }
+    Ok(())
+}
+
+// mactime adapters: input formats available at runtime, built-in ones & registered adapters
+fn adapters(registry: &InputAdapters) {
+    for (name, description) in registry.list() {
+        println!("{name:<14}{description}");
+    }
+}
+
+// mactime timezones: the names accepted by -z, with their offset now
+fn timezones(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let filter = matches.value_of("filter").map(str::to_lowercase);
+    let now = Utc::now();
+    let names : Vec<String> = TimeZone::names().into_iter()
+        .filter(|n| filter.as_ref().is_none_or(|f| n.to_lowercase().contains(f.as_str())))
+        .collect();
+    if names.is_empty() {
+        return Err(format!("No time zone matching '{}'", matches.value_of("filter").unwrap_or_default()).into());
+    }
+    let mut stdout = std::io::stdout().lock();
+    for name in names {
+        let Ok(timezone) = TimeZone::from_name(&name) else { continue };
+        let offset = timezone.offset(now).to_string();
+        // stopped quietly when piped to head
+        if writeln!(stdout, "{name:<34}{offset:<8}{}", timezone.local_type(now).abbreviation).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// mactime repl: filters tried on the parsed inputs, then the timeline exported
+fn repl(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let (inputs, labels) : (Vec<String>, Vec<String>) = expand_inputs(matches.values_of("bodyfile").expect("required bodyfile"))?.into_iter().unzip();
+    let inputs : Vec<&str> = inputs.iter().map(String::as_str).collect();
+    let options = ParseOptions { format: matches.value_of_t("input-format")?, ..ParseOptions::default() };
+    let mut timelines = inputs.iter().zip(labels.iter())
+        .map(|(input, label)| {
+            let mut bodyfile = BodyFileParser::build(Path::new(input), &options)?;
+            bodyfile.set_host(label);
+            eprintln!("Number of datetime records read from {input}: {}", bodyfile.datetime_len());
+            Ok(bodyfile)
+        })
+        .collect::<Result<Vec<BodyFile>, Box<dyn Error>>>()?;
+
+    let mut bodyfile = match timelines.len() {
+        1 => timelines.remove(0),
+        _ => supertimeline::merge(timelines)
+    };
+    bodyfile.sort_timeline(SortKey::Datetime);
+    Repl::new(bodyfile, &inputs).run(std::io::stdin().lock(), std::io::stdout())
+}
+
+// mactime schema: event schema of the outputs
+fn print_schema(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let format : SchemaFormat = matches.value_of_t("format")?;
+    println!("{}", serde_json::to_string_pretty(&schema::schema(format))?);
+    Ok(())
+}
+
+// mactime selftest: fails if any check fails
+fn selftest() -> Result<(), Box<dyn Error>> {
+    let mut failed = 0;
+    for (check, result) in selftest::run() {
+        match result {
+            Ok(()) => println!("{check}: ok"),
+            Err(e) => {
+                println!("{check}: FAILED ({e})");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{failed} selftest check(s) failed, do not trust the output of this binary").into());
+    }
+    println!("mactime {} ({}): all checks passed", env!("CARGO_PKG_VERSION"), version::COMMIT);
+    Ok(())
+}
+
+// mactime merge: timelines exported in JSON (shards, hosts, ...) merged in a single one
+fn merge(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let output = output_path(matches)?;
+    let inputs : Vec<&str> = matches.values_of("input").expect("required input").collect();
+    if matches.is_present("assume-sorted") {
+        return merge_sorted(matches, &inputs, output.as_deref());
+    }
+    let options = ParseOptions { format: InputFormat::Events, ..ParseOptions::default() };
+    let timelines = inputs.iter()
+        .map(|input| {
+            let mut bodyfile = BodyFileParser::build(Path::new(input), &options)?;
+            bodyfile.set_host(&host_name(input));
+            eprintln!("Number of datetime records read from {input}: {}", bodyfile.datetime_len());
+            Ok(bodyfile)
+        })
+        .collect::<Result<Vec<BodyFile>, Box<dyn Error>>>()?;
+
+    let mut bodyfile = supertimeline::merge(timelines);
+    let sort = matches.value_of("sort").map(|s| s.parse::<SortKey>()).transpose()?;
+    let mut metadata = serde_json::json!({
+        "tool": "mactime",
+        "version": env!("CARGO_PKG_VERSION"),
+        "schema_version": schema::VERSION,
+        "build": version::build_info(),
+        "input": inputs,
+        "input_format": "merge",
+        "group_by": matches.value_of("group-by"),
+        "datetime_records": bodyfile.datetime_len(),
+        "sort": matches.value_of("sort"),
+        "sort_by": matches.value_of("sort-by"),
+        "schema": matches.value_of("schema"),
+        "timezone": "UTC"
+    });
+    if matches.is_present("deterministic") {
+        bodyfile.sort_deterministic(sort);
+    } else {
+        if let Some(key) = sort {
+            bodyfile.sort_timeline(key);
+        }
+        metadata["generated"] = serde_json::json!(Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string());
+    }
+    if let Some(sort_by) = matches.value_of("sort-by") {
+        bodyfile.sort_by(&sort_by.parse()?);
+    }
+
+    let format : OutputFormat = matches.value_of_t("format")?;
+    output_options(&mut bodyfile, matches)?;
+    bodyfile.set_provenance(matches.is_present("provenance"));
+    bodyfile.set_schema(matches.value_of_t("schema")?);
+    generate_groups(&mut bodyfile, matches, format, &metadata)?;
+    generate(&bodyfile, output.as_deref(), format, metadata)
+}
+
+// mactime merge --assume-sorted: inputs streamed, never loaded
+fn merge_sorted(matches: &ArgMatches, inputs: &[&str], output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let mut metadata = serde_json::json!({
+        "tool": "mactime",
+        "version": env!("CARGO_PKG_VERSION"),
+        "schema_version": schema::VERSION,
+        "build": version::build_info(),
+        "input": inputs,
+        "input_format": "merge",
+        "assume_sorted": true,
+        "datetime_records": null, // not known until the inputs are read through
+        "schema": matches.value_of("schema"),
+        "timezone": "UTC"
+    });
+    if !matches.is_present("deterministic") {
+        metadata["generated"] = serde_json::json!(Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string());
+    }
+
+    let format : OutputFormat = matches.value_of_t("format")?;
+    let mut bodyfile = BodyFile::new();
+    output_options(&mut bodyfile, matches)?;
+    let (written, duplicates) = sorted::merge(inputs, &bodyfile, output, format, &metadata, matches.is_present("provenance"), matches.value_of_t("schema")?)?;
+    eprintln!("Number of datetime records written: {written} ({duplicates} duplicates removed)");
+    if interrupt::interrupted() {
+        eprintln!("Run interrupted: the output is partial");
+        std::process::exit(interrupt::EXIT_CODE);
+    }
+    Ok(())
+}
+
+// mactime convert: binary timeline to CSV/JSON (streamed), or JSON output to binary/CSV
+fn convert(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let output = output_path(matches)?;
+    let input = matches.value_of("input").expect("required input");
+    let format : OutputFormat = matches.value_of_t("format")?;
+    let mut reader = BufReader::new(File::open(input)?);
+
+    if binary::is_binary(reader.fill_buf()?) {
+        let mut bodyfile = BodyFile::new();
+        output_options(&mut bodyfile, matches)?;
+        let written = binary::convert(reader, &bodyfile, output.as_deref(), format)?;
+        eprintln!("Number of datetime records converted: {written}");
+        if interrupt::interrupted() {
+            eprintln!("Run interrupted: the output is partial");
+            std::process::exit(interrupt::EXIT_CODE);
+        }
+        return Ok(());
+    }
+
+    // metadata of the --format json document (first line), kept as is
+    let mut first = String::new();
+    reader.read_line(&mut first)?;
+    let metadata = first.trim_end().strip_suffix(",\"events\":[")
+        .and_then(|header| serde_json::from_str::<serde_json::Value>(&format!("{header}}}")).ok())
+        .map(|mut document| document["metadata"].take())
+        .unwrap_or_else(|| serde_json::json!({
+            "tool": "mactime",
+            "version": env!("CARGO_PKG_VERSION"),
+            "schema_version": schema::VERSION,
+            "build": version::build_info(),
+            "input": [input],
+            "input_format": "convert",
+            "timezone": "UTC"
+        }));
+
+    let options = ParseOptions { format: InputFormat::Events, ..ParseOptions::default() };
+    let mut bodyfile = BodyFileParser::build(Path::new(input), &options)?;
+    eprintln!("Number of datetime records converted: {}", bodyfile.datetime_len());
+    output_options(&mut bodyfile, matches)?;
+    bodyfile.set_provenance(matches.is_present("provenance"));
+    bodyfile.set_schema(metadata["schema"].as_str().and_then(|s| s.parse().ok()).unwrap_or_default());
+    generate(&bodyfile, output.as_deref(), format, metadata)
+}
+
+// mactime supertimeline: all artifacts of a host in a single timeline
+fn supertimeline(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let output = output_path(matches)?;
+    let sources = Sources {
+        mft: matches.value_of("mft").map(Path::new),
+        usn: matches.value_of("usn").map(Path::new),
+        evtx: matches.value_of("evtx").map(Path::new),
+        prefetch: matches.value_of("prefetch").map(Path::new),
+        bodyfiles: matches.values_of("bodyfile").map(|b| b.map(Path::new).collect()).unwrap_or_default(),
+        offsets: time_offsets(matches),
+        epoch_base: matches.value_of_t("epoch-base")?,
+        unit: matches.value_of_t("unit")?
+    };
+    let filter = matches.value_of("filter")
+        .map(|d| parse_filter_args(d).unwrap())
+        .map(DateFilter::new);
+    let threads = matches.value_of("threads").map(str::parse).transpose()?.unwrap_or_else(parallel::default_threads);
+
+    // stages run on their own read/write their intermediate rows in the checkpoint directory
+    let stage = matches.value_of("stage").map(|s| s.parse::<Stage>()).transpose()?;
+    let checkpoints = Path::new(matches.value_of("checkpoint-dir").expect("default value"));
+    let mut bodyfile = match stage {
+        None => supertimeline::merge(supertimeline::parse(&sources, filter, None, threads)?),
+        Some(Stage::Parse) => {
+            std::fs::create_dir_all(checkpoints)?;
+            supertimeline::parse(&sources, filter, Some(checkpoints), threads)?;
+            eprintln!("Stage {} done, checkpoints written to {}", Stage::Parse, checkpoints.display());
+            return Ok(());
+        }
+        Some(Stage::Merge) => {
+            let merged = supertimeline::merge(supertimeline::read_parsed(checkpoints)?);
+            merged.write_checkpoint(&supertimeline::merged_path(checkpoints))?;
+            eprintln!("Stage {} done, checkpoint written to {}", Stage::Merge, supertimeline::merged_path(checkpoints).display());
+            return Ok(());
+        }
+        Some(Stage::Sort) => {
+            let mut merged = BodyFile::read_checkpoint(&supertimeline::merged_path(checkpoints))?;
+            merged.retain_dates(&filter);
+            merged
+        }
+    };
+    if let Some(key) = matches.value_of("sort").map(|s| s.parse::<SortKey>()).transpose()? {
+        bodyfile.sort_timeline(key);
+    }
+
+    let mut metadata = serde_json::json!({
+        "tool": "mactime",
+        "version": env!("CARGO_PKG_VERSION"),
+        "schema_version": schema::VERSION,
+        "build": version::build_info(),
+        "input": {
+            "mft": matches.value_of("mft"),
+            "usn": matches.value_of("usn"),
+            "evtx": matches.value_of("evtx"),
+            "prefetch": matches.value_of("prefetch"),
+            "bodyfile": matches.values_of("bodyfile").map(|b| b.collect::<Vec<&str>>())
+        },
+        "input_format": "supertimeline",
+        "stage": matches.value_of("stage"),
+        "datetime_records": bodyfile.datetime_len(),
+        "sources": bodyfile.source_counts().into_iter().map(|(s, c)| (s.to_string(), c)).collect::<BTreeMap<String, usize>>(),
+        "filter": matches.value_of("filter"),
+        "sort": matches.value_of("sort"),
+        "sort_by": matches.value_of("sort-by"),
+        "schema": matches.value_of("schema"),
+        "timezone": "UTC"
+    });
+
+    if matches.is_present("deterministic") {
+        bodyfile.sort_deterministic(matches.value_of("sort").map(|s| s.parse::<SortKey>()).transpose()?);
+    } else {
+        metadata["generated"] = serde_json::json!(Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string());
+    }
+    if let Some(sort_by) = matches.value_of("sort-by") {
+        bodyfile.sort_by(&sort_by.parse()?);
+    }
+
+    let format : OutputFormat = matches.value_of_t("format")?;
+    output_options(&mut bodyfile, matches)?;
+    bodyfile.set_precision(matches.is_present("precision"));
+    bodyfile.set_provenance(matches.is_present("provenance"));
+    bodyfile.set_schema(matches.value_of_t("schema")?);
+    generate(&bodyfile, output.as_deref(), format, metadata)
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -53,19 +1044,480 @@ fn main() -> Result<(), Box<dyn Error>> {
     - output in CSV
     - No date filters required by default
     */
+    let long_version = version::long_version();
     let matches = Command::new("mactime")
+        .version(long_version.as_str())
         .author("CERT Intrinsec")
+        .subcommand_negates_reqs(true)
+        .subcommand(Command::new("adapters")
+            .about("List the input formats available for --input-format"))
+        .subcommand(Command::new("timezones")
+            .about("List the IANA time zones accepted by -z, with their current offset")
+            .arg(Arg::new("filter")
+                .takes_value(true)
+                .help("Only list the names containing this text (case insensitive), e.g. europe")))
+        .subcommand(Command::new("selftest")
+            .about("Check the parser & output backends against embedded sample data (validation of a copied binary)"))
+        .subcommand(Command::new("repl")
+            .about("Interactive prompt to apply/remove filters on the parsed inputs, preview the rows, then export them")
+            .arg(Arg::new("bodyfile")
+                .short('b')
+                .long("bodyfile")
+                .required(true)
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Input file, glob pattern, directory or archive member, can be repeated"))
+            .arg(Arg::new("input-format")
+                .long("input-format")
+                .takes_value(true)
+                .default_value("bodyfile")
+                .possible_values(["bodyfile", "events", "velociraptor", "kape"])
+                .help("Input format")))
+        .subcommand(Command::new("schema")
+            .about("Print the event schema of the CSV/JSON outputs (columns, types, semantics)")
+            .arg(Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .default_value("json-schema")
+                .possible_values(["json-schema", "arrow"])
+                .help("Schema format: JSON Schema of the --format json document, or Arrow schema (JSON representation)")))
+        .subcommand(Command::new("merge")
+            .about("Merge, dedup and sort timelines exported in JSON or binary (--format json|binary), e.g. the outputs of --shard")
+            .args(output_args())
+            .arg(Arg::new("input")
+                .required(true)
+                .multiple_values(true)
+                .help("Timelines exported by mactime in JSON or binary"))
+            .arg(Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .default_value("csv")
+                .possible_values(["csv", "json", "jsonl", "binary"])
+                .help("Output format"))
+            .arg(Arg::new("sort")
+                .short('s')
+                .long("sort")
+                .takes_value(true)
+                .possible_values(["datetime", "path", "meta"])
+                .help("Sort timeline by datetime (default), or by full path or meta address, then datetime"))
+            .arg(Arg::new("sort-by")
+                .long("sort-by")
+                .takes_value(true)
+                .conflicts_with("sort")
+                .validator(validate_sort_by)
+                .help("Sort timeline by these fields, each one optionally :asc or :desc (e.g. \"datetime,filename:desc,size\"): datetime, macb, meta, size, filename, source, score, md5, uid"))
+            .arg(Arg::new("assume-sorted")
+                .long("assume-sorted")
+                .takes_value(false)
+                .conflicts_with_all(&["sort", "sort-by", "group-by"])
+                .help("Inputs are already sorted by datetime (previous outputs, per-shard results): stream them through a k-way merge in constant memory instead of loading and re-sorting them"))
+            .arg(Arg::new("provenance")
+                .long("provenance")
+                .takes_value(false)
+                .help("Add SourceFile and SourceLine columns: input file and line each row was read from (kept from inputs written with --provenance)"))
+            .arg(Arg::new("schema")
+                .long("schema")
+                .takes_value(true)
+                .default_value("v1")
+                .possible_values(["v1", "v2", "v3"])
+                .help("Output columns: v1 the historical layout, v2 adding MD5, UID and SourceType to every row, v3 also Mode and GID (full mactime column set)"))
+            .arg(Arg::new("group-by")
+                .long("group-by")
+                .takes_value(true)
+                .possible_values(["host"])
+                .help("After the combined rows, write a section per host (input), or one file per host with --group-dir"))
+            .arg(Arg::new("group-dir")
+                .long("group-dir")
+                .takes_value(true)
+                .requires("group-by")
+                .help("Write the rows of each group to <group-dir>/<group>.<csv|json>, the output keeping the combined rows"))
+            .arg(Arg::new("deterministic")
+                .long("deterministic")
+                .takes_value(false)
+                .help("Byte-identical output for identical inputs and options (total order of the rows, no generation time)")))
+        .subcommand(Command::new("convert")
+            .about("Convert a binary timeline (--format binary) to CSV/JSON, or a JSON output to binary")
+            .args(output_args())
+            .arg(Arg::new("input")
+                .required(true)
+                .help("Timeline exported by mactime in binary or JSON"))
+            .arg(Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .default_value("csv")
+                .possible_values(["csv", "json", "jsonl", "binary"])
+                .help("Output format"))
+            .arg(Arg::new("provenance")
+                .long("provenance")
+                .takes_value(false)
+                .help("Add SourceFile and SourceLine columns to the rows of a JSON input (kept from inputs written with --provenance, binary inputs keeping their columns)")))
+        .subcommand(Command::new("supertimeline")
+            .about("Parse raw artifacts & bodyfiles, then merge, dedup and sort them in a single timeline")
+            .args(output_args())
+            .arg(Arg::new("mft")
+                .long("mft")
+                .takes_value(true)
+                .help("Raw $MFT"))
+            .arg(Arg::new("usn")
+                .long("usn")
+                .takes_value(true)
+                .help("Raw $UsnJrnl:$J (paths resolved with --mft)"))
+            .arg(Arg::new("evtx")
+                .long("evtx")
+                .takes_value(true)
+                .help("EVTX file or directory of .evtx files"))
+            .arg(Arg::new("prefetch")
+                .long("prefetch")
+                .takes_value(true)
+                .help("Prefetch file or directory of .pf files"))
+            .arg(Arg::new("bodyfile")
+                .short('b')
+                .long("bodyfile")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Bodyfile (fls, MacOS collectors, ...), can be repeated"))
+            .arg(Arg::new("time-offset")
+                .long("time-offset")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .allow_hyphen_values(true)
+                .validator(validate_time_offset)
+                .help("Correct the clock skew of an input, or of all inputs: [<input>=]<+|->HH:MM:SS (e.g. winevt/Logs/=-00:05:00), can be repeated"))
+            .arg(Arg::new("threads")
+                .long("threads")
+                .takes_value(true)
+                .validator(validate_threads)
+                .help("Number of inputs parsed concurrently [default: number of CPUs]"))
+            .arg(Arg::new("epoch-base")
+                .long("epoch-base")
+                .takes_value(true)
+                .default_value("1970")
+                .possible_values(["1601", "1970"])
+                .help("Epoch of the bodyfile timestamps: 1970 (Unix), 1601 (Windows FILETIME, WebKit/Chrome)"))
+            .arg(Arg::new("unit")
+                .long("unit")
+                .takes_value(true)
+                .default_value("s")
+                .possible_values(["s", "ms", "us", "ns"])
+                .help("Unit of the bodyfile timestamps, e.g. us for WebKit/Chrome browser history"))
+            .arg(Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .default_value("csv")
+                .possible_values(["csv", "json", "jsonl", "binary"])
+                .help("Output format"))
+            .arg(Arg::new("filter")
+                .short('f')
+                .long("filter")
+                .takes_value(true)
+                .help(FORMAT)
+                .validator(validate_filter_args))
+            .arg(Arg::new("sort")
+                .short('s')
+                .long("sort")
+                .takes_value(true)
+                .possible_values(["datetime", "path", "meta"])
+                .help("Sort timeline by datetime (default), or by full path or meta address, then datetime"))
+            .arg(Arg::new("sort-by")
+                .long("sort-by")
+                .takes_value(true)
+                .conflicts_with("sort")
+                .validator(validate_sort_by)
+                .help("Sort timeline by these fields, each one optionally :asc or :desc (e.g. \"datetime,filename:desc,size\"): datetime, macb, meta, size, filename, source, score, md5, uid"))
+            .arg(Arg::new("stage")
+                .long("stage")
+                .takes_value(true)
+                .possible_values(["parse", "merge", "sort"])
+                .help("Only run one stage: parse the inputs, merge the parsed inputs, or filter/sort/write the merged timeline (intermediate rows in --checkpoint-dir)"))
+            .arg(Arg::new("checkpoint-dir")
+                .long("checkpoint-dir")
+                .alias("tmpdir")
+                .takes_value(true)
+                .default_value("checkpoints")
+                .help("Directory of the intermediate rows written/read by --stage (alias --tmpdir), its free space is checked before each write"))
+            .arg(Arg::new("precision")
+                .long("precision")
+                .takes_value(false)
+                .help("Add a Precision column: second, sub-second or 100ns (FILETIME) timestamps"))
+            .arg(Arg::new("provenance")
+                .long("provenance")
+                .takes_value(false)
+                .help("Add SourceFile and SourceLine columns: input file and line each row was read from"))
+            .arg(Arg::new("schema")
+                .long("schema")
+                .takes_value(true)
+                .default_value("v1")
+                .possible_values(["v1", "v2", "v3"])
+                .help("Output columns: v1 the historical layout, v2 adding MD5, UID and SourceType to every row, v3 also Mode and GID (full mactime column set)"))
+            .arg(Arg::new("deterministic")
+                .long("deterministic")
+                .takes_value(false)
+                .help("Byte-identical output for identical inputs and options (total order of the rows, no generation time)")))
         .arg(Arg::new("bodyfile")
             .short('b')
             .long("bodyfile")
-            .required(true)
-            .takes_value(true))
-        .arg(Arg::new("output")
-            .short('o')
-            .long("output")
             .required(false)
-            .help("CSV output to file (stdout if not specified)")
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .help("Input file, glob pattern (\"collections/*/fls_*.body\"), directory of bodyfiles (*.body, *.txt, recursively), zip/tar(.gz) archive and member (collection.zip::fls/host1.body) or - for the standard input, can be repeated to merge several inputs [default: the standard input]"))
+        .arg(Arg::new("time-offset")
+            .long("time-offset")
+            .required(false)
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .allow_hyphen_values(true)
+            .validator(validate_time_offset)
+            .help("Correct the clock skew of an input, or of all inputs: [<input>=]<+|->HH:MM:SS (e.g. host2.body=+02:13:45), can be repeated"))
+        .arg(Arg::new("threads")
+            .long("threads")
+            .required(false)
+            .takes_value(true)
+            .validator(validate_threads)
+            .help("Number of inputs parsed concurrently, each one in memory until merged [default: number of CPUs]"))
+        .arg(Arg::new("intern")
+            .long("intern")
+            .required(false)
+            .takes_value(false)
+            .help("Store the identical file names, meta addresses, MD5 and UID of all the inputs once (large or repeated collections), at the cost of a table while parsing"))
+        .arg(Arg::new("io-retries")
+            .long("io-retries")
+            .required(false)
+            .takes_value(true)
+            .default_value("0")
+            .validator(|v| v.parse::<u32>())
+            .help("Retry the reads of an input failing with a transient I/O error (network share, S3 mount) this many times"))
+        .arg(Arg::new("io-retry-delay")
+            .long("io-retry-delay")
+            .required(false)
+            .takes_value(true)
+            .default_value("1s")
+            .validator(validate_duration)
+            .help("Delay before the first retry of a read, doubled at each attempt (e.g. 1s, 1m)"))
+        .arg(Arg::new("preflight")
+            .long("preflight")
+            .required(false)
+            .takes_value(false)
+            .help("Sample the inputs and report the estimated number of rows and peak memory of the run before it starts"))
+        .arg(Arg::new("max-memory")
+            .long("max-memory")
+            .required(false)
+            .takes_value(true)
+            .requires("preflight")
+            .validator(|v| preflight::parse_size(v).map(|_| ()))
+            .help("Refuse to run if the --preflight estimate of the peak memory is over this size (e.g. 16G)"))
+        .arg(Arg::new("stream")
+            .long("stream")
+            .required(false)
+            .takes_value(false)
+            .conflicts_with_all(&["sort", "sort-by", "deterministic", "dedup-inputs", "group-by", "group-dir", "max-output-rows", "abort-if-over", "previous", "state", "annotate", "stats", "tree", "hardlinks", "index", "heatmap", "sessions", "session-id", "findings", "lateral-movement", "context", "sequences", "tag-context", "vt-lookup", "export-stix", "export-misp", "misp-url", "manifest", "intern"])
+            .help("Parse, filter and write the rows of bodyfile inputs by chunks of lines, in the order of the inputs, with bounded memory (no sort nor reports of the whole timeline)"))
+        .arg(Arg::new("follow")
+            .long("follow")
+            .required(false)
+            .takes_value(false)
+            .requires("stream")
+            .help("With --stream, keep reading the last input as it grows (tail -f) and write its new rows as they are appended, until Ctrl-C"))
+        .arg(Arg::new("max-output-rows")
+            .long("max-output-rows")
+            .required(false)
+            .takes_value(true)
+            .validator(|v| v.parse::<usize>())
+            .help("Only write the first rows of the timeline, followed by a warning row tagged \"truncated\""))
+        .arg(Arg::new("abort-if-over")
+            .long("abort-if-over")
+            .required(false)
+            .takes_value(true)
+            .validator(|v| v.parse::<usize>())
+            .help("Fail without writing any output if the filtered timeline has more rows than this"))
+        .arg(Arg::new("on-unreadable")
+            .long("on-unreadable")
+            .required(false)
+            .takes_value(true)
+            .default_value("abort")
+            .possible_values(["abort", "skip"])
+            .help("Input that can't be read, once retried: abort the run, or skip it and record the error in the metadata"))
+        .arg(Arg::new("dedup-inputs")
+            .long("dedup-inputs")
+            .required(false)
+            .takes_value(false)
+            .help("Skip the inputs identical to another one and remove the rows read several times"))
+        .arg(Arg::new("group-by")
+            .long("group-by")
+            .required(false)
+            .takes_value(true)
+            .possible_values(["host"])
+            .help("After the combined rows, write a section per host (input), or one file per host with --group-dir"))
+        .arg(Arg::new("group-dir")
+            .long("group-dir")
+            .required(false)
+            .takes_value(true)
+            .requires("group-by")
+            .help("Write the rows of each group to <group-dir>/<group>.<csv|json>, the output keeping the combined rows"))
+        .arg(Arg::new("input-format")
+            .long("input-format")
+            .required(false)
+            .takes_value(true)
+            .default_value("bodyfile")
+            .possible_values(["bodyfile", "events", "velociraptor", "kape"])
+            .help("Input format: bodyfile, timeline previously exported by mactime in JSON (events), Velociraptor timeline JSON or KAPE (MFTECmd) CSV"))
+        .arg(Arg::new("lines")
+            .long("lines")
+            .required(false)
+            .takes_value(true)
+            .help("Only process these input lines (e.g. 1000000..2000000, ..500 or 1000..)")
+            .validator(validate_line_range))
+        .arg(Arg::new("shard")
+            .long("shard")
+            .required(false)
+            .takes_value(true)
+            .help("Only process one shard of the input lines, e.g. 3/8 for every 8th line from the 3rd one (outputs merged with `mactime merge`)")
+            .validator(validate_shard))
+        .args(output_args())
+        .arg(Arg::new("metrics")
+            .long("metrics")
+            .required(false)
+            .takes_value(true)
+            .validator(validate_tcp_address)
+            .help("Serve the counters of the run (rows read & written, parse errors, output time) in the Prometheus format on http://host:port/metrics"))
+        .arg(Arg::new("format")
+            .long("format")
+            .required(false)
+            .takes_value(true)
+            .default_value("csv")
+            .possible_values(["csv", "json", "jsonl", "binary"])
+            .help("Output format, json being a single {\"metadata\", \"events\"} document, jsonl one event per line (SIEM ingestion), binary a compact timeline for mactime merge/convert"))
+        .arg(Arg::new("input-delimiter")
+            .long("input-delimiter")
+            .required(false)
+            .takes_value(true)
+            .validator(validate_delimiter)
+            .help("Column separator of the bodyfile input, e.g. ',' or tab [default: |]"))
+        .arg(Arg::new("on-invalid-timestamp")
+            .long("on-invalid-timestamp")
+            .required(false)
+            .takes_value(true)
+            .default_value("zero")
+            .possible_values(["skip", "zero", "flag"])
+            .help("Bodyfile timestamps out of the supported dates (beyond i64, the year 262143): skip the whole record, set them to 0, or set them to 0 and tag the rows \"invalid-timestamp\" (the sentinels of unset timestamps, e.g. 0xFFFFFFFF, never have a row, and are listed in an Unset column with flag)"))
+        .arg(Arg::new("epoch-base")
+            .long("epoch-base")
+            .required(false)
+            .takes_value(true)
+            .default_value("1970")
+            .possible_values(["1601", "1970"])
+            .help("Epoch of the bodyfile timestamps: 1970 (Unix), 1601 (Windows FILETIME, WebKit/Chrome)"))
+        .arg(Arg::new("unit")
+            .long("unit")
+            .required(false)
+            .takes_value(true)
+            .default_value("s")
+            .possible_values(["s", "ms", "us", "ns"])
+            .help("Unit of the bodyfile timestamps, e.g. us for WebKit/Chrome browser history"))
+        .arg(Arg::new("has-header")
+            .long("has-header")
+            .required(false)
+            .takes_value(false)
+            .help("Skip the first line of the bodyfile input (header)"))
+        .arg(Arg::new("include-raw")
+            .long("include-raw")
+            .required(false)
+            .takes_value(false)
+            .help("Append the source line of each row as a Raw column"))
+        .arg(Arg::new("precision")
+            .long("precision")
+            .required(false)
+            .takes_value(false)
+            .help("Add a Precision column: second, sub-second or 100ns (FILETIME) timestamps"))
+        .arg(Arg::new("provenance")
+            .long("provenance")
+            .required(false)
+            .takes_value(false)
+            .help("Add SourceFile and SourceLine columns: input file and line each row was read from"))
+        .arg(Arg::new("schema")
+            .long("schema")
+            .required(false)
+            .takes_value(true)
+            .default_value("v1")
+            .possible_values(["v1", "v2", "v3"])
+            .help("Output columns: v1 the historical layout (existing consumers), v2 adding MD5, UID and SourceType to every row, v3 also Mode and GID (full mactime column set)"))
+        .arg(Arg::new("passwd")
+            .short('p')
+            .long("passwd")
+            .required(false)
+            .takes_value(true)
+            .help("Write the user names of this passwd file (name:x:UID:...) in the UID column instead of the UIDs (--schema v2, v3)"))
+        .arg(Arg::new("group")
+            .short('g')
+            .long("group")
+            .required(false)
+            .takes_value(true)
+            .help("Write the group names of this group file (name:x:GID:...) in the GID column instead of the GIDs (--schema v3)"))
+        .arg(Arg::new("redact")
+            .long("redact")
+            .required(false)
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .validator(validate_redaction)
+            .help("Rewrite a column of the output rows: <column>:<regex>=<replacement> (e.g. \"filename:/Users/[^/]+/=/Users/REDACTED/\"), column being filename, meta or raw, can be repeated"))
+        .arg(Arg::new("encrypt")
+            .long("encrypt")
+            .required(false)
+            .takes_value(true)
+            .conflicts_with("tcp-out")
+            .validator(validate_encryption)
+            .help("Stream the output through age encryption: age:<recipients file> (public keys) or passphrase (asked by age)"))
+        .arg(Arg::new("manifest")
+            .long("manifest")
+            .required(false)
+            .takes_value(true)
+            .requires("manifest-key")
+            .help("Write a chain-of-custody manifest (SHA-256 of the inputs & outputs, version, options, times) to file, signed with --manifest-key"))
+        .arg(Arg::new("manifest-key")
+            .long("manifest-key")
+            .required(false)
+            .takes_value(true)
+            .requires("manifest")
+            .help("Ed25519 private key (PKCS#8 DER or PEM) signing the manifest"))
+        .arg(Arg::new("timezone")
+            .short('z')
+            .long("timezone")
+            .required(false)
+            .takes_value(true)
+            .validator(validate_timezone)
+            .help("Write the datetimes in the local time of this IANA time zone (e.g. Europe/Paris), named in the CSV header [default: UTC]"))
+        .arg(Arg::new("system-tz-from")
+            .long("system-tz-from")
+            .required(false)
+            .takes_value(true)
+            .conflicts_with("timezone")
+            .help("Write the datetimes in the time zone configured on the suspect machine, read from its SYSTEM registry hive or from a JSON file of clock settings ({\"timezone\": \"Europe/Paris\"})"))
+        .arg(Arg::new("extra-tz")
+            .long("extra-tz")
+            .required(false)
+            .takes_value(true)
+            .use_value_delimiter(true)
+            .validator(validate_timezone)
+            .help("Add a datetime column in the local time of each of these IANA time zones (comma separated), after the UTC or -z one"))
+        .arg(Arg::new("missing-value")
+            .long("missing-value")
+            .required(false)
+            .takes_value(true)
+            .default_value("empty")
+            .possible_values(["empty", "-", "NULL"])
+            .help("Placeholder of the CSV fields missing from the source (e.g. no VirusTotal result)"))
+        .arg(Arg::new("thousands-separator")
+            .long("thousands-separator")
+            .required(false)
+            .takes_value(true)
+            .help("Group the digits of the CSV Size column with this separator (e.g. \",\" or \" \")")
+            .validator(locale::validate_separator))
+        .arg(Arg::new("decimal-separator")
+            .long("decimal-separator")
+            .required(false)
+            .takes_value(true)
+            .default_value(".")
+            .help("Decimal point of the sub-seconds of the CSV Datetime column (e.g. \",\")")
+            .validator(locale::validate_separator))
         .arg(Arg::new("filter")
             .short('f')
             .long("filter")
@@ -77,8 +1529,356 @@ fn main() -> Result<(), Box<dyn Error>> {
             .short('s')
             .long("sort")
             .required(false)
-            .help("Sort timeline by datetime")
+            .help("Sort timeline by datetime (default), or by full path or meta address, then datetime")
+            .takes_value(true)
+            .min_values(0)
+            .max_values(1)
+            .default_missing_value("datetime")
+            .possible_values(["datetime", "path", "meta"]))
+        .arg(Arg::new("sort-by")
+            .long("sort-by")
+            .required(false)
+            .takes_value(true)
+            .conflicts_with("sort")
+            .validator(validate_sort_by)
+            .help("Sort timeline by these fields, each one optionally :asc or :desc (e.g. \"datetime,filename:desc,size\"): datetime, macb, meta, size, filename, source, score, md5, uid"))
+        .arg(Arg::new("deterministic")
+            .long("deterministic")
+            .required(false)
+            .takes_value(false)
+            .help("Byte-identical output for identical inputs and options (total order of the rows, no generation time)"))
+        .arg(Arg::new("source-type")
+            .long("source-type")
+            .required(false)
+            .takes_value(true)
+            .use_value_delimiter(true)
+            .help("Only keep rows from these sources (comma separated): FILE, MFT, USNJRNL, EVTX, REG, PF")
+            .validator(|v| v.parse::<SourceType>()))
+        .arg(Arg::new("include")
+            .long("include")
+            .required(false)
+            .takes_value(true)
+            .use_value_delimiter(true)
+            .multiple_occurrences(true)
+            .help("Only keep rows whose file name contains one of these keywords (comma separated)"))
+        .arg(Arg::new("exclude")
+            .long("exclude")
+            .required(false)
+            .takes_value(true)
+            .use_value_delimiter(true)
+            .multiple_occurrences(true)
+            .help("Drop rows whose file name contains one of these keywords (comma separated)"))
+        .arg(Arg::new("keywords-fd")
+            .long("keywords-fd")
+            .required(false)
+            .takes_value(true)
+            .validator(validate_fd)
+            .help("Read --include keywords, one per line, from this file descriptor (e.g. 3 with 3<keywords.txt, 0 for stdin)"))
+        .arg(Arg::new("exclude-fd")
+            .long("exclude-fd")
+            .required(false)
+            .takes_value(true)
+            .validator(validate_fd)
+            .help("Read --exclude keywords, one per line, from this file descriptor"))
+        .arg(Arg::new("ignore-case")
+            .long("ignore-case")
+            .required(false)
+            .takes_value(false)
+            .help("Unicode case-insensitive file name matching of --include/--exclude keywords and --rules regexes"))
+        .arg(Arg::new("user")
+            .long("user")
+            .required(false)
+            .takes_value(true)
+            .help("Only keep the activity of a user: <name> or <name>:<uid> (profile directories and owned entries)"))
+        .arg(Arg::new("stats")
+            .long("stats")
+            .required(false)
+            .help("Display statistics on deleted vs allocated entries")
             .takes_value(false))
+        .arg(Arg::new("tree")
+            .long("tree")
+            .required(false)
+            .takes_value(true)
+            .help("Write the directory tree of the rows, with earliest/latest activity and event counts, to file"))
+        .arg(Arg::new("previous")
+            .long("previous")
+            .required(false)
+            .takes_value(true)
+            .help("Only output the rows missing from the JSON output (--format json) of a previous run over the same evidence"))
+        .arg(Arg::new("state")
+            .long("state")
+            .required(false)
+            .takes_value(true)
+            .help("Persist the hashes of the emitted rows to this file, and only output the rows not emitted by the previous runs using it (growing inputs)"))
+        .arg(Arg::new("annotate")
+            .long("annotate")
+            .required(false)
+            .takes_value(true)
+            .help("Label the rows falling in the date ranges of the annotation file (one start,end,label per line) in an Annotation column"))
+        .arg(Arg::new("index")
+            .short('i')
+            .long("index")
+            .required(false)
+            .takes_value(true)
+            .help("Write the number of rows per day/hour, broken down by MACB flags, to file (CSV)"))
+        .arg(Arg::new("index-period")
+            .long("index-period")
+            .required(false)
+            .takes_value(true)
+            .default_value("day")
+            .possible_values(["day", "hour"])
+            .help("Period of the --index rows"))
+        .arg(Arg::new("heatmap")
+            .long("heatmap")
+            .required(false)
+            .takes_value(true)
+            .help("Write the number of rows per day with its ISO year, week and weekday (calendar heatmap) to file"))
+        .arg(Arg::new("heatmap-format")
+            .long("heatmap-format")
+            .required(false)
+            .takes_value(true)
+            .default_value("csv")
+            .possible_values(["csv", "json"])
+            .help("Format of the --heatmap file"))
+        .arg(Arg::new("sessions")
+            .long("sessions")
+            .required(false)
+            .takes_value(true)
+            .help("Group the rows less than --session-gap apart into sessions, and write their summary (start, end, count, top directories) to file (CSV)"))
+        .arg(Arg::new("session-gap")
+            .long("session-gap")
+            .required(false)
+            .takes_value(true)
+            .default_value("30m")
+            .validator(validate_duration)
+            .help("Idle gap ending a session (e.g. 30s, 10m, 1h)"))
+        .arg(Arg::new("session-id")
+            .long("session-id")
+            .required(false)
+            .takes_value(false)
+            .help("Add a SessionId column: session of each row, numbered from 1 in chronological order"))
+        .arg(Arg::new("hardlinks")
+            .long("hardlinks")
+            .required(false)
+            .takes_value(true)
+            .help("Write the meta addresses shared by several file names (hardlinks, index entries) to file (CSV)"))
+        .arg(Arg::new("findings")
+            .long("findings")
+            .required(false)
+            .takes_value(true)
+            .help("Run analysis rules and write the findings report (CSV) to file"))
+        .arg(Arg::new("burst-threshold")
+            .long("burst-threshold")
+            .required(false)
+            .takes_value(true)
+            .default_value("50")
+            .help("Minimum number of entries sharing the same ctime to flag a mass-deletion burst")
+            .validator(|v| v.parse::<usize>()))
+        .arg(Arg::new("staging-count")
+            .long("staging-count")
+            .required(false)
+            .takes_value(true)
+            .default_value("100")
+            .help("Minimum number of births in a directory within --staging-window to flag data staging")
+            .validator(|v| v.parse::<usize>()))
+        .arg(Arg::new("staging-window")
+            .long("staging-window")
+            .required(false)
+            .takes_value(true)
+            .default_value("10m")
+            .help("Window for data staging detection (e.g. 30s, 10m, 1h)")
+            .validator(validate_duration))
+        .arg(Arg::new("encryption-count")
+            .long("encryption-count")
+            .required(false)
+            .takes_value(true)
+            .default_value("100")
+            .help("Minimum number of files modified & renamed to a common new extension or to high-entropy names to flag a ransomware encryption burst")
+            .validator(|v| v.parse::<usize>()))
+        .arg(Arg::new("encryption-directories")
+            .long("encryption-directories")
+            .required(false)
+            .takes_value(true)
+            .default_value("10")
+            .help("Minimum number of directories of an encryption burst")
+            .validator(|v| v.parse::<usize>()))
+        .arg(Arg::new("encryption-window")
+            .long("encryption-window")
+            .required(false)
+            .takes_value(true)
+            .default_value("10m")
+            .help("Maximum gap between the modifications of an encryption burst (e.g. 30s, 10m, 1h)")
+            .validator(validate_duration))
+        .arg(Arg::new("lateral-movement")
+            .long("lateral-movement")
+            .required(false)
+            .takes_value(true)
+            .help("Correlate the remote-execution artifacts of each host (PsExec, scheduled tasks, WMI, services) and write the likely lateral-movement times to file (CSV)"))
+        .arg(Arg::new("lateral-window")
+            .long("lateral-window")
+            .required(false)
+            .takes_value(true)
+            .default_value("5m")
+            .help("Maximum gap between the remote-execution artifacts of a lateral movement (e.g. 30s, 10m, 1h)")
+            .validator(validate_duration))
+        .arg(Arg::new("context")
+            .long("context")
+            .required(false)
+            .takes_value(true)
+            .help("Export rows within this duration of each finding/IOC hit to a context CSV (e.g. 10m)")
+            .validator(validate_duration))
+        .arg(Arg::new("context-dir")
+            .long("context-dir")
+            .required(false)
+            .takes_value(true)
+            .default_value("context")
+            .help("Directory of the context CSV files"))
+        .arg(Arg::new("ioc")
+            .long("ioc")
+            .required(false)
+            .takes_value(true)
+            .help("Tag rows matching the IOC file (one filename, path or MD5 per line, optional |weight)"))
+        .arg(Arg::new("ioc-fd")
+            .long("ioc-fd")
+            .required(false)
+            .takes_value(true)
+            .validator(validate_fd)
+            .help("Read the IOC list from this file descriptor (e.g. 3 with 3<iocs.txt, 0 for stdin), keeping it out of the shell history & process listings"))
+        .group(ArgGroup::new("iocs")
+            .args(&["ioc", "ioc-fd"]))
+        .arg(Arg::new("rules")
+            .long("rules")
+            .required(false)
+            .takes_value(true)
+            .help("Tag rows matching the rules file (one tag|weight|regex per line)"))
+        .arg(Arg::new("sequences")
+            .long("sequences")
+            .required(false)
+            .takes_value(true)
+            .requires("sequence-report")
+            .help("Match the sequence rules file (ordered steps, each one within a window of the previous one) over the rows sorted by datetime"))
+        .arg(Arg::new("sequence-report")
+            .long("sequence-report")
+            .required(false)
+            .takes_value(true)
+            .requires("sequences")
+            .help("Write the matched sequences (CSV) to file"))
+        .arg(Arg::new("valid-years")
+            .long("valid-years")
+            .required(false)
+            .takes_value(true)
+            .min_values(0)
+            .default_missing_value("1990..2035")
+            .validator(validate_year_range)
+            .help("Drop the rows dated outside this range of plausible years (overflowed values, ...), 1990..2035 if given without a range (off by default)"))
+        .arg(Arg::new("flag-invalid-years")
+            .long("flag-invalid-years")
+            .required(false)
+            .takes_value(false)
+            .help("Tag the rows outside --valid-years as \"invalid-year\" instead of dropping them"))
+        .arg(Arg::new("flag-dst")
+            .long("flag-dst")
+            .required(false)
+            .takes_value(false)
+            .help("Tag the rows whose local time (-z, --system-tz-from) is repeated by a DST switch back \"dst-ambiguous\", and the ones just after the hour skipped by a switch forward \"dst-skipped\""))
+        .arg(Arg::new("min-score")
+            .long("min-score")
+            .required(false)
+            .takes_value(true)
+            .help("Only output rows whose score (sum of matched rule/IOC weights) is at least this value")
+            .validator(|v| v.parse::<u64>()))
+        .arg(Arg::new("save-filter")
+            .long("save-filter")
+            .required(false)
+            .takes_value(true)
+            .help("Save the active filters (dates, source types, keywords, user, valid years, rules, minimum score) to this JSON file"))
+        .arg(Arg::new("load-filter")
+            .long("load-filter")
+            .required(false)
+            .takes_value(true)
+            .help("Apply the filters of a file written by --save-filter, the options of the command line taking precedence"))
+        .arg(Arg::new("script")
+            .long("script")
+            .required(false)
+            .takes_value(true)
+            .help("Run this row script on each row of the filtered timeline, to rewrite, tag or drop it (requires the `scripting` feature)"))
+        .arg(Arg::new("tag-cmd")
+            .long("tag-cmd")
+            .required(false)
+            .takes_value(true)
+            .help("Tag the rows with an external classifier: rows streamed as JSON lines to the stdin of this command, a JSON array of tags read back per row"))
+        .arg(Arg::new("tag-batch")
+            .long("tag-batch")
+            .required(false)
+            .takes_value(true)
+            .default_value("1000")
+            .requires("tag-cmd")
+            .help("Number of rows sent to --tag-cmd at once")
+            .validator(validate_batch))
+        .arg(Arg::new("tag-timeout")
+            .long("tag-timeout")
+            .required(false)
+            .takes_value(true)
+            .default_value("30s")
+            .requires("tag-cmd")
+            .help("Maximum time --tag-cmd takes to answer a batch (e.g. 30s, 5m)")
+            .validator(validate_duration))
+        .arg(Arg::new("tag-context")
+            .long("tag-context")
+            .required(false)
+            .takes_value(true)
+            .help("Tag rows of the parent directory of tagged files within this window as \"context\" (e.g. 10m)")
+            .validator(validate_duration))
+        .arg(Arg::new("export-stix")
+            .long("export-stix")
+            .required(false)
+            .takes_value(true)
+            .requires("iocs")
+            .help("Export IOC matches as a STIX 2.1 bundle (JSON) to file"))
+        .arg(Arg::new("export-misp")
+            .long("export-misp")
+            .required(false)
+            .takes_value(true)
+            .requires("iocs")
+            .help("Export IOC matches as a MISP event (JSON) to file"))
+        .arg(Arg::new("misp-url")
+            .long("misp-url")
+            .required(false)
+            .takes_value(true)
+            .requires_all(&["iocs", "misp-key"])
+            .help("Push IOC matches as a new event to this MISP instance (requires the `misp` feature)"))
+        .arg(Arg::new("misp-key")
+            .long("misp-key")
+            .required(false)
+            .takes_value(true)
+            .env("MISP_KEY")
+            .hide_env_values(true)
+            .help("MISP API key, better set in the MISP_KEY environment variable than on the command line (process list, shell history)"))
+        .arg(Arg::new("vt-lookup")
+            .long("vt-lookup")
+            .required(false)
+            .takes_value(false)
+            .requires("vt-key")
+            .help("Add a Detection column from VirusTotal for the MD5 of each row (requires the `vt` feature)"))
+        .arg(Arg::new("vt-key")
+            .long("vt-key")
+            .required(false)
+            .takes_value(true)
+            .env("VT_API_KEY")
+            .help("VirusTotal API key"))
+        .arg(Arg::new("vt-rate")
+            .long("vt-rate")
+            .required(false)
+            .takes_value(true)
+            .default_value("4")
+            .help("Maximum number of VirusTotal requests per minute")
+            .validator(|v| v.parse::<u32>()))
+        .arg(Arg::new("vt-cache")
+            .long("vt-cache")
+            .required(false)
+            .takes_value(true)
+            .default_value("mactime_vt_cache.json")
+            .help("VirusTotal results cache file"))
         /*.arg(Arg::new("verbose")
             .short('v')
             .long("verbose")
@@ -87,20 +1887,495 @@ fn main() -> Result<(), Box<dyn Error>> {
             .takes_value(false)*/
         .get_matches();
 
-    let input = matches.value_of("bodyfile").expect("required bodyfile");
-    let output = matches.value_of("output").map(Path::new); // map to path if present, None otherwise
-    let filter = matches.value_of("filter")
-        .map(|d| parse_filter_args(d).unwrap() ) // parse dates (we can unwrap because it has been validated by clap)
-        .map(|d| DateFilter::new(d) ); // convert to DateFilter
+    // Ctrl-C leaves the prompt, instead of stopping a run that writes a partial output
+    if let Some(("repl", matches)) = matches.subcommand() {
+        return repl(matches);
+    }
+    interrupt::install()?;
+
+    match matches.subcommand() {
+        Some(("supertimeline", matches)) => return supertimeline(matches),
+        Some(("merge", matches)) => return merge(matches),
+        Some(("convert", matches)) => return convert(matches),
+        Some(("schema", matches)) => return print_schema(matches),
+        Some(("selftest", _)) => return selftest(),
+        Some(("timezones", matches)) => return timezones(matches),
+        Some(("adapters", _)) => {
+            adapters(&InputAdapters::default());
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // read before the run, a bad key must not be found once the timeline is written
+    let started = Utc::now();
+    let manifest_key = matches.value_of("manifest-key").map(|k| manifest::read_key(Path::new(k))).transpose()?;
+    let timezone = output_timezone(&matches)?;
+    if matches.is_present("flag-dst") && timezone.is_none() {
+        return Err("--flag-dst requires -z or --system-tz-from".into());
+    }
+    let schema : SchemaLevel = matches.value_of_t("schema")?;
+    if matches.is_present("passwd") && schema < SchemaLevel::V2 {
+        return Err("--passwd names the UID column of --schema v2 and v3".into());
+    }
+    if matches.is_present("group") && schema < SchemaLevel::V3 {
+        return Err("--group names the GID column of --schema v3".into());
+    }
+
+    // like Sleuthkit's mactime, the bodyfile piped to the standard input without -b (fls -r -m C: image.dd | mactime -s)
+    if !matches.is_present("bodyfile") && std::io::stdin().is_terminal() {
+        return Err("No input: give a bodyfile with -b, or pipe one to the standard input".into());
+    }
+    let values = matches.values_of("bodyfile").map_or_else(|| vec![archive::STDIN], |b| b.collect());
+    let (inputs, labels) : (Vec<String>, Vec<String>) = expand_inputs(values.into_iter())?.into_iter().unzip();
+    let inputs : Vec<&str> = inputs.iter().map(String::as_str).collect();
+    let output = output_path(&matches)?;
+    let output = output.as_deref();
+
+    // pipes & devices are read once, as their content comes: no format guessed, no second read
+    if let Some(stream) = inputs.iter().find(|i| archive::is_stream(Path::new(i))) {
+        if matches.is_present("bodyfile") && matches.occurrences_of("input-format") == 0 {
+            return Err(format!("{stream} is a pipe or device: --input-format is required").into());
+        }
+        if matches.is_present("manifest") {
+            return Err(format!("{stream} is a pipe or device: --manifest can't hash it once read").into());
+        }
+    }
+    if let Some(output) = output.filter(|o| archive::is_stream(o) && matches.is_present("manifest")) {
+        return Err(format!("{} is a pipe: --manifest can't hash the output once read", output.display()).into());
+    }
+
+    // filters of the command line, over the ones of a saved filter file
+    let mut filters = command_line_filters(&matches)?;
+    if let Some(path) = matches.value_of("load-filter") {
+        filters = FilterSet::read(Path::new(path))?.overridden_by(filters);
+        validate_filters(&filters).map_err(|e| format!("Invalid filter file {path}: {e}"))?;
+        eprintln!("Filters loaded from {path}");
+    }
+    validate_filters(&filters)?;
+    if let Some(path) = matches.value_of("save-filter") {
+        filters.write(Path::new(path))?;
+        eprintln!("Filters saved to {path}");
+    }
+    let filter = filters.filter.as_deref()
+        .map(|d| parse_filter_args(d).unwrap() ) // parse dates (we can unwrap because it has been validated by clap or validate_filters)
+        .map(DateFilter::new); // convert to DateFilter
 
     // build bodyfile object: parse bodyfile entries & build timeline with datetime entries
-    let bodyfile = BodyFileParser::build(Path::new(input), filter, matches.is_present("sort"))?;
+    let sort = matches.value_of("sort").map(|s| s.parse::<SortKey>()).transpose()?;
+    let input_format : InputFormat = matches.value_of_t("input-format")?;
+    let options = ParseOptions {
+        format: input_format,
+        filter,
+        sort,
+        include_raw: matches.is_present("include-raw"),
+        lines: matches.value_of("lines").map(parse_line_range).transpose()?,
+        shard: matches.value_of("shard").map(parse_shard).transpose()?,
+        delimiter: matches.value_of("input-delimiter").map(parse_delimiter).transpose()?,
+        has_header: matches.is_present("has-header"),
+        on_invalid_timestamp: matches.value_of_t("on-invalid-timestamp")?,
+        time_offset: None,
+        epoch_base: matches.value_of_t("epoch-base")?,
+        unit: matches.value_of_t("unit")?,
+        intern: matches.is_present("intern"),
+        io_retry: IoRetry {
+            retries: matches.value_of_t("io-retries")?,
+            delay: parse_duration(matches.value_of("io-retry-delay").expect("default value"))?.to_std()?
+        }
+    };
+    let on_unreadable : OnUnreadable = matches.value_of_t("on-unreadable")?;
+    let offsets = time_offsets(&matches);
+    let threads = matches.value_of("threads").map(str::parse).transpose()?.unwrap_or_else(parallel::default_threads);
+    if let Some(address) = matches.value_of("metrics") {
+        metrics::serve(address)?;
+        eprintln!("Metrics served on http://{address}/metrics");
+    }
+    if matches.is_present("preflight") {
+        let max_memory = matches.value_of("max-memory").map(preflight::parse_size).transpose()?;
+        let sorted = ["sort", "sort-by", "deterministic", "dedup-inputs"].into_iter().any(|o| matches.is_present(o));
+        run_preflight(&inputs, &options, threads, sorted, max_memory)?;
+    }
+    if matches.is_present("stream") {
+        return stream_run(&matches, &inputs, output, &options, &filters, &offsets, timezone.as_ref());
+    }
+    // one worker per input up to --threads, each one producing a sorted run
+    let parsed = parallel::map(inputs.iter().zip(labels.iter()).collect(), threads, |(input, label)| {
+        let offset = TimeOffset::find(&offsets, Path::new(input));
+        BodyFileParser::build(Path::new(input), &ParseOptions { time_offset: offset.map(TimeOffset::offset), ..options.clone() })
+            .map(|mut bodyfile| {
+                metrics::input_parsed(bodyfile.datetime_len(), bodyfile.invalid_lines() + bodyfile.invalid_timestamps());
+                bodyfile.set_host(label);
+                (bodyfile, offset)
+            })
+            .map_err(|e| match e.downcast_ref::<Unreadable>() {
+                Some(_) => (e.to_string(), true),
+                None => (format!("{input}: {e}"), false)
+            })
+    });
+    let mut bodyfiles = vec![];
+    let mut readable = vec![];
+    let mut unreadable = vec![];
+    for (input, parsed) in inputs.iter().zip(parsed) {
+        let (bodyfile, offset) = match parsed {
+            Ok(parsed) => parsed,
+            Err((error, true)) if on_unreadable == OnUnreadable::Skip => {
+                eprintln!("Input skipped, {error}");
+                unreadable.push(serde_json::json!({ "input": input, "error": error }));
+                continue;
+            }
+            Err((error, _)) => return Err(error.into())
+        };
+        if let Some(offset) = offset {
+            eprintln!("Clock of {input} corrected by {offset}");
+        }
+        if bodyfile.encoding() != Encoding::Utf8 {
+            eprintln!("Input {input} transcoded from {} ({} characters replaced)", bodyfile.encoding(), bodyfile.replaced_chars());
+        }
+        if bodyfile.skipped_lines() > 0 {
+            eprintln!("Number of comment/header lines skipped in {input}: {}", bodyfile.skipped_lines());
+        }
+        if bodyfile.invalid_lines() > 0 {
+            eprintln!("Number of invalid lines in {input}: {}", bodyfile.invalid_lines());
+        }
+        if bodyfile.invalid_timestamps() > 0 {
+            eprintln!("Number of invalid timestamps {} in {input}: {}", options.on_invalid_timestamp, bodyfile.invalid_timestamps());
+        }
+        if bodyfile.sentinel_timestamps() > 0 {
+            eprintln!("Number of sentinel timestamps unset in {input}: {}", bodyfile.sentinel_timestamps());
+        }
+        eprintln!("Number of file records read from {input}: {}", bodyfile.file_len());
+        eprintln!("Number of datetime records read from {input}: {}", bodyfile.datetime_len());
+        bodyfiles.push(bodyfile);
+        readable.push(*input);
+    }
+    if bodyfiles.is_empty() {
+        return Err("No readable input".into());
+    }
+    let inputs = readable;
+    let input = &inputs.join(", ");
+    let mut bodyfile = match bodyfiles.len() {
+        1 => bodyfiles.remove(0),
+        _ => merge_inputs(&inputs, bodyfiles, matches.is_present("dedup-inputs"), sort)?
+    };
+    if matches.is_present("intern") {
+        eprintln!("Number of distinct strings stored (file names, meta addresses, MD5, UID): {}", bodyfile.interned_len());
+        bodyfile.set_intern(false); // the rows keep sharing them, the table is no longer needed
+    }
 
-    eprintln!("Number of file records read from {input}: {}", bodyfile.file_len());
-    eprintln!("Number of datetime records read from {input}: {}", bodyfile.datetime_len());
+    if let Some(years) = &filters.valid_years {
+        let flag = filters.flag_invalid_years;
+        let count = bodyfile.check_years(&parse_year_range(years)?, flag);
+        eprintln!("Number of datetime records outside {years} {}: {count}", if flag { "flagged" } else { "dropped" });
+    }
+    // local times repeated or following a skipped hour, ambiguous in the output zone
+    if let Some(timezone) = timezone.as_ref().filter(|_| matches.is_present("flag-dst")) {
+        let count = bodyfile.flag_dst(timezone);
+        eprintln!("Number of datetime records in the DST switches of {timezone} flagged: {count}");
+    }
 
-    // write CSV to output (stdout or file)
-    bodyfile.generate_csv(output)?;
+    if let Some(previous) = matches.value_of("previous") {
+        let run = BodyFileParser::build(Path::new(previous), &ParseOptions { format: InputFormat::Events, ..ParseOptions::default() })?;
+        let count = bodyfile.retain_new(&run);
+        eprintln!("Number of datetime records already in {previous}: {count}");
+        eprintln!("Number of new datetime records: {}", bodyfile.datetime_len());
+    }
+
+    let mut state = matches.value_of("state").map(|s| SeenSet::read(Path::new(s))).transpose()?;
+    if let (Some(path), Some(state)) = (matches.value_of("state"), &state) {
+        let count = bodyfile.retain_unseen(state);
+        eprintln!("Number of datetime records already emitted ({path}): {count}");
+        eprintln!("Number of new datetime records: {}", bodyfile.datetime_len());
+    }
 
+    if let Some(source_types) = &filters.source_type {
+        let sources = source_types.iter().map(|s| s.parse()).collect::<Result<Vec<SourceType>, String>>()?;
+        bodyfile.retain_sources(&sources);
+        eprintln!("Number of datetime records from {}: {}", source_types.join(", "), bodyfile.datetime_len());
+    }
+
+    // keyword filters, all keywords matched at once
+    for (filter, keywords, include) in [("include", &filters.include, true), ("exclude", &filters.exclude, false)] {
+        if !keywords.is_empty() {
+            bodyfile.retain_keywords(&KeywordMatcher::new(keywords.iter().map(String::as_str), filters.ignore_case)?, include);
+            eprintln!("Number of datetime records after --{filter} of {} keywords: {}", keywords.len(), bodyfile.datetime_len());
+        }
+    }
+
+    if let Some(user) = &filters.user {
+        bodyfile.retain_user(&UserFilter::new(user));
+        eprintln!("Number of file records of user {user}: {}", bodyfile.file_len());
+        eprintln!("Number of datetime records of user {user}: {}", bodyfile.datetime_len());
+    }
+
+    if let Some(script_path) = matches.value_of("script") {
+        let dropped = bodyfile.run_script(&Script::from_path(Path::new(script_path))?)?;
+        eprintln!("Number of datetime records dropped by {script_path}: {dropped}");
+    }
+
+    if let Some(annotations_path) = matches.value_of("annotate") {
+        let mut annotations = Annotations::from_path(Path::new(annotations_path))?;
+        annotations.count(bodyfile.events().map(|(_, date)| date));
+        eprintln!("Annotated rows ({} annotations read from {annotations_path}):", annotations.len());
+        for annotation in annotations.iter() {
+            eprintln!("\t{} ({} .. {}): {}", annotation.label, annotation.start.format("%Y-%m-%d %H:%M:%S"), annotation.end.format("%Y-%m-%d %H:%M:%S"), annotation.hits);
+        }
+        bodyfile.set_annotations(annotations);
+    }
+
+    if matches.is_present("stats") || filters.user.is_some() {
+        eprintln!("{}", Stats::build(&bodyfile));
+    }
+
+    let mut findings = vec![];
+    if let Some(findings_path) = matches.value_of("findings") {
+        let options = AnalysisOptions {
+            burst_threshold: matches.value_of_t("burst-threshold")?,
+            staging_count: matches.value_of_t("staging-count")?,
+            staging_window: parse_duration(matches.value_of("staging-window").expect("default value"))?,
+            encryption_count: matches.value_of_t("encryption-count")?,
+            encryption_directories: matches.value_of_t("encryption-directories")?,
+            encryption_window: parse_duration(matches.value_of("encryption-window").expect("default value"))?
+        };
+        findings = analysis::analyze(&bodyfile, &options);
+        for finding in findings.iter() {
+            eprintln!("{finding}");
+        }
+        eprintln!("Number of findings written to {findings_path}: {}", findings.len());
+        analysis::write_findings(&findings, Path::new(findings_path))?;
+    }
+
+    if let Some(lateral_path) = matches.value_of("lateral-movement") {
+        let window = parse_duration(matches.value_of("lateral-window").expect("default value"))?;
+        let movements = lateral::correlate(&bodyfile, window);
+        for movement in movements.iter() {
+            eprintln!("Lateral movement to {}: {} .. {} ({} artifacts)", movement.host, movement.start.format("%Y-%m-%d %H:%M:%S"), movement.end.format("%Y-%m-%d %H:%M:%S"), movement.count);
+        }
+        eprintln!("Number of lateral movements written to {lateral_path}: {}", movements.len());
+        lateral::write_movements(&movements, Path::new(lateral_path))?;
+    }
+
+    if let Some(saved) = &filters.rules {
+        let mut rules = RuleSet::parse(&saved.content, filters.ignore_case)?;
+        bodyfile.tag_rules(&mut rules);
+        eprintln!("Rule hits ({} rules read from {}):", rules.len(), saved.path);
+        for rule in rules.iter() {
+            eprintln!("\t{} (weight {}): {}", rule.tag, rule.weight, rule.hits);
+        }
+    }
+
+    if let Some(command) = matches.value_of("tag-cmd") {
+        let timeout = parse_duration(matches.value_of("tag-timeout").expect("default value"))?.to_std()?;
+        let mut classifier = TagCommand::start(command, matches.value_of("tag-batch").expect("default value").parse()?, timeout)?;
+        let tagged = bodyfile.tag_command(&mut classifier)?;
+        classifier.finish()?;
+        eprintln!("Number of datetime records tagged by {command}: {tagged}");
+    }
+
+    if let (Some(sequences_path), Some(report_path)) = (matches.value_of("sequences"), matches.value_of("sequence-report")) {
+        let mut sequences = sequence::from_path(Path::new(sequences_path), filters.ignore_case)?;
+        let found = sequence::find(&bodyfile, &mut sequences);
+        sequence::write_matches(&found, Path::new(report_path))?;
+        eprintln!("Sequence hits ({} sequences read from {sequences_path}, {} matches written to {report_path}):", sequences.len(), found.len());
+        for sequence in sequences.iter() {
+            eprintln!("\t{}: {}", sequence.name, sequence.hits);
+        }
+    }
+
+    let ioc_path = matches.value_of("ioc").map(PathBuf::from).or_else(|| matches.value_of("ioc-fd").map(fd_path));
+    if let Some(ioc_path) = ioc_path {
+        let mut iocs = IocList::from_path(&ioc_path)?;
+        bodyfile.tag_iocs(&mut iocs);
+        eprintln!("IOC hits ({} IOCs read from {}):", iocs.len(), ioc_path.display());
+        for ioc in iocs.iter() {
+            eprintln!("\t{} ({}, weight {}): {}", ioc.value, ioc.kind, ioc.weight, ioc.hits);
+        }
+
+        if let Some(stix_path) = matches.value_of("export-stix") {
+            export::stix(&bodyfile.matches(), &iocs, Path::new(stix_path))?;
+            eprintln!("STIX bundle written to {stix_path}");
+        }
+
+        let info = format!("mactime IOC matches in {input}");
+        if let Some(misp_path) = matches.value_of("export-misp") {
+            export::misp(&bodyfile.matches(), &info, Path::new(misp_path))?;
+            eprintln!("MISP event written to {misp_path}");
+        }
+
+        if let (Some(url), Some(key)) = (matches.value_of("misp-url"), matches.value_of("misp-key")) {
+            let event_id = export::misp_push(&bodyfile.matches(), &info, url, key)?;
+            eprintln!("MISP event {event_id} created on {url}");
+        }
+    }
+
+    if let Some(window) = matches.value_of("tag-context") {
+        bodyfile.propagate_tags(parse_duration(window)?);
+    }
+
+    if let Some(min_score) = filters.min_score {
+        bodyfile.retain_min_score(min_score);
+    }
+
+    // checked once the rows are filtered, before the lookups & reports of a run that would fill the output volume
+    if let Some(limit) = matches.value_of("abort-if-over") {
+        let limit : usize = limit.parse()?;
+        if bodyfile.datetime_len() > limit {
+            return Err(format!("{} datetime records, over --abort-if-over {limit}: no output written", bodyfile.datetime_len()).into());
+        }
+    }
+
+    if matches.is_present("vt-lookup") {
+        let mut virustotal = VirusTotal::new(
+            matches.value_of("vt-key").expect("required by vt-lookup"),
+            matches.value_of_t("vt-rate")?,
+            Path::new(matches.value_of("vt-cache").expect("default value"))
+        )?;
+        let detections = virustotal.lookup(&bodyfile.md5s())?;
+        bodyfile.set_detections(&detections);
+    }
+
+    let mut context_files = vec![];
+    if let Some(context) = matches.value_of("context") {
+        let context = parse_duration(context)?;
+        let directory = Path::new(matches.value_of("context-dir").expect("default value"));
+        std::fs::create_dir_all(directory)?;
+
+        // one CSV per rule finding and per IOC-matched file
+        let mut hits : Vec<(String, DateTime<Utc>, DateTime<Utc>)> = findings.iter()
+            .map(|f| (f.rule.to_string(), f.start, f.end))
+            .collect();
+        hits.extend(bodyfile.matches().into_iter()
+            .filter(|m| m.tag != "context")
+            .map(|m| (m.tag, m.first, m.last)));
+
+        for (i, (label, start, end)) in hits.iter().enumerate() {
+            let path = directory.join(format!("finding_{:03}_{}.csv", i + 1, analysis::file_label(label)));
+            let count = bodyfile.generate_context_csv(&path, *start - context, *end + context)?;
+            eprintln!("Context of {label} ({count} rows) written to {}", path.display());
+            context_files.push(path);
+        }
+    }
+
+    if let Some(tree_path) = matches.value_of("tree") {
+        tree::write_tree(&bodyfile, Path::new(tree_path))?;
+        eprintln!("Directory tree written to {tree_path}");
+    }
+
+    if let Some(hardlinks_path) = matches.value_of("hardlinks") {
+        let count = hardlinks::write_hardlinks(&bodyfile, Path::new(hardlinks_path))?;
+        eprintln!("Number of meta addresses with several names written to {hardlinks_path}: {count}");
+    }
+
+    if let Some(index_path) = matches.value_of("index") {
+        let period : IndexPeriod = matches.value_of_t("index-period")?;
+        let count = index::write_index(&bodyfile, period, Path::new(index_path))?;
+        eprintln!("Number of {period} periods written to {index_path}: {count}");
+    }
+
+    if let Some(heatmap_path) = matches.value_of("heatmap") {
+        let count = heatmap::write_heatmap(&bodyfile, matches.value_of_t::<HeatmapFormat>("heatmap-format")?, Path::new(heatmap_path))?;
+        eprintln!("Number of days written to {heatmap_path}: {count}");
+    }
+
+    if matches.is_present("sessions") || matches.is_present("session-id") {
+        let gap = parse_duration(matches.value_of("session-gap").expect("default value"))?;
+        let count = bodyfile.assign_sessions(gap);
+        if let Some(sessions_path) = matches.value_of("sessions") {
+            session::write_sessions(&session::sessions(&bodyfile), Path::new(sessions_path))?;
+            eprintln!("Number of sessions written to {sessions_path}: {count}");
+        }
+        bodyfile.set_session_id(matches.is_present("session-id"));
+    }
+
+    // run information for self-describing outputs
+    let mut metadata = serde_json::json!({
+        "tool": "mactime",
+        "version": env!("CARGO_PKG_VERSION"),
+        "schema_version": schema::VERSION,
+        "build": version::build_info(),
+        "input": input,
+        "input_format": input_format.to_string(),
+        "encoding": bodyfile.encoding().to_string(),
+        "file_records": bodyfile.file_len(),
+        "datetime_records": bodyfile.datetime_len(),
+        "lines": matches.value_of("lines"),
+        "shard": matches.value_of("shard"),
+        "time_offset": matches.values_of("time-offset").map(|o| o.collect::<Vec<&str>>()),
+        "filter": filters.filter,
+        "valid_years": filters.valid_years,
+        "on_invalid_timestamp": matches.value_of("on-invalid-timestamp"),
+        "epoch_base": matches.value_of("epoch-base"),
+        "unit": matches.value_of("unit"),
+        "unreadable_inputs": (!unreadable.is_empty()).then_some(&unreadable),
+        "max_output_rows": matches.value_of("max-output-rows"),
+        "abort_if_over": matches.value_of("abort-if-over"),
+        "previous": matches.value_of("previous"),
+        "state": matches.value_of("state"),
+        "sort": matches.value_of("sort"),
+        "sort_by": matches.value_of("sort-by"),
+        "group_by": matches.value_of("group-by"),
+        "user": filters.user,
+        "source_type": filters.source_type,
+        "include": matches.values_of("include").map(|k| k.collect::<Vec<&str>>()), // keywords of file descriptors left out
+        "exclude": matches.values_of("exclude").map(|k| k.collect::<Vec<&str>>()),
+        "ignore_case": filters.ignore_case,
+        "load_filter": matches.value_of("load-filter"),
+        "encrypt": matches.value_of("encrypt"),
+        "redact": matches.values_of("redact").map(|r| r.collect::<Vec<&str>>()),
+        "min_score": filters.min_score.map(|s| s.to_string()),
+        "tag_cmd": matches.value_of("tag-cmd"),
+        "script": matches.value_of("script"),
+        "session_gap": (matches.is_present("sessions") || matches.is_present("session-id")).then(|| matches.value_of("session-gap")),
+        "schema": matches.value_of("schema"),
+        "timezone": timezone.as_ref().map_or("UTC", TimeZone::name),
+        "extra_tz": matches.values_of("extra-tz").map(|t| t.collect::<Vec<&str>>())
+    });
+    metadata["passwd"] = serde_json::json!(matches.value_of("passwd"));
+    metadata["group"] = serde_json::json!(matches.value_of("group"));
+
+    // byte-identical outputs for identical inputs & options: total order of the rows, no generation time
+    if matches.is_present("deterministic") {
+        bodyfile.sort_deterministic(sort);
+    } else {
+        metadata["generated"] = serde_json::json!(Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string());
+    }
+    // composite sort, after the total order so that rows equal on all the keys are still deterministic
+    if let Some(sort_by) = matches.value_of("sort-by") {
+        bodyfile.sort_by(&sort_by.parse()?);
+    }
+    // the first rows of the final order
+    if let Some(max) = matches.value_of("max-output-rows") {
+        let dropped = bodyfile.truncate(max.parse()?);
+        if dropped > 0 {
+            eprintln!("Output truncated to {max} datetime records: {dropped} dropped");
+            metadata["truncated_rows"] = serde_json::json!(dropped);
+        }
+    }
+
+    // write CSV/JSON to output (stdout or file)
+    let format : OutputFormat = matches.value_of_t("format")?;
+    run_output_options(&mut bodyfile, &matches, timezone.as_ref())?;
+    let group_files = generate_groups(&mut bodyfile, &matches, format, &metadata)?;
+    generate(&bodyfile, output, format, metadata.clone())?;
+
+    // once the output is written: an interrupted or failed run emits its rows again
+    if let (Some(path), Some(state)) = (matches.value_of("state"), state.as_mut()) {
+        bodyfile.record_seen(state);
+        state.write(Path::new(path))?;
+        eprintln!("State {path} updated: {} datetime records added, {} in total", state.added(), state.len());
+    }
+
+    if let (Some(manifest_path), Some(key)) = (matches.value_of("manifest"), manifest_key) {
+        let mut outputs : Vec<PathBuf> = output.into_iter().map(Path::to_path_buf).collect();
+        outputs.extend(["findings", "export-stix", "export-misp", "tree", "hardlinks", "index", "heatmap", "sessions", "sequence-report", "lateral-movement"].into_iter()
+            .filter_map(|report| matches.value_of(report))
+            .map(PathBuf::from)
+            .filter(|p| p.exists()));
+        outputs.extend(context_files);
+        outputs.extend(group_files);
+        let mut inputs : Vec<PathBuf> = inputs.iter().map(|i| archive::evidence(i)).collect(); // archives of the members
+        inputs.dedup();
+        let signature = manifest::write_manifest(Path::new(manifest_path), &key, started, &inputs, &outputs, &metadata)?;
+        eprintln!("Manifest written to {manifest_path} (signature {})", signature.display());
+    }
     Ok(())
 }
\ No newline at end of file
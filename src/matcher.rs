@@ -0,0 +1,58 @@
+use std::{error::Error, borrow::Cow};
+use aho_corasick::AhoCorasick;
+use regex::{RegexSet, RegexSetBuilder};
+
+/*
+Multi-pattern matching of file names, evaluated once per row whatever the number of patterns:
+keywords (--include/--exclude filters, IOC names & paths) in a single Aho-Corasick automaton, regexes (rules) in a RegexSet.
+With --ignore-case, matching is Unicode case-insensitive (Windows paths): keywords & file names are lowercased, regexes use Unicode case folding.
+*/
+#[derive(Debug)]
+pub struct KeywordMatcher {
+    automaton: AhoCorasick,
+    ignore_case: bool
+}
+
+impl KeywordMatcher {
+    pub fn new<'a>(keywords: impl IntoIterator<Item = &'a str>, ignore_case: bool) -> Result<Self, Box<dyn Error>> {
+        let keywords : Vec<String> = keywords.into_iter().map(|k| fold(k, ignore_case).into_owned()).collect();
+        Ok(Self { automaton: AhoCorasick::new(keywords)?, ignore_case })
+    }
+
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.automaton.is_match(fold(haystack, self.ignore_case).as_ref())
+    }
+
+    // indices of the keywords found in `haystack`, sorted
+    pub fn matches(&self, haystack: &str) -> Vec<usize> {
+        let mut matched : Vec<usize> = self.automaton.find_overlapping_iter(fold(haystack, self.ignore_case).as_ref())
+            .map(|m| m.pattern().as_usize())
+            .collect();
+        matched.sort_unstable();
+        matched.dedup();
+        matched
+    }
+}
+
+fn fold(value: &str, ignore_case: bool) -> Cow<'_, str> {
+    match ignore_case {
+        true => Cow::Owned(value.to_lowercase()),
+        false => Cow::Borrowed(value)
+    }
+}
+
+#[derive(Debug)]
+pub struct RegexMatcher {
+    set: RegexSet
+}
+
+impl RegexMatcher {
+    pub fn new<'a>(patterns: impl IntoIterator<Item = &'a str>, ignore_case: bool) -> Result<Self, Box<dyn Error>> {
+        Ok(Self { set: RegexSetBuilder::new(patterns).case_insensitive(ignore_case).build()? })
+    }
+
+    // indices of the regexes matching `haystack`, sorted
+    pub fn matches(&self, haystack: &str) -> Vec<usize> {
+        self.set.matches(haystack).into_iter().collect()
+    }
+}
@@ -0,0 +1,112 @@
+use std::{error::Error, io::{BufRead, BufReader, Write}, net::TcpListener, time::{Duration, Instant}};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::sink::{Event, OutputSink};
+
+/*
+Metrics endpoint (--metrics): counters of the run served in the Prometheus text format on GET /metrics, for the
+duration of the run, so that long ingestion runs (many inputs, outputs streamed to --tcp-out) are watched by the
+existing observability stack. Counters are process-wide: inputs & rows parsed, parse errors, rows written and the
+time spent writing them to the output (sink latency).
+*/
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static INPUTS: AtomicU64 = AtomicU64::new(0);
+static ROWS_READ: AtomicU64 = AtomicU64::new(0);
+static PARSE_ERRORS: AtomicU64 = AtomicU64::new(0);
+static ROWS_WRITTEN: AtomicU64 = AtomicU64::new(0);
+static WRITE_NANOS: AtomicU64 = AtomicU64::new(0);
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// an input parsed: its datetime records, and its invalid lines & timestamps
+pub fn input_parsed(rows: usize, errors: usize) {
+    INPUTS.fetch_add(1, Ordering::Relaxed);
+    rows_parsed(rows, errors);
+}
+
+// rows of an input read so far (--stream)
+pub fn rows_parsed(rows: usize, errors: usize) {
+    ROWS_READ.fetch_add(rows as u64, Ordering::Relaxed);
+    PARSE_ERRORS.fetch_add(errors as u64, Ordering::Relaxed);
+}
+
+pub fn render() -> String {
+    let metrics : [(&str, &str, String); 5] = [
+        ("mactime_inputs_parsed_total", "Inputs parsed", INPUTS.load(Ordering::Relaxed).to_string()),
+        ("mactime_rows_read_total", "Datetime records read from the inputs", ROWS_READ.load(Ordering::Relaxed).to_string()),
+        ("mactime_parse_errors_total", "Invalid lines and timestamps of the inputs", PARSE_ERRORS.load(Ordering::Relaxed).to_string()),
+        ("mactime_rows_written_total", "Rows written to the output", ROWS_WRITTEN.load(Ordering::Relaxed).to_string()),
+        ("mactime_sink_write_seconds_total", "Time spent writing the rows to the output", format!("{:.6}", Duration::from_nanos(WRITE_NANOS.load(Ordering::Relaxed)).as_secs_f64()))
+    ];
+    metrics.iter()
+        .map(|(name, help, value)| format!("# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}\n"))
+        .collect()
+}
+
+// listens on `address` until the end of the run, each request being answered at once
+pub fn serve(address: &str) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(address).map_err(|e| format!("Metrics endpoint {address}: {e}"))?;
+    ENABLED.store(true, Ordering::Relaxed);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut request = String::new();
+            if BufReader::new(&stream).read_line(&mut request).is_err() {
+                continue;
+            }
+            let response = match request.split_whitespace().nth(1) {
+                Some("/metrics") => {
+                    let body = render();
+                    format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len())
+                }
+                _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+            };
+            let _ = (&stream).write_all(response.as_bytes()); // scraper gone
+        }
+    });
+    Ok(())
+}
+
+// rows written & time spent in the wrapped sink
+pub struct MeteredSink {
+    sink: Box<dyn OutputSink>
+}
+
+impl MeteredSink {
+    pub fn new(sink: Box<dyn OutputSink>) -> Self {
+        Self { sink }
+    }
+}
+
+impl OutputSink for MeteredSink {
+    fn write_header(&mut self, columns: &[&str], metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        self.sink.write_header(columns, metadata)
+    }
+
+    fn write_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let start = Instant::now();
+        let result = self.sink.write_event(event);
+        WRITE_NANOS.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        ROWS_WRITTEN.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+
+    fn write_section(&mut self, name: &str, columns: &[&str]) -> Result<(), Box<dyn Error>> {
+        self.sink.write_section(name, columns)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        let start = Instant::now();
+        let result = self.sink.flush();
+        WRITE_NANOS.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        result
+    }
+
+    fn finish(&mut self, partial: bool) -> Result<(), Box<dyn Error>> {
+        let start = Instant::now();
+        let result = self.sink.finish(partial);
+        WRITE_NANOS.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        result
+    }
+}
@@ -0,0 +1,213 @@
+use std::{path::Path, error::Error, collections::HashMap};
+use chrono::{DateTime, Utc};
+
+use crate::bodyfile::{BodyFileEntry, SourceType, MACB};
+
+/* Raw NTFS $MFT parser: $STANDARD_INFORMATION timestamps, $FILE_NAME name/parent & $DATA size of each record */
+
+const ATTRIBUTE_STANDARD_INFORMATION: u32 = 0x10;
+const ATTRIBUTE_FILE_NAME: u32 = 0x30;
+const ATTRIBUTE_DATA: u32 = 0x80;
+const ATTRIBUTE_END: u32 = 0xFFFF_FFFF;
+const ROOT_ENTRY: u64 = 5;
+const NAMESPACE_DOS: u8 = 2;
+
+// placeholders of an unset FILETIME: beyond the ones Windows converts (0x7FFFFFFFFFFFFFFF, all bits set), or 0xFFFFFFFF
+pub fn sentinel_filetime(value: u64) -> bool {
+    value >= i64::MAX as u64 || value == u64::from(u32::MAX)
+}
+
+// FILETIME (100ns since 1601-01-01) => UTC, 0 & sentinels stay unset (epoch 0) as in bodyfiles
+pub fn filetime(value: u64) -> DateTime<Utc> {
+    if value == 0 || sentinel_filetime(value) {
+        return DateTime::default();
+    }
+    let seconds = (value / 10_000_000) as i64 - 11_644_473_600;
+    let nanos = (value % 10_000_000) as u32 * 100;
+    DateTime::from_timestamp(seconds, nanos).unwrap_or_default()
+}
+
+pub(crate) fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+pub(crate) fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+pub(crate) fn u64_at(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8).map(|b| u64::from_le_bytes(b.try_into().expect("8 bytes")))
+}
+
+pub(crate) fn utf16_at(data: &[u8], offset: usize, chars: usize) -> Option<String> {
+    let bytes = data.get(offset..offset + chars * 2)?;
+    let units : Vec<u16> = bytes.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+#[derive(Debug, Default)]
+struct Record {
+    entry: u64,
+    sequence: u16,
+    in_use: bool,
+    name: Option<(u8, String)>, // (namespace, name)
+    parent: u64,
+    size: u64,
+    times: [u64; 4] // $SI: created, modified, MFT changed, accessed
+}
+
+// apply the update sequence array: the last 2 bytes of each sector are stored in the USA
+fn fixup(record: &mut [u8]) -> Option<()> {
+    let offset = u16_at(record, 4)? as usize;
+    let count = u16_at(record, 6)? as usize;
+    let usn = u16_at(record, offset)?;
+    for i in 1..count {
+        let end = i * 512 - 2;
+        if u16_at(record, end)? != usn {
+            return None; // torn write
+        }
+        let value = record.get(offset + i * 2..offset + i * 2 + 2)?.to_vec();
+        record.get_mut(end..end + 2)?.copy_from_slice(&value);
+    }
+    Some(())
+}
+
+fn parse_record(entry: u64, data: &mut [u8]) -> Option<Record> {
+    if data.get(0..4)? != b"FILE" {
+        return None;
+    }
+    fixup(data)?;
+    if u64_at(data, 0x20)? & 0xFFFF_FFFF_FFFF != 0 {
+        return None; // extension record, attributes belong to the base record
+    }
+
+    let flags = u16_at(data, 0x16)?;
+    let mut record = Record {
+        entry,
+        sequence: u16_at(data, 0x10)?,
+        in_use: flags & 0x1 != 0,
+        ..Record::default()
+    };
+
+    let mut offset = u16_at(data, 0x14)? as usize;
+    while let Some(kind) = u32_at(data, offset) {
+        let length = u32_at(data, offset + 4)? as usize;
+        if kind == ATTRIBUTE_END || length == 0 {
+            break;
+        }
+        let non_resident = *data.get(offset + 8)? != 0;
+        let name_length = *data.get(offset + 9)?;
+        let content = offset + u16_at(data, offset + 0x14)? as usize;
+
+        match (kind, non_resident) {
+            (ATTRIBUTE_STANDARD_INFORMATION, false) => {
+                for (i, time) in record.times.iter_mut().enumerate() {
+                    *time = u64_at(data, content + i * 8)?;
+                }
+            }
+            (ATTRIBUTE_FILE_NAME, false) => {
+                let namespace = *data.get(content + 65)?;
+                let name = utf16_at(data, content + 66, *data.get(content + 64)? as usize)?;
+                // prefer the long (Win32/POSIX) name over the DOS 8.3 one
+                if record.name.as_ref().is_none_or(|(ns, _)| *ns == NAMESPACE_DOS) {
+                    record.parent = u64_at(data, content)? & 0xFFFF_FFFF_FFFF;
+                    record.name = Some((namespace, name));
+                }
+            }
+            (ATTRIBUTE_DATA, false) if name_length == 0 => record.size = u32_at(data, offset + 0x10)? as u64,
+            (ATTRIBUTE_DATA, true) if name_length == 0 => record.size = u64_at(data, offset + 0x30)?,
+            _ => {}
+        }
+        offset += length;
+    }
+
+    Some(record)
+}
+
+pub struct Mft {
+    records: HashMap<u64, Record>
+}
+
+impl Mft {
+    pub fn from_path(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut data = std::fs::read(path)?;
+        // record size from the first record ($MFT itself), 1024 bytes otherwise
+        let record_size = match u32_at(&data, 0x1C) {
+            Some(size) if data.starts_with(b"FILE") && size.is_power_of_two() && size >= 512 => size as usize,
+            _ => 1024
+        };
+
+        let mut records = HashMap::new();
+        for (entry, chunk) in data.chunks_exact_mut(record_size).enumerate() {
+            if let Some(record) = parse_record(entry as u64, chunk) {
+                records.insert(entry as u64, record);
+            }
+        }
+        Ok(Self { records })
+    }
+
+    // full path of an entry, resolved through the parent references ("/Windows/System32/...")
+    pub fn path(&self, entry: u64) -> Option<String> {
+        let mut components = vec![];
+        let mut current = entry;
+        while current != ROOT_ENTRY {
+            let record = self.records.get(&current)?;
+            let (_, name) = record.name.as_ref()?;
+            components.push(name.as_str());
+            if components.len() > 255 || record.parent == current {
+                return None; // loop in a corrupted MFT
+            }
+            current = record.parent;
+        }
+        components.reverse();
+        Some(format!("/{}", components.join("/")))
+    }
+
+    pub fn entries(&self) -> Vec<BodyFileEntry> {
+        let mut records : Vec<&Record> = self.records.values().filter(|r| r.name.is_some()).collect();
+        records.sort_by_key(|r| r.entry);
+        records.into_iter()
+            .map(|r| {
+                let mut name = self.path(r.entry)
+                    .unwrap_or_else(|| format!("$OrphanFiles/{}", r.name.as_ref().map(|(_, n)| n.as_str()).unwrap_or_default()));
+                if !r.in_use {
+                    name.push_str(" (deleted)"); // same marker as fls
+                }
+                BodyFileEntry {
+                    md5: "0".to_string(),
+                    name,
+                    meta: format!("{}-{}", r.entry, r.sequence),
+                    mode: String::new(),
+                    uid: String::new(),
+                    gid: String::new(),
+                    size: r.size,
+                    crtime: filetime(r.times[0]),
+                    mtime: filetime(r.times[1]),
+                    ctime: filetime(r.times[2]),
+                    atime: filetime(r.times[3]),
+                    extra: vec![],
+                    source: SourceType::Mft,
+                    raw: None,
+                    line: None,
+                    invalid: false,
+                    unset: [MACB::BIRTH, MACB::MODIFIED, MACB::CHANGED, MACB::ACCESSED].into_iter().zip(r.times)
+                        .filter(|(_, t)| sentinel_filetime(*t))
+                        .fold(MACB::empty(), |unset, (flag, _)| unset | flag)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filetime_sentinels_are_unset() {
+        assert_eq!(filetime(0), DateTime::<Utc>::default());
+        assert_eq!(filetime(0x7FFF_FFFF_FFFF_FFFF), DateTime::<Utc>::default());
+        assert_eq!(filetime(u64::MAX), DateTime::<Utc>::default());
+        assert_eq!(filetime(132_398_000_000_000_000).timestamp(), 1_595_326_400);
+    }
+}
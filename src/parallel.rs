@@ -0,0 +1,31 @@
+use std::{sync::Mutex, thread};
+
+/*
+Per-file workers (--threads): inputs are parsed concurrently, each worker taking the next input once done with its own,
+so that a few large inputs don't hold the small ones back. Results are returned in input order, the merged timeline &
+run report being the same whatever the number of threads.
+*/
+
+// threads to use when --threads is not given
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+pub fn map<T: Send, R: Send>(items: Vec<T>, threads: usize, f: impl Fn(T) -> R + Sync) -> Vec<R> {
+    let count = items.len();
+    let queue = Mutex::new(items.into_iter().enumerate());
+    let results = Mutex::new(Vec::with_capacity(count));
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, count.max(1)) {
+            scope.spawn(|| loop {
+                let Some((i, item)) = queue.lock().expect("queue lock").next() else { break };
+                let result = f(item);
+                results.lock().expect("results lock").push((i, result));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().expect("results lock");
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
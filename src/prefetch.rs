@@ -0,0 +1,171 @@
+use std::{path::Path, error::Error};
+
+use crate::bodyfile::{BodyFile, DateFilter, TimestampDesc, TimestampEntry, SourceType};
+use crate::mft::{self, u16_at, u32_at, u64_at, utf16_at};
+
+/* Prefetch parser (versions 17 to 30): executable name & last run times, Windows 10 MAM (Xpress Huffman) files are decompressed first */
+
+const MAX_CODE_LENGTH: usize = 15;
+const BLOCK_SIZE: usize = 65536;
+
+// MS-XCA LZ77+Huffman decompression
+fn decompress_huffman(input: &[u8], size: usize) -> Option<Vec<u8>> {
+    // the size is read from the file: grown block by block
+    let mut output = Vec::with_capacity(size.min(BLOCK_SIZE));
+    let mut position = 0;
+
+    while output.len() < size {
+        // 256 bytes of 4 bits code lengths for 512 symbols, then a canonical Huffman code
+        let table = input.get(position..position + 256)?;
+        let lengths : Vec<usize> = (0..512).map(|i| ((table[i / 2] >> (4 * (i % 2))) & 0x0F) as usize).collect();
+        let mut decoding = vec![(0u16, 0u8); 1 << MAX_CODE_LENGTH];
+        let mut code = 0usize;
+        for length in 1..=MAX_CODE_LENGTH {
+            for (symbol, _) in lengths.iter().enumerate().filter(|(_, &l)| l == length) {
+                let span = 1 << (MAX_CODE_LENGTH - length);
+                decoding.get_mut(code..code + span)?.fill((symbol as u16, length as u8));
+                code += span;
+            }
+        }
+        position += 256;
+
+        let read_u16 = |position: &mut usize| -> Option<u32> {
+            let value = u16_at(input, *position).map(|v| v as u32);
+            *position += 2;
+            value
+        };
+        let mut bits = (read_u16(&mut position)? << 16) | read_u16(&mut position)?;
+        let mut extra = 16i32;
+
+        let block_end = (output.len() + BLOCK_SIZE).min(size);
+        while output.len() < block_end {
+            let (symbol, length) = decoding[(bits >> (32 - MAX_CODE_LENGTH)) as usize];
+            if length == 0 {
+                return None; // invalid code
+            }
+            bits <<= length;
+            extra -= length as i32;
+            if extra < 0 {
+                bits |= read_u16(&mut position).unwrap_or_default() << -extra;
+                extra += 16;
+            }
+
+            if symbol < 256 {
+                output.push(symbol as u8);
+                continue;
+            }
+
+            let symbol = symbol as usize - 256;
+            let mut match_length = symbol % 16;
+            let offset_bits = (symbol / 16) as u32;
+            if match_length == 15 {
+                match_length = *input.get(position)? as usize;
+                position += 1;
+                if match_length == 255 {
+                    match_length = u16_at(input, position)? as usize;
+                    position += 2;
+                    match_length = match_length.checked_sub(15)?;
+                }
+                match_length += 15;
+            }
+            match_length += 3;
+
+            let offset = if offset_bits == 0 { 1 } else { ((bits >> (32 - offset_bits)) | (1 << offset_bits)) as usize };
+            if offset_bits > 0 {
+                bits = bits.checked_shl(offset_bits).unwrap_or_default();
+                extra -= offset_bits as i32;
+                if extra < 0 {
+                    bits |= read_u16(&mut position).unwrap_or_default() << -extra;
+                    extra += 16;
+                }
+            }
+
+            let start = output.len().checked_sub(offset)?;
+            for i in 0..match_length {
+                output.push(output[start + i]);
+            }
+        }
+    }
+
+    output.truncate(size);
+    Some(output)
+}
+
+// raw SCCA data, MAM\x04 files are decompressed
+fn scca(data: Vec<u8>) -> Result<Vec<u8>, String> {
+    if data.starts_with(b"MAM\x04") {
+        let size = u32_at(&data, 4).unwrap_or_default() as usize;
+        return data.get(8..).and_then(|input| decompress_huffman(input, size)).ok_or_else(|| "invalid MAM compressed data".to_string());
+    }
+    if data.starts_with(b"MAM") {
+        return Err("unsupported MAM compression".to_string());
+    }
+    Ok(data)
+}
+
+pub fn parse_prefetch(bodyfile: &mut BodyFile, path: &Path, filter: &Option<DateFilter>) -> Result<(), Box<dyn Error>> {
+    let data = scca(std::fs::read(path)?).map_err(|e| format!("{}: {e}", path.display()))?;
+    if data.get(4..8) != Some(b"SCCA") {
+        return Err(format!("{} is not a prefetch file", path.display()).into());
+    }
+
+    let version = u32_at(&data, 0).unwrap_or_default();
+    let (times, run_count) = match version {
+        17 => (vec![0x78], 0x90),
+        23 => (vec![0x80], 0x98),
+        // 8 last run times, the run count moved in some Windows 10 builds (file metrics at 0x128)
+        26 => ((0..8).map(|i| 0x80 + i * 8).collect(), 0xD0),
+        30 if u32_at(&data, 0x54) == Some(0x128) => ((0..8).map(|i| 0x80 + i * 8).collect(), 0xC8),
+        30 => ((0..8).map(|i| 0x80 + i * 8).collect(), 0xD0),
+        _ => return Err(format!("{}: unsupported prefetch version {version}", path.display()).into())
+    };
+
+    let executable = utf16_at(&data, 0x10, 30).unwrap_or_default();
+    let executable = executable.split('\0').next().unwrap_or_default().to_string();
+    let hash = u32_at(&data, 0x4C).unwrap_or_default();
+    let run_count = u32_at(&data, run_count).unwrap_or_default();
+
+    for offset in times {
+        let Some(timestamp) = u64_at(&data, offset).filter(|&t| t != 0 && !mft::sentinel_filetime(t)) else { continue };
+        let datetime = mft::filetime(timestamp);
+        if !DateFilter::in_range(filter, datetime) {
+            continue;
+        }
+
+        bodyfile.add_event(TimestampEntry {
+            datetime,
+            desc: TimestampDesc::Label(format!("Program Executed ({run_count} runs)")),
+            source: SourceType::Pf,
+            meta: format!("{hash:08X}").into(),
+            size: data.len() as u64,
+            filename: executable.as_str().into(),
+            md5: "0".into(),
+            mode: "".into(),
+            uid: "".into(),
+            gid: "".into(),
+            tags: vec![],
+            score: 0,
+            detection: None,
+            raw: None,
+            input: None,
+            host: None,
+            session: None,
+            line: None,
+            unset: Default::default()
+        }, false, false);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated_mam_files() {
+        assert!(scca(b"MAM\x04".to_vec()).is_err());
+        assert!(scca(b"MAM\x04\xFF\xFF\xFF\xFF".to_vec()).is_err());
+        assert_eq!(scca(b"\x1E\0\0\0SCCA".to_vec()), Ok(b"\x1E\0\0\0SCCA".to_vec()));
+    }
+}
@@ -0,0 +1,138 @@
+use std::{path::Path, error::Error, fs::File, io::Read, mem::size_of};
+
+use crate::archive;
+use crate::bodyfile::{BodyFileParser, BodyFileEntry, TimestampEntry, ParseOptions};
+
+/*
+Pre-flight check (--preflight): the first megabytes of each input are parsed with the options of the run, and the row
+counts & string lengths of this sample are scaled to the size of the input. The peak memory of the run is estimated
+from them: inputs being parsed (their content, decoded), the file & datetime records of all the inputs, and the extra
+buffers of the sorts & merges. The estimate is meant to stop a run bound to run out of memory (--max-memory) before it
+starts, not to be exact: rows filtered out or read several times are only accounted for as in the sample.
+*/
+const SAMPLE_SIZE: u64 = 4 << 20;
+const ALLOCATION: f64 = 16.0; // allocator overhead of each string
+const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+#[derive(Debug, Clone)]
+pub struct Estimate {
+    pub input: String,
+    pub bytes: u64,
+    pub lines: u64,
+    pub file_records: u64,
+    pub datetime_records: u64,
+    pub records_memory: u64 // file & datetime records, strings included
+}
+
+// beginning of the input, cut after its last complete line
+fn sample(input: &str) -> Result<(Vec<u8>, u64), Box<dyn Error>> {
+    let (mut bytes, size) = match archive::split(input) {
+        Some(_) => {
+            let content = archive::read(Path::new(input))?; // members are decompressed in memory anyway
+            let size = content.len() as u64;
+            (content, size)
+        }
+        None => {
+            let file = File::open(input)?;
+            let size = file.metadata()?.len();
+            let mut bytes = vec![];
+            file.take(SAMPLE_SIZE).read_to_end(&mut bytes)?;
+            (bytes, size)
+        }
+    };
+    if (bytes.len() as u64) < size {
+        bytes.truncate(bytes.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1));
+    }
+    Ok((bytes, size))
+}
+
+pub fn estimate(input: &str, options: &ParseOptions) -> Result<Estimate, Box<dyn Error>> {
+    if archive::is_stream(Path::new(input)) {
+        return Err("pipe or device, its content can only be read once, by the run".into());
+    }
+    let (bytes, size) = sample(input)?;
+    let sampled = bytes.len().max(1) as f64;
+    let lines = bytes.iter().filter(|b| **b == b'\n').count();
+    let bodyfile = BodyFileParser::build_bytes(bytes, options)?;
+
+    // strings of the sampled records: name, meta, MD5 & UID, kept by the record & shared by its datetime records
+    let entries = bodyfile.entries();
+    let strings = |e: &BodyFileEntry| (e.name.len() + e.meta.len() + e.md5.len() + e.mode.len() + e.uid.len() + e.gid.len()) as f64;
+    let record_strings = 4.0 * ALLOCATION + match entries.is_empty() {
+        true => bodyfile.events().map(|(filename, _)| filename.len() as f64 + 32.0).sum::<f64>() / bodyfile.datetime_len().max(1) as f64,
+        false => entries.iter().map(strings).sum::<f64>() / entries.len() as f64
+    };
+
+    let scale = size as f64 / sampled;
+    let file_records = (bodyfile.file_len() as f64 * scale) as u64;
+    let datetime_records = (bodyfile.datetime_len() as f64 * scale) as u64;
+    // rows of the other inputs (events, artifacts) have no record, their strings are their own
+    let shared = match entries.is_empty() {
+        true => datetime_records,
+        false => file_records
+    };
+    let records_memory = file_records as f64 * (size_of::<BodyFileEntry>() as f64 + record_strings)
+        + shared as f64 * record_strings
+        + datetime_records as f64 * size_of::<TimestampEntry>() as f64;
+    Ok(Estimate {
+        input: input.to_string(),
+        bytes: size,
+        lines: (lines as f64 * scale) as u64,
+        file_records,
+        datetime_records,
+        records_memory: records_memory as u64
+    })
+}
+
+// peak memory of a run over these inputs: `threads` inputs parsed at once, rows `sorted` (sort, dedup, ...) or not
+pub fn peak_memory(estimates: &[Estimate], threads: usize, sorted: bool) -> u64 {
+    let records : u64 = estimates.iter().map(|e| e.records_memory).sum();
+    let rows : u64 = estimates.iter().map(|e| e.datetime_records).sum();
+    let row_size = size_of::<TimestampEntry>() as u64;
+
+    // content of the inputs being parsed (bytes read, then decoded), until their rows are sorted
+    let mut sizes : Vec<u64> = estimates.iter().map(|e| e.bytes).collect();
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    let contents : u64 = sizes.iter().take(threads.max(1)).map(|s| s * 2).sum();
+
+    // merge of several inputs in a new timeline, sort buffer of half of the rows (dedup included, rows sorted in place)
+    let merge = match estimates.len() {
+        0 | 1 => 0,
+        _ => rows * row_size
+    };
+    let sort = match sorted {
+        true => rows * row_size / 2,
+        false => 0
+    };
+    records + contents + merge + sort
+}
+
+// 512M, 4G, 16GiB, ... (binary units)
+pub fn parse_size(arg: &str) -> Result<u64, String> {
+    let error = || format!("Invalid size '{arg}', expected <number>[K|M|G|T] (e.g. 16G)");
+    let (value, unit) = arg.split_at(arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len()));
+    let value : u64 = value.parse().map_err(|_| error())?;
+    let unit = unit.trim().to_uppercase();
+    let power = match unit.trim_end_matches("IB").trim_end_matches('B') {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return Err(error())
+    };
+    value.checked_mul(1 << (10 * power)).ok_or_else(error)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit])
+    }
+}
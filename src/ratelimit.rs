@@ -0,0 +1,68 @@
+use std::{error::Error, time::{Duration, Instant}};
+
+use crate::sink::{Event, OutputSink};
+
+/*
+Rate limiting of the network outputs (--rate-limit): rows are written at most at the given rate, smoothed by a token
+bucket holding one second of rows, so that bulk-loading a 100M-row timeline doesn't take down a shared SIEM cluster.
+Bursts stay within the bucket, then each row waits for its token.
+*/
+pub struct TokenBucket {
+    rate: f64, // tokens per second, also the capacity of the bucket
+    tokens: f64,
+    last: Instant
+}
+
+impl TokenBucket {
+    pub fn new(rate: u64) -> Self {
+        Self { rate: rate as f64, tokens: rate as f64, last: Instant::now() }
+    }
+
+    // waits until a token is available, then takes it
+    pub fn take(&mut self) {
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.last).as_secs_f64() * self.rate).min(self.rate);
+        self.last = now;
+        if self.tokens < 1.0 {
+            std::thread::sleep(Duration::from_secs_f64((1.0 - self.tokens) / self.rate));
+            self.tokens = 1.0;
+            self.last = Instant::now();
+        }
+        self.tokens -= 1.0;
+    }
+}
+
+// rows of the wrapped sink written at most at `rate` per second
+pub struct RateLimitedSink {
+    sink: Box<dyn OutputSink>,
+    bucket: TokenBucket
+}
+
+impl RateLimitedSink {
+    pub fn new(sink: Box<dyn OutputSink>, rate: u64) -> Self {
+        Self { sink, bucket: TokenBucket::new(rate) }
+    }
+}
+
+impl OutputSink for RateLimitedSink {
+    fn write_header(&mut self, columns: &[&str], metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        self.sink.write_header(columns, metadata)
+    }
+
+    fn write_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        self.bucket.take();
+        self.sink.write_event(event)
+    }
+
+    fn write_section(&mut self, name: &str, columns: &[&str]) -> Result<(), Box<dyn Error>> {
+        self.sink.write_section(name, columns)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sink.flush()
+    }
+
+    fn finish(&mut self, partial: bool) -> Result<(), Box<dyn Error>> {
+        self.sink.finish(partial)
+    }
+}
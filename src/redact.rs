@@ -0,0 +1,84 @@
+use std::{borrow::Cow, fmt};
+use regex::Regex;
+
+/*
+Redaction rules (--redact): `column:regex=replacement` rewrites applied to the output rows only, so that sensitive
+patterns (user names, patient IDs in paths, ...) are masked per engagement while the analysis still runs on the real values.
+The replacement can use the regex groups ($1, ${name}).
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedactColumn {
+    FileName,
+    Meta,
+    Raw
+}
+
+impl fmt::Display for RedactColumn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            RedactColumn::FileName => "filename",
+            RedactColumn::Meta => "meta",
+            RedactColumn::Raw => "raw"
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::str::FromStr for RedactColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "filename" => Ok(RedactColumn::FileName),
+            "meta" => Ok(RedactColumn::Meta),
+            "raw" => Ok(RedactColumn::Raw),
+            _ => Err(format!("Unknown redacted column '{s}' (filename, meta, raw)"))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Redaction {
+    pub column: RedactColumn,
+    regex: Regex,
+    replacement: String
+}
+
+impl Redaction {
+    // rules are applied in order, each one to the output of the previous one
+    pub fn apply<'a>(redactions: &[Redaction], column: RedactColumn, value: &'a str) -> Cow<'a, str> {
+        let mut value = Cow::Borrowed(value);
+        for redaction in redactions.iter().filter(|r| r.column == column) {
+            let redacted = match redaction.regex.replace_all(&value, redaction.replacement.as_str()) {
+                Cow::Owned(redacted) => Some(redacted),
+                Cow::Borrowed(_) => None // no match
+            };
+            if let Some(redacted) = redacted {
+                value = Cow::Owned(redacted);
+            }
+        }
+        value
+    }
+}
+
+impl fmt::Display for Redaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}={}", self.column, self.regex, self.replacement)
+    }
+}
+
+impl std::str::FromStr for Redaction {
+    type Err = String;
+
+    // the replacement is after the last '='
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Invalid redaction '{s}', expected <column>:<regex>=<replacement>");
+        let (column, rule) = s.split_once(':').ok_or_else(error)?;
+        let (pattern, replacement) = rule.rsplit_once('=').ok_or_else(error)?;
+        Ok(Self {
+            column: column.parse()?,
+            regex: Regex::new(pattern).map_err(|e| format!("Invalid redaction regex '{pattern}': {e}"))?,
+            replacement: replacement.to_string()
+        })
+    }
+}
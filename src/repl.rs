@@ -0,0 +1,158 @@
+use std::{path::Path, error::Error, io::{BufRead, Write}};
+use chrono::Utc;
+
+use crate::bodyfile::{BodyFile, OutputFormat};
+use crate::filterset::{self, FilterSet, SavedRules};
+use crate::schema;
+use crate::version;
+
+/*
+Interactive filtering (mactime repl): the inputs are parsed once, then filters are set & cleared at a prompt, each change
+being applied to a copy of the parsed timeline so that its row count is known at once. Rows are previewed, and the
+filtered timeline is exported (or its filters saved for --load-filter) once satisfied, instead of a full run per try.
+*/
+const HELP: &str = "\
+filter <start>..<end>       keep the rows of this date range (YYYY-MM-DD[THH:MM[:SS]], one bound can be left out)
+years <first>..<last>       drop the rows dated outside this range of plausible years
+source <types>              keep the rows of these sources (comma separated): FILE, MFT, USNJRNL, EVTX, REG, PF
+include <keywords>          keep the rows whose file name contains one of these keywords (comma separated)
+exclude <keywords>          drop the rows whose file name contains one of these keywords (comma separated)
+ignore-case [on|off]        case-insensitive keywords & rules
+user <name>[:<uid>]         keep the activity of a user
+rules <file>                tag the rows matching the rules file (tag|weight|regex per line)
+min-score <score>           keep the rows whose score is at least this value
+clear [<filter>|all]        remove a filter (filter, years, source, include, exclude, ignore-case, user, rules, min-score)
+filters                     print the active filters
+count                       print the number of rows
+show [<rows>]               print the first rows in CSV [default: 10]
+export <file> [<format>]    write the filtered timeline to file: csv, json, jsonl or binary [default: csv]
+save <file>                 save the active filters (--load-filter)
+load <file>                 apply the filters of a file written by save or --save-filter
+quit                        leave, as Ctrl-D";
+
+pub struct Repl {
+    inputs: Vec<String>,
+    timeline: BodyFile, // rows of the inputs, never filtered
+    filters: FilterSet,
+    view: BodyFile // rows kept by `filters`
+}
+
+// filters at the start & after `clear`
+fn no_filters() -> FilterSet {
+    FilterSet { version: filterset::VERSION, ..FilterSet::default() }
+}
+
+// keywords, source types
+fn list(argument: &str) -> Vec<String> {
+    argument.split(',').map(str::trim).filter(|a| !a.is_empty()).map(String::from).collect()
+}
+
+impl Repl {
+    pub fn new(timeline: BodyFile, inputs: &[&str]) -> Self {
+        Self { inputs: inputs.iter().map(|i| i.to_string()).collect(), view: timeline.clone(), timeline, filters: no_filters() }
+    }
+
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> Result<(), Box<dyn Error>> {
+        writeln!(output, "{} datetime records, `help` for the commands", self.view.datetime_len())?;
+        write!(output, "mactime> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let (command, argument) = line.trim().split_once(' ').map_or((line.trim(), ""), |(c, a)| (c, a.trim()));
+            match command {
+                "" => {}
+                "quit" | "exit" => return Ok(()),
+                _ => if let Err(e) = self.execute(command, argument, &mut output) {
+                    writeln!(output, "Error: {e}")?;
+                }
+            }
+            write!(output, "mactime> ")?;
+            output.flush()?;
+        }
+        writeln!(output)?;
+        Ok(())
+    }
+
+    fn execute(&mut self, command: &str, argument: &str, output: &mut impl Write) -> Result<(), Box<dyn Error>> {
+        let required = || match argument {
+            "" => Err(format!("{command}: argument expected, `help` for the commands")),
+            _ => Ok(argument)
+        };
+        let mut filters = self.filters.clone();
+        match command {
+            "help" => return Ok(writeln!(output, "{HELP}")?),
+            "filters" => return Ok(writeln!(output, "{}", serde_json::to_string_pretty(&self.filters)?)?),
+            "count" => return Ok(writeln!(output, "{} datetime records", self.view.datetime_len())?),
+            "show" => {
+                let rows = match argument {
+                    "" => 10,
+                    rows => rows.parse()?
+                };
+                return self.view.preview(output, rows);
+            }
+            "export" => return self.export(required()?, output),
+            "save" => {
+                self.filters.write(Path::new(required()?))?;
+                return Ok(writeln!(output, "Filters saved to {argument}")?);
+            }
+            "load" => filters = FilterSet::read(Path::new(required()?))?,
+            "filter" => filters.filter = Some(required()?.to_string()),
+            "years" => filters.valid_years = Some(required()?.to_string()),
+            "source" => filters.source_type = Some(list(required()?)),
+            "include" => filters.include.extend(list(required()?)),
+            "exclude" => filters.exclude.extend(list(required()?)),
+            "ignore-case" => filters.ignore_case = argument != "off",
+            "user" => filters.user = Some(required()?.to_string()),
+            "rules" => filters.rules = Some(SavedRules { path: required()?.to_string(), content: std::fs::read_to_string(argument)? }),
+            "min-score" => filters.min_score = Some(required()?.parse()?),
+            "clear" => match argument {
+                "" | "all" => filters = no_filters(),
+                "filter" => filters.filter = None,
+                "years" => filters.valid_years = None,
+                "source" => filters.source_type = None,
+                "include" => filters.include.clear(),
+                "exclude" => filters.exclude.clear(),
+                "ignore-case" => filters.ignore_case = false,
+                "user" => filters.user = None,
+                "rules" => filters.rules = None,
+                "min-score" => filters.min_score = None,
+                _ => return Err(format!("Unknown filter '{argument}', `help` for the commands").into())
+            },
+            _ => return Err(format!("Unknown command '{command}', `help` for the commands").into())
+        }
+
+        // the new filters are kept only once applied without error
+        let mut view = self.timeline.clone();
+        filters.apply(&mut view)?;
+        self.filters = filters;
+        self.view = view;
+        writeln!(output, "{} datetime records", self.view.datetime_len())?;
+        Ok(())
+    }
+
+    // export <file> [<format>]
+    fn export(&self, argument: &str, output: &mut impl Write) -> Result<(), Box<dyn Error>> {
+        let (path, format) = match argument.rsplit_once(' ').map(|(path, format)| (path.trim(), format.parse::<OutputFormat>())) {
+            Some((path, Ok(format))) => (path, format),
+            _ => (argument, OutputFormat::Csv) // file name with spaces
+        };
+        if Path::new(path).exists() {
+            return Err(format!("Output file {path} already exists").into());
+        }
+        let metadata = serde_json::json!({
+            "tool": "mactime",
+            "version": env!("CARGO_PKG_VERSION"),
+            "schema_version": schema::VERSION,
+            "build": version::build_info(),
+            "input": self.inputs,
+            "input_format": "repl",
+            "datetime_records": self.view.datetime_len(),
+            "filters": self.filters,
+            "timezone": "UTC",
+            "generated": Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string()
+        });
+        self.view.generate(Some(Path::new(path)), format, &metadata)?;
+        writeln!(output, "{} datetime records written to {path}", self.view.datetime_len())?;
+        Ok(())
+    }
+}
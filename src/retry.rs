@@ -0,0 +1,94 @@
+use std::{path::Path, error::Error, fmt, io, time::Duration};
+
+use crate::archive;
+
+/*
+Transient I/O errors: inputs read from network shares or S3 mounts fail now and then (timeouts, dropped connections)
+while the same read succeeds a few seconds later. Reads are retried (--io-retries) after a delay doubled at each attempt
+(--io-retry-delay), and an input still unreadable can be skipped (--on-unreadable skip) rather than aborting a multi-hour
+multi-input run, the error being recorded in the run metadata. Errors that a retry can't fix (missing file, access
+denied) are not retried.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IoRetry {
+    pub retries: u32,
+    pub delay: Duration // before the first retry, doubled at each attempt
+}
+
+// input that could not be read, once retried
+#[derive(Debug)]
+pub struct Unreadable {
+    pub input: String,
+    pub attempts: u32,
+    pub error: io::Error
+}
+
+impl fmt::Display for Unreadable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.attempts {
+            1 => write!(f, "{}: {}", self.input, self.error),
+            attempts => write!(f, "{}: {} ({attempts} attempts)", self.input, self.error)
+        }
+    }
+}
+
+impl Error for Unreadable {}
+
+fn transient(error: &io::Error) -> bool {
+    !matches!(error.kind(), io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied | io::ErrorKind::InvalidInput
+        | io::ErrorKind::InvalidData | io::ErrorKind::IsADirectory | io::ErrorKind::Unsupported)
+}
+
+impl IoRetry {
+    // content of a file or of an archive member, I/O errors retried
+    pub fn read(&self, path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let error = match archive::read(path) {
+                Ok(content) => return Ok(content),
+                Err(e) => match e.downcast::<io::Error>() {
+                    Ok(error) => *error,
+                    Err(e) => return Err(e) // not an I/O error (corrupted archive, ...)
+                }
+            };
+            // the content of a pipe read before the error is gone, it can't be read again
+            if attempts > self.retries || !transient(&error) || archive::is_stream(path) {
+                return Err(Box::new(Unreadable { input: path.display().to_string(), attempts, error }));
+            }
+            let delay = self.delay * 2u32.saturating_pow(attempts - 1);
+            eprintln!("Reading {} failed ({error}), retry {attempts}/{} in {}s", path.display(), self.retries, delay.as_secs_f64());
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+// what happens to an input that can't be read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnUnreadable {
+    #[default]
+    Abort, // run stopped
+    Skip // input left out, error recorded
+}
+
+impl fmt::Display for OnUnreadable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            OnUnreadable::Abort => "abort",
+            OnUnreadable::Skip => "skip"
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::str::FromStr for OnUnreadable {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "abort" => Ok(OnUnreadable::Abort),
+            "skip" => Ok(OnUnreadable::Skip),
+            _ => Err(format!("Unknown unreadable input policy '{s}'"))
+        }
+    }
+}
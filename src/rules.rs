@@ -0,0 +1,79 @@
+use std::{path::Path, error::Error};
+
+use crate::matcher::RegexMatcher;
+
+/*
+Tagging rules: one rule per line, `tag|weight|regex`, the regex being matched against the filename.
+The score of a row is the sum of the weights of the rules (and IOCs) it matches.
+*/
+#[derive(Debug)]
+pub struct Rule {
+    pub tag: String,
+    pub weight: u32,
+    pattern: String,
+    pub hits: usize
+}
+
+impl Rule {
+    fn parse(line: &str) -> Result<Self, Box<dyn Error>> {
+        let fields : Vec<&str> = line.splitn(3, '|').collect();
+        if fields.len() != 3 {
+            return Err(format!("Invalid rule '{line}', expected tag|weight|regex").into());
+        }
+
+        Ok(Self {
+            tag: fields[0].trim().to_string(),
+            weight: fields[1].trim().parse().map_err(|e| format!("Invalid weight in rule '{line}': {e}"))?,
+            pattern: fields[2].to_string(),
+            hits: 0
+        })
+    }
+}
+
+// the regexes of all rules are evaluated at once
+#[derive(Debug)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    matcher: RegexMatcher
+}
+
+impl RuleSet {
+    pub fn from_path(path: &Path, ignore_case: bool) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&std::fs::read_to_string(path)?, ignore_case)
+    }
+
+    // content of a rules file (--load-filter)
+    pub fn parse(content: &str, ignore_case: bool) -> Result<Self, Box<dyn Error>> {
+        let rules = content.lines()
+            .filter(|l| !l.trim().is_empty() && !l.starts_with('#')) // skip blank lines & comments
+            .map(Rule::parse)
+            .collect::<Result<Vec<Rule>, Box<dyn Error>>>()?;
+        let matcher = RegexMatcher::new(rules.iter().map(|r| r.pattern.as_str()), ignore_case)?;
+        Ok(Self { rules, matcher })
+    }
+
+    // indices of the rules matching a filename, hit counters are updated: called once per file, not per row
+    pub fn check(&mut self, filename: &str) -> Vec<usize> {
+        let matched = self.matcher.matches(filename);
+        for &i in matched.iter() {
+            self.rules[i].hits += 1;
+        }
+        matched
+    }
+
+    pub fn get(&self, index: usize) -> &Rule {
+        &self.rules[index]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
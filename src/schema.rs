@@ -0,0 +1,204 @@
+use std::fmt;
+use serde_json::{json, Value};
+
+/*
+Event schema of the CSV/JSON outputs (mactime schema), so that ingestion pipelines can check their compatibility.
+The version is written to the JSON metadata: minor bump when an optional column is added, major bump when a column changes or goes away.
+Compatibility levels (--schema) keep the layout of existing consumers: v1 is the historical layout, the 5 base columns and
+the ones of the options of the run, v2 adds the columns of new deployments (SourceType, MD5 & UID) whatever the inputs, v3 the full column set of the
+Sleuthkit's mactime (Mode & GID too).
+*/
+pub const VERSION : &str = "1.6";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Timestamp, // UTC, CSV "YYYY-MM-DD HH:MM:SS[.f]", JSON RFC 3339
+    String,
+    UInt64,
+    StringList, // CSV ';' separated
+    LocalTimes // CSV one column per zone, JSON object of the RFC 3339 local times by zone name
+}
+
+#[derive(Debug)]
+pub struct Column {
+    pub name: &'static str, // CSV header
+    pub key: &'static str, // JSON event key
+    pub kind: Kind,
+    pub nullable: bool,
+    pub option: Option<&'static str>, // what adds the column, always present if none
+    pub description: &'static str
+}
+
+// output order
+pub const COLUMNS : &[Column] = &[
+    Column { name: "Datetime", key: "datetime", kind: Kind::Timestamp, nullable: false, option: None, description: "Time of the event, UTC" },
+    Column { name: "Datetime (<zone>)", key: "local_datetimes", kind: Kind::LocalTimes, nullable: false, option: Some("--extra-tz"), description: "Time of the event in the local time of each zone" },
+    Column { name: "MACB", key: "macb", kind: Kind::String, nullable: false, option: None, description: "MACB flags of a filesystem timestamp (\"m.c.\"), or label of another timestamp (\"Program Executed\", ...)" },
+    Column { name: "Meta", key: "meta", kind: Kind::String, nullable: false, option: None, description: "Meta address (inode, MFT entry-sequence)" },
+    Column { name: "Size", key: "size", kind: Kind::UInt64, nullable: false, option: None, description: "File size in bytes" },
+    Column { name: "FileName", key: "filename", kind: Kind::String, nullable: false, option: None, description: "Full path of the file, or description of the event" },
+    Column { name: "MD5", key: "md5", kind: Kind::String, nullable: false, option: Some("--schema v2, v3"), description: "MD5 of the file, \"0\" if not computed" },
+    Column { name: "Mode", key: "mode", kind: Kind::String, nullable: false, option: Some("--schema v3"), description: "Type & permissions of the file (\"r/rrwxr-xr-x\"), empty when unknown" },
+    Column { name: "UID", key: "uid", kind: Kind::String, nullable: false, option: Some("--schema v2, v3"), description: "Owner of the file (UID, or user name with --passwd), empty when unknown" },
+    Column { name: "GID", key: "gid", kind: Kind::String, nullable: false, option: Some("--schema v3"), description: "Group of the file (GID, or group name with --group), empty when unknown" },
+    Column { name: "Unset", key: "unset", kind: Kind::String, nullable: false, option: Some("--schema v3, --on-invalid-timestamp flag"), description: "MACB flags of the timestamps of the record set to a sentinel by the source (0xFFFFFFFF, ...), which have no row, empty if none" },
+    Column { name: "Tags", key: "tags", kind: Kind::StringList, nullable: false, option: Some("--rules, --ioc, --flag-invalid-years, --flag-dst, --on-invalid-timestamp flag"), description: "Tags of the matched rules/IOCs" },
+    Column { name: "Score", key: "score", kind: Kind::UInt64, nullable: false, option: Some("--rules, --ioc, --flag-invalid-years, --flag-dst, --on-invalid-timestamp flag"), description: "Sum of the weights of the matched rules/IOCs" },
+    Column { name: "Detection", key: "detection", kind: Kind::String, nullable: true, option: Some("--vt-lookup"), description: "VirusTotal detection ratio of the MD5, null when unknown" },
+    Column { name: "SourceType", key: "source_type", kind: Kind::String, nullable: false, option: Some("non-bodyfile inputs, --source-type, supertimeline, --schema v2, v3"), description: "Artifact of the event: FILE, MFT, USNJRNL, EVTX, REG or PF" },
+    Column { name: "Precision", key: "precision", kind: Kind::String, nullable: false, option: Some("--precision"), description: "Precision of the timestamp: second, sub-second or 100ns" },
+    Column { name: "Annotation", key: "annotation", kind: Kind::String, nullable: false, option: Some("--annotate"), description: "Labels of the annotated date ranges of the event, ';' separated" },
+    Column { name: "SessionId", key: "session_id", kind: Kind::UInt64, nullable: false, option: Some("--session-id"), description: "Session of the event: rows less than --session-gap apart, numbered from 1 in chronological order" },
+    Column { name: "SourceFile", key: "source_file", kind: Kind::String, nullable: true, option: Some("--provenance"), description: "Input file the event was read from" },
+    Column { name: "SourceLine", key: "source_line", kind: Kind::UInt64, nullable: true, option: Some("--provenance"), description: "Line of the input file the event was read from, null for binary artifacts ($MFT, EVTX, ...)" },
+    Column { name: "Raw", key: "raw", kind: Kind::String, nullable: true, option: Some("--include-raw"), description: "Source line/record of the event" }
+];
+
+// columns of the outputs (--schema), v1 until consumers opt into the new ones
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SchemaLevel {
+    #[default]
+    V1,
+    V2,
+    V3
+}
+
+impl fmt::Display for SchemaLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaLevel::V1 => write!(f, "v1"),
+            SchemaLevel::V2 => write!(f, "v2"),
+            SchemaLevel::V3 => write!(f, "v3")
+        }
+    }
+}
+
+impl std::str::FromStr for SchemaLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "v1" => Ok(SchemaLevel::V1),
+            "v2" => Ok(SchemaLevel::V2),
+            "v3" => Ok(SchemaLevel::V3),
+            _ => Err(format!("Unknown schema level '{s}'"))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaFormat {
+    JsonSchema,
+    Arrow
+}
+
+impl fmt::Display for SchemaFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaFormat::JsonSchema => write!(f, "json-schema"),
+            SchemaFormat::Arrow => write!(f, "arrow")
+        }
+    }
+}
+
+impl std::str::FromStr for SchemaFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json-schema" => Ok(SchemaFormat::JsonSchema),
+            "arrow" => Ok(SchemaFormat::Arrow),
+            _ => Err(format!("Unknown schema format '{s}'"))
+        }
+    }
+}
+
+pub fn schema(format: SchemaFormat) -> Value {
+    match format {
+        SchemaFormat::JsonSchema => json_schema(),
+        SchemaFormat::Arrow => arrow_schema()
+    }
+}
+
+fn description(column: &Column) -> String {
+    match column.option {
+        Some(option) => format!("{} (optional: {option})", column.description),
+        None => column.description.to_string()
+    }
+}
+
+// JSON Schema of the --format json document
+fn json_schema() -> Value {
+    let properties : serde_json::Map<String, Value> = COLUMNS.iter().map(|c| {
+        let mut property = match c.kind {
+            Kind::Timestamp => json!({"type": "string", "format": "date-time"}),
+            Kind::String => json!({"type": "string"}),
+            Kind::UInt64 => json!({"type": "integer", "minimum": 0}),
+            Kind::StringList => json!({"type": "array", "items": {"type": "string"}}),
+            Kind::LocalTimes => json!({"type": "object", "additionalProperties": {"type": "string", "format": "date-time"}})
+        };
+        if c.nullable {
+            property["type"] = json!([property["type"], "null"]);
+        }
+        property["description"] = json!(description(c));
+        (c.key.to_string(), property)
+    }).collect();
+    let required : Vec<&str> = COLUMNS.iter().filter(|c| c.option.is_none()).map(|c| c.key).collect();
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "mactime timeline",
+        "description": format!("mactime {} --format json output, event schema {VERSION}", env!("CARGO_PKG_VERSION")),
+        "type": "object",
+        "required": ["metadata", "events"],
+        "properties": {
+            "metadata": {
+                "type": "object",
+                "description": "Run information (tool, version, input, options, ...)",
+                "properties": {
+                    "schema_version": {"type": "string", "const": VERSION}
+                }
+            },
+            "events": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": required,
+                    "properties": properties
+                }
+            },
+            "partial": {
+                "type": "boolean",
+                "description": "Interrupted (Ctrl-C) while writing the events"
+            }
+        }
+    })
+}
+
+// Arrow schema of the events, in the JSON representation of the Arrow integration tests
+fn arrow_schema() -> Value {
+    let field = |name: &str, kind: Kind, nullable: bool| {
+        let (kind, children) = match kind {
+            Kind::Timestamp => (json!({"name": "timestamp", "unit": "NANOSECOND", "timezone": "UTC"}), json!([])),
+            Kind::String => (json!({"name": "utf8"}), json!([])),
+            Kind::UInt64 => (json!({"name": "int", "bitWidth": 64, "isSigned": false}), json!([])),
+            Kind::StringList => (json!({"name": "list"}), json!([{"name": "item", "nullable": false, "type": {"name": "utf8"}, "children": []}])),
+            Kind::LocalTimes => (json!({"name": "map", "keysSorted": false}), json!([{"name": "entries", "nullable": false, "type": {"name": "struct"}, "children": [
+                {"name": "key", "nullable": false, "type": {"name": "utf8"}, "children": []},
+                {"name": "value", "nullable": false, "type": {"name": "utf8"}, "children": []}
+            ]}]))
+        };
+        json!({"name": name, "nullable": nullable, "type": kind, "children": children})
+    };
+
+    let fields : Vec<Value> = COLUMNS.iter().map(|c| {
+        let mut field = field(c.key, c.kind, c.nullable);
+        field["metadata"] = json!([{"key": "description", "value": description(c)}]);
+        field
+    }).collect();
+
+    json!({
+        "fields": fields,
+        "metadata": [{"key": "schema_version", "value": VERSION}]
+    })
+}
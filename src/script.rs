@@ -0,0 +1,807 @@
+#![cfg_attr(not(feature = "scripting"), allow(dead_code))]
+use std::{error::Error, fmt, path::Path};
+use chrono::{DateTime, Timelike, Utc};
+use regex::Regex;
+
+/*
+Row scripts (--script, `scripting` feature): a small language run on each row of the timeline to filter, rewrite or
+tag it (path normalization, bespoke scoring, ...) until a needed feature lands natively. One statement per line, #
+comments:
+
+    filename = lower(replace(filename, "\\", "/"))       # fields: filename, meta, md5, uid, size
+    if size == 0 && filename ~ "/temp/" { drop }           # ~ matches a regex
+    if hour < 6 || hour > 20 { tag "off-hours", 10 }       # tag, optional weight added to the score
+    else if has_tag("invalid-year") { tag "check" }
+
+Readable fields: datetime ("2020-07-21 00:38:18"), timestamp, hour, macb, meta, size, filename, md5, uid, source,
+score. Values are strings, integers & booleans, a type error stops the run with the line of the statement. The grammar is
+specified in the README (Row scripts).
+*/
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool)
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Str(s) => write!(f, "{s:?}"),
+            Value::Int(i) => write!(f, "{i}"),
+            Value::Bool(b) => write!(f, "{b}")
+        }
+    }
+}
+
+impl Value {
+    fn str(self) -> Result<String, String> {
+        match self {
+            Value::Str(s) => Ok(s),
+            Value::Int(i) => Ok(i.to_string()),
+            other => Err(format!("expected a string, got {other}"))
+        }
+    }
+
+    fn int(self) -> Result<i64, String> {
+        match self {
+            Value::Int(i) => Ok(i),
+            other => Err(format!("expected an integer, got {other}"))
+        }
+    }
+
+    fn bool(self) -> Result<bool, String> {
+        match self {
+            Value::Bool(b) => Ok(b),
+            other => Err(format!("expected a boolean, got {other}"))
+        }
+    }
+}
+
+// fields of the row a script runs on, written back once it ran
+pub struct Row {
+    pub datetime: DateTime<Utc>,
+    pub macb: String,
+    pub meta: String,
+    pub size: u64,
+    pub filename: String,
+    pub md5: String,
+    pub uid: String,
+    pub source: String,
+    pub score: u64,
+    pub tags: Vec<String>,
+    pub added: Vec<(String, u32)>, // tags added by the script, with their weights
+    pub dropped: bool
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Datetime,
+    Timestamp,
+    Hour,
+    Macb,
+    Meta,
+    Size,
+    FileName,
+    Md5,
+    Uid,
+    Source,
+    Score
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "datetime" => Field::Datetime,
+            "timestamp" => Field::Timestamp,
+            "hour" => Field::Hour,
+            "macb" => Field::Macb,
+            "meta" => Field::Meta,
+            "size" => Field::Size,
+            "filename" => Field::FileName,
+            "md5" => Field::Md5,
+            "uid" => Field::Uid,
+            "source" => Field::Source,
+            "score" => Field::Score,
+            _ => return None
+        })
+    }
+
+    fn writable(&self) -> bool {
+        matches!(self, Field::Meta | Field::Size | Field::FileName | Field::Md5 | Field::Uid)
+    }
+
+    fn get(&self, row: &Row) -> Value {
+        match self {
+            Field::Datetime => Value::Str(row.datetime.format("%Y-%m-%d %H:%M:%S").to_string()),
+            Field::Timestamp => Value::Int(row.datetime.timestamp()),
+            Field::Hour => Value::Int(row.datetime.hour() as i64),
+            Field::Macb => Value::Str(row.macb.clone()),
+            Field::Meta => Value::Str(row.meta.clone()),
+            Field::Size => Value::Int(row.size as i64),
+            Field::FileName => Value::Str(row.filename.clone()),
+            Field::Md5 => Value::Str(row.md5.clone()),
+            Field::Uid => Value::Str(row.uid.clone()),
+            Field::Source => Value::Str(row.source.clone()),
+            Field::Score => Value::Int(row.score as i64)
+        }
+    }
+
+    fn set(&self, row: &mut Row, value: Value) -> Result<(), String> {
+        match self {
+            Field::Meta => row.meta = value.str()?,
+            Field::FileName => row.filename = value.str()?,
+            Field::Md5 => row.md5 = value.str()?,
+            Field::Uid => row.uid = value.str()?,
+            Field::Size => row.size = u64::try_from(value.int()?).map_err(|_| "negative size".to_string())?,
+            _ => unreachable!("read-only field checked by the parser")
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Lower,
+    Upper,
+    Replace,
+    Contains,
+    StartsWith,
+    EndsWith,
+    Len,
+    Basename,
+    Dirname,
+    HasTag
+}
+
+impl Function {
+    // function & its number of arguments
+    fn parse(name: &str) -> Option<(Self, usize)> {
+        Some(match name {
+            "lower" => (Function::Lower, 1),
+            "upper" => (Function::Upper, 1),
+            "replace" => (Function::Replace, 3),
+            "contains" => (Function::Contains, 2),
+            "starts_with" => (Function::StartsWith, 2),
+            "ends_with" => (Function::EndsWith, 2),
+            "len" => (Function::Len, 1),
+            "basename" => (Function::Basename, 1),
+            "dirname" => (Function::Dirname, 1),
+            "has_tag" => (Function::HasTag, 1),
+            _ => return None
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Expr {
+    Literal(Value),
+    Field(Field),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Match(Box<Expr>, Regex),
+    RegexReplace(Box<Expr>, Regex, Box<Expr>),
+    Call(Function, Vec<Expr>)
+}
+
+#[derive(Debug)]
+enum Statement {
+    Set(Field, Expr),
+    Tag(Expr, Option<Expr>),
+    Drop,
+    If(Vec<(Expr, Vec<Line>)>, Vec<Line>) // conditions & their statements, else
+}
+
+// statement & its line in the script
+#[derive(Debug)]
+struct Line(usize, Statement);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Symbol(&'static str),
+    Newline
+}
+
+// nested parentheses, blocks, unary & binary operators: a deeper script is refused rather than overflowing the stack
+const MAX_DEPTH: usize = 64;
+
+const SYMBOLS: [&str; 21] = ["==", "!=", "<=", ">=", "&&", "||", "(", ")", "{", "}", ",", "=", "<", ">", "~", "!", "+", "-", "*", "/", "%"];
+
+// tokens & their lines
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\n' | ';' => {
+                chars.next();
+                tokens.push((Token::Newline, line));
+                if c == '\n' {
+                    line += 1;
+                }
+            }
+            '#' => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => value.push(match chars.next() {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some(c @ ('\\' | '"')) => c,
+                            other => return Err(format!("line {line}: invalid escape {other:?}"))
+                        }),
+                        Some('\n') | None => return Err(format!("line {line}: unterminated string")),
+                        Some(c) => value.push(c)
+                    }
+                }
+                tokens.push((Token::Str(value), line));
+            }
+            c if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                    digits.push(c);
+                    chars.next();
+                }
+                tokens.push((Token::Int(digits.parse().map_err(|e| format!("line {line}: {e}"))?), line));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                    name.push(c);
+                    chars.next();
+                }
+                tokens.push((Token::Ident(name), line));
+            }
+            _ => {
+                let rest : String = chars.clone().take(2).collect();
+                let symbol = SYMBOLS.iter().find(|s| rest.starts_with(**s)).ok_or_else(|| format!("line {line}: unexpected '{c}'"))?;
+                for _ in 0..symbol.len() {
+                    chars.next();
+                }
+                tokens.push((Token::Symbol(symbol), line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    depth: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens.get(self.position).or(self.tokens.last()).map_or(1, |(_, l)| *l)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {message}", self.line())
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).map(|(t, _)| t.clone());
+        self.position += 1;
+        token
+    }
+
+    fn accept(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.accept(symbol) {
+            true => Ok(()),
+            false => Err(self.error(&format!("expected '{symbol}'")))
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(name)) if name == keyword) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    // `else` on the line of the closing brace, or on the next one
+    fn else_keyword(&mut self) -> bool {
+        let position = self.position;
+        self.skip_newlines();
+        if self.keyword("else") {
+            return true;
+        }
+        self.position = position;
+        false
+    }
+
+    // one more level of nesting, restored by the caller once parsed (a parse error ends the parse)
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        match self.depth > MAX_DEPTH {
+            true => Err(self.error(&format!("nested deeper than {MAX_DEPTH} levels"))),
+            false => Ok(())
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::Newline) {
+            self.position += 1;
+        }
+    }
+
+    // statements up to the end of the script, or of a block
+    fn statements(&mut self, block: bool) -> Result<Vec<Line>, String> {
+        let mut statements = vec![];
+        loop {
+            self.skip_newlines();
+            match self.peek() {
+                None if block => return Err(self.error("expected '}'")),
+                None => return Ok(statements),
+                Some(Token::Symbol("}")) if block => {
+                    self.position += 1;
+                    return Ok(statements);
+                }
+                _ => {}
+            }
+            let line = self.line();
+            statements.push(Line(line, self.statement()?));
+            if !matches!(self.peek(), None | Some(Token::Newline) | Some(Token::Symbol("}"))) {
+                return Err(self.error("expected the end of the statement"));
+            }
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Line>, String> {
+        self.expect("{")?;
+        let depth = self.depth;
+        self.enter()?;
+        let statements = self.statements(true)?;
+        self.depth = depth;
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        if self.keyword("drop") {
+            return Ok(Statement::Drop);
+        }
+        if self.keyword("tag") {
+            let tag = self.expression()?;
+            let weight = self.accept(",").then(|| self.expression()).transpose()?;
+            return Ok(Statement::Tag(tag, weight));
+        }
+        if self.keyword("if") {
+            let mut branches = vec![(self.expression()?, self.block()?)];
+            let mut otherwise = vec![];
+            while self.else_keyword() {
+                if self.keyword("if") {
+                    branches.push((self.expression()?, self.block()?));
+                } else {
+                    otherwise = self.block()?;
+                    break;
+                }
+            }
+            return Ok(Statement::If(branches, otherwise));
+        }
+        let line = self.line();
+        match self.next() {
+            Some(Token::Ident(name)) => {
+                let field = Field::parse(&name).ok_or_else(|| format!("line {line}: unknown field '{name}'"))?;
+                if !field.writable() {
+                    return Err(format!("line {line}: field '{name}' is read-only"));
+                }
+                self.expect("=")?;
+                Ok(Statement::Set(field, self.expression()?))
+            }
+            _ => Err(format!("line {line}: expected a statement (drop, tag, if, <field> = ...)"))
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        self.enter()?;
+        let expr = self.binary(0)?;
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    // operators by increasing precedence
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        const LEVELS: [&[(&str, Op)]; 5] = [
+            &[("||", Op::Or)],
+            &[("&&", Op::And)],
+            &[("==", Op::Eq), ("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge), ("<", Op::Lt), (">", Op::Gt)],
+            &[("+", Op::Add), ("-", Op::Sub)],
+            &[("*", Op::Mul), ("/", Op::Div), ("%", Op::Rem)]
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        // left associative: each operator nests the expression on its left
+        let depth = self.depth;
+        let mut left = self.binary(level + 1)?;
+        loop {
+            if level == 2 && self.accept("~") {
+                self.enter()?;
+                left = Expr::Match(Box::new(left), self.pattern()?);
+                continue;
+            }
+            let Some((_, op)) = LEVELS[level].iter().find(|(symbol, _)| matches!(self.peek(), Some(Token::Symbol(s)) if s == symbol)) else {
+                self.depth = depth;
+                return Ok(left);
+            };
+            self.position += 1;
+            self.enter()?;
+            left = Expr::Binary(*op, Box::new(left), Box::new(self.binary(level + 1)?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let expr = if self.accept("!") {
+            self.enter()?;
+            Expr::Not(Box::new(self.unary()?))
+        } else if self.accept("-") {
+            self.enter()?;
+            Expr::Neg(Box::new(self.unary()?))
+        } else {
+            self.primary()?
+        };
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    // regexes are string literals, compiled once
+    fn pattern(&mut self) -> Result<Regex, String> {
+        let line = self.line();
+        match self.next() {
+            Some(Token::Str(pattern)) => Regex::new(&pattern).map_err(|e| format!("line {line}: {e}")),
+            _ => Err(format!("line {line}: expected a regex string"))
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let line = self.line();
+        match self.next() {
+            Some(Token::Int(i)) => Ok(Expr::Literal(Value::Int(i))),
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::Str(s))),
+            Some(Token::Symbol("(")) => {
+                let expr = self.expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Ident(name)) if name == "true" || name == "false" => Ok(Expr::Literal(Value::Bool(name == "true"))),
+            Some(Token::Ident(name)) if name == "regex_replace" => {
+                self.expect("(")?;
+                let value = self.expression()?;
+                self.expect(",")?;
+                let pattern = self.pattern()?;
+                self.expect(",")?;
+                let replacement = self.expression()?;
+                self.expect(")")?;
+                Ok(Expr::RegexReplace(Box::new(value), pattern, Box::new(replacement)))
+            }
+            Some(Token::Ident(name)) => {
+                if let Some(field) = Field::parse(&name) {
+                    return Ok(Expr::Field(field));
+                }
+                let (function, arity) = Function::parse(&name).ok_or_else(|| format!("line {line}: unknown field or function '{name}'"))?;
+                self.expect("(")?;
+                let mut arguments = vec![];
+                while !self.accept(")") {
+                    if !arguments.is_empty() {
+                        self.expect(",")?;
+                    }
+                    arguments.push(self.expression()?);
+                }
+                if arguments.len() != arity {
+                    return Err(format!("line {line}: {name} takes {arity} argument(s)"));
+                }
+                Ok(Expr::Call(function, arguments))
+            }
+            _ => Err(format!("line {line}: expected a value"))
+        }
+    }
+}
+
+fn eval(expr: &Expr, row: &Row) -> Result<Value, String> {
+    Ok(match expr {
+        Expr::Literal(value) => value.clone(),
+        Expr::Field(field) => field.get(row),
+        Expr::Not(expr) => Value::Bool(!eval(expr, row)?.bool()?),
+        Expr::Neg(expr) => Value::Int(eval(expr, row)?.int()?.checked_neg().ok_or("integer overflow")?),
+        Expr::Binary(Op::And, left, right) => Value::Bool(eval(left, row)?.bool()? && eval(right, row)?.bool()?),
+        Expr::Binary(Op::Or, left, right) => Value::Bool(eval(left, row)?.bool()? || eval(right, row)?.bool()?),
+        Expr::Binary(op, left, right) => {
+            let (left, right) = (eval(left, row)?, eval(right, row)?);
+            let ordering = match (&left, &right) {
+                (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+                (Value::Str(a), Value::Str(b)) => Some(a.cmp(b)),
+                (Value::Bool(a), Value::Bool(b)) if matches!(op, Op::Eq | Op::Ne) => Some(a.cmp(b)),
+                _ => None
+            };
+            let compared = || ordering.ok_or_else(|| format!("can't compare {left} and {right}"));
+            match op {
+                Op::Eq => Value::Bool(compared()?.is_eq()),
+                Op::Ne => Value::Bool(compared()?.is_ne()),
+                Op::Lt => Value::Bool(compared()?.is_lt()),
+                Op::Le => Value::Bool(compared()?.is_le()),
+                Op::Gt => Value::Bool(compared()?.is_gt()),
+                Op::Ge => Value::Bool(compared()?.is_ge()),
+                Op::Add => match (left, right) {
+                    (Value::Int(a), Value::Int(b)) => Value::Int(a.checked_add(b).ok_or("integer overflow")?),
+                    (left, right) => Value::Str(left.str()? + &right.str()?) // concatenation
+                },
+                Op::Sub | Op::Mul | Op::Div | Op::Rem => {
+                    let (a, b) = (left.int()?, right.int()?);
+                    Value::Int(match op {
+                        Op::Sub => a.checked_sub(b),
+                        Op::Mul => a.checked_mul(b),
+                        Op::Div => a.checked_div(b),
+                        _ => a.checked_rem(b)
+                    }.ok_or("integer overflow or division by zero")?)
+                }
+                Op::And | Op::Or => unreachable!("short-circuited")
+            }
+        }
+        Expr::Match(value, regex) => Value::Bool(regex.is_match(&eval(value, row)?.str()?)),
+        Expr::RegexReplace(value, regex, replacement) => {
+            Value::Str(regex.replace_all(&eval(value, row)?.str()?, eval(replacement, row)?.str()?.as_str()).into_owned())
+        }
+        Expr::Call(function, arguments) => {
+            let mut values = vec![];
+            for argument in arguments {
+                values.push(eval(argument, row)?.str()?);
+            }
+            match function {
+                Function::Lower => Value::Str(values[0].to_lowercase()),
+                Function::Upper => Value::Str(values[0].to_uppercase()),
+                Function::Replace => Value::Str(values[0].replace(&values[1], &values[2])),
+                Function::Contains => Value::Bool(values[0].contains(&values[1])),
+                Function::StartsWith => Value::Bool(values[0].starts_with(&values[1])),
+                Function::EndsWith => Value::Bool(values[0].ends_with(&values[1])),
+                Function::Len => Value::Int(values[0].chars().count() as i64),
+                Function::Basename => Value::Str(values[0].rsplit(['/', '\\']).next().unwrap_or_default().to_string()),
+                Function::Dirname => Value::Str(values[0].rsplit_once(['/', '\\']).map_or("", |(dir, _)| dir).to_string()),
+                Function::HasTag => Value::Bool(row.tags.contains(&values[0]) || row.added.iter().any(|(t, _)| *t == values[0]))
+            }
+        }
+    })
+}
+
+// false once the row is dropped
+fn execute(statements: &[Line], row: &mut Row) -> Result<bool, String> {
+    for Line(line, statement) in statements {
+        let at = |e: String| format!("line {line}: {e}");
+        match statement {
+            Statement::Set(field, expr) => {
+                let value = eval(expr, row).map_err(at)?;
+                field.set(row, value).map_err(at)?;
+            }
+            Statement::Tag(tag, weight) => {
+                let tag = eval(tag, row).and_then(Value::str).map_err(at)?;
+                let weight = match weight {
+                    Some(weight) => u32::try_from(eval(weight, row).and_then(Value::int).map_err(at)?).map_err(|_| at("invalid weight".to_string()))?,
+                    None => 0
+                };
+                row.added.push((tag, weight));
+            }
+            Statement::Drop => {
+                row.dropped = true;
+                return Ok(false);
+            }
+            Statement::If(branches, otherwise) => {
+                let mut taken = None;
+                for (condition, statements) in branches {
+                    if eval(condition, row).and_then(Value::bool).map_err(at)? {
+                        taken = Some(statements);
+                        break;
+                    }
+                }
+                if !execute(taken.unwrap_or(otherwise), row)? {
+                    return Ok(false);
+                }
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn tags(statements: &[Line]) -> bool {
+    statements.iter().any(|Line(_, statement)| match statement {
+        Statement::Tag(..) => true,
+        Statement::If(branches, otherwise) => branches.iter().any(|(_, s)| tags(s)) || tags(otherwise),
+        _ => false
+    })
+}
+
+pub struct Script {
+    path: String,
+    statements: Vec<Line>
+}
+
+impl Script {
+    fn parse(source: &str, path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut parser = Parser { tokens: tokenize(source).map_err(|e| format!("Script {path}: {e}"))?, position: 0, depth: 0 };
+        let statements = parser.statements(false).map_err(|e| format!("Script {path}: {e}"))?;
+        Ok(Self { path: path.to_string(), statements })
+    }
+
+    #[cfg(feature = "scripting")]
+    pub fn from_path(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&std::fs::read_to_string(path)?, &path.display().to_string())
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    pub fn from_path(_path: &Path) -> Result<Self, Box<dyn Error>> {
+        Err("Row scripts require mactime to be built with the `scripting` feature".into())
+    }
+
+    // Tags & Score columns in the output
+    pub fn tags(&self) -> bool {
+        tags(&self.statements)
+    }
+
+    // the row rewritten, tagged or dropped, a runtime error stops the run
+    pub fn run(&self, row: &mut Row) -> Result<(), Box<dyn Error>> {
+        execute(&self.statements, row).map_err(|e| format!("Script {}: {e}", self.path))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row() -> Row {
+        Row {
+            datetime: DateTime::from_timestamp(1595291898, 0).expect("datetime"), // 2020-07-21 00:38:18
+            macb: "m.c.".to_string(),
+            meta: "42-128-1".to_string(),
+            size: 0,
+            filename: "C:\\Users\\Bob\\AppData\\Local\\Temp\\Evil.EXE".to_string(),
+            md5: "0".to_string(),
+            uid: "0".to_string(),
+            source: "bodyfile".to_string(),
+            score: 0,
+            tags: vec!["ioc".to_string()],
+            added: vec![],
+            dropped: false
+        }
+    }
+
+    fn run(source: &str) -> Result<Row, Box<dyn Error>> {
+        let mut row = row();
+        Script::parse(source, "test")?.run(&mut row)?;
+        Ok(row)
+    }
+
+    #[test]
+    fn rewrites_fields() {
+        let row = run(r#"filename = lower(replace(filename, "\\", "/"))  # normalized
+size = len(basename(filename)) * 2 + 1"#).expect("run");
+        assert_eq!(row.filename, "c:/users/bob/appdata/local/temp/evil.exe");
+        assert_eq!(row.size, 17);
+        let row = run(r#"filename = regex_replace(filename, "(?i)^c:", "")"#).expect("run");
+        assert!(row.filename.starts_with("\\Users"));
+    }
+
+    #[test]
+    fn tags_and_branches() {
+        let script = r#"if hour < 6 || hour > 20 { tag "off-hours", 10 }
+else if has_tag("ioc") { tag "check" }
+if filename ~ "(?i)\\\\temp\\\\" && size == 0 { tag "temp" + "-empty", 1 }"#;
+        let row = run(script).expect("run");
+        assert_eq!(row.added, vec![("off-hours".to_string(), 10), ("temp-empty".to_string(), 1)]);
+        assert!(Script::parse(script, "test").expect("parsed").tags());
+        assert!(!Script::parse("size = 1", "test").expect("parsed").tags());
+    }
+
+    #[test]
+    fn drop_stops_the_script() {
+        let row = run("if macb == \"m.c.\" { drop }\ntag \"never\"").expect("run");
+        assert!(row.dropped);
+        assert!(row.added.is_empty());
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = |source: &str| run(source).err().map(|e| e.to_string()).unwrap_or_default();
+        assert!(error("size = 1\nsize = filename").starts_with("Script test: line 2: expected an integer"));
+        assert!(error("\n\nif size { drop }").starts_with("Script test: line 3: "));
+        assert!(error("tag \"x\", -1").contains("invalid weight"));
+        assert!(error("size = 1 / 0").contains("division by zero"));
+        assert!(error("size = -(0 - 9223372036854775807 - 1)").contains("integer overflow"));
+        assert!(error("size = 99999999999999999999").starts_with("Script test: line 1: "));
+        assert!(Script::parse("if size > 1 { drop", "test").is_err());
+        assert!(Script::parse("filename = unknown(filename)", "test").is_err());
+        assert!(Script::parse("tag \"unterminated", "test").is_err());
+    }
+
+    #[test]
+    fn operator_precedence() {
+        let size = |expression: &str| run(&format!("size = {expression}")).expect("run").size;
+        assert_eq!(size("1 + 2 * 3"), 7);
+        assert_eq!(size("(1 + 2) * 3"), 9);
+        assert_eq!(size("10 - 4 - 3"), 3);
+        assert_eq!(size("20 / 2 / 5"), 2);
+        assert_eq!(size("7 % 4 * 2"), 6);
+        assert_eq!(size("-2 * 3 + 10"), 4);
+        assert_eq!(size("- -5"), 5);
+        let tagged = |condition: &str| !run(&format!("if {condition} {{ tag \"t\" }}")).expect("run").added.is_empty();
+        assert!(tagged("true || false && false"));
+        assert!(!tagged("!false && false"));
+        assert!(tagged("1 + 1 == 2 && size < 1"));
+        assert!(tagged("\"a\" + \"b\" ~ \"^ab$\""));
+        assert!(tagged("!(1 > 2) && -1 < 0"));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let error = |source: &str| Script::parse(source, "test").err().map(|e| e.to_string()).unwrap_or_default();
+        assert_eq!(error("size = (1 + 2"), "Script test: line 1: expected ')'");
+        assert_eq!(error("size = 1 +"), "Script test: line 1: expected a value");
+        assert_eq!(error("size = 1 2"), "Script test: line 1: expected the end of the statement");
+        assert_eq!(error("\n\nif size > 1 {\n drop"), "Script test: line 4: expected '}'");
+        assert_eq!(error("timestamp = 1"), "Script test: line 1: field 'timestamp' is read-only");
+        assert_eq!(error("size = len(filename, 1)"), "Script test: line 1: len takes 1 argument(s)");
+        assert_eq!(error("else { drop }"), "Script test: line 1: unknown field 'else'");
+        assert_eq!(error("if filename ~ size { drop }"), "Script test: line 1: expected a regex string");
+        assert!(error("if filename ~ \"(\" { drop }").starts_with("Script test: line 1: regex parse error"));
+    }
+
+    #[test]
+    fn nesting_depth() {
+        let parse = |source: String| Script::parse(&source, "test").map(|_| ()).map_err(|e| e.to_string());
+        let nested = "Script test: line 1: nested deeper than 64 levels";
+        assert_eq!(parse(format!("size = {}1{}", "(".repeat(60), ")".repeat(60))), Ok(()));
+        assert_eq!(parse(format!("size = {}1{}", "(".repeat(100), ")".repeat(100))).unwrap_err(), nested);
+        assert_eq!(parse(format!("size = 1{}", " + 1".repeat(60))), Ok(()));
+        // long chains of operators & deep blocks fail early, without overflowing the stack
+        assert_eq!(parse(format!("size = 1{}", " + 1".repeat(100_000))).unwrap_err(), nested);
+        assert_eq!(parse(format!("size = {}1", "- ".repeat(100_000))).unwrap_err(), nested);
+        assert_eq!(parse(format!("{}drop{}", "if true { ".repeat(100_000), " }".repeat(100_000))).unwrap_err(), nested);
+        assert_eq!(parse(format!("{}drop{}", "if true { ".repeat(30), " }".repeat(30))), Ok(()));
+    }
+}
@@ -0,0 +1,124 @@
+use std::error::Error;
+
+use crate::bodyfile::{BodyFile, BodyFileParser, ParseOptions, SortKey, LineEnding, MissingValue};
+use crate::input::InputFormat;
+use crate::locale::NumberFormat;
+use crate::sink::{CsvSink, JsonSink};
+
+/*
+mactime selftest: the parser & output backends run against an embedded sample, and their results are compared to the
+expected ones, so that a binary copied to an air-gapped machine can be validated before its output is trusted.
+*/
+const SAMPLE : &str = "\
+# selftest sample
+0|/Users/alice/Documents/report.docx|1000-128-1|r/r|0|0|48213|1595291898|1595291898|1595291898|1595291898
+d41d8cd98f00b204e9800998ecf8427e|/Users/alice/AppData/Local/Temp/tool.exe (deleted)|1001-128-1|r/r|1000|1000|1024|1595292000|1595291950|1595291950|1595291900
+0|/Users/alice/AppData/Local/Temp|1002-144-1|d/d|0|0|4096|1595291900|1595292000|1595292000|1577836800
+";
+
+const EXPECTED_CSV : &str = "\
+Datetime,MACB,Meta,Size,FileName
+2020-01-01 00:00:00,...b,1002-144-1,4096,/Users/alice/AppData/Local/Temp
+2020-07-21 00:38:18,macb,1000-128-1,48213,/Users/alice/Documents/report.docx
+2020-07-21 00:38:20,.a..,1002-144-1,4096,/Users/alice/AppData/Local/Temp
+2020-07-21 00:38:20,...b,1001-128-1,1024,/Users/alice/AppData/Local/Temp/tool.exe (deleted)
+2020-07-21 00:39:10,m.c.,1001-128-1,1024,/Users/alice/AppData/Local/Temp/tool.exe (deleted)
+2020-07-21 00:40:00,m.c.,1002-144-1,4096,/Users/alice/AppData/Local/Temp
+2020-07-21 00:40:00,.a..,1001-128-1,1024,/Users/alice/AppData/Local/Temp/tool.exe (deleted)
+";
+
+const EXPECTED_JSON : &str = r#"{"metadata":{"selftest":true},"events":[
+{"datetime":"2020-01-01T00:00:00Z","filename":"/Users/alice/AppData/Local/Temp","macb":"...b","meta":"1002-144-1","size":4096},
+{"datetime":"2020-07-21T00:38:18Z","filename":"/Users/alice/Documents/report.docx","macb":"macb","meta":"1000-128-1","size":48213},
+{"datetime":"2020-07-21T00:38:20Z","filename":"/Users/alice/AppData/Local/Temp","macb":".a..","meta":"1002-144-1","size":4096},
+{"datetime":"2020-07-21T00:38:20Z","filename":"/Users/alice/AppData/Local/Temp/tool.exe (deleted)","macb":"...b","meta":"1001-128-1","size":1024},
+{"datetime":"2020-07-21T00:39:10Z","filename":"/Users/alice/AppData/Local/Temp/tool.exe (deleted)","macb":"m.c.","meta":"1001-128-1","size":1024},
+{"datetime":"2020-07-21T00:40:00Z","filename":"/Users/alice/AppData/Local/Temp","macb":"m.c.","meta":"1002-144-1","size":4096},
+{"datetime":"2020-07-21T00:40:00Z","filename":"/Users/alice/AppData/Local/Temp/tool.exe (deleted)","macb":".a..","meta":"1001-128-1","size":1024}
+]}
+"#;
+
+fn options(format: InputFormat) -> ParseOptions {
+    ParseOptions { format, sort: Some(SortKey::Datetime), ..ParseOptions::default() }
+}
+
+fn csv(bodyfile: &BodyFile) -> Result<String, Box<dyn Error>> {
+    let mut output = vec![];
+    bodyfile.write_to(&mut CsvSink::new(&mut output, LineEnding::Lf, NumberFormat::default(), MissingValue::Empty), &serde_json::Value::Null)?;
+    Ok(String::from_utf8(output)?)
+}
+
+fn json(bodyfile: &BodyFile) -> Result<String, Box<dyn Error>> {
+    let mut output = vec![];
+    bodyfile.write_to(&mut JsonSink::new(&mut output, LineEnding::Lf), &serde_json::json!({"selftest": true}))?;
+    Ok(String::from_utf8(output)?)
+}
+
+fn compare(actual: &str, expected: &str) -> Result<(), Box<dyn Error>> {
+    match actual.lines().zip(expected.lines()).position(|(a, e)| a != e) {
+        Some(i) => Err(format!("line {}: got '{}', expected '{}'", i + 1, actual.lines().nth(i).unwrap_or_default(), expected.lines().nth(i).unwrap_or_default()).into()),
+        None if actual.lines().count() != expected.lines().count() => Err(format!("got {} lines, expected {}", actual.lines().count(), expected.lines().count()).into()),
+        None => Ok(())
+    }
+}
+
+fn sample() -> Result<BodyFile, Box<dyn Error>> {
+    BodyFileParser::build_bytes(SAMPLE.into(), &options(InputFormat::Bodyfile))
+}
+
+fn parser() -> Result<(), Box<dyn Error>> {
+    let bodyfile = sample()?;
+    let counts = (bodyfile.skipped_lines(), bodyfile.file_len(), bodyfile.datetime_len());
+    if counts != (1, 3, 7) {
+        return Err(format!("got {counts:?} skipped lines, file & datetime records, expected (1, 3, 7)").into());
+    }
+    Ok(())
+}
+
+// UTF-16LE with BOM, as exported by some Windows tools
+fn utf16() -> Result<(), Box<dyn Error>> {
+    let bytes : Vec<u8> = [0xFF, 0xFE].into_iter()
+        .chain(SAMPLE.encode_utf16().flat_map(u16::to_le_bytes))
+        .collect();
+    compare(&csv(&BodyFileParser::build_bytes(bytes, &options(InputFormat::Bodyfile))?)?, EXPECTED_CSV)
+}
+
+fn csv_output() -> Result<(), Box<dyn Error>> {
+    compare(&csv(&sample()?)?, EXPECTED_CSV)
+}
+
+fn json_output() -> Result<(), Box<dyn Error>> {
+    compare(&json(&sample()?)?, EXPECTED_JSON)
+}
+
+// JSON output read back as input (--input-format events, mactime merge), rows get a SourceType column
+fn events_input() -> Result<(), Box<dyn Error>> {
+    let expected : String = EXPECTED_CSV.lines().enumerate()
+        .map(|(i, line)| format!("{line},{}\n", if i == 0 { "SourceType" } else { "FILE" }))
+        .collect();
+    compare(&csv(&BodyFileParser::build_bytes(EXPECTED_JSON.into(), &options(InputFormat::Events))?)?, &expected)
+}
+
+// name & outcome of a check
+pub type Check = (&'static str, Result<(), Box<dyn Error>>);
+
+// outcome of each check, in order
+pub fn run() -> Vec<Check> {
+    vec![
+        ("bodyfile parser", parser()),
+        ("UTF-16 input", utf16()),
+        ("CSV output", csv_output()),
+        ("JSON output", json_output()),
+        ("JSON events input", events_input())
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn selftest_passes() {
+        for (name, outcome) in super::run() {
+            assert!(outcome.is_ok(), "{name}: {outcome:?}");
+        }
+    }
+}
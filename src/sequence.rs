@@ -0,0 +1,156 @@
+use std::{path::Path, error::Error};
+use chrono::{DateTime, Utc, Duration};
+use regex::{Regex, RegexBuilder};
+
+use crate::bodyfile::{BodyFile, MACB, parse_duration};
+
+/*
+Sequence rules (--sequences): ordered patterns over the timeline sorted by datetime, for multi-step behaviors that
+single-row rules miss. A rule is a [name] line followed by its steps, one per line:
+- first step: <flags> <regex>
+- next steps: <window> <flags> <regex>, the row following the previous step's one within the window (30s, 5m, 1h, ...)
+flags being the MACB flags the row must have (b, mc, ...) or * for any row, the regex being matched against the filename.
+    [exe-in-temp-then-system32]
+    b (?i)/temp/[^/]+\.exe$
+    5m b (?i)/windows/system32/
+*/
+#[derive(Debug)]
+struct Step {
+    window: Option<Duration>, // none for the first step
+    flags: MACB, // empty: any row
+    regex: Regex
+}
+
+impl Step {
+    fn parse(line: &str, first: bool, ignore_case: bool) -> Result<Self, Box<dyn Error>> {
+        let error = || format!("Invalid sequence step '{line}', expected {}<flags> <regex>", if first { "" } else { "<window> " });
+        let mut fields = line.trim().splitn(if first { 2 } else { 3 }, char::is_whitespace);
+        let window = match first {
+            true => None,
+            false => Some(parse_duration(fields.next().ok_or_else(error)?)?)
+        };
+        let flags = match fields.next().ok_or_else(error)? {
+            "*" => MACB::empty(),
+            flags => flags.chars().try_fold(MACB::empty(), |macb, c| match c {
+                'm' => Ok(macb | MACB::MODIFIED),
+                'a' => Ok(macb | MACB::ACCESSED),
+                'c' => Ok(macb | MACB::CHANGED),
+                'b' => Ok(macb | MACB::BIRTH),
+                _ => Err(error())
+            })?
+        };
+        let pattern = fields.next().map(str::trim).filter(|p| !p.is_empty()).ok_or_else(error)?;
+        Ok(Self {
+            window,
+            flags,
+            regex: RegexBuilder::new(pattern).case_insensitive(ignore_case).build()?
+        })
+    }
+
+    fn matches(&self, macb: MACB, filename: &str) -> bool {
+        macb.contains(self.flags) && self.regex.is_match(filename)
+    }
+}
+
+#[derive(Debug)]
+pub struct Sequence {
+    pub name: String,
+    steps: Vec<Step>,
+    pub hits: usize
+}
+
+// a matched sequence: the rows of its steps, `count` first-step rows leading to the same next steps
+#[derive(Debug)]
+pub struct SequenceMatch {
+    pub name: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub count: usize,
+    pub rows: Vec<(DateTime<Utc>, String, String)> // datetime, MACB, filename
+}
+
+pub fn from_path(path: &Path, ignore_case: bool) -> Result<Vec<Sequence>, Box<dyn Error>> {
+    let content = std::fs::read_to_string(path)?;
+    let mut sequences : Vec<Sequence> = vec![];
+    for line in content.lines().filter(|l| !l.trim().is_empty() && !l.starts_with('#')) { // skip blank lines & comments
+        if let Some(name) = line.trim().strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sequences.push(Sequence { name: name.trim().to_string(), steps: vec![], hits: 0 });
+            continue;
+        }
+        let sequence = sequences.last_mut().ok_or_else(|| format!("Sequence step '{line}' before any [name] line"))?;
+        let step = Step::parse(line, sequence.steps.is_empty(), ignore_case)?;
+        sequence.steps.push(step);
+    }
+    if let Some(sequence) = sequences.iter().find(|s| s.steps.len() < 2) {
+        return Err(format!("Sequence '{}' needs at least 2 steps", sequence.name).into());
+    }
+    Ok(sequences)
+}
+
+// each first-step row is followed by the earliest rows matching the next steps, hit counters are updated
+pub fn find(bodyfile: &BodyFile, sequences: &mut [Sequence]) -> Vec<SequenceMatch> {
+    let mut rows : Vec<(DateTime<Utc>, MACB, &str)> = bodyfile.macb_events().zip(bodyfile.events())
+        .map(|((date, macb), (filename, _))| (date, macb, filename))
+        .collect();
+    rows.sort_by_key(|(date, _, _)| *date);
+
+    let mut matches = vec![];
+    for sequence in sequences.iter_mut() {
+        let mut previous : Option<(Vec<usize>, SequenceMatch)> = None; // next steps' rows of the last match
+        for start in (0..rows.len()).filter(|&i| sequence.steps[0].matches(rows[i].1, rows[i].2)) {
+            let Some(next) = follow(&rows, &sequence.steps[1..], start) else { continue };
+            sequence.hits += 1;
+            match previous.as_mut() {
+                Some((previous_next, current)) if *previous_next == next => current.count += 1,
+                _ => {
+                    let current = SequenceMatch {
+                        name: sequence.name.clone(),
+                        start: rows[start].0,
+                        end: rows[*next.last().expect("2 steps")].0,
+                        count: 1,
+                        rows: std::iter::once(start).chain(next.iter().copied()).map(|i| (rows[i].0, rows[i].1.to_string(), rows[i].2.to_string())).collect()
+                    };
+                    matches.extend(previous.replace((next, current)).map(|(_, m)| m));
+                }
+            }
+        }
+        matches.extend(previous.map(|(_, m)| m));
+    }
+    matches.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.name.cmp(&b.name)));
+    matches
+}
+
+// rows of the steps following the row `start`, none if a step isn't found within its window
+fn follow(rows: &[(DateTime<Utc>, MACB, &str)], steps: &[Step], start: usize) -> Option<Vec<usize>> {
+    let mut current = start;
+    let mut found = vec![];
+    for step in steps {
+        let window = step.window.expect("next step");
+        current = (current + 1..rows.len())
+            .take_while(|&i| rows[i].0 - rows[current].0 <= window)
+            .find(|&i| step.matches(rows[i].1, rows[i].2))?;
+        found.push(current);
+    }
+    Some(found)
+}
+
+pub fn write_matches(matches: &[SequenceMatch], path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["Sequence", "Start", "End", "Count", "Steps"])?;
+
+    for m in matches {
+        let steps : Vec<String> = m.rows.iter()
+            .map(|(date, macb, filename)| format!("{} {macb} {filename}", date.format("%Y-%m-%d %H:%M:%S")))
+            .collect();
+        writer.write_record([
+            m.name.clone(),
+            m.start.format("%Y-%m-%d %H:%M:%S").to_string(),
+            m.end.format("%Y-%m-%d %H:%M:%S").to_string(),
+            m.count.to_string(),
+            steps.join(" => ")
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
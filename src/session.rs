@@ -0,0 +1,67 @@
+use std::{path::Path, error::Error};
+use chrono::{DateTime, Utc, Duration};
+
+use crate::analysis::top_directories;
+use crate::bodyfile::BodyFile;
+
+/*
+Sessions (--sessions): consecutive rows less than an idle gap apart, in chronological order. Attack activity clusters
+into sessions (hands-on-keyboard, scheduled tool runs), so the summary of each one is the skeleton of the narrative.
+Session IDs are assigned to the rows by `BodyFile::assign_sessions`, and written in a SessionId column with --session-id.
+*/
+#[derive(Debug)]
+pub struct Session {
+    pub id: usize,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub count: usize,
+    pub top_directories: String
+}
+
+// summary of the sessions of the rows, once assigned
+pub fn sessions(bodyfile: &BodyFile) -> Vec<Session> {
+    let mut rows : Vec<Vec<(&str, DateTime<Utc>)>> = vec![];
+    for (id, filename, date) in bodyfile.session_rows() {
+        if rows.len() < id {
+            rows.resize_with(id, Vec::new);
+        }
+        rows[id - 1].push((filename, date));
+    }
+
+    rows.into_iter().enumerate()
+        .filter(|(_, rows)| !rows.is_empty())
+        .map(|(i, rows)| Session {
+            id: i + 1,
+            start: rows.iter().map(|(_, d)| *d).min().expect("session rows"),
+            end: rows.iter().map(|(_, d)| *d).max().expect("session rows"),
+            count: rows.len(),
+            top_directories: top_directories(rows.iter().map(|(f, _)| f.rsplit_once('/').map_or("", |(parent, _)| parent)))
+        })
+        .collect()
+}
+
+pub fn write_sessions(sessions: &[Session], path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["Session", "Start", "End", "Duration", "Count", "TopDirectories"])?;
+
+    for session in sessions {
+        let duration = session.end - session.start;
+        writer.write_record([
+            session.id.to_string(),
+            session.start.format("%Y-%m-%d %H:%M:%S").to_string(),
+            session.end.format("%Y-%m-%d %H:%M:%S").to_string(),
+            format_duration(duration),
+            session.count.to_string(),
+            session.top_directories.clone()
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+// HH:MM:SS, hours beyond 24
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds();
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
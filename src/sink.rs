@@ -0,0 +1,420 @@
+use std::{borrow::Cow, error::Error, io::Write};
+use chrono::{DateTime, Utc};
+
+use crate::binary::{self, Record};
+use crate::bodyfile::{LineEnding, MissingValue};
+use crate::locale::NumberFormat;
+use crate::timezone::TimeZone;
+
+/*
+Output sinks: where the timeline rows go once parsed, filtered and sorted.
+CSV, JSON & binary are the built-in ones, library users implement `OutputSink` (database, message queue, ...) and pass it to `BodyFile::write_to`.
+*/
+#[derive(Debug, Clone)]
+pub struct Event<'a> {
+    pub datetime: DateTime<Utc>,
+    pub macb: String,
+    pub meta: Cow<'a, str>,
+    pub size: u64,
+    pub filename: Cow<'a, str>,
+    // optional columns, None when not part of the output
+    pub md5: Option<&'a str>,
+    pub mode: Option<&'a str>,
+    pub uid: Option<&'a str>,
+    pub gid: Option<&'a str>,
+    pub unset: Option<String>, // MACB of the timestamps unset with a sentinel, empty if none
+    pub tags: Option<(&'a [String], u64)>, // tags & score
+    pub detection: Option<Option<String>>,
+    pub source_type: Option<String>,
+    pub precision: Option<String>,
+    pub annotation: Option<String>,
+    pub session: Option<usize>,
+    pub provenance: Option<(Option<&'a str>, Option<u64>)>, // source file & line
+    pub raw: Option<Option<Cow<'a, str>>>
+}
+
+pub trait OutputSink {
+    // called once before the events, `columns` are the CSV headers of the run
+    fn write_header(&mut self, columns: &[&str], metadata: &serde_json::Value) -> Result<(), Box<dyn Error>>;
+
+    fn write_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>>;
+
+    // start of the rows of a group (--group-by), written after the combined rows
+    fn write_section(&mut self, name: &str, _columns: &[&str]) -> Result<(), Box<dyn Error>> {
+        Err(format!("Output sections ({name}) are not supported by this sink").into())
+    }
+
+    // rows written so far pushed to the output (--follow), binary blocks being only written once full
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    // `partial`: interrupted (Ctrl-C) before all the events were written
+    fn finish(&mut self, partial: bool) -> Result<(), Box<dyn Error>>;
+}
+
+pub struct CsvSink<W: Write> {
+    writer: csv::Writer<W>,
+    number_format: NumberFormat,
+    missing_value: MissingValue,
+    timezone: Option<TimeZone>, // local times of the zone, UTC if None
+    extra_timezones: Vec<TimeZone> // one more datetime column per zone (--extra-tz)
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(writer: W, line_ending: LineEnding, number_format: NumberFormat, missing_value: MissingValue) -> Self {
+        let terminator = match line_ending {
+            LineEnding::Lf => csv::Terminator::Any(b'\n'),
+            LineEnding::Crlf => csv::Terminator::CRLF
+        };
+        Self {
+            writer: csv::WriterBuilder::new().terminator(terminator).flexible(true).from_writer(writer), // flexible: section lines
+            number_format,
+            missing_value,
+            timezone: None,
+            extra_timezones: vec![]
+        }
+    }
+
+    // datetimes written in the local time of `timezone` (-z), named in the header
+    pub fn with_timezone(mut self, timezone: Option<TimeZone>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    // the datetime in the local time of each zone too, in the columns following Datetime
+    pub fn with_extra_timezones(mut self, timezones: Vec<TimeZone>) -> Self {
+        self.extra_timezones = timezones;
+        self
+    }
+
+    // "Datetime (Europe/Paris)" in local times
+    fn columns(&self, columns: &[&str]) -> Vec<String> {
+        let mut names = vec![];
+        for c in columns {
+            match (&self.timezone, *c) {
+                (Some(timezone), "Datetime") => names.push(format!("Datetime ({timezone})")),
+                _ => names.push(c.to_string())
+            }
+            if *c == "Datetime" {
+                names.extend(self.extra_timezones.iter().map(|t| format!("Datetime ({t})")));
+            }
+        }
+        names
+    }
+}
+
+impl<W: Write> OutputSink for CsvSink<W> {
+    fn write_header(&mut self, columns: &[&str], _metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        self.writer.write_record(self.columns(columns))?;
+        Ok(())
+    }
+
+    fn write_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let mut record = vec![
+            match &self.timezone {
+                Some(timezone) => self.number_format.datetime(timezone.local(event.datetime)),
+                None => self.number_format.datetime(event.datetime)
+            }
+        ];
+        record.extend(self.extra_timezones.iter().map(|t| self.number_format.datetime(t.local(event.datetime))));
+        record.extend([
+            event.macb.clone(),
+            event.meta.to_string(),
+            self.number_format.size(event.size),
+            event.filename.to_string()
+        ]);
+        record.extend(event.md5.map(String::from));
+        // --missing-value for the fields the source doesn't have
+        for value in [event.mode, event.uid, event.gid].into_iter().flatten() {
+            record.push(self.missing_value.or(Some(value).filter(|v| !v.is_empty()).map(String::from)));
+        }
+        record.extend(event.unset.clone());
+        if let Some((tags, score)) = event.tags {
+            record.push(tags.join(";"));
+            record.push(score.to_string());
+        }
+        if let Some(detection) = &event.detection {
+            record.push(self.missing_value.or(detection.clone()));
+        }
+        record.extend(event.source_type.clone());
+        record.extend(event.precision.clone());
+        record.extend(event.annotation.clone());
+        record.extend(event.session.map(|s| s.to_string()));
+        if let Some((file, line)) = event.provenance {
+            record.push(self.missing_value.or(file.map(String::from)));
+            record.push(self.missing_value.or(line.map(|l| l.to_string())));
+        }
+        if let Some(raw) = &event.raw {
+            record.push(self.missing_value.or(raw.as_ref().map(|r| r.to_string())));
+        }
+
+        self.writer.write_record(&record).map_err(|e| format!("Error writing CSV result: {e}"))?; // row set aside with --dead-letter
+        Ok(())
+    }
+
+    // "# <name>" line, then the header again
+    fn write_section(&mut self, name: &str, columns: &[&str]) -> Result<(), Box<dyn Error>> {
+        self.writer.write_record([format!("# {name}")])?;
+        self.writer.write_record(self.columns(columns))?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self, partial: bool) -> Result<(), Box<dyn Error>> {
+        if partial {
+            self.writer.write_record(["# partial"])?; // interrupted while writing the events
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// object of an event in the JSON output
+pub fn json_value(event: &Event) -> serde_json::Value {
+    let mut value = serde_json::json!({
+        "datetime": event.datetime.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        "macb": event.macb,
+        "meta": event.meta,
+        "size": event.size,
+        "filename": event.filename
+    });
+    if let Some(md5) = event.md5 {
+        value["md5"] = serde_json::json!(md5);
+    }
+    if let Some(mode) = event.mode {
+        value["mode"] = serde_json::json!(mode);
+    }
+    if let Some(uid) = event.uid {
+        value["uid"] = serde_json::json!(uid);
+    }
+    if let Some(gid) = event.gid {
+        value["gid"] = serde_json::json!(gid);
+    }
+    if let Some(unset) = &event.unset {
+        value["unset"] = serde_json::json!(unset);
+    }
+    if let Some((tags, score)) = event.tags {
+        value["tags"] = serde_json::json!(tags);
+        value["score"] = serde_json::json!(score);
+    }
+    if let Some(detection) = &event.detection {
+        value["detection"] = serde_json::json!(detection);
+    }
+    if let Some(source_type) = &event.source_type {
+        value["source_type"] = serde_json::json!(source_type);
+    }
+    if let Some(precision) = &event.precision {
+        value["precision"] = serde_json::json!(precision);
+    }
+    if let Some(annotation) = &event.annotation {
+        value["annotation"] = serde_json::json!(annotation);
+    }
+    if let Some(session) = event.session {
+        value["session_id"] = serde_json::json!(session);
+    }
+    if let Some((file, line)) = event.provenance {
+        value["source_file"] = serde_json::json!(file);
+        value["source_line"] = serde_json::json!(line);
+    }
+    if let Some(raw) = &event.raw {
+        value["raw"] = serde_json::json!(raw);
+    }
+    value
+}
+
+// JSON of an event, its datetime in the local time of the output zone (RFC 3339, with its offset)
+// and in the ones of --extra-tz: "local_datetimes": {"<zone>": ..., ...}
+fn local_json_value(event: &Event, timezone: Option<&TimeZone>, extra_timezones: &[TimeZone]) -> serde_json::Value {
+    let rfc3339 = |t: &TimeZone| t.local(event.datetime).format("%Y-%m-%dT%H:%M:%S%:z").to_string();
+    let mut value = json_value(event);
+    if let Some(timezone) = timezone {
+        value["datetime"] = serde_json::json!(rfc3339(timezone));
+    }
+    if !extra_timezones.is_empty() {
+        value["local_datetimes"] = extra_timezones.iter().map(|t| (t.name().to_string(), serde_json::json!(rfc3339(t)))).collect();
+    }
+    value
+}
+
+// {"metadata": {...}, "events": [...]}, events are streamed one by one
+// with sections: {"metadata": {...}, "events": [...], "sections": {"<name>": [...], ...}}
+pub struct JsonSink<W: Write> {
+    writer: W,
+    line_ending: LineEnding,
+    events: usize,
+    sections: usize,
+    timezone: Option<TimeZone>, // RFC 3339 local times with their offset, UTC if None
+    extra_timezones: Vec<TimeZone>
+}
+
+impl<W: Write> JsonSink<W> {
+    pub fn new(writer: W, line_ending: LineEnding) -> Self {
+        Self { writer, line_ending, events: 0, sections: 0, timezone: None, extra_timezones: vec![] }
+    }
+
+    pub fn with_timezone(mut self, timezone: Option<TimeZone>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn with_extra_timezones(mut self, timezones: Vec<TimeZone>) -> Self {
+        self.extra_timezones = timezones;
+        self
+    }
+}
+
+impl<W: Write> OutputSink for JsonSink<W> {
+    fn write_header(&mut self, _columns: &[&str], metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        write!(self.writer, "{{\"metadata\":")?;
+        serde_json::to_writer(&mut self.writer, metadata)?;
+        write!(self.writer, ",\"events\":[")?;
+        Ok(())
+    }
+
+    fn write_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let value = local_json_value(event, self.timezone.as_ref(), &self.extra_timezones);
+        if self.events > 0 {
+            write!(self.writer, ",")?;
+        }
+        write!(self.writer, "{}", self.line_ending.as_str())?;
+        serde_json::to_writer(&mut self.writer, &value)?;
+        self.events += 1;
+        Ok(())
+    }
+
+    fn write_section(&mut self, name: &str, _columns: &[&str]) -> Result<(), Box<dyn Error>> {
+        write!(self.writer, "{}]{}", self.line_ending.as_str(), if self.sections == 0 { ",\"sections\":{" } else { "," })?;
+        serde_json::to_writer(&mut self.writer, name)?;
+        write!(self.writer, ":[")?;
+        self.events = 0;
+        self.sections += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self, partial: bool) -> Result<(), Box<dyn Error>> {
+        write!(self.writer, "{}]", self.line_ending.as_str())?;
+        if self.sections > 0 {
+            write!(self.writer, "}}")?;
+        }
+        if partial {
+            write!(self.writer, ",\"partial\":true")?; // interrupted while writing the events
+        }
+        write!(self.writer, "}}{}", self.line_ending.as_str())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// one JSON object per event and per line (SIEM ingestion), without the metadata that would be ingested as an event
+// the rows of a group (--group-by) carry its name
+pub struct JsonlSink<W: Write> {
+    writer: W,
+    line_ending: LineEnding,
+    group: Option<String>,
+    timezone: Option<TimeZone>,
+    extra_timezones: Vec<TimeZone>
+}
+
+impl<W: Write> JsonlSink<W> {
+    pub fn new(writer: W, line_ending: LineEnding) -> Self {
+        Self { writer, line_ending, group: None, timezone: None, extra_timezones: vec![] }
+    }
+
+    pub fn with_timezone(mut self, timezone: Option<TimeZone>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn with_extra_timezones(mut self, timezones: Vec<TimeZone>) -> Self {
+        self.extra_timezones = timezones;
+        self
+    }
+}
+
+impl<W: Write> OutputSink for JsonlSink<W> {
+    fn write_header(&mut self, _columns: &[&str], _metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn write_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let mut value = local_json_value(event, self.timezone.as_ref(), &self.extra_timezones);
+        if let Some(group) = &self.group {
+            value["group"] = serde_json::json!(group);
+        }
+        serde_json::to_writer(&mut self.writer, &value)?;
+        write!(self.writer, "{}", self.line_ending.as_str())?;
+        Ok(())
+    }
+
+    fn write_section(&mut self, name: &str, _columns: &[&str]) -> Result<(), Box<dyn Error>> {
+        self.group = Some(name.to_string());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self, _partial: bool) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?; // every line is a whole event, a partial output is only told by the exit code
+        Ok(())
+    }
+}
+
+// compressed blocks of events (binary.rs), the header holding the columns & metadata
+pub struct BinarySink<W: Write> {
+    writer: Option<W>, // until the header is written
+    blocks: Option<binary::Writer<W>>
+}
+
+impl<W: Write> BinarySink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer: Some(writer), blocks: None }
+    }
+}
+
+impl<W: Write> OutputSink for BinarySink<W> {
+    fn write_header(&mut self, columns: &[&str], metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        let writer = self.writer.take().ok_or("Binary timeline header already written")?;
+        let header = serde_json::json!({ "kind": "events", "columns": columns, "metadata": metadata });
+        self.blocks = Some(binary::Writer::new(writer, &header)?);
+        Ok(())
+    }
+
+    fn write_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        self.blocks.as_mut().ok_or("Binary timeline header not written")?.push(&Record::from(event))
+    }
+
+    fn finish(&mut self, partial: bool) -> Result<(), Box<dyn Error>> {
+        self.blocks.take().ok_or("Binary timeline header not written")?.finish(partial)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interrupted_csv_ends_with_a_partial_line() {
+        for (partial, last) in [(false, "Datetime,FileName"), (true, "# partial")] {
+            let mut output = Vec::new();
+            let mut sink = CsvSink::new(&mut output, LineEnding::Lf, NumberFormat::default(), MissingValue::default());
+            sink.write_header(&["Datetime", "FileName"], &serde_json::Value::Null).unwrap();
+            sink.finish(partial).unwrap();
+            drop(sink);
+            assert_eq!(String::from_utf8(output).unwrap().lines().last(), Some(last));
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::{path::Path, error::Error, fs::File, io::{BufRead, BufReader}, borrow::Cow, collections::BinaryHeap, cmp::Ordering};
+use chrono::{DateTime, Utc};
+
+use crate::binary::{self, Record};
+use crate::bodyfile::{BodyFile, OutputFormat, SourceType};
+use crate::input::JsonEvent;
+use crate::interrupt;
+use crate::schema::SchemaLevel;
+use crate::sink::{Event, OutputSink};
+
+/*
+Merge of pre-sorted timelines (mactime merge --assume-sorted): JSON outputs already sorted by datetime (previous runs,
+per-shard results) are read one event at a time and merged with a heap-based k-way merge, in constant memory whatever
+their size, instead of being loaded & re-sorted. Events are expected one per line, as written by --format json (or JSON
+lines), only the combined rows of sectioned outputs being read, or binary timelines (--format binary) one record at a
+time. Rows read several times (same datetime, file name, MACB,
+meta, size & source) are written once, as by the merge of unsorted inputs.
+*/
+enum Events {
+    Lines(BufReader<File>),
+    Binary(binary::Reader<BufReader<File>>)
+}
+
+struct EventStream<'a> {
+    path: &'a str,
+    events: Events,
+    line: u64,
+    last: Option<(DateTime<Utc>, String)> // sort key of the previous event
+}
+
+impl<'a> EventStream<'a> {
+    fn open(path: &'a str) -> Result<Self, Box<dyn Error>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let events = match binary::is_binary(reader.fill_buf()?) {
+            true => Events::Binary(binary::Reader::new(reader).map_err(|e| format!("{path}: {e}"))?),
+            false => Events::Lines(reader)
+        };
+        Ok(Self { path, events, line: 0, last: None })
+    }
+
+    // next event & its line, inputs that turn out not to be sorted are an error rather than an unsorted output
+    fn next(&mut self) -> Result<Option<(JsonEvent, u64)>, Box<dyn Error>> {
+        let Some(event) = self.read()? else { return Ok(None) };
+        let key = (event.datetime, event.filename.clone());
+        if self.last.as_ref().is_some_and(|last| key < *last) {
+            return Err(format!("{} is not sorted by datetime (line {}), merge it without --assume-sorted", self.path, self.line).into());
+        }
+        self.last = Some(key);
+        Ok(Some((event, self.line)))
+    }
+
+    // line: line of the JSON output, or index of the record in the binary timeline
+    fn read(&mut self) -> Result<Option<JsonEvent>, Box<dyn Error>> {
+        let reader = match &mut self.events {
+            Events::Lines(reader) => reader,
+            Events::Binary(reader) => {
+                let record = reader.read::<Record>().map_err(|e| format!("{}, record {}: {e}", self.path, self.line + 1))?;
+                self.line += 1;
+                return Ok(record.map(Record::into_json_event));
+            }
+        };
+        let mut buffer = String::new();
+        loop {
+            buffer.clear();
+            if reader.read_line(&mut buffer)? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            let line = buffer.trim().trim_end_matches(',');
+            if line.is_empty() || line.starts_with("{\"metadata\"") {
+                continue;
+            }
+            if line.starts_with(']') {
+                return Ok(None); // end of the combined rows, host sections follow
+            }
+            return serde_json::from_str(line)
+                .map(Some)
+                .map_err(|e| format!("{}, line {}: {e} (events expected one per line, as written by --format json)", self.path, self.line).into());
+        }
+    }
+}
+
+// next event of an input: the heap pops the earliest one (datetime, then file name), of the first input on ties
+struct Head {
+    event: JsonEvent,
+    line: u64,
+    input: usize
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.event.datetime, &other.event.filename, other.input).cmp(&(self.event.datetime, &self.event.filename, self.input))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+// (rows written, duplicate rows skipped)
+pub fn merge(inputs: &[&str], bodyfile: &BodyFile, output: Option<&Path>, format: OutputFormat, metadata: &serde_json::Value, provenance: bool, schema: SchemaLevel) -> Result<(usize, usize), Box<dyn Error>> {
+    let mut streams = inputs.iter().map(|input| EventStream::open(input)).collect::<Result<Vec<EventStream>, Box<dyn Error>>>()?;
+    let mut heap = BinaryHeap::new();
+    for (input, stream) in streams.iter_mut().enumerate() {
+        heap.extend(stream.next()?.map(|(event, line)| Head { event, line, input }));
+    }
+
+    // optional columns of the first events, mactime outputs having the same columns throughout
+    let tagged = heap.iter().any(|h| h.event.tags.is_some());
+    let enriched = heap.iter().any(|h| h.event.detection.is_some());
+    let mut columns = vec!["Datetime", "MACB", "Meta", "Size", "FileName"];
+    match schema {
+        SchemaLevel::V1 => {}
+        SchemaLevel::V2 => columns.extend(["MD5", "UID"]),
+        SchemaLevel::V3 => columns.extend(["MD5", "Mode", "UID", "GID", "Unset"])
+    }
+    if tagged {
+        columns.extend(["Tags", "Score"]);
+    }
+    if enriched {
+        columns.push("Detection");
+    }
+    columns.push("SourceType");
+    if provenance {
+        columns.extend(["SourceFile", "SourceLine"]);
+    }
+
+    let (mut written, mut duplicates) = (0, 0);
+    bodyfile.write_with(output, format, |sink: &mut dyn OutputSink| {
+        sink.write_header(&columns, metadata)?;
+        // rows of the current datetime & file name written so far: (MACB, meta, size, source)
+        let mut group : Vec<(String, String, u64, String)> = vec![];
+        while let Some(Head { event, line, input }) = heap.pop() {
+            if interrupt::interrupted() {
+                break;
+            }
+            let source = event.source_type.as_deref().and_then(|s| s.parse().ok()).unwrap_or(SourceType::default()).to_string();
+            let row = (event.macb.clone(), event.meta.clone(), event.size, source.clone());
+            match group.contains(&row) {
+                true => duplicates += 1,
+                false => {
+                    sink.write_event(&Event {
+                        datetime: event.datetime,
+                        macb: event.macb.clone(),
+                        meta: Cow::Borrowed(&event.meta),
+                        size: event.size,
+                        filename: Cow::Borrowed(&event.filename),
+                        md5: (schema >= SchemaLevel::V2).then(|| event.md5.as_deref().unwrap_or("0")),
+                        mode: (schema >= SchemaLevel::V3).then(|| event.mode.as_deref().unwrap_or_default()),
+                        uid: (schema >= SchemaLevel::V2).then(|| event.uid.as_deref().unwrap_or_default()),
+                        gid: (schema >= SchemaLevel::V3).then(|| event.gid.as_deref().unwrap_or_default()),
+                        unset: (schema >= SchemaLevel::V3).then(|| event.unset.clone().unwrap_or_default()),
+                        tags: tagged.then(|| (event.tags.as_deref().unwrap_or(&[]), event.score.unwrap_or(0))),
+                        detection: enriched.then(|| event.detection.clone().flatten()),
+                        source_type: Some(source),
+                        precision: None,
+                        annotation: None,
+                        session: None,
+                        // rows of a timeline exported with --provenance keep their own source file & line
+                        provenance: provenance.then(|| match &event.source_file {
+                            Some(file) => (Some(file.as_str()), event.source_line),
+                            None => (Some(inputs[input]), Some(line))
+                        }),
+                        raw: None
+                    })?;
+                    written += 1;
+                    group.push(row);
+                }
+            }
+
+            let next = streams[input].next()?;
+            heap.extend(next.map(|(event, line)| Head { event, line, input }));
+            if heap.peek().is_none_or(|h| (h.event.datetime, &h.event.filename) != (event.datetime, &event.filename)) {
+                group.clear();
+            }
+        }
+        sink.finish(interrupt::interrupted())
+    })?;
+    Ok((written, duplicates))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(datetime: &str, filename: &str, meta: &str) -> String {
+        format!("{{\"datetime\":\"{datetime}\",\"macb\":\"m...\",\"meta\":\"{meta}\",\"size\":1,\"filename\":\"{filename}\"}}\n")
+    }
+
+    // datetime, file name & meta of an output row
+    type Row = (String, String, String);
+
+    // JSON lines inputs merged to a JSON lines output: its rows & the duplicates skipped
+    fn merged(name: &str, inputs: &[String]) -> Result<(Vec<Row>, usize), Box<dyn Error>> {
+        let directory = std::env::temp_dir().join(format!("mactime-sorted-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&directory)?;
+        let paths : Vec<String> = (0..inputs.len()).map(|i| directory.join(format!("{i}.jsonl")).display().to_string()).collect();
+        for (path, content) in paths.iter().zip(inputs) {
+            std::fs::write(path, content)?;
+        }
+        let output = directory.join("merged.jsonl");
+        let paths : Vec<&str> = paths.iter().map(String::as_str).collect();
+        let result = merge(&paths, &BodyFile::new(), Some(&output), OutputFormat::Jsonl, &serde_json::json!({}), false, SchemaLevel::V1);
+        let content = std::fs::read_to_string(&output).unwrap_or_default();
+        std::fs::remove_dir_all(&directory).ok();
+        let (_, duplicates) = result?;
+        let rows = content.lines().map(|line| {
+            let value : serde_json::Value = serde_json::from_str(line).expect("JSON line");
+            let field = |name: &str| value[name].as_str().unwrap_or_default().to_string();
+            (field("datetime"), field("filename"), field("meta"))
+        }).collect();
+        Ok((rows, duplicates))
+    }
+
+    #[test]
+    fn sorted_inputs_merged_with_ties() {
+        let first = [event("2020-07-21T00:00:00Z", "/b", "1"), event("2020-07-21T00:00:01Z", "/a", "2"), event("2020-07-21T00:00:02Z", "/z", "3")].concat();
+        let second = [event("2020-07-21T00:00:00Z", "/a", "4"), event("2020-07-21T00:00:01Z", "/a", "5"), event("2020-07-21T00:00:01Z", "/a", "2")].concat();
+        let (rows, duplicates) = merged("ties", &[first, second]).expect("merged");
+        let row = |datetime: &str, filename: &str, meta: &str| (format!("2020-07-21T00:00:0{datetime}Z"), filename.to_string(), meta.to_string());
+        // datetime, then file name, then the order of the inputs, the row read twice written once
+        assert_eq!(rows, vec![row("0", "/a", "4"), row("0", "/b", "1"), row("1", "/a", "2"), row("1", "/a", "5"), row("2", "/z", "3")]);
+        assert_eq!(duplicates, 1);
+    }
+
+    #[test]
+    fn unsorted_input_is_rejected() {
+        let sorted = event("2020-07-21T00:00:00Z", "/a", "1");
+        let unsorted = [event("2020-07-21T00:00:05Z", "/a", "2"), event("2020-07-21T00:00:01Z", "/a", "3")].concat();
+        let error = merged("unsorted", &[sorted, unsorted]).expect_err("unsorted input").to_string();
+        assert!(error.ends_with("is not sorted by datetime (line 2), merge it without --assume-sorted"), "{error}");
+    }
+}
@@ -0,0 +1,92 @@
+use std::{path::Path, error::Error, collections::HashSet};
+use ring::digest;
+use serde::{Serialize, Deserialize};
+
+use crate::bodyfile::TimestampEntry;
+
+/*
+Persistent seen-set (--state): the hashes of the rows already emitted by the previous runs, so that repeated runs over
+growing inputs (continuous collection, append workflows) only emit the new rows, e.g. shipped to a SIEM without
+duplicates. Rows are identified as by the dedup of the inputs: datetime, MACB, source, meta, size & file name.
+The state is only updated once the output is completely written, an interrupted run emitting its rows again.
+*/
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct StateFile {
+    version: u32,
+    hashes: Vec<u128>
+}
+
+#[derive(Debug, Default)]
+pub struct SeenSet {
+    hashes: HashSet<u128>,
+    added: usize // rows recorded by this run
+}
+
+// SHA-256 of the row identity, truncated to 128 bits: no collision in practice over billions of rows
+pub(crate) fn row_hash(entry: &TimestampEntry) -> u128 {
+    let mut context = digest::Context::new(&digest::SHA256);
+    for field in [
+        &entry.datetime.timestamp_nanos_opt().unwrap_or(entry.datetime.timestamp()).to_string(),
+        &entry.desc.to_string(),
+        &entry.source.to_string(),
+        &*entry.meta,
+        &entry.size.to_string(),
+        &*entry.filename
+    ] {
+        context.update(field.as_bytes());
+        context.update(b"\0");
+    }
+    let mut hash = [0; 16];
+    hash.copy_from_slice(&context.finish().as_ref()[..16]);
+    u128::from_be_bytes(hash)
+}
+
+impl SeenSet {
+    // empty on the first run, when the state file doesn't exist yet
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let state : StateFile = bincode::deserialize(&std::fs::read(path)?)
+            .map_err(|e| format!("Invalid state file {}: {e}", path.display()))?;
+        if state.version != VERSION {
+            return Err(format!("State file {} was written by another version of mactime (version {}, expected {VERSION})", path.display(), state.version).into());
+        }
+        Ok(Self { hashes: state.hashes.into_iter().collect(), added: 0 })
+    }
+
+    // written to a temporary file first, so that a crash never leaves a truncated state (all the rows emitted again)
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut hashes : Vec<u128> = self.hashes.iter().copied().collect();
+        hashes.sort_unstable();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, bincode::serialize(&StateFile { version: VERSION, hashes })?)?;
+        std::fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    pub fn added(&self) -> usize {
+        self.added
+    }
+
+    pub(crate) fn contains(&self, entry: &TimestampEntry) -> bool {
+        self.hashes.contains(&row_hash(entry))
+    }
+
+    pub(crate) fn insert(&mut self, entry: &TimestampEntry) {
+        if self.hashes.insert(row_hash(entry)) {
+            self.added += 1;
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+use chrono::{DateTime, Utc};
+
+use crate::bodyfile::BodyFile;
+
+/* Summary of the parsed bodyfile: deleted vs allocated entries are a quick indicator of mass-deletion/anti-forensics */
+#[derive(Debug, Default)]
+pub struct EntryStats {
+    count: usize,
+    first: Option<DateTime<Utc>>,
+    last: Option<DateTime<Utc>>
+}
+
+impl EntryStats {
+    fn add(&mut self, timestamps: impl Iterator<Item = DateTime<Utc>>) {
+        self.count += 1;
+        for date in timestamps {
+            self.first = Some(self.first.map_or(date, |first| first.min(date)));
+            self.last = Some(self.last.map_or(date, |last| last.max(date)));
+        }
+    }
+}
+
+impl fmt::Display for EntryStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.count)?;
+        if let (Some(first), Some(last)) = (self.first, self.last) {
+            write!(f, " ({} .. {})", first.format("%Y-%m-%d %H:%M:%S"), last.format("%Y-%m-%d %H:%M:%S"))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    allocated: EntryStats,
+    deleted: EntryStats
+}
+
+impl Stats {
+    pub fn build(bodyfile: &BodyFile) -> Self {
+        let mut stats = Stats::default();
+        for entry in bodyfile.entries() {
+            let current = if entry.is_deleted() { &mut stats.deleted } else { &mut stats.allocated };
+            current.add(entry.timestamps());
+        }
+        stats
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.allocated.count + self.deleted.count;
+        let ratio = if total > 0 { self.deleted.count as f64 * 100.0 / total as f64 } else { 0.0 };
+        writeln!(f, "Allocated entries: {}", self.allocated)?;
+        writeln!(f, "Deleted entries: {}", self.deleted)?;
+        write!(f, "Deleted ratio: {ratio:.2}%")
+    }
+}
@@ -0,0 +1,158 @@
+use std::{error::Error, fs::File, io::{BufRead, BufReader}, path::Path, time::Duration};
+
+use crate::bodyfile::{BodyFile, BodyFileParser, ParseOptions, TimeOffset};
+use crate::{archive, interrupt, metrics};
+
+/*
+Streaming mode (--stream): the bodyfiles of full disk images take tens of gigabytes once parsed, when the rows are
+only written once, in the order of the input. Each input is read by chunks of lines, each chunk parsed, expanded to
+its MACB rows, filtered and written before the next one is read, so that the memory of the run is bounded by a
+chunk whatever the size of the inputs. Options needing the whole timeline (sorts, deduplication, reports, ...) are
+refused, and the inputs are read as UTF-8 bodyfiles, plain files, pipes or the standard input.
+With --follow, the end of the last input is waited on (tail -f) until Ctrl-C, the lines appended being written as soon
+as they are read.
+*/
+pub const CHUNK_LINES: usize = 10_000;
+const FOLLOW_POLL: Duration = Duration::from_millis(500);
+
+// chunks of an input, in order
+pub struct Chunks<R: BufRead> {
+    reader: R,
+    options: ParseOptions,
+    input: usize, // index of the input (--provenance)
+    lines: usize, // lines read so far
+    line: Vec<u8>, // line being read, the end of a line still being appended kept until it is complete (--follow)
+    follow: bool,
+    done: bool
+}
+
+impl<R: BufRead> Chunks<R> {
+    pub fn new(reader: R, options: ParseOptions, input: usize) -> Self {
+        Self { reader, options, input, lines: 0, line: vec![], follow: false, done: false }
+    }
+
+    // the end of the input waited on for new lines until Ctrl-C (--follow)
+    pub fn follow(mut self) -> Self {
+        self.follow = true;
+        self
+    }
+}
+
+impl<R: BufRead> Iterator for Chunks<R> {
+    type Item = Result<BodyFile, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || interrupt::interrupted() {
+            return None;
+        }
+        let first_line = self.lines + 1;
+        let mut content = String::new();
+        while self.lines + 1 < first_line + CHUNK_LINES {
+            match self.reader.read_until(b'\n', &mut self.line) {
+                // end of the input for now: the lines read so far written at once, then new ones waited for
+                Ok(0) if self.follow && !interrupt::interrupted() => {
+                    if !content.is_empty() {
+                        break;
+                    }
+                    std::thread::sleep(FOLLOW_POLL);
+                    continue;
+                }
+                Ok(0) => {
+                    self.done = true;
+                    metrics::input_parsed(0, 0); // its rows counted by chunk
+                    break;
+                }
+                Ok(_) if self.follow && !self.line.ends_with(b"\n") => continue, // line still being appended
+                Ok(_) => self.lines += 1,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+            }
+            let Ok(text) = std::str::from_utf8(&self.line) else {
+                self.done = true;
+                return Some(Err(format!("line {} is not UTF-8, --stream only reads UTF-8 inputs", self.lines).into()));
+            };
+            // the other lines blanked (--lines, --shard), so that the line numbers are still the ones of the input
+            let header = self.lines == 1 && self.options.has_header;
+            match header || self.options.selects(self.lines) {
+                true => content += if self.lines == 1 { text.trim_start_matches('\u{feff}') } else { text },
+                false => content.push('\n')
+            }
+            self.line.clear();
+        }
+        if content.is_empty() {
+            return None;
+        }
+        let chunk = BodyFileParser::build_chunk(&content, &self.options, first_line == 1 && self.options.has_header, first_line as u64, self.input);
+        metrics::rows_parsed(chunk.datetime_len(), chunk.invalid_lines() + chunk.invalid_timestamps());
+        Some(Ok(chunk))
+    }
+}
+
+// chunks of the inputs one after the other, each one opened once the previous one is read
+// `follow`: the last input followed as it grows (--follow)
+pub fn chunks<'a>(inputs: &'a [&str], options: &'a ParseOptions, offsets: &'a [TimeOffset], follow: bool) -> impl Iterator<Item = Result<BodyFile, Box<dyn Error>>> + 'a {
+    inputs.iter().enumerate().flat_map(move |(index, input)| -> Box<dyn Iterator<Item = Result<BodyFile, Box<dyn Error>>>> {
+        if archive::split(input).is_some() {
+            return Box::new(std::iter::once(Err(format!("{input}: --stream reads plain files and pipes, not archive members").into())));
+        }
+        let offset = TimeOffset::find(offsets, Path::new(input));
+        if let Some(offset) = offset {
+            eprintln!("Clock of {input} corrected by {offset}");
+        }
+        let options = ParseOptions { time_offset: offset.map(TimeOffset::offset), ..options.clone() };
+        let reader : Box<dyn BufRead> = match *input == archive::STDIN {
+            true => Box::new(std::io::stdin().lock()),
+            false => match File::open(input) {
+                Ok(file) => Box::new(BufReader::new(file)),
+                Err(e) => return Box::new(std::iter::once(Err(format!("{input}: {e}").into())))
+            }
+        };
+        let chunks = match follow && index + 1 == inputs.len() {
+            true => Chunks::new(reader, options, index).follow(),
+            false => Chunks::new(reader, options, index)
+        };
+        Box::new(chunks.map(move |chunk| chunk.map_err(|e| format!("{input}: {e}").into())))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bodyfile::DateFilter;
+    use crate::filterset::parse_filter_args;
+    use crate::matcher::KeywordMatcher;
+
+    type Row = (String, String, i64);
+
+    fn rows(bodyfile: &BodyFile) -> Vec<Row> {
+        bodyfile.meta_events().map(|(meta, name, datetime)| (meta.to_string(), name.to_string(), datetime.timestamp())).collect()
+    }
+
+    #[test]
+    fn streamed_rows_are_the_buffered_ones() {
+        // more lines than a chunk, invalid lines & several rows per record
+        let content : String = (0..2 * CHUNK_LINES + 500).map(|i| match i % 4999 {
+            0 => "not a bodyfile line\n".to_string(),
+            _ => format!("0|/dir{}/f{i}|{i}-128-1|r|0|0|1|{}|{}|1595291898|1595291898\n", i % 7, 1595200000 + i * 10, 1595291898 + i % 3)
+        }).collect();
+        let options = ParseOptions {
+            filter: Some(DateFilter::new(parse_filter_args("2020-07-20T00:00..2020-07-21T00:00").expect("filter"))),
+            lines: Some(3..=2 * CHUNK_LINES),
+            ..ParseOptions::default()
+        };
+        let keywords = KeywordMatcher::new(["/dir3/", "/dir5/"], false).expect("keywords");
+
+        let mut buffered = BodyFileParser::build_bytes(content.clone().into_bytes(), &options).expect("bodyfile parsed");
+        buffered.retain_keywords(&keywords, true);
+        let mut streamed = vec![];
+        for chunk in Chunks::new(std::io::Cursor::new(content), options, 0) {
+            let mut chunk = chunk.expect("chunk parsed");
+            chunk.retain_keywords(&keywords, true);
+            streamed.extend(rows(&chunk));
+        }
+        assert!(!streamed.is_empty());
+        assert_eq!(streamed, rows(&buffered));
+    }
+}
@@ -0,0 +1,196 @@
+use std::{path::{Path, PathBuf}, error::Error, fmt};
+
+use crate::analysis::file_label;
+use crate::bodyfile::{BodyFile, BodyFileParser, DateFilter, EpochBase, ParseOptions, TimeOffset, TimeUnit};
+use crate::evtx;
+use crate::interrupt;
+use crate::mft::Mft;
+use crate::parallel;
+use crate::prefetch;
+use crate::usn;
+
+/*
+Supertimeline: run every artifact parser, then merge, dedup & sort their rows in a single timeline.
+Each stage can run on its own, intermediate rows being checkpointed to disk (parse => one file per input, merge => merged timeline).
+*/
+
+const PARSED_EXTENSION: &str = "parsed";
+const MERGED: &str = "merged.bin";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Parse,
+    Merge,
+    Sort
+}
+
+impl std::str::FromStr for Stage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parse" => Ok(Stage::Parse),
+            "merge" => Ok(Stage::Merge),
+            "sort" => Ok(Stage::Sort),
+            _ => Err(format!("Unknown stage '{s}' (parse, merge, sort)"))
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Stage::Parse => "parse",
+            Stage::Merge => "merge",
+            Stage::Sort => "sort"
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Sources<'a> {
+    pub mft: Option<&'a Path>,
+    pub usn: Option<&'a Path>,
+    pub evtx: Option<&'a Path>,
+    pub prefetch: Option<&'a Path>,
+    pub bodyfiles: Vec<&'a Path>,
+    pub offsets: Vec<TimeOffset>, // clock skew corrections
+    pub epoch_base: EpochBase, // bodyfile timestamps epoch ...
+    pub unit: TimeUnit // ... & unit
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Artifact {
+    Mft,
+    Usn,
+    Evtx,
+    Prefetch,
+    Bodyfile
+}
+
+// a file, or the files of a directory with this extension (sorted for a reproducible output)
+fn files(path: &Path, extension: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files : Vec<PathBuf> = std::fs::read_dir(path)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case(extension)))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+impl Sources<'_> {
+    // every input file, in a fixed order so checkpoint names are stable between runs
+    fn inputs(&self) -> Result<Vec<(Artifact, PathBuf)>, Box<dyn Error>> {
+        let mut inputs = vec![];
+        inputs.extend(self.mft.map(|p| (Artifact::Mft, p.to_path_buf())));
+        inputs.extend(self.usn.map(|p| (Artifact::Usn, p.to_path_buf())));
+        if let Some(directory) = self.evtx {
+            inputs.extend(files(directory, "evtx")?.into_iter().map(|p| (Artifact::Evtx, p)));
+        }
+        if let Some(directory) = self.prefetch {
+            inputs.extend(files(directory, "pf")?.into_iter().map(|p| (Artifact::Prefetch, p)));
+        }
+        inputs.extend(self.bodyfiles.iter().map(|p| (Artifact::Bodyfile, p.to_path_buf())));
+        Ok(inputs)
+    }
+}
+
+fn parse_input(artifact: Artifact, path: &Path, mft: Option<&Mft>, filter: &Option<DateFilter>, sources: &Sources) -> Result<BodyFile, Box<dyn Error>> {
+    let mut bodyfile = BodyFile::new();
+    match (artifact, mft) {
+        (Artifact::Mft, Some(mft)) => {
+            for entry in mft.entries() {
+                bodyfile.add_entry(entry);
+            }
+            bodyfile.build_timeline(filter);
+        }
+        (Artifact::Mft, None) => unreachable!("$MFT parsed first"),
+        (Artifact::Usn, _) => usn::parse_usn(&mut bodyfile, path, mft, filter)?,
+        (Artifact::Evtx, _) => evtx::parse_evtx(&mut bodyfile, path, filter)?,
+        (Artifact::Prefetch, _) => prefetch::parse_prefetch(&mut bodyfile, path, filter)?,
+        (Artifact::Bodyfile, _) => bodyfile = BodyFileParser::build(path, &ParseOptions { filter: *filter, epoch_base: sources.epoch_base, unit: sources.unit, ..ParseOptions::default() })?
+    }
+    bodyfile.set_input(path); // rows of the binary artifacts have no line
+    Ok(bodyfile)
+}
+
+// parse stage: one timeline per input, sorted, written to `checkpoints` if given (inputs already checkpointed are skipped)
+// inputs are parsed by up to `threads` workers, the $MFT being shared by the ones that need it
+pub fn parse(sources: &Sources, filter: Option<DateFilter>, checkpoints: Option<&Path>, threads: usize) -> Result<Vec<BodyFile>, Box<dyn Error>> {
+    let mut inputs = vec![];
+    for (i, (artifact, path)) in sources.inputs()?.into_iter().enumerate() {
+        let checkpoint = checkpoints.map(|directory| {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            directory.join(format!("{:03}_{}.{PARSED_EXTENSION}", i + 1, file_label(&name)))
+        });
+        match checkpoint.as_ref().filter(|c| c.exists()) {
+            Some(checkpoint) => eprintln!("Checkpoint {} of {} already written, skipping", checkpoint.display(), path.display()),
+            None => inputs.push((artifact, path, checkpoint))
+        }
+    }
+
+    // the $MFT is also used to resolve the paths of the USN journal records, only read when needed
+    let mft = match (sources.mft, inputs.iter().any(|(artifact, _, _)| matches!(artifact, Artifact::Mft | Artifact::Usn))) {
+        (Some(path), true) => Some(Mft::from_path(path)?),
+        _ => None
+    };
+
+    let parsed = parallel::map(inputs, threads, |(artifact, path, checkpoint)| -> Result<Option<BodyFile>, String> {
+        if interrupt::interrupted() {
+            return Ok(None);
+        }
+        // errors on a single file (corrupted EVTX, ...) are reported & skipped, the date filter applies to the corrected times
+        let offset = TimeOffset::find(&sources.offsets, &path);
+        let mut bodyfile = match parse_input(artifact, &path, mft.as_ref(), if offset.is_some() { &None } else { &filter }, sources) {
+            Ok(bodyfile) => bodyfile,
+            Err(e) => {
+                eprintln!("Error parsing {} => {e}", path.display());
+                return Ok(None);
+            }
+        };
+        if interrupt::interrupted() && checkpoint.is_some() {
+            return Ok(None); // a partially parsed input must not be taken for a complete checkpoint
+        }
+        if let Some(offset) = offset {
+            bodyfile.shift(offset.offset());
+            bodyfile.retain_dates(&filter);
+            eprintln!("Clock of {} corrected by {offset}", path.display());
+        }
+        bodyfile.sort_total(); // sorted run, k-way merged by the merge stage
+        eprintln!("Number of datetime records read from {}: {}", path.display(), bodyfile.datetime_len());
+
+        match checkpoint {
+            Some(checkpoint) => bodyfile.write_checkpoint(&checkpoint).map(|_| None).map_err(|e| e.to_string()),
+            None => Ok(Some(bodyfile))
+        }
+    });
+    Ok(parsed.into_iter().collect::<Result<Vec<Option<BodyFile>>, String>>()?.into_iter().flatten().collect())
+}
+
+// timelines checkpointed by the parse stage
+pub fn read_parsed(checkpoints: &Path) -> Result<Vec<BodyFile>, Box<dyn Error>> {
+    files(checkpoints, PARSED_EXTENSION)?.iter()
+        .map(|path| BodyFile::read_checkpoint(path))
+        .collect()
+}
+
+// merge stage: dedup & sort by datetime, with the number of rows of each source
+pub fn merge(parsed: Vec<BodyFile>) -> BodyFile {
+    let mut supertimeline = BodyFile::new();
+    supertimeline.merge(BodyFile::merge_total(parsed)); // k-way merge of the sorted runs
+
+    let duplicates = supertimeline.dedup();
+    eprintln!("Number of duplicate datetime records removed: {duplicates}");
+    for (source, count) in supertimeline.source_counts() {
+        eprintln!("\t{source}: {count}");
+    }
+    supertimeline
+}
+
+pub fn merged_path(checkpoints: &Path) -> PathBuf {
+    checkpoints.join(MERGED)
+}
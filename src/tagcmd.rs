@@ -0,0 +1,108 @@
+use std::{error::Error, io::{BufRead, BufReader, Write}, process::{Child, Command, Stdio}, sync::mpsc, time::{Duration, Instant}};
+use serde::Deserialize;
+
+/*
+External tagging (--tag-cmd): the rows are streamed as JSON lines to the stdin of a classifier process, kept running
+for the whole run, by batches of --tag-batch rows; for each row, it answers a line on its stdout, in the same order:
+a JSON array of tags, each one a string or a {"tag": "...", "weight": <n>} object (e.g. [] or ["c2", {"tag": "lolbin",
+"weight": 20}]). A batch not answered within --tag-timeout stops the run, so that a hung classifier doesn't hang it too.
+Teams hook their proprietary classifiers into the timeline without Rust changes.
+*/
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Tag {
+    Name(String),
+    Weighted { tag: String, #[serde(default)] weight: u32 }
+}
+
+// tags & weights of a row
+pub type RowTags = Vec<(String, u32)>;
+
+pub struct TagCommand {
+    command: String,
+    child: Child,
+    rows: Option<mpsc::Sender<String>>, // to the stdin writer, closed at the end
+    answers: mpsc::Receiver<std::io::Result<String>>, // from the stdout reader
+    batch: usize,
+    timeout: Duration
+}
+
+impl TagCommand {
+    // classifier run through the shell, the rows written & its answers read by threads of their own (no pipe deadlock)
+    pub fn start(command: &str, batch: usize, timeout: Duration) -> Result<Self, Box<dyn Error>> {
+        let mut shell = match cfg!(windows) {
+            true => Command::new("cmd"),
+            false => Command::new("sh")
+        };
+        shell.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(command);
+        let mut child = shell.stdin(Stdio::piped()).stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Tag command '{command}' failed to start: {e}"))?;
+
+        let mut stdin = child.stdin.take().expect("piped stdin");
+        let (rows, to_write) = mpsc::channel::<String>();
+        std::thread::spawn(move || {
+            for row in to_write {
+                if stdin.write_all(row.as_bytes()).and_then(|_| stdin.flush()).is_err() {
+                    break; // classifier gone, reported by the reader
+                }
+            }
+        });
+
+        let stdout = child.stdout.take().expect("piped stdout");
+        let (answer, answers) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if answer.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self { command: command.to_string(), child, rows: Some(rows), answers, batch, timeout })
+    }
+
+    pub fn batch(&self) -> usize {
+        self.batch
+    }
+
+    // tags of each row of a batch, in order
+    pub fn classify(&mut self, rows: &[serde_json::Value]) -> Result<Vec<RowTags>, Box<dyn Error>> {
+        let mut lines = String::new();
+        for row in rows {
+            lines += &serde_json::to_string(row)?;
+            lines.push('\n');
+        }
+        self.rows.as_ref().expect("tag command running").send(lines).map_err(|_| format!("Tag command '{}' exited", self.command))?;
+
+        let deadline = Instant::now() + self.timeout;
+        let mut tags = Vec::with_capacity(rows.len());
+        while tags.len() < rows.len() {
+            let line = match self.answers.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(line) => line?,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    let _ = self.child.kill();
+                    return Err(format!("Tag command '{}' didn't answer a batch of {} rows within {:?}", self.command, rows.len(), self.timeout).into());
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Err(format!("Tag command '{}' exited after {} of {} rows of a batch", self.command, tags.len(), rows.len()).into())
+            };
+            let answer : Vec<Tag> = serde_json::from_str(&line)
+                .map_err(|e| format!("Invalid answer of tag command '{}': {e} ({line})", self.command))?;
+            tags.push(answer.into_iter().map(|t| match t {
+                Tag::Name(tag) => (tag, 0),
+                Tag::Weighted { tag, weight } => (tag, weight)
+            }).collect());
+        }
+        Ok(tags)
+    }
+
+    // end of the rows: the classifier is expected to exit
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.rows.take();
+        let status = self.child.wait()?;
+        if !status.success() {
+            return Err(format!("Tag command '{}' failed ({status})", self.command).into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,511 @@
+use std::{error::Error, fmt, fs, io::Read, path::{Path, PathBuf}, sync::OnceLock};
+use flate2::read::DeflateDecoder;
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Utc};
+
+/*
+Time zones of the output (-z): timestamps are stored & compared in UTC, and only converted when written, to the
+zone of the evidence so that rows read like the suspect machine's clock. Zones are IANA names (Europe/Paris) read
+from the system time zone database (TZif files of $TZDIR or /usr/share/zoneinfo): the transitions recorded in the
+file, then the POSIX TZ rule of its footer (CET-1CEST,M3.5.0,M10.5.0/3) for the dates after the last one.
+Without a system database (Windows), the copy of the tzdata embedded in the binary is read instead.
+*/
+const ZONEINFO: [&str; 3] = ["/usr/share/zoneinfo", "/usr/lib/zoneinfo", "/usr/share/lib/zoneinfo"];
+
+// tzdata 2025b, raw deflate of the TZif files sorted by name: [u16 LE name length][name][u32 LE TZif length][TZif]...
+// built by scripts/zoneinfo.py from the zoneinfo directory of a tzdata release
+const EMBEDDED: &[u8] = include_bytes!("zoneinfo.bin");
+
+// offset, DST flag & abbreviation in force at an instant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalType {
+    pub offset: i32, // seconds east of UTC
+    pub dst: bool,
+    pub abbreviation: String
+}
+
+// day of a year a POSIX rule switches on
+#[derive(Debug, Clone, Copy)]
+enum RuleDay {
+    Julian(u16), // Jn: 1..=365, February 29th never counted
+    Day(u16), // n: 0..=365
+    Month(u32, u32, u32) // Mm.w.d: month, week (5: last), weekday (0: Sunday)
+}
+
+impl RuleDay {
+    fn date(&self, year: i32) -> Option<NaiveDate> {
+        match *self {
+            RuleDay::Julian(day) => {
+                let date = NaiveDate::from_yo_opt(year, day as u32)?;
+                Some(if date.leap_year() && day >= 60 { date.succ_opt()? } else { date })
+            }
+            RuleDay::Day(day) => NaiveDate::from_yo_opt(year, day as u32 + 1),
+            RuleDay::Month(month, week, weekday) => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let mut day = 1 + (weekday + 7 - first.weekday().num_days_from_sunday()) % 7 + (week - 1) * 7;
+                while NaiveDate::from_ymd_opt(year, month, day).is_none() {
+                    day -= 7; // 5th week: last one of the month
+                }
+                NaiveDate::from_ymd_opt(year, month, day)
+            }
+        }
+    }
+}
+
+// day of a DST switch, and the local time of the switch in seconds
+type Switch = (RuleDay, i32);
+
+// POSIX TZ rule: standard time, and DST with the local times it starts & ends at
+#[derive(Debug, Clone)]
+struct Rule {
+    standard: LocalType,
+    dst: Option<(LocalType, Switch, Switch)>
+}
+
+impl Rule {
+    fn local_type(&self, utc: i64) -> &LocalType {
+        let Some((dst, start, end)) = &self.dst else { return &self.standard };
+        let year = DateTime::<Utc>::from_timestamp(utc + self.standard.offset as i64, 0).map_or(1970, |d| d.year());
+        let instant = |(day, time): &Switch, offset: i32| day.date(year)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|d| d.and_utc().timestamp() + *time as i64 - offset as i64);
+        // the switch to DST at standard time, back at DST time
+        let (Some(start), Some(end)) = (instant(start, self.standard.offset), instant(end, dst.offset)) else { return &self.standard };
+        let in_dst = match start < end {
+            true => start <= utc && utc < end,
+            false => !(end <= utc && utc < start) // southern hemisphere
+        };
+        if in_dst { dst } else { &self.standard }
+    }
+
+    fn parse(tz: &str) -> Result<Self, String> {
+        let error = || format!("Unsupported TZ rule '{tz}'");
+        let mut rest = tz;
+        let standard_name = Self::name(&mut rest).ok_or_else(error)?;
+        let standard = LocalType { offset: -Self::time(&mut rest).ok_or_else(error)?, dst: false, abbreviation: standard_name };
+        if rest.is_empty() {
+            return Ok(Self { standard, dst: None });
+        }
+        let dst_name = Self::name(&mut rest).ok_or_else(error)?;
+        let dst_offset = match rest.starts_with(',') {
+            true => standard.offset + 3600,
+            false => -Self::time(&mut rest).ok_or_else(error)?
+        };
+        let dst = LocalType { offset: dst_offset, dst: true, abbreviation: dst_name };
+        let mut switch = || -> Option<Switch> {
+            rest = rest.strip_prefix(',')?;
+            let day = match rest.chars().next()? {
+                'J' => {
+                    rest = &rest[1..];
+                    RuleDay::Julian(Self::number(&mut rest)? as u16)
+                }
+                'M' => {
+                    rest = &rest[1..];
+                    let month = Self::number(&mut rest)?;
+                    rest = rest.strip_prefix('.')?;
+                    let week = Self::number(&mut rest)?;
+                    rest = rest.strip_prefix('.')?;
+                    let weekday = Self::number(&mut rest)?;
+                    if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+                        return None;
+                    }
+                    RuleDay::Month(month, week, weekday)
+                }
+                _ => RuleDay::Day(Self::number(&mut rest)? as u16)
+            };
+            let time = match rest.strip_prefix('/') {
+                Some(time) => {
+                    rest = time;
+                    Self::time(&mut rest)?
+                }
+                None => 7200
+            };
+            Some((day, time))
+        };
+        let (start, end) = (switch().ok_or_else(error)?, switch().ok_or_else(error)?);
+        if !rest.is_empty() {
+            return Err(error());
+        }
+        Ok(Self { standard, dst: Some((dst, start, end)) })
+    }
+
+    // CET, or <+03> for numeric abbreviations
+    fn name(rest: &mut &str) -> Option<String> {
+        let (name, remaining) = match rest.strip_prefix('<') {
+            Some(quoted) => {
+                let (name, remaining) = quoted.split_once('>')?;
+                (name, remaining)
+            }
+            None => {
+                let end = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+                rest.split_at(end)
+            }
+        };
+        *rest = remaining;
+        (name.len() >= 3).then(|| name.to_string())
+    }
+
+    fn number(rest: &mut &str) -> Option<u32> {
+        let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let (digits, remaining) = rest.split_at(end);
+        *rest = remaining;
+        digits.parse().ok()
+    }
+
+    // [+-]hh[:mm[:ss]] in seconds
+    fn time(rest: &mut &str) -> Option<i32> {
+        let sign = match rest.chars().next()? {
+            '-' => -1,
+            '+' => 1,
+            _ => 0
+        };
+        if sign != 0 {
+            *rest = &rest[1..];
+        }
+        let mut seconds = Self::number(rest)? as i32 * 3600;
+        for unit in [60, 1] {
+            let Some(remaining) = rest.strip_prefix(':') else { break };
+            *rest = remaining;
+            seconds += Self::number(rest)? as i32 * unit;
+        }
+        Some(if sign < 0 { -seconds } else { seconds })
+    }
+}
+
+// longest change of offset at a DST switch is 2 hours (Antarctica/Troll), a switch is looked for around a row by half a day
+const SWITCH_WINDOW: i64 = 6 * 3600;
+
+// rows around a DST switch (--flag-dst), their local time read wrong without the offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Ambiguous, // local time seen twice: the hour repeated by a switch back
+    Skipped // local time just after the hour skipped by a switch forward
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Transition::Ambiguous => write!(f, "dst-ambiguous"),
+            Transition::Skipped => write!(f, "dst-skipped")
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TimeZone {
+    name: String,
+    transitions: Vec<(i64, usize)>, // UTC instant & local type from then on
+    types: Vec<LocalType>,
+    rule: Option<Rule> // after the last transition
+}
+
+impl fmt::Display for TimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+fn be_i64(bytes: &[u8]) -> i64 {
+    i64::from_be_bytes(bytes.try_into().expect("8 bytes"))
+}
+
+fn be_i32(bytes: &[u8]) -> i32 {
+    i32::from_be_bytes(bytes.try_into().expect("4 bytes"))
+}
+
+impl TimeZone {
+    pub fn utc() -> Self {
+        let utc = LocalType { offset: 0, dst: false, abbreviation: "UTC".to_string() };
+        Self { name: "UTC".to_string(), transitions: vec![], types: vec![utc.clone()], rule: Some(Rule { standard: utc, dst: None }) }
+    }
+
+    // directory of the system time zone database
+    pub fn database() -> Option<PathBuf> {
+        std::env::var_os("TZDIR").map(PathBuf::from).into_iter()
+            .chain(ZONEINFO.iter().map(PathBuf::from))
+            .find(|d| d.is_dir())
+    }
+
+    // IANA names of the database, sorted: the TZif files, without the posix/ & right/ copies, and the embedded zones
+    pub fn names() -> Vec<String> {
+        let mut names = vec!["UTC".to_string()];
+        if let Some(database) = Self::database() {
+            walk(&database, &database, &mut names);
+        }
+        names.extend(embedded().iter().map(|(name, _)| name.clone()));
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // IANA name (Europe/Paris), UTC being always known
+    pub fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        if name.eq_ignore_ascii_case("UTC") {
+            return Ok(Self::utc());
+        }
+        if name.is_empty() || name.starts_with('/') || name.split('/').any(|part| part == "..") {
+            return Err(format!("Invalid time zone '{name}'").into());
+        }
+        // a zone missing from an old system database is still read from the embedded one
+        let bytes = Self::database().and_then(|database| fs::read(database.join(name)).ok())
+            .or_else(|| embedded().iter().find(|(n, _)| n == name).map(|(_, bytes)| bytes.clone()));
+        let bytes = bytes.ok_or_else(|| match suggest(name, &Self::names()) {
+            Some(suggestion) => format!("Unknown time zone '{name}', did you mean '{suggestion}'? (see mactime timezones)"),
+            None => format!("Unknown time zone '{name}' (see mactime timezones)")
+        })?;
+        Self::parse(name, &bytes).map_err(|e| format!("Time zone {name}: {e}").into())
+    }
+
+    // TZif file (RFC 8536), version 2+ data block when present
+    fn parse(name: &str, bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let invalid = || "not a TZif file".to_string();
+        // counts of the header: none above the size of the file, negative ones invalid, for the sizes computed from them
+        let header = |bytes: &[u8]| -> Option<[usize; 6]> {
+            if bytes.len() < 44 || !bytes.starts_with(b"TZif") {
+                return None;
+            }
+            let counts : [Option<usize>; 6] = std::array::from_fn(|i| usize::try_from(be_i32(&bytes[20 + i * 4..24 + i * 4])).ok());
+            counts.iter().all(|c| c.is_some_and(|c| c <= bytes.len())).then(|| counts.map(Option::unwrap_or_default))
+        };
+        let [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt] = header(bytes).ok_or_else(invalid)?;
+        let mut data = &bytes[44..];
+        let mut time_size = 4;
+        if bytes[4] >= b'2' {
+            let v1 = timecnt * 5 + typecnt * 6 + charcnt + leapcnt * 8 + isstdcnt + isutcnt;
+            data = data.get(v1..).ok_or_else(invalid)?;
+            time_size = 8;
+        }
+        let [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt] = match time_size {
+            8 => {
+                let counts = header(data).ok_or_else(invalid)?;
+                data = &data[44..];
+                counts
+            }
+            _ => [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt]
+        };
+        let size = timecnt * (time_size + 1) + typecnt * 6 + charcnt + leapcnt * (time_size + 4) + isstdcnt + isutcnt;
+        if data.len() < size || typecnt == 0 {
+            return Err(invalid().into());
+        }
+
+        let times = &data[..timecnt * time_size];
+        let indexes = &data[timecnt * time_size..timecnt * (time_size + 1)];
+        let infos = &data[timecnt * (time_size + 1)..timecnt * (time_size + 1) + typecnt * 6];
+        let chars = &data[timecnt * (time_size + 1) + typecnt * 6..][..charcnt];
+        let types = infos.chunks(6).map(|info| LocalType {
+            offset: be_i32(&info[..4]),
+            dst: info[4] != 0,
+            abbreviation: chars.get(info[5] as usize..)
+                .map(|a| String::from_utf8_lossy(a.split(|b| *b == 0).next().unwrap_or_default()).to_string())
+                .unwrap_or_default()
+        }).collect::<Vec<LocalType>>();
+        let mut transitions = vec![];
+        for (i, index) in indexes.iter().enumerate() {
+            let time = match time_size {
+                8 => be_i64(&times[i * 8..i * 8 + 8]),
+                _ => be_i32(&times[i * 4..i * 4 + 4]) as i64
+            };
+            if *index as usize >= types.len() {
+                return Err(invalid().into());
+            }
+            transitions.push((time, *index as usize));
+        }
+
+        // footer: \n<POSIX TZ rule>\n
+        let footer = &data[size..];
+        let rule = match (time_size, footer.strip_prefix(b"\n").and_then(|f| f.split(|b| *b == b'\n').next())) {
+            (8, Some(tz)) if !tz.is_empty() => Some(Rule::parse(&String::from_utf8_lossy(tz))?),
+            _ => None
+        };
+        Ok(Self { name: name.to_string(), transitions, types, rule })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_utc(&self) -> bool {
+        self.transitions.is_empty() && self.types.iter().all(|t| t.offset == 0)
+            && self.rule.as_ref().is_none_or(|r| r.dst.is_none() && r.standard.offset == 0)
+    }
+
+    // local type in force at `datetime`
+    pub fn local_type(&self, datetime: DateTime<Utc>) -> &LocalType {
+        let utc = datetime.timestamp();
+        match self.transitions.partition_point(|(time, _)| *time <= utc) {
+            0 => &self.types[0], // before the first transition (RFC 8536 3.2)
+            n if n == self.transitions.len() && self.rule.is_some() => self.rule.as_ref().expect("rule").local_type(utc),
+            n => &self.types[self.transitions[n - 1].1]
+        }
+    }
+
+    pub fn offset(&self, datetime: DateTime<Utc>) -> FixedOffset {
+        FixedOffset::east_opt(self.local_type(datetime).offset).unwrap_or(FixedOffset::east_opt(0).expect("UTC"))
+    }
+
+    pub fn local(&self, datetime: DateTime<Utc>) -> DateTime<FixedOffset> {
+        datetime.with_timezone(&self.offset(datetime))
+    }
+
+    // switch of offset around `datetime` the row is part of, found by bisection so that the transitions of the file
+    // and the ones of the TZ rule are handled alike
+    pub fn transition(&self, datetime: DateTime<Utc>) -> Option<Transition> {
+        let utc = datetime.timestamp();
+        let offset = |utc: i64| DateTime::from_timestamp(utc, 0).map_or(0, |d| self.local_type(d).offset) as i64;
+        let (mut before, mut after) = (utc - SWITCH_WINDOW, utc + SWITCH_WINDOW);
+        let (old, new) = (offset(before), offset(after));
+        if old == new {
+            return None;
+        }
+        while after - before > 1 {
+            let middle = before + (after - before) / 2;
+            match offset(middle) == old {
+                true => before = middle,
+                false => after = middle
+            }
+        }
+        // `after`: first second of the new offset
+        let change = (new - old).abs();
+        match new < old {
+            true => (after - change <= utc && utc < after + change).then_some(Transition::Ambiguous),
+            false => (after <= utc && utc < after + change).then_some(Transition::Skipped)
+        }
+    }
+}
+
+// zones of the embedded tzdata, decompressed once
+fn embedded() -> &'static [(String, Vec<u8>)] {
+    static ZONES: OnceLock<Vec<(String, Vec<u8>)>> = OnceLock::new();
+    ZONES.get_or_init(|| {
+        let mut bytes = vec![];
+        if DeflateDecoder::new(EMBEDDED).read_to_end(&mut bytes).is_err() {
+            return vec![];
+        }
+        let mut zones = vec![];
+        let mut rest = bytes.as_slice();
+        while rest.len() >= 2 {
+            let length = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+            let Some((name, after)) = rest.get(2..).and_then(|r| (r.len() >= length + 4).then(|| r.split_at(length))) else { break };
+            let size = u32::from_le_bytes(after[..4].try_into().expect("4 bytes")) as usize;
+            let Some(tzif) = after.get(4..4 + size) else { break };
+            zones.push((String::from_utf8_lossy(name).to_string(), tzif.to_vec()));
+            rest = &after[4 + size..];
+        }
+        zones
+    })
+}
+
+fn walk(database: &Path, dir: &Path, names: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if !matches!(entry.file_name().to_str(), Some("posix" | "right")) {
+                walk(database, &path, names);
+            }
+            continue;
+        }
+        let mut magic = [0; 4];
+        let is_tzif = fs::File::open(&path).and_then(|mut f| f.read_exact(&mut magic)).is_ok() && &magic == b"TZif";
+        let name = path.strip_prefix(database).ok().and_then(Path::to_str).map(|n| n.replace('\\', "/"));
+        match name {
+            Some(name) if is_tzif && !matches!(name.as_str(), "localtime" | "posixrules" | "Factory") => names.push(name),
+            _ => {}
+        }
+    }
+}
+
+// closest name to a mistyped one: other case, city without or with a wrong region, then a few typos away
+pub fn suggest(name: &str, names: &[String]) -> Option<String> {
+    let lower = name.to_lowercase();
+    let city = lower.rsplit('/').next().unwrap_or_default();
+    names.iter().find(|n| n.to_lowercase() == lower)
+        .or_else(|| names.iter().find(|n| n.to_lowercase().rsplit('/').next() == Some(city)))
+        .or_else(|| names.iter()
+            .map(|n| (strsim::levenshtein(&n.to_lowercase(), &lower), n))
+            .filter(|(distance, _)| *distance <= 3.max(lower.len() / 4))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, n)| n))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(datetime: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(datetime).expect("RFC 3339").with_timezone(&Utc)
+    }
+
+    fn embedded_zone(name: &str) -> TimeZone {
+        let (_, bytes) = embedded().iter().find(|(n, _)| n == name).expect("embedded zone");
+        TimeZone::parse(name, bytes).expect("TZif")
+    }
+
+    #[test]
+    fn embedded_tzdata_is_the_one_of_the_script() {
+        // SHA-256 printed by scripts/zoneinfo.py
+        let checksum = ring::digest::digest(&ring::digest::SHA256, EMBEDDED);
+        let checksum : String = checksum.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(checksum, "e912eeeaa61c5ace9b104acfce13f6143ddc112b9c96ed23e6dcc81d67e71317");
+    }
+
+    #[test]
+    fn tzif_counts_out_of_the_file() {
+        let (_, bytes) = embedded().iter().find(|(n, _)| n == "Europe/Paris").expect("embedded zone");
+        for (count, value) in [(3, -1i32), (3, i32::MAX), (5, 1 << 30), (0, -8)] {
+            let mut bytes = bytes.clone();
+            bytes[20 + count * 4..24 + count * 4].copy_from_slice(&value.to_be_bytes());
+            assert!(TimeZone::parse("Europe/Paris", &bytes).is_err(), "count {count}: {value}");
+        }
+    }
+
+    #[test]
+    fn embedded_zones_all_parse() {
+        assert!(embedded().len() > 500);
+        for (name, bytes) in embedded() {
+            assert!(TimeZone::parse(name, bytes).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn offsets_of_the_transitions_and_of_the_rule() {
+        let paris = embedded_zone("Europe/Paris");
+        assert_eq!(paris.local_type(at("2020-07-21T00:00:00Z")).offset, 7200);
+        assert_eq!(paris.local_type(at("2020-01-21T00:00:00Z")).offset, 3600);
+        // after the last transition of the file: POSIX rule of the footer
+        assert_eq!(paris.local_type(at("2100-07-21T00:00:00Z")).abbreviation, "CEST");
+        assert_eq!(paris.local_type(at("2100-12-21T00:00:00Z")).abbreviation, "CET");
+    }
+
+    #[test]
+    fn before_the_first_transition_is_type_0() {
+        // local mean time of Paris (+00:09:21) until 1891
+        let paris = embedded_zone("Europe/Paris");
+        assert_eq!(paris.local_type(at("1850-01-01T00:00:00Z")).offset, 561);
+    }
+
+    #[test]
+    fn transitions_around_a_switch() {
+        let paris = embedded_zone("Europe/Paris");
+        // 2020-10-25 03:00 CEST => 02:00 CET, at 01:00 UTC
+        assert_eq!(paris.transition(at("2020-10-25T00:30:00Z")), Some(Transition::Ambiguous));
+        assert_eq!(paris.transition(at("2020-07-21T00:00:00Z")), None);
+    }
+
+    #[test]
+    fn posix_rules() {
+        let rule = Rule::parse("<+03>-3").expect("rule");
+        assert_eq!(rule.local_type(0).offset, 3 * 3600);
+        assert_eq!(rule.local_type(0).abbreviation, "+03");
+        assert!(Rule::parse("CET-1CEST,M3.5.0,M10.5.0/3").is_ok());
+        assert!(Rule::parse("CET-1CEST,M13.5.0,M10.5.0/3").is_err());
+    }
+
+    #[test]
+    fn invalid_names_and_files() {
+        assert!(TimeZone::from_name("../etc/passwd").is_err());
+        assert!(TimeZone::from_name("utc").expect("UTC").is_utc());
+        assert!(TimeZone::parse("x", b"not a TZif file").is_err());
+        assert_eq!(suggest("europe/paris", &["Europe/Paris".to_string()]).as_deref(), Some("Europe/Paris"));
+    }
+}
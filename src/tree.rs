@@ -0,0 +1,54 @@
+use std::{path::Path, error::Error, collections::BTreeMap, io::Write};
+use chrono::{DateTime, Utc};
+
+use crate::bodyfile::BodyFile;
+
+/* Directory tree of the timeline rows, each node annotated with its earliest/latest activity and event count */
+#[derive(Debug, Default)]
+struct Node {
+    count: usize,
+    first: Option<DateTime<Utc>>,
+    last: Option<DateTime<Utc>>,
+    children: BTreeMap<String, Node>
+}
+
+impl Node {
+    fn add(&mut self, date: DateTime<Utc>) {
+        self.count += 1;
+        self.first = Some(self.first.map_or(date, |first| first.min(date)));
+        self.last = Some(self.last.map_or(date, |last| last.max(date)));
+    }
+
+    fn write<W: Write>(&self, writer: &mut W, name: &str, depth: usize) -> std::io::Result<()> {
+        if let (Some(first), Some(last)) = (self.first, self.last) {
+            writeln!(writer, "{}{name} [{} events, {} .. {}]",
+                "  ".repeat(depth),
+                self.count,
+                first.format("%Y-%m-%d %H:%M:%S"),
+                last.format("%Y-%m-%d %H:%M:%S"))?;
+        }
+        for (child_name, child) in self.children.iter() {
+            child.write(writer, child_name, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn write_tree(bodyfile: &BodyFile, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut root = Node::default();
+    for (filename, date) in bodyfile.events() {
+        // every directory on the path accounts for the event
+        let mut node = &mut root;
+        for component in filename.split('/').filter(|c| !c.is_empty()) {
+            node = node.children.entry(component.to_string()).or_default();
+            node.add(date);
+        }
+    }
+
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for (name, node) in root.children.iter() {
+        node.write(&mut writer, name, 0)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use std::{path::Path, error::Error};
+
+use crate::bodyfile::{BodyFile, DateFilter, TimestampDesc, TimestampEntry, SourceType};
+use crate::mft::{self, Mft, u16_at, u32_at, u64_at, utf16_at};
+
+/* Raw $UsnJrnl:$J parser (USN_RECORD_V2/V3): one labeled row per record, parent paths resolved through the $MFT if given */
+
+const REASONS : [(u32, &str); 23] = [
+    (0x0000_0001, "DATA_OVERWRITE"),
+    (0x0000_0002, "DATA_EXTEND"),
+    (0x0000_0004, "DATA_TRUNCATION"),
+    (0x0000_0010, "NAMED_DATA_OVERWRITE"),
+    (0x0000_0020, "NAMED_DATA_EXTEND"),
+    (0x0000_0040, "NAMED_DATA_TRUNCATION"),
+    (0x0000_0100, "FILE_CREATE"),
+    (0x0000_0200, "FILE_DELETE"),
+    (0x0000_0400, "EA_CHANGE"),
+    (0x0000_0800, "SECURITY_CHANGE"),
+    (0x0000_1000, "RENAME_OLD_NAME"),
+    (0x0000_2000, "RENAME_NEW_NAME"),
+    (0x0000_4000, "INDEXABLE_CHANGE"),
+    (0x0000_8000, "BASIC_INFO_CHANGE"),
+    (0x0001_0000, "HARD_LINK_CHANGE"),
+    (0x0002_0000, "COMPRESSION_CHANGE"),
+    (0x0004_0000, "ENCRYPTION_CHANGE"),
+    (0x0008_0000, "OBJECT_ID_CHANGE"),
+    (0x0010_0000, "REPARSE_POINT_CHANGE"),
+    (0x0020_0000, "STREAM_CHANGE"),
+    (0x0040_0000, "TRANSACTED_CHANGE"),
+    (0x0080_0000, "INTEGRITY_CHANGE"),
+    (0x8000_0000, "CLOSE")
+];
+
+// "FILE_CREATE|CLOSE"
+pub fn reasons(flags: u32) -> String {
+    REASONS.iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<&str>>()
+        .join("|")
+}
+
+// (entry, sequence) of a file reference, the low 64 bits of V3 128 bits references
+fn reference(value: u64) -> (u64, u16) {
+    (value & 0xFFFF_FFFF_FFFF, (value >> 48) as u16)
+}
+
+pub fn parse_usn(bodyfile: &mut BodyFile, path: &Path, mft: Option<&Mft>, filter: &Option<DateFilter>) -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(path)?;
+
+    let mut offset = 0;
+    while offset + 8 <= data.len() {
+        let length = u32_at(&data, offset).unwrap_or_default() as usize;
+        // $J is sparse: skip the zeroed ranges, records are 8 bytes aligned
+        if length == 0 {
+            offset += 8;
+            continue;
+        }
+        if length < 0x3C || offset + length > data.len() {
+            eprintln!("Error parsing USN record at offset {offset}: invalid length {length}");
+            offset += 8;
+            continue;
+        }
+        let record = &data[offset..offset + length];
+        offset += (length + 7) & !7;
+
+        let major = u16_at(record, 4).unwrap_or_default();
+        // fields after the references are shifted by 16 bytes in V3 (128 bits references)
+        let (file, parent, shift) = match major {
+            2 => (u64_at(record, 8), u64_at(record, 16), 0),
+            3 => (u64_at(record, 8), u64_at(record, 24), 16),
+            _ => {
+                eprintln!("Error parsing USN record: unsupported version {major}");
+                continue;
+            }
+        };
+        let (Some(file), Some(parent)) = (file, parent) else { continue };
+        let fields = (u64_at(record, 32 + shift), u32_at(record, 40 + shift), u16_at(record, 56 + shift), u16_at(record, 58 + shift));
+        let (Some(timestamp), Some(reason), Some(name_length), Some(name_offset)) = fields else { continue };
+        let Some(name) = utf16_at(record, name_offset as usize, name_length as usize / 2) else { continue };
+
+        let datetime = mft::filetime(timestamp);
+        if mft::sentinel_filetime(timestamp) || !DateFilter::in_range(filter, datetime) {
+            continue;
+        }
+
+        let (entry, sequence) = reference(file);
+        let (parent, _) = reference(parent);
+        let filename = match mft.and_then(|m| m.path(parent)) {
+            Some(directory) if directory == "/" => format!("/{name}"),
+            Some(directory) => format!("{directory}/{name}"),
+            None => name
+        };
+
+        bodyfile.add_event(TimestampEntry {
+            datetime,
+            desc: TimestampDesc::Label(format!("USN {}", reasons(reason))),
+            source: SourceType::UsnJrnl,
+            meta: format!("{entry}-{sequence}").into(),
+            size: 0,
+            filename: filename.into(),
+            md5: "0".into(),
+            mode: "".into(),
+            uid: "".into(),
+            gid: "".into(),
+            tags: vec![],
+            score: 0,
+            detection: None,
+            raw: None,
+            input: None,
+            host: None,
+            session: None,
+            line: None,
+            unset: Default::default()
+        }, false, false);
+    }
+
+    Ok(())
+}
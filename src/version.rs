@@ -0,0 +1,49 @@
+use crate::bodyfile::OutputFormat;
+use crate::input::InputFormat;
+
+/* Build of the binary (mactime --version, JSON metadata), so that reports can state exactly which build produced a timeline */
+pub const COMMIT : &str = env!("MACTIME_COMMIT");
+pub const BUILD_DATE : &str = env!("MACTIME_BUILD_DATE");
+
+// enabled cargo features
+pub fn features() -> Vec<&'static str> {
+    let mut features = vec![];
+    if cfg!(feature = "misp") {
+        features.push("misp");
+    }
+    if cfg!(feature = "scripting") {
+        features.push("scripting");
+    }
+    if cfg!(feature = "vt") {
+        features.push("vt");
+    }
+    features
+}
+
+fn input_formats() -> Vec<String> {
+    InputFormat::ALL.iter().map(|f| f.to_string()).collect()
+}
+
+fn output_formats() -> Vec<String> {
+    OutputFormat::ALL.iter().map(|f| f.to_string().to_lowercase()).collect()
+}
+
+// "mactime <long_version>"
+pub fn long_version() -> String {
+    let features = features();
+    format!("{} (commit {COMMIT}, built {BUILD_DATE})\nfeatures: {}\ninput formats: {}\noutput formats: {}",
+        env!("CARGO_PKG_VERSION"),
+        if features.is_empty() { "none".to_string() } else { features.join(", ") },
+        input_formats().join(", "),
+        output_formats().join(", "))
+}
+
+pub fn build_info() -> serde_json::Value {
+    serde_json::json!({
+        "commit": COMMIT,
+        "date": BUILD_DATE,
+        "features": features(),
+        "input_formats": input_formats(),
+        "output_formats": output_formats()
+    })
+}
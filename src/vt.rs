@@ -0,0 +1,105 @@
+use std::{path::{Path, PathBuf}, error::Error, collections::{BTreeMap, BTreeSet}, fmt, thread, time::{Duration, Instant}};
+use serde::{Serialize, Deserialize};
+
+/* VirusTotal hash lookup: unique MD5s are queried once, rate limited, and cached on disk between runs */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Detection {
+    pub malicious: u64,
+    pub total: u64
+}
+
+impl fmt::Display for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.malicious, self.total)
+    }
+}
+
+impl std::str::FromStr for Detection {
+    type Err = String;
+
+    // "<malicious>/<total>"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Invalid detection '{s}'");
+        let (malicious, total) = s.split_once('/').ok_or_else(error)?;
+        Ok(Self {
+            malicious: malicious.parse().map_err(|_| error())?,
+            total: total.parse().map_err(|_| error())?
+        })
+    }
+}
+
+pub struct VirusTotal {
+    key: String,
+    interval: Duration, // minimum delay between two requests
+    cache_path: PathBuf,
+    cache: BTreeMap<String, Option<Detection>> // None: unknown to VirusTotal
+}
+
+impl VirusTotal {
+    pub fn new(key: &str, requests_per_minute: u32, cache_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let cache = match std::fs::read_to_string(cache_path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into())
+        };
+
+        Ok(Self {
+            key: key.to_string(),
+            interval: Duration::from_secs(60) / requests_per_minute.max(1),
+            cache_path: cache_path.to_path_buf(),
+            cache
+        })
+    }
+
+    // look up every MD5 missing from the cache, the cache is saved even if a request fails
+    pub fn lookup(&mut self, md5s: &BTreeSet<String>) -> Result<BTreeMap<String, Option<Detection>>, Box<dyn Error>> {
+        let missing : Vec<&String> = md5s.iter().filter(|md5| !self.cache.contains_key(*md5)).collect();
+        eprintln!("VirusTotal lookup: {} hashes, {} cached", md5s.len(), md5s.len() - missing.len());
+
+        let mut result = Ok(());
+        let mut last_request : Option<Instant> = None;
+        for md5 in missing {
+            if let Some(last) = last_request {
+                thread::sleep(self.interval.saturating_sub(last.elapsed()));
+            }
+            last_request = Some(Instant::now());
+
+            match query(&self.key, md5) {
+                Ok(detection) => { self.cache.insert(md5.clone(), detection); }
+                Err(e) => { result = Err(e); break; }
+            }
+        }
+
+        std::fs::write(&self.cache_path, serde_json::to_string_pretty(&self.cache)?)?;
+        result?;
+
+        Ok(md5s.iter()
+            .filter_map(|md5| self.cache.get(md5).map(|d| (md5.clone(), *d)))
+            .collect())
+    }
+}
+
+#[cfg(feature = "vt")]
+fn query(key: &str, md5: &str) -> Result<Option<Detection>, Box<dyn Error>> {
+    let response = ureq::get(&format!("https://www.virustotal.com/api/v3/files/{md5}"))
+        .set("x-apikey", key)
+        .call();
+
+    let report : serde_json::Value = match response {
+        Ok(response) => response.into_json()?,
+        Err(ureq::Error::Status(404, _)) => return Ok(None), // unknown hash
+        Err(e) => return Err(e.into())
+    };
+
+    let stats = &report["data"]["attributes"]["last_analysis_stats"];
+    let count = |field: &str| stats[field].as_u64().unwrap_or(0);
+    Ok(Some(Detection {
+        malicious: count("malicious"),
+        total: ["malicious", "suspicious", "undetected", "harmless"].iter().map(|f| count(f)).sum()
+    }))
+}
+
+#[cfg(not(feature = "vt"))]
+fn query(_key: &str, _md5: &str) -> Result<Option<Detection>, Box<dyn Error>> {
+    Err("VirusTotal lookup requires mactime to be built with the `vt` feature".into())
+}